@@ -1,7 +1,7 @@
-/// Example demonstrating different connection types with the Any engine
-/// 
-/// This shows how you can use different database backends without changing code
-/// Run with: cargo run --example memory_test
+//! Example demonstrating different connection types with the Any engine
+//! 
+//! This shows how you can use different database backends without changing code
+//! Run with: cargo run --example memory_test
 
 use anyhow::Result;
 use surrealdb::{engine::any::connect, RecordId};
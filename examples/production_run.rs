@@ -1,12 +1,12 @@
-/// Example demonstrating a full production run of the embed_star service
-///
-/// This example shows how to:
-/// 1. Set up a test database with sample repositories
-/// 2. Run the embedding service
-/// 3. Verify embeddings are generated
-/// 4. Monitor performance metrics
-///
-/// Run with: cargo run --example production_run
+//! Example demonstrating a full production run of the embed_star service
+//!
+//! This example shows how to:
+//! 1. Set up a test database with sample repositories
+//! 2. Run the embedding service
+//! 3. Verify embeddings are generated
+//! 4. Monitor performance metrics
+//!
+//! Run with: cargo run --example production_run
 
 use anyhow::Result;
 use surrealdb::{ engine::any::{ Any, connect }, opt::auth::Root, RecordId, Surreal, sql::Datetime };
@@ -1,4 +1,3 @@
-use reqwest;
 use serde_json::Value;
 
 #[tokio::main]
@@ -1,8 +1,8 @@
 use embed_star::{
     config::Config,
     embedder::Embedder,
-    embedding_validation::together_e5_validator,
     metrics::Metrics,
+    validation::{EmbeddingValidator, ValidationConfig},
 };
 use prometheus::Registry;
 use std::sync::Arc;
@@ -31,28 +31,26 @@ async fn main() -> anyhow::Result<()> {
     // Create config
     let config = Config {
         db_url: "ws://localhost:8000".to_string(),
-        db_user: "root".to_string(),
-        db_pass: "root".to_string(),
-        db_namespace: "test".to_string(),
-        db_database: "test".to_string(),
         embedding_provider: "together".to_string(),
-        ollama_url: "http://localhost:11434".to_string(),
-        openai_api_key: None,
         together_api_key: std::env::var("TOGETHER_API_KEY").ok(),
         embedding_model: "intfloat/multilingual-e5-large-instruct".to_string(),
-        batch_size: 10,
         batch_delay_ms: 100,
         pool_size: 10,
         retry_attempts: 3,
         retry_delay_ms: 1000,
         monitoring_port: None,
-        parallel_workers: 1,
-        token_limit: 8000,
         pool_max_size: 10,
         pool_timeout_secs: 30,
         pool_wait_timeout_secs: 10,
         pool_create_timeout_secs: 30,
         pool_recycle_timeout_secs: 30,
+        price_per_million_tokens: None,
+        provider_probe_interval_secs: 300,
+        monitoring_bind_addr: "0.0.0.0".to_string(),
+        mock_dimensions: 768,
+        poll_interval_secs: 5,
+        shutdown_drain_secs: 20,
+        ..Config::for_tests()
     };
 
     // Validate config
@@ -72,7 +70,7 @@ async fn main() -> anyhow::Result<()> {
     ];
 
     // Create validator
-    let validator = together_e5_validator();
+    let validator = EmbeddingValidator::new(ValidationConfig::for_model(embedder.model_name()));
     info!("Using validator for multilingual-e5-large model (1024 dimensions)");
 
     for (name, text) in &test_cases {
@@ -1,6 +1,6 @@
 use embed_star::{
-    embedding_validation::EmbeddingValidator,
     metrics::Metrics,
+    validation::{EmbeddingValidator, ValidationConfig},
 };
 use prometheus::Registry;
 use tracing::info;
@@ -23,9 +23,15 @@ async fn main() -> anyhow::Result<()> {
     info!("Metrics initialized");
 
     // Create a strict validator
-    let validator = EmbeddingValidator::new()
-        .with_dimension(1024)
-        .with_magnitude_range(0.8, 1.2); // Strict range for normalized embeddings
+    let validator = EmbeddingValidator::new(ValidationConfig {
+        expected_dimension: Some(1024),
+        // Strict range for normalized embeddings
+        min_magnitude: 0.8,
+        max_magnitude: 1.2,
+        max_zero_ratio: 0.5,
+        min_variance: 1e-6,
+        ..Default::default()
+    });
 
     // Test cases that should fail
     let test_cases = vec![
@@ -39,8 +45,8 @@ async fn main() -> anyhow::Result<()> {
         }, "Contains NaN"),
         ("too_sparse", {
             let mut v = vec![0.0; 1024];
-            for i in 0..100 {
-                v[i] = 0.1;
+            for value in v.iter_mut().take(100) {
+                *value = 0.1;
             }
             v
         }, "Too many zeros"),
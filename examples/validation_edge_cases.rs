@@ -1,4 +1,4 @@
-use embed_star::embedding_validation::{EmbeddingValidator, BatchValidationResult};
+use embed_star::validation::{BatchValidationResult, EmbeddingValidator, ValidationConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -14,13 +14,19 @@ async fn main() -> anyhow::Result<()> {
     info!("Testing embedding validation edge cases");
 
     // Create validators with different configurations
-    let strict_validator = EmbeddingValidator::new()
-        .with_dimension(1024)
-        .with_magnitude_range(0.5, 2.0);
-
-    let lenient_validator = EmbeddingValidator::new()
-        .with_dimension(1024)
-        .with_magnitude_range(0.1, 10.0);
+    let strict_validator = EmbeddingValidator::new(ValidationConfig {
+        min_magnitude: 0.5,
+        max_magnitude: 2.0,
+        ..ValidationConfig::for_model("intfloat/multilingual-e5-large-instruct")
+    });
+
+    let lenient_validator = EmbeddingValidator::new(ValidationConfig {
+        expected_dimension: Some(1024),
+        min_magnitude: 0.1,
+        max_magnitude: 10.0,
+        min_variance: 1e-6,
+        ..Default::default()
+    });
 
     // Test cases with artificial embeddings
     let test_cases: Vec<(&str, Vec<f32>, &str)> = vec![
@@ -0,0 +1,331 @@
+use crate::{
+    config::Config,
+    embedder::Embedder,
+    metrics::Metrics,
+    models::Repo,
+    pool::create_pool,
+    surreal_client::{StorageMode, StoredEmbeddingRow, SurrealClient},
+    validation::EmbeddingValidator,
+};
+use prometheus::Registry;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{collections::HashMap, fmt, sync::Arc};
+use tracing::info;
+
+/// Page size used when reading both models' embeddings
+const AB_PAGE_SIZE: usize = 1000;
+
+/// Repo pairs compared for the similarity structure; larger samples draw this many at random
+const MAX_PAIRS: usize = 200_000;
+
+/// Repos whose nearest neighbours are compared
+const MAX_QUERIES: usize = 200;
+
+/// Fixed seed so repeated reports over the same data agree
+const AB_SEED: u64 = 0xab_7e57;
+
+/// Whether the repo is in the A/B sample. Decided by a hash of the id (FNV-1a, which unlike the
+/// std hasher is stable across runs), so a repo stays in or out of the sample on every re-embed.
+pub fn in_sample(id: &surrealdb::RecordId, rate: f64) -> bool {
+    let hash = id
+        .to_string()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+    ((hash % 1_000_000) as f64) < rate * 1_000_000.0
+}
+
+/// Embeds the sampled repos of each batch a second time with the A/B model. The workers run
+/// these through `process_batch` like any other batch, writing to the tenant's shadow sink.
+pub struct AbShadow {
+    embedder: Arc<Embedder>,
+    validator: Arc<EmbeddingValidator>,
+    sample_rate: f64,
+}
+
+impl AbShadow {
+    pub fn new(embedder: Arc<Embedder>, validator: Arc<EmbeddingValidator>, sample_rate: f64) -> Self {
+        Self {
+            embedder,
+            validator,
+            sample_rate,
+        }
+    }
+
+    pub fn embedder(&self) -> &Arc<Embedder> {
+        &self.embedder
+    }
+
+    /// Validator with the A/B model's profile
+    pub fn validator(&self) -> &Arc<EmbeddingValidator> {
+        &self.validator
+    }
+
+    /// The repos of `batch` that are in the sample
+    pub fn sample(&self, batch: &[Repo]) -> Vec<Repo> {
+        batch
+            .iter()
+            .filter(|repo| in_sample(&repo.id, self.sample_rate))
+            .cloned()
+            .collect()
+    }
+}
+
+/// How closely model B reproduces model A's view of the repos both have embedded
+#[derive(Debug, Clone, Default)]
+pub struct AbComparison {
+    /// Repos embedded by both models
+    pub repos: usize,
+    /// Repo pairs whose similarities were compared
+    pub pairs: usize,
+    /// Spearman rank correlation between the two models' pairwise similarities
+    pub similarity_correlation: Option<f64>,
+    pub mean_similarity_a: Option<f64>,
+    pub mean_similarity_b: Option<f64>,
+    pub k: usize,
+    /// Repos whose neighbours were compared
+    pub queries: usize,
+    /// Mean share of a repo's top-k neighbours (among the compared repos) both models agree on
+    pub neighbour_overlap: Option<f64>,
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let magnitude = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        vector.iter().map(|x| x / magnitude).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Ranks starting at 1, ties sharing their average rank
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len() as f64;
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a).powi(2);
+        variance_b += (y - mean_b).powi(2);
+    }
+    (variance_a > 0.0 && variance_b > 0.0).then(|| covariance / (variance_a * variance_b).sqrt())
+}
+
+/// Compare two models' embeddings of the same repos: how well the orderings of their pairwise
+/// similarities agree, and how many nearest neighbours they share. Repos missing from either
+/// side, or whose dimensions differ from the rest of their side, are left out.
+pub fn compare(a: &[StoredEmbeddingRow], b: &[StoredEmbeddingRow], k: usize) -> AbComparison {
+    let dimensions_b = b.first().map_or(0, |row| row.embedding.len());
+    let by_id: HashMap<String, &[f32]> = b
+        .iter()
+        .filter(|row| row.embedding.len() == dimensions_b)
+        .map(|row| (row.id.to_string(), row.embedding.as_slice()))
+        .collect();
+    let dimensions_a = a.first().map_or(0, |row| row.embedding.len());
+    let (vectors_a, vectors_b): (Vec<Vec<f32>>, Vec<Vec<f32>>) = a
+        .iter()
+        .filter(|row| row.embedding.len() == dimensions_a)
+        .filter_map(|row| {
+            let other = by_id.get(&row.id.to_string())?;
+            Some((normalized(&row.embedding), normalized(other)))
+        })
+        .unzip();
+
+    let n = vectors_a.len();
+    let mut comparison = AbComparison {
+        repos: n,
+        k,
+        ..Default::default()
+    };
+    if n < 2 {
+        return comparison;
+    }
+
+    let mut rng = StdRng::seed_from_u64(AB_SEED);
+    let total_pairs = n * (n - 1) / 2;
+    let pairs: Vec<(usize, usize)> = if total_pairs <= MAX_PAIRS {
+        (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))).collect()
+    } else {
+        (0..MAX_PAIRS)
+            .map(|_| {
+                let i = rng.gen_range(0..n);
+                let j = (i + rng.gen_range(1..n)) % n;
+                (i.min(j), i.max(j))
+            })
+            .collect()
+    };
+    let similarities_a: Vec<f64> = pairs.iter().map(|&(i, j)| dot(&vectors_a[i], &vectors_a[j]) as f64).collect();
+    let similarities_b: Vec<f64> = pairs.iter().map(|&(i, j)| dot(&vectors_b[i], &vectors_b[j]) as f64).collect();
+    comparison.pairs = pairs.len();
+    comparison.similarity_correlation = pearson(&ranks(&similarities_a), &ranks(&similarities_b));
+    comparison.mean_similarity_a = Some(similarities_a.iter().sum::<f64>() / pairs.len() as f64);
+    comparison.mean_similarity_b = Some(similarities_b.iter().sum::<f64>() / pairs.len() as f64);
+
+    let k = k.min(n - 1);
+    if k == 0 {
+        return comparison;
+    }
+    let mut queries: Vec<usize> = (0..n).collect();
+    queries.shuffle(&mut rng);
+    queries.truncate(MAX_QUERIES);
+    let neighbours = |vectors: &[Vec<f32>], query: usize| {
+        let mut others: Vec<(usize, f32)> = (0..n)
+            .filter(|&i| i != query)
+            .map(|i| (i, dot(&vectors[query], &vectors[i])))
+            .collect();
+        others.sort_by(|x, y| y.1.total_cmp(&x.1));
+        others.into_iter().take(k).map(|(i, _)| i).collect::<Vec<_>>()
+    };
+    let overlap: f64 = queries
+        .iter()
+        .map(|&query| {
+            let top_a = neighbours(&vectors_a, query);
+            let top_b = neighbours(&vectors_b, query);
+            top_a.iter().filter(|i| top_b.contains(i)).count() as f64 / k as f64
+        })
+        .sum();
+    comparison.queries = queries.len();
+    comparison.neighbour_overlap = Some(overlap / queries.len() as f64);
+    comparison
+}
+
+/// Result of the `ab-report` command
+#[derive(Debug, Default)]
+pub struct AbReport {
+    pub model_a: String,
+    pub model_b: String,
+    pub embedded_a: usize,
+    pub embedded_b: usize,
+    pub comparison: AbComparison,
+}
+
+impl fmt::Display for AbReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.4}", v));
+        let comparison = &self.comparison;
+        writeln!(f, "A/B model comparison:")?;
+        writeln!(f, "  Model A: {} ({} embeddings)", self.model_a, self.embedded_a)?;
+        writeln!(f, "  Model B: {} ({} embeddings)", self.model_b, self.embedded_b)?;
+        writeln!(f, "  Repos embedded by both: {}", comparison.repos)?;
+        writeln!(f, "  Similarity rank correlation: {} over {} pairs",
+            optional(comparison.similarity_correlation),
+            comparison.pairs
+        )?;
+        writeln!(f, "  Mean pairwise similarity: A {}, B {}",
+            optional(comparison.mean_similarity_a),
+            optional(comparison.mean_similarity_b)
+        )?;
+        writeln!(f, "  Top-{} neighbour overlap: {} over {} repos",
+            comparison.k,
+            optional(comparison.neighbour_overlap),
+            comparison.queries
+        )?;
+        Ok(())
+    }
+}
+
+/// Run the `ab-report` command, comparing the embeddings of `AB_MODEL` with those of the
+/// configured model for the repos both have embedded
+pub async fn run_ab_report(config: Config, k: usize) -> anyhow::Result<AbReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+    let Some(model_b) = config.ab_model.clone() else {
+        anyhow::bail!("AB_MODEL must be set to compare models");
+    };
+    if k == 0 {
+        anyhow::bail!("--k must be greater than 0");
+    }
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+    let pool = create_pool(config.clone()).await?;
+
+    let client_a = SurrealClient::new(pool.clone())
+        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone());
+    let client_b = SurrealClient::new(pool).with_storage_mode(StorageMode::Table, model_b.clone());
+    let rows_a = client_a.get_all_stored_embeddings(AB_PAGE_SIZE).await?;
+    let rows_b = client_b.get_all_stored_embeddings(AB_PAGE_SIZE).await?;
+    info!(model_a = %config.embedding_model, model_b = %model_b, a = rows_a.len(), b = rows_b.len(), "Comparing models");
+
+    let (embedded_a, embedded_b) = (rows_a.len(), rows_b.len());
+    let comparison = tokio::task::spawn_blocking(move || compare(&rows_a, &rows_b, k)).await?;
+    Ok(AbReport {
+        model_a: config.embedding_model.clone(),
+        model_b,
+        embedded_a,
+        embedded_b,
+        comparison,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use surrealdb::RecordId;
+
+    #[test]
+    fn test_sampling_and_compare() {
+        let ids: Vec<RecordId> = (0..2000).map(|i| RecordId::from(("repo", format!("r{}", i).as_str()))).collect();
+        let sampled = ids.iter().filter(|id| in_sample(id, 0.25)).count();
+        assert!((400..600).contains(&sampled), "sampled {}", sampled);
+        assert_eq!(ids.iter().filter(|id| in_sample(id, 0.25)).count(), sampled);
+        assert!(ids.iter().all(|id| in_sample(id, 1.0)));
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let a: Vec<StoredEmbeddingRow> = ids[..40]
+            .iter()
+            .map(|id| StoredEmbeddingRow {
+                id: id.clone(),
+                embedding: (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+            })
+            .collect();
+        // Reversing and negating the axes preserves every similarity
+        let rotated: Vec<StoredEmbeddingRow> = a
+            .iter()
+            .map(|row| StoredEmbeddingRow {
+                id: row.id.clone(),
+                embedding: row.embedding.iter().rev().map(|x| -x).collect(),
+            })
+            .collect();
+        let same = compare(&a, &rotated[..30], 5);
+        assert_eq!(same.repos, 30);
+        assert_eq!(same.pairs, 30 * 29 / 2);
+        assert!(same.similarity_correlation.unwrap() > 0.999);
+        assert_eq!(same.neighbour_overlap, Some(1.0));
+
+        let unrelated: Vec<StoredEmbeddingRow> = a
+            .iter()
+            .map(|row| StoredEmbeddingRow {
+                id: row.id.clone(),
+                embedding: (0..8).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+            })
+            .collect();
+        let different = compare(&a, &unrelated, 5);
+        assert!(different.similarity_correlation.unwrap().abs() < 0.5);
+        assert!(different.neighbour_overlap.unwrap() < 0.5);
+        assert_eq!(compare(&a, &[], 5).repos, 0);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{error::EmbedError, rate_limiter::RateLimitHint};
+use parking_lot::Mutex;
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::{error, warn};
+
+/// Backoff applied to a rate-limited key when the provider doesn't say how long to wait
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+
+struct ApiKey {
+    secret: String,
+    disabled: AtomicBool,
+    rate_limited_until: Mutex<Option<Instant>>,
+}
+
+/// A key handed out for one request
+#[derive(Debug, Clone)]
+pub struct KeyLease {
+    index: usize,
+    secret: String,
+}
+
+impl KeyLease {
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// Metric label for the key; never the secret itself
+    pub fn label(&self) -> String {
+        format!("key{}", self.index)
+    }
+}
+
+/// Round-robin pool of API keys for one provider.
+///
+/// Keys that hit a rate limit are skipped until their backoff expires; keys that fail
+/// authentication are disabled for the lifetime of the process.
+pub struct ApiKeyPool {
+    provider: String,
+    keys: Vec<ApiKey>,
+    next: AtomicUsize,
+}
+
+impl ApiKeyPool {
+    pub fn new(provider: &str, secrets: Vec<String>) -> Self {
+        let keys = secrets
+            .into_iter()
+            .map(|secret| ApiKey {
+                secret,
+                disabled: AtomicBool::new(false),
+                rate_limited_until: Mutex::new(None),
+            })
+            .collect::<Vec<_>>();
+
+        let pool = Self {
+            provider: provider.to_string(),
+            keys,
+            next: AtomicUsize::new(0),
+        };
+        crate::metrics::set_api_keys_active(&pool.provider, pool.active_count());
+        pool
+    }
+
+    /// Build a pool from a comma-separated key list, as accepted by `OPENAI_API_KEY`/`TOGETHER_API_KEY`
+    pub fn from_list(provider: &str, keys: &str) -> Self {
+        Self::new(provider, split_keys(keys))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Number of keys that have not been disabled
+    pub fn active_count(&self) -> usize {
+        self.keys
+            .iter()
+            .filter(|key| !key.disabled.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Pick the next usable key. When every key is rate limited, the one whose backoff ends
+    /// first is returned so callers can still make progress.
+    pub fn next_key(&self) -> Option<KeyLease> {
+        let len = self.keys.len();
+        if len == 0 {
+            return None;
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let mut earliest: Option<(usize, Instant)> = None;
+
+        for offset in 0..len {
+            let index = (start + offset) % len;
+            let key = &self.keys[index];
+            if key.disabled.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            match *key.rate_limited_until.lock() {
+                Some(until) if until > now => {
+                    if earliest.is_none_or(|(_, best)| until < best) {
+                        earliest = Some((index, until));
+                    }
+                }
+                _ => return Some(self.lease(index)),
+            }
+        }
+
+        earliest.map(|(index, _)| self.lease(index))
+    }
+
+    fn lease(&self, index: usize) -> KeyLease {
+        KeyLease {
+            index,
+            secret: self.keys[index].secret.clone(),
+        }
+    }
+
+    pub fn record_success(&self, lease: &KeyLease) {
+        crate::metrics::record_api_key_request(&self.provider, &lease.label(), "success");
+    }
+
+    pub fn record_error(&self, lease: &KeyLease) {
+        crate::metrics::record_api_key_request(&self.provider, &lease.label(), "error");
+    }
+
+    /// Skip this key until the provider's backoff has passed
+    pub fn mark_rate_limited(&self, lease: &KeyLease, backoff: Option<Duration>) {
+        let backoff = backoff.unwrap_or(DEFAULT_RATE_LIMIT_BACKOFF);
+        *self.keys[lease.index].rate_limited_until.lock() = Some(Instant::now() + backoff);
+        warn!(
+            "{} API {} rate limited, rotating to the next key for {:?}",
+            self.provider,
+            lease.label(),
+            backoff
+        );
+        crate::metrics::record_api_key_request(&self.provider, &lease.label(), "rate_limited");
+    }
+
+    /// Stop using a key the provider rejected as unauthorized
+    pub fn disable(&self, lease: &KeyLease) {
+        if !self.keys[lease.index].disabled.swap(true, Ordering::Relaxed) {
+            error!(
+                "{} API {} was rejected as unauthorized and has been disabled ({} keys left)",
+                self.provider,
+                lease.label(),
+                self.active_count()
+            );
+        }
+        crate::metrics::record_api_key_request(&self.provider, &lease.label(), "auth_error");
+        crate::metrics::set_api_keys_active(&self.provider, self.active_count());
+    }
+}
+
+impl ApiKeyPool {
+    /// Update the key's state from a provider response, given its classified error or `None` on
+    /// success, and return the rate-limit hint that applies to the provider as a whole. Only a
+    /// genuine credential failure disables the key: a 403 can also mean the input was too long.
+    pub fn observe(
+        &self,
+        lease: &KeyLease,
+        error: Option<&EmbedError>,
+        hint: Option<RateLimitHint>,
+    ) -> Option<RateLimitHint> {
+        match error {
+            None => self.record_success(lease),
+            Some(EmbedError::AuthFailed(_)) => self.disable(lease),
+            // An exhausted quota shares the 429 and is per key too, so rotate away from it
+            Some(EmbedError::RateLimitExceeded { .. } | EmbedError::QuotaExceeded(_)) => {
+                self.mark_rate_limited(lease, hint.as_ref().and_then(|h| h.backoff()))
+            }
+            Some(_) => self.record_error(lease),
+        }
+
+        let active = self.active_count() as u32;
+        if active <= 1 {
+            return hint;
+        }
+
+        // Quotas are per key, and a backoff only applies to the key that was rate limited
+        hint.map(|hint| RateLimitHint {
+            retry_after: None,
+            requests_per_minute: hint.requests_per_minute.map(|rpm| rpm.saturating_mul(active)),
+            remaining_requests: None,
+            reset_requests: None,
+            tokens_per_minute: hint.tokens_per_minute.map(|tpm| tpm.saturating_mul(active)),
+        })
+    }
+}
+
+/// Split a comma-separated key list, ignoring blanks
+pub fn split_keys(keys: &str) -> Vec<String> {
+    keys.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin_and_disable() {
+        let pool = ApiKeyPool::from_list("test", "a, b,,c");
+        assert_eq!(pool.len(), 3);
+
+        let picked: Vec<_> = (0..3).map(|_| pool.next_key().unwrap().secret().to_string()).collect();
+        assert_eq!(picked, vec!["a", "b", "c"]);
+
+        pool.next_key();
+        let b = pool.next_key().unwrap();
+        assert_eq!(b.secret(), "b");
+        pool.disable(&b);
+        assert_eq!(pool.active_count(), 2);
+
+        for _ in 0..6 {
+            assert_ne!(pool.next_key().unwrap().secret(), "b");
+        }
+    }
+
+    #[test]
+    fn test_rate_limited_keys_are_skipped() {
+        let pool = ApiKeyPool::from_list("test", "a,b");
+
+        let a = pool.next_key().unwrap();
+        pool.mark_rate_limited(&a, Some(Duration::from_secs(60)));
+        for _ in 0..4 {
+            assert_eq!(pool.next_key().unwrap().secret(), "b");
+        }
+
+        // With every key limited, the one that frees up first is used
+        let b = pool.next_key().unwrap();
+        pool.mark_rate_limited(&b, Some(Duration::from_secs(120)));
+        assert_eq!(pool.next_key().unwrap().secret(), "a");
+
+        pool.disable(&a);
+        pool.disable(&b);
+        assert!(pool.next_key().is_none());
+    }
+
+    #[test]
+    fn test_observe_scales_hint_across_keys() {
+        let pool = ApiKeyPool::from_list("test", "a,b");
+        let hint = RateLimitHint {
+            retry_after: Some(Duration::from_secs(5)),
+            requests_per_minute: Some(100),
+            ..Default::default()
+        };
+
+        let lease = pool.next_key().unwrap();
+        let limited = EmbedError::RateLimitExceeded {
+            provider: "test".to_string(),
+        };
+        let scaled = pool.observe(&lease, Some(&limited), Some(hint.clone())).unwrap();
+        assert_eq!(scaled.requests_per_minute, Some(200));
+        assert_eq!(scaled.retry_after, None);
+
+        // Once only one key is left, its hint applies to the whole provider
+        pool.observe(&lease, Some(&EmbedError::AuthFailed("revoked".to_string())), None);
+        let remaining = pool.next_key().unwrap();
+        assert_eq!(pool.observe(&remaining, None, Some(hint.clone())), Some(hint));
+    }
+
+    #[test]
+    fn test_too_long_403_leaves_key_usable() {
+        let pool = ApiKeyPool::from_list("together", "a");
+        let lease = pool.next_key().unwrap();
+
+        let error = crate::provider_error::from_together_response(
+            reqwest::StatusCode::FORBIDDEN,
+            r#"{"error":{"message":"Input is too long for the model context length"}}"#,
+            "req-1",
+        );
+        assert!(matches!(error, EmbedError::InputTooLong(_)), "{:?}", error);
+        pool.observe(&lease, Some(&error), None);
+
+        assert_eq!(pool.active_count(), 1);
+        assert_eq!(pool.next_key().unwrap().secret(), "a");
+    }
+}
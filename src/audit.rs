@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use surrealdb::RecordId;
+use uuid::Uuid;
+
+/// What happened to a repo in a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// An embedding was written
+    Stored,
+    /// Embedding, validation or the database write failed
+    Failed,
+    /// Not attempted (rate limits, recently cached failure)
+    Skipped,
+}
+
+/// One row of the `embedding_audit` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub repo: RecordId,
+    pub batch_id: String,
+    pub provider: String,
+    pub model: String,
+    pub duration_ms: u64,
+    pub cache_hit: bool,
+    pub outcome: AuditOutcome,
+    pub error_code: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One row of the `batch_run` table: what a batch achieved, for throughput history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRun {
+    pub batch_id: String,
+    /// Worker that processed the batch; `None` until the worker loop fills it in
+    pub worker: Option<usize>,
+    pub provider: String,
+    pub model: String,
+    pub size: usize,
+    pub stored: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub cache_hits: usize,
+    pub duration_ms: u64,
+    /// Batch settings in effect, so runs can be compared across configuration changes
+    pub batch_size: Option<usize>,
+    pub batch_delay_ms: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Collects audit records while a batch is processed
+pub struct BatchAudit {
+    batch_id: String,
+    provider: String,
+    model: String,
+    records: Vec<AuditRecord>,
+}
+
+impl BatchAudit {
+    pub fn new(batch_id: Uuid, provider: &str, model: &str) -> Self {
+        Self {
+            batch_id: batch_id.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Record a repo's outcome. `Stored` means queued for writing until [`Self::settle_write`].
+    pub fn record(
+        &mut self,
+        repo: &RecordId,
+        outcome: AuditOutcome,
+        error_code: Option<&str>,
+        duration: Duration,
+        cache_hit: bool,
+    ) {
+        self.records.push(AuditRecord {
+            repo: repo.clone(),
+            batch_id: self.batch_id.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            duration_ms: duration.as_millis() as u64,
+            cache_hit,
+            outcome,
+            error_code: error_code.map(str::to_string),
+            created_at: Utc::now(),
+        });
+    }
+
+    /// Mark queued repos as failed if their write failed; `None` means the whole write failed
+    pub fn settle_write(&mut self, failed: Option<&[(RecordId, String)]>) {
+        for record in &mut self.records {
+            let write_failed = match failed {
+                Some(errors) => errors.iter().any(|(id, _)| id == &record.repo),
+                None => true,
+            };
+            if record.outcome == AuditOutcome::Stored && write_failed {
+                record.outcome = AuditOutcome::Failed;
+                record.error_code = Some("DATABASE_ERROR".to_string());
+            }
+        }
+    }
+
+    /// Summarize the batch once its write has been settled
+    pub fn summary(&self, stored: usize, duration: Duration) -> BatchRun {
+        let count = |outcome| self.records.iter().filter(|record| record.outcome == outcome).count();
+        BatchRun {
+            batch_id: self.batch_id.clone(),
+            worker: None,
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            size: self.records.len(),
+            stored,
+            failed: count(AuditOutcome::Failed),
+            skipped: count(AuditOutcome::Skipped),
+            cache_hits: self.records.iter().filter(|record| record.cache_hit).count(),
+            duration_ms: duration.as_millis() as u64,
+            batch_size: None,
+            batch_delay_ms: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn into_records(self) -> Vec<AuditRecord> {
+        self.records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settle_write_marks_failed_writes() {
+        let stored = RecordId::from(("repo", "stored"));
+        let lost = RecordId::from(("repo", "lost"));
+        let skipped = RecordId::from(("repo", "skipped"));
+
+        let mut audit = BatchAudit::new(Uuid::new_v4(), "openai", "text-embedding-3-small");
+        audit.record(&stored, AuditOutcome::Stored, None, Duration::from_millis(20), false);
+        audit.record(&lost, AuditOutcome::Stored, None, Duration::ZERO, true);
+        audit.record(&skipped, AuditOutcome::Skipped, Some("RATE_LIMIT"), Duration::ZERO, false);
+
+        audit.settle_write(Some(&[(lost.clone(), "not found".to_string())]));
+        let run = audit.summary(1, Duration::from_millis(50));
+        assert_eq!((run.size, run.stored, run.failed, run.skipped, run.cache_hits), (3, 1, 1, 1, 1));
+        let records = audit.into_records();
+
+        assert_eq!(records[0].outcome, AuditOutcome::Stored);
+        assert_eq!(records[1].outcome, AuditOutcome::Failed);
+        assert_eq!(records[1].error_code.as_deref(), Some("DATABASE_ERROR"));
+        assert_eq!(records[2].outcome, AuditOutcome::Skipped);
+        assert_eq!(records[2].error_code.as_deref(), Some("RATE_LIMIT"));
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+/// Pending count and total stored embeddings at one point in time
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    pending: usize,
+    stored: u64,
+}
+
+/// Estimates how many workers would keep up with the backlog, for an HPA or another external
+/// controller to act on. Arrivals are inferred from consecutive samples: whatever was stored in
+/// between plus however much the pending count grew.
+pub struct ScalingEstimator {
+    /// How soon the current backlog should be worked off, on top of keeping up with arrivals
+    drain_target: Duration,
+    previous: Option<Sample>,
+}
+
+/// What one estimate is based on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScalingInputs {
+    pub pending: usize,
+    /// New repos per second
+    pub arrival_rate: f64,
+    /// Time one embedding takes
+    pub latency: Duration,
+    pub batch_size: usize,
+    pub batch_delay: Duration,
+}
+
+impl ScalingEstimator {
+    pub fn new(drain_target: Duration) -> Self {
+        Self {
+            drain_target,
+            previous: None,
+        }
+    }
+
+    /// Record the current counts and return the inputs for an estimate; `None` until a
+    /// provider latency has been measured
+    pub fn observe(
+        &mut self,
+        pending: usize,
+        stored: u64,
+        latency: Option<Duration>,
+        batch_size: usize,
+        batch_delay: Duration,
+    ) -> Option<ScalingInputs> {
+        let now = Sample {
+            at: Instant::now(),
+            pending,
+            stored,
+        };
+        let arrival_rate = match self.previous.replace(now) {
+            Some(previous) if now.at > previous.at => {
+                let arrived = (now.stored.saturating_sub(previous.stored) as f64
+                    + now.pending as f64
+                    - previous.pending as f64)
+                    .max(0.0);
+                arrived / now.at.duration_since(previous.at).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        Some(ScalingInputs {
+            pending,
+            arrival_rate,
+            latency: latency?,
+            batch_size,
+            batch_delay,
+        })
+    }
+
+    /// Workers needed to absorb the arrivals and clear the backlog within the drain target
+    pub fn desired_workers(&self, inputs: &ScalingInputs) -> usize {
+        // A worker embeds its batch one repo at a time, then waits for the next tick
+        let batch_secs = inputs.latency.as_secs_f64() * inputs.batch_size as f64;
+        let per_worker = inputs.batch_size as f64 / batch_secs.max(inputs.batch_delay.as_secs_f64());
+        if !per_worker.is_finite() || per_worker <= 0.0 {
+            return 1;
+        }
+        let drain_secs = self.drain_target.as_secs_f64().max(1.0);
+        let needed = inputs.arrival_rate + inputs.pending as f64 / drain_secs;
+        ((needed / per_worker).ceil() as usize).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desired_workers() {
+        let mut estimator = ScalingEstimator::new(Duration::from_secs(3600));
+        let latency = Some(Duration::from_millis(100));
+        let delay = Duration::from_millis(100);
+
+        assert!(estimator.observe(36_000, 0, None, 10, delay).is_none());
+        // 10 repos per second per worker; 36000 pending over an hour needs 1
+        let inputs = estimator.observe(36_000, 0, latency, 10, delay).unwrap();
+        assert_eq!(inputs.arrival_rate, 0.0);
+        assert_eq!(estimator.desired_workers(&inputs), 1);
+
+        // Another 40 repos arriving per second
+        let inputs = ScalingInputs { arrival_rate: 40.0, ..inputs };
+        assert_eq!(estimator.desired_workers(&inputs), 5);
+
+        // An idle pipeline still keeps one worker
+        let idle = ScalingInputs { pending: 0, arrival_rate: 0.0, ..inputs };
+        assert_eq!(estimator.desired_workers(&idle), 1);
+    }
+}
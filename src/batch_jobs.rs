@@ -0,0 +1,617 @@
+use crate::{
+    config::Config,
+    cost::price_per_million_tokens,
+    embedder::{provider_client, truncate_dimensions, truncate_to_limit, OpenAIAccount},
+    import::parse_repo_id,
+    metrics::{self, Metrics},
+    pool::create_pool,
+    surreal_client::{EmbeddingUpdate, SurrealClient},
+    validation::{EmbeddingValidator, ValidationOutcome},
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use prometheus::Registry;
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, sync::Arc, time::Duration};
+use surrealdb::RecordId;
+use tracing::{info, warn};
+
+const OPENAI_API: &str = "https://api.openai.com/v1";
+
+/// Most requests OpenAI accepts in one batch
+pub const MAX_BATCH_REQUESTS: usize = 50_000;
+
+/// Batch requests are billed at half the synchronous price
+const BATCH_DISCOUNT: f64 = 0.5;
+
+/// Batch files run to hundreds of megabytes, far more than one embedding request
+const BATCH_HTTP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Statuses after which OpenAI does no more work on a batch
+const FINAL_STATUSES: &[&str] = &["completed", "failed", "expired", "cancelled"];
+
+/// State of one submitted batch, persisted in the `batch_job` table so polling survives restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub batch_id: String,
+    pub model: String,
+    /// OpenAI's status: validating, in_progress, finalizing, completed, failed, expired, ...
+    pub status: String,
+    pub input_file_id: String,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    /// Repos in the batch; they aren't submitted again while it is open
+    pub repos: Vec<RecordId>,
+    pub completed_requests: usize,
+    pub failed_requests: usize,
+    /// Embeddings stored from the output
+    pub ingested: usize,
+    /// Set once the output has been ingested, or the batch ended without one
+    pub done: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BatchJob {
+    pub fn is_final(&self) -> bool {
+        FINAL_STATUSES.contains(&self.status.as_str())
+    }
+}
+
+#[derive(Serialize)]
+struct BatchRequestLine<'a> {
+    custom_id: String,
+    method: &'static str,
+    url: &'static str,
+    body: BatchRequestBody<'a>,
+}
+
+#[derive(Serialize)]
+struct BatchRequestBody<'a> {
+    model: &'a str,
+    input: &'a str,
+    encoding_format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+/// JSONL input file with one embeddings request per repo, identified by its record id
+pub fn build_batch_file(model: &str, dimensions: Option<u32>, texts: &[(RecordId, String)]) -> anyhow::Result<Vec<u8>> {
+    let mut file = Vec::new();
+    for (repo_id, text) in texts {
+        let line = BatchRequestLine {
+            custom_id: repo_id.to_string(),
+            method: "POST",
+            url: "/v1/embeddings",
+            body: BatchRequestBody {
+                model,
+                input: text,
+                encoding_format: "float",
+                dimensions,
+            },
+        };
+        serde_json::to_writer(&mut file, &line)?;
+        file.push(b'\n');
+    }
+    Ok(file)
+}
+
+#[derive(Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingBody {
+    data: Vec<EmbeddingData>,
+    usage: Option<EmbeddingUsage>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingUsage {
+    prompt_tokens: u64,
+}
+
+/// One request's result from a batch output or error file
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    pub custom_id: String,
+    /// The embedding and the prompt tokens billed for it, or why the request failed
+    pub outcome: Result<(Vec<f32>, Option<u64>), String>,
+}
+
+/// Parse a batch output or error file. Malformed lines are logged and skipped.
+pub fn parse_batch_output(text: &str) -> Vec<BatchResult> {
+    let mut results = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: BatchOutputLine = match serde_json::from_str(line) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(line = number + 1, error = %e, "Skipping malformed batch output line");
+                continue;
+            }
+        };
+        let outcome = match (line.response, line.error) {
+            (_, Some(error)) if !error.is_null() => Err(error.to_string()),
+            (Some(response), _) if response.status_code == 200 => {
+                serde_json::from_value::<EmbeddingBody>(response.body)
+                    .map_err(|e| format!("invalid response body: {}", e))
+                    .and_then(|body| {
+                        let tokens = body.usage.map(|usage| usage.prompt_tokens);
+                        body.data
+                            .into_iter()
+                            .next()
+                            .map(|data| (data.embedding, tokens))
+                            .ok_or_else(|| "response contained no embedding".to_string())
+                    })
+            }
+            (Some(response), _) => Err(format!("HTTP {}: {}", response.status_code, response.body)),
+            (None, _) => Err("no response".to_string()),
+        };
+        results.push(BatchResult {
+            custom_id: line.custom_id,
+            outcome,
+        });
+    }
+    results
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchObject {
+    id: String,
+    status: String,
+    input_file_id: String,
+    output_file_id: Option<String>,
+    error_file_id: Option<String>,
+    #[serde(default)]
+    request_counts: RequestCounts,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RequestCounts {
+    completed: usize,
+    failed: usize,
+}
+
+#[derive(Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+/// The files and batches endpoints of the OpenAI API
+pub struct OpenAIBatchClient {
+    client: reqwest::Client,
+    api_key: String,
+    account: OpenAIAccount,
+}
+
+impl OpenAIBatchClient {
+    /// Uses the first of the configured OpenAI keys; a batch belongs to the key that created it
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let api_key = config
+            .openai_api_key
+            .as_deref()
+            .and_then(|keys| keys.split(',').map(str::trim).find(|key| !key.is_empty()))
+            .ok_or_else(|| anyhow::anyhow!("OpenAI API key not provided"))?
+            .to_string();
+        Ok(Self {
+            client: provider_client(config)?,
+            api_key,
+            account: config.openai_account(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{}", OPENAI_API, path))
+            .timeout(BATCH_HTTP_TIMEOUT)
+            .bearer_auth(&self.api_key);
+        self.account.apply(request)
+    }
+
+    async fn check(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("OpenAI API error ({}): {}", status, body)
+    }
+
+    async fn upload(&self, file: Vec<u8>) -> anyhow::Result<String> {
+        let part = Part::bytes(file)
+            .file_name("embeddings.jsonl")
+            .mime_str("application/jsonl")?;
+        let form = Form::new().text("purpose", "batch").part("file", part);
+        let response = self
+            .request(reqwest::Method::POST, "/files")
+            .multipart(form)
+            .send()
+            .await?;
+        Ok(Self::check(response).await?.json::<FileObject>().await?.id)
+    }
+
+    async fn create(&self, input_file_id: &str) -> anyhow::Result<BatchObject> {
+        let response = self
+            .request(reqwest::Method::POST, "/batches")
+            .json(&serde_json::json!({
+                "input_file_id": input_file_id,
+                "endpoint": "/v1/embeddings",
+                "completion_window": "24h",
+            }))
+            .send()
+            .await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    async fn retrieve(&self, batch_id: &str) -> anyhow::Result<BatchObject> {
+        let response = self
+            .request(reqwest::Method::GET, &format!("/batches/{}", batch_id))
+            .send()
+            .await?;
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    async fn file_content(&self, file_id: &str) -> anyhow::Result<String> {
+        let response = self
+            .request(reqwest::Method::GET, &format!("/files/{}/content", file_id))
+            .send()
+            .await?;
+        Ok(Self::check(response).await?.text().await?)
+    }
+}
+
+/// Summary of a batch command
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Batch id and request count of each batch submitted
+    pub submitted: Vec<(String, usize)>,
+    /// Batches still waiting on OpenAI afterwards
+    pub open: Vec<BatchJob>,
+    /// Batches whose output was ingested (or that ended without one)
+    pub finished: usize,
+    pub ingested: usize,
+    /// Results rejected by validation
+    pub rejected: usize,
+    /// Requests that failed at OpenAI or whose embedding could not be stored
+    pub failed: usize,
+    pub tokens: u64,
+    /// At the batch price, when the model's price is known
+    pub estimated_cost_usd: Option<f64>,
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "OpenAI batch report:")?;
+        let requests: usize = self.submitted.iter().map(|(_, requests)| requests).sum();
+        writeln!(f, "  Submitted: {} batches ({} requests)", self.submitted.len(), requests)?;
+        for (batch_id, requests) in &self.submitted {
+            writeln!(f, "    {}: {} requests", batch_id, requests)?;
+        }
+        writeln!(f, "  Open: {}", self.open.len())?;
+        for job in &self.open {
+            writeln!(
+                f,
+                "    {}: {} ({}/{} completed, {} failed)",
+                job.batch_id,
+                job.status,
+                job.completed_requests,
+                job.repos.len(),
+                job.failed_requests
+            )?;
+        }
+        writeln!(f, "  Finished: {}", self.finished)?;
+        writeln!(f, "  Ingested: {}", self.ingested)?;
+        writeln!(f, "  Rejected by validation: {}", self.rejected)?;
+        writeln!(f, "  Failed: {}", self.failed)?;
+        match self.estimated_cost_usd {
+            Some(cost) => writeln!(f, "  Tokens: {} (~${:.4} at the batch price)", self.tokens, cost)?,
+            None => writeln!(f, "  Tokens: {}", self.tokens)?,
+        }
+        Ok(())
+    }
+}
+
+/// Backfills embeddings through OpenAI's Batch API: pending repos are written to batch files
+/// and submitted, and finished batches are ingested like pipeline results
+pub struct BatchBackfill {
+    config: Arc<Config>,
+    api: OpenAIBatchClient,
+    client: SurrealClient,
+    validator: EmbeddingValidator,
+}
+
+impl BatchBackfill {
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        let config = Arc::new(config);
+        config.validate()?;
+        if config.embedding_provider != "openai" {
+            anyhow::bail!(
+                "The OpenAI Batch API needs EMBEDDING_PROVIDER=openai (configured: {})",
+                config.embedding_provider
+            );
+        }
+
+        // Pool code records metrics, so they must exist even for one-off commands
+        let registry = Registry::new();
+        Metrics::register(&registry)?;
+
+        let pool = create_pool(config.clone()).await?;
+        let client = SurrealClient::new(pool)
+            .with_quantization(config.quantization_mode()?, config.quantized_only)
+            .with_precision(config.storage_precision()?)
+            .with_storage_mode(config.storage_mode()?, config.embedding_model.clone())
+            .with_write_chunk_size(config.db_write_chunk_size)
+            .with_history(config.embedding_history, !config.embedding_history_metadata_only);
+        let validator = EmbeddingValidator::new(config.validation_config())
+            .with_metrics(config.embedding_model.clone());
+
+        Ok(Self {
+            api: OpenAIBatchClient::new(&config)?,
+            config,
+            client,
+            validator,
+        })
+    }
+
+    /// Submit one batch of up to `max_requests` pending repos not already in an open batch.
+    /// Returns `None` when nothing is left to submit.
+    pub async fn submit(&self, max_requests: usize, report: &mut BatchReport) -> anyhow::Result<Option<BatchJob>> {
+        let model = &self.config.embedding_model;
+        let open = self.client.load_open_batch_jobs().await?;
+        let in_flight: HashSet<String> = open.iter().flat_map(|job| job.repos.iter().map(|id| id.to_string())).collect();
+
+        let limit = max_requests.clamp(1, MAX_BATCH_REQUESTS);
+        let sanitizer = self.config.text_sanitizer();
+        let texts: Vec<(RecordId, String)> = self
+            .client
+            .get_repos_needing_embeddings(limit + in_flight.len())
+            .await?
+            .into_iter()
+            .filter(|repo| !in_flight.contains(&repo.id.to_string()))
+            .take(limit)
+            .map(|repo| {
+                let mut text = repo.prepare_text_for_embedding();
+                if let Some(sanitizer) = &sanitizer {
+                    text = sanitizer.sanitize(&text);
+                }
+                let text = truncate_to_limit(&text, self.config.token_limit);
+                (repo.id, text)
+            })
+            .collect();
+        if texts.is_empty() {
+            return Ok(None);
+        }
+
+        let file = build_batch_file(model, self.config.native_dimensions(), &texts)?;
+        let input_file_id = self.api.upload(file).await.context("Failed to upload batch file")?;
+        let batch = self.api.create(&input_file_id).await.context("Failed to create batch")?;
+
+        let now = Utc::now();
+        let job = BatchJob {
+            batch_id: batch.id,
+            model: model.clone(),
+            status: batch.status,
+            input_file_id: batch.input_file_id,
+            output_file_id: None,
+            error_file_id: None,
+            repos: texts.into_iter().map(|(repo_id, _)| repo_id).collect(),
+            completed_requests: 0,
+            failed_requests: 0,
+            ingested: 0,
+            done: false,
+            created_at: now,
+            updated_at: now,
+        };
+        self.client.save_batch_job(&job).await?;
+        info!(batch_id = %job.batch_id, requests = job.repos.len(), "Submitted OpenAI batch");
+        report.submitted.push((job.batch_id.clone(), job.repos.len()));
+        Ok(Some(job))
+    }
+
+    /// Refresh every open batch, ingesting those that have finished. Returns the ones still open.
+    pub async fn poll(&self, report: &mut BatchReport) -> anyhow::Result<Vec<BatchJob>> {
+        let mut open = Vec::new();
+        for mut job in self.client.load_open_batch_jobs().await? {
+            let batch = match self.api.retrieve(&job.batch_id).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    warn!(batch_id = %job.batch_id, error = %e, "Failed to poll OpenAI batch");
+                    open.push(job);
+                    continue;
+                }
+            };
+            job.status = batch.status;
+            job.output_file_id = batch.output_file_id;
+            job.error_file_id = batch.error_file_id;
+            job.completed_requests = batch.request_counts.completed;
+            job.failed_requests = batch.request_counts.failed;
+            job.updated_at = Utc::now();
+
+            if job.is_final() {
+                self.ingest(&mut job, report).await?;
+                report.finished += 1;
+                info!(batch_id = %job.batch_id, status = %job.status, ingested = job.ingested, "OpenAI batch finished");
+            } else {
+                open.push(job.clone());
+            }
+            self.client.save_batch_job(&job).await?;
+        }
+        report.open = open.clone();
+        Ok(open)
+    }
+
+    async fn ingest(&self, job: &mut BatchJob, report: &mut BatchReport) -> anyhow::Result<()> {
+        let mut results = Vec::new();
+        for file_id in job.output_file_id.iter().chain(job.error_file_id.iter()) {
+            let content = self
+                .api
+                .file_content(file_id)
+                .await
+                .with_context(|| format!("Failed to download batch file {}", file_id))?;
+            results.extend(parse_batch_output(&content));
+        }
+
+        let mut updates = Vec::new();
+        let mut tokens = 0;
+        for result in results {
+            let repo_id = match parse_repo_id(&result.custom_id) {
+                Ok(repo_id) => repo_id,
+                Err(e) => {
+                    warn!(custom_id = %result.custom_id, error = %e, "Unknown request in batch output");
+                    report.failed += 1;
+                    continue;
+                }
+            };
+            let (mut embedding, usage) = match result.outcome {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    warn!(repo = %repo_id, error = %e, "Batch request failed");
+                    report.failed += 1;
+                    continue;
+                }
+            };
+            tokens += usage.unwrap_or(0);
+            // Models without native `dimensions` support come back full length
+            if let Some(dimensions) = self.config.target_dimensions {
+                embedding = truncate_dimensions(embedding, dimensions);
+            }
+
+            let source = repo_id.to_string();
+            let validated = self.validator.validate_or_repair(&mut embedding, &source).and_then(|outcome| {
+                let normalized = self.validator.prepare_for_storage(&mut embedding)?;
+                Ok((normalized, outcome == ValidationOutcome::Repaired))
+            });
+            match validated {
+                Ok((normalized, repaired)) => updates.push(EmbeddingUpdate {
+                    repo_id,
+                    embedding: embedding.into(),
+                    normalized,
+                    repaired,
+                    updated_at: Utc::now(),
+                    model: None,
+                    language: None,
+                }),
+                Err(e) => {
+                    warn!(repo = %repo_id, error = %e, "Batch embedding failed validation");
+                    report.rejected += 1;
+                }
+            }
+        }
+
+        metrics::record_prompt_tokens("openai", &job.model, tokens, true);
+        report.tokens += tokens;
+        if let Some(price) = price_per_million_tokens("openai", &job.model) {
+            *report.estimated_cost_usd.get_or_insert(0.0) += tokens as f64 / 1_000_000.0 * price * BATCH_DISCOUNT;
+        }
+
+        let result = self.client.batch_update_embeddings(updates).await?;
+        job.ingested = result.successful;
+        report.ingested += result.successful;
+        report.failed += result.failed;
+        job.done = true;
+        Ok(())
+    }
+}
+
+/// Run the `openai-batch submit` command
+pub async fn run_submit(config: Config, max_requests: usize) -> anyhow::Result<BatchReport> {
+    let backfill = BatchBackfill::new(config).await?;
+    let mut report = BatchReport::default();
+    backfill.submit(max_requests, &mut report).await?;
+    Ok(report)
+}
+
+/// Run the `openai-batch poll` command
+pub async fn run_poll(config: Config) -> anyhow::Result<BatchReport> {
+    let backfill = BatchBackfill::new(config).await?;
+    let mut report = BatchReport::default();
+    backfill.poll(&mut report).await?;
+    Ok(report)
+}
+
+/// Run the `openai-batch run` command: keep up to `max_open` batches in flight, polling every
+/// `poll_interval`, until no repo is left to submit and every batch has been ingested
+pub async fn run_backfill(
+    config: Config,
+    max_requests: usize,
+    max_open: usize,
+    poll_interval: Duration,
+) -> anyhow::Result<BatchReport> {
+    let backfill = BatchBackfill::new(config).await?;
+    let mut report = BatchReport::default();
+    loop {
+        let mut open = backfill.poll(&mut report).await?.len();
+        let mut exhausted = false;
+        while open < max_open.max(1) {
+            if backfill.submit(max_requests, &mut report).await?.is_none() {
+                exhausted = true;
+                break;
+            }
+            open += 1;
+        }
+        if exhausted && open == 0 {
+            break;
+        }
+        report.open = backfill.client.load_open_batch_jobs().await?;
+        info!(open, "Waiting for OpenAI batches");
+        tokio::time::sleep(poll_interval).await;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_file_round_trip() {
+        let texts = vec![
+            (RecordId::from(("repo", "abc")), "A web framework".to_string()),
+            (RecordId::from(("repo", "owner/name")), "A CLI tool".to_string()),
+        ];
+        let file = build_batch_file("text-embedding-3-small", Some(256), &texts).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["url"], "/v1/embeddings");
+        assert_eq!(lines[0]["body"]["dimensions"], 256);
+        assert_eq!(lines[1]["body"]["input"], "A CLI tool");
+        let custom_id = lines[1]["custom_id"].as_str().unwrap();
+        assert_eq!(parse_repo_id(custom_id).unwrap(), texts[1].0);
+
+        let output = [
+            r#"{"custom_id":"repo:abc","response":{"status_code":200,"body":{"data":[{"embedding":[0.5,0.5]}],"usage":{"prompt_tokens":7}}},"error":null}"#,
+            r#"{"custom_id":"repo:def","response":{"status_code":400,"body":{"error":"too long"}},"error":null}"#,
+            r#"{"custom_id":"repo:ghi","response":null,"error":{"code":"batch_expired"}}"#,
+            "not json",
+        ]
+        .join("\n");
+        let results = parse_batch_output(&output);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].outcome, Ok((vec![0.5, 0.5], Some(7))));
+        assert!(results[1].outcome.as_ref().unwrap_err().contains("HTTP 400"));
+        assert!(results[2].outcome.as_ref().unwrap_err().contains("batch_expired"));
+    }
+}
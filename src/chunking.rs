@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// How the vectors of a chunked text are combined into the repo's embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolingStrategy {
+    /// Every chunk counts the same
+    #[default]
+    Mean,
+    /// Chunks count in proportion to their length, so a short tail chunk doesn't skew the result
+    Weighted,
+}
+
+impl PoolingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolingStrategy::Mean => "mean",
+            PoolingStrategy::Weighted => "weighted",
+        }
+    }
+}
+
+impl FromStr for PoolingStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mean" | "" => Ok(PoolingStrategy::Mean),
+            "weighted" => Ok(PoolingStrategy::Weighted),
+            other => Err(format!(
+                "Unknown chunk pooling strategy '{}' (expected mean or weighted)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PoolingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Splits texts longer than the model accepts into overlapping chunks and pools their vectors,
+/// so long READMEs are embedded whole instead of being truncated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunker {
+    /// Maximum chunk length in characters
+    chunk_size: usize,
+    /// Characters repeated at the start of each chunk from the end of the previous one
+    overlap: usize,
+    pooling: PoolingStrategy,
+}
+
+impl Chunker {
+    /// `overlap` is clamped below `chunk_size` so chunking always advances
+    pub fn new(chunk_size: usize, overlap: usize, pooling: PoolingStrategy) -> Self {
+        let chunk_size = chunk_size.max(1);
+        Self {
+            chunk_size,
+            overlap: overlap.min(chunk_size - 1),
+            pooling,
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn pooling(&self) -> PoolingStrategy {
+        self.pooling
+    }
+
+    /// Split `text` into chunks of at most `chunk_size` characters. Chunks end at whitespace
+    /// when there is some in their second half, so words aren't cut in two.
+    pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        // Byte offset of every character, plus the end of the text
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let chars = offsets.len() - 1;
+        if chars <= self.chunk_size {
+            return vec![text];
+        }
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        loop {
+            let mut end = (start + self.chunk_size).min(chars);
+            if end < chars {
+                if let Some(space) = (start + self.chunk_size / 2 + 1..end)
+                    .rev()
+                    .find(|&i| text[offsets[i]..].starts_with(char::is_whitespace))
+                {
+                    end = space;
+                }
+            }
+            chunks.push(&text[offsets[start]..offsets[end]]);
+            if end == chars {
+                return chunks;
+            }
+            start = end.saturating_sub(self.overlap).max(start + 1);
+        }
+    }
+
+    /// Combine the vectors of `chunks` into one, scaled to their average magnitude so the result
+    /// looks like a single provider response to validation and normalization
+    pub fn pool(&self, chunks: &[&str], embeddings: &[Vec<f32>]) -> Vec<f32> {
+        let dimensions = embeddings.first().map_or(0, Vec::len);
+        let weights: Vec<f32> = match self.pooling {
+            PoolingStrategy::Mean => vec![1.0; embeddings.len()],
+            PoolingStrategy::Weighted => chunks.iter().map(|chunk| chunk.chars().count() as f32).collect(),
+        };
+        let total_weight: f32 = weights.iter().sum();
+        if dimensions == 0 || total_weight <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut pooled = vec![0.0f32; dimensions];
+        for (embedding, weight) in embeddings.iter().zip(&weights) {
+            for (sum, value) in pooled.iter_mut().zip(embedding) {
+                *sum += value * weight / total_weight;
+            }
+        }
+
+        let magnitude = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let target = embeddings.iter().map(|e| magnitude(e)).sum::<f32>() / embeddings.len() as f32;
+        let current = magnitude(&pooled);
+        if current > 0.0 {
+            for value in pooled.iter_mut() {
+                *value *= target / current;
+            }
+        }
+        pooled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_pool() {
+        let chunker = Chunker::new(10, 3, PoolingStrategy::Mean);
+        assert_eq!(chunker.split("short text"), vec!["short text"]);
+
+        let text = "alpha beta gamma delta epsilon";
+        let chunks = chunker.split(text);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 10));
+        // Breaks fall on whitespace and each chunk repeats the end of the previous one
+        assert_eq!(chunks[0], "alpha beta");
+        assert!(chunks[1].starts_with("eta"));
+        assert!(text.ends_with(chunks.last().unwrap()));
+
+        // Multi-byte characters are never split
+        let text = "é".repeat(25);
+        assert!(chunker.split(&text).iter().all(|chunk| chunk.chars().all(|c| c == 'é')));
+
+        let pooled = chunker.pool(&["aaaa", "bb"], &[vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!((pooled[0] - pooled[1]).abs() < 1e-6);
+        assert!((pooled.iter().map(|x| x * x).sum::<f32>().sqrt() - 1.0).abs() < 1e-6);
+
+        let weighted = Chunker::new(10, 3, PoolingStrategy::Weighted)
+            .pool(&["aaaa", "bb"], &[vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!(weighted[0] > weighted[1]);
+        assert!("max".parse::<PoolingStrategy>().is_err());
+    }
+}
@@ -1,9 +1,12 @@
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::Arc,
     time::{Duration, Instant},
 };
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, info, warn};
 
 /// Circuit breaker states
@@ -17,8 +20,42 @@ pub enum CircuitState {
     HalfOpen,
 }
 
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "closed" => Some(CircuitState::Closed),
+            "open" => Some(CircuitState::Open),
+            "half_open" => Some(CircuitState::HalfOpen),
+            _ => None,
+        }
+    }
+}
+
+/// Persisted form of a breaker, written on every state transition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitSnapshot {
+    pub service: String,
+    pub state: String,
+    pub changed_at: DateTime<Utc>,
+    /// How long the circuit stays open, including an operator override
+    pub open_secs: u64,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    pub successful_requests: u64,
+    pub consecutive_failures: u32,
+    pub state_changes: u64,
+}
+
 /// Circuit breaker statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CircuitStats {
     pub total_requests: u64,
     pub failed_requests: u64,
@@ -26,19 +63,10 @@ pub struct CircuitStats {
     pub consecutive_failures: u32,
     pub last_failure_time: Option<Instant>,
     pub state_changes: u64,
-}
-
-impl Default for CircuitStats {
-    fn default() -> Self {
-        Self {
-            total_requests: 0,
-            failed_requests: 0,
-            successful_requests: 0,
-            consecutive_failures: 0,
-            last_failure_time: None,
-            state_changes: 0,
-        }
-    }
+    /// Requests within the sliding window
+    pub window_requests: u64,
+    /// Failures within the sliding window
+    pub window_failures: u64,
 }
 
 /// Configuration for a circuit breaker
@@ -52,8 +80,12 @@ pub struct CircuitBreakerConfig {
     pub success_threshold: u32,
     /// Failure rate threshold (0.0 to 1.0) for opening the circuit
     pub failure_rate_threshold: f64,
-    /// Minimum number of requests before failure rate is considered
+    /// Minimum number of requests in the window before failure rate is considered
     pub min_requests: u64,
+    /// Sliding window over which the failure rate is computed
+    pub window: Duration,
+    /// Maximum concurrent trial requests while half-open
+    pub half_open_max_probes: u32,
 }
 
 impl Default for CircuitBreakerConfig {
@@ -64,6 +96,41 @@ impl Default for CircuitBreakerConfig {
             success_threshold: 3,
             failure_rate_threshold: 0.5,
             min_requests: 10,
+            window: Duration::from_secs(60),
+            half_open_max_probes: 1,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Defaults tuned for each embedding provider
+    pub fn for_provider(provider: &str) -> Self {
+        match provider {
+            "openai" => Self {
+                failure_threshold: 5,
+                timeout_duration: Duration::from_secs(120),
+                success_threshold: 3,
+                failure_rate_threshold: 0.5,
+                min_requests: 10,
+                ..Self::default()
+            },
+            "together" => Self {
+                failure_threshold: 10,
+                timeout_duration: Duration::from_secs(60),
+                success_threshold: 5,
+                failure_rate_threshold: 0.6,
+                min_requests: 20,
+                ..Self::default()
+            },
+            "ollama" => Self {
+                failure_threshold: 3,
+                timeout_duration: Duration::from_secs(30),
+                success_threshold: 2,
+                failure_rate_threshold: 0.3,
+                min_requests: 5,
+                ..Self::default()
+            },
+            _ => Self::default(),
         }
     }
 }
@@ -75,6 +142,11 @@ struct CircuitBreaker {
     config: CircuitBreakerConfig,
     last_state_change: Instant,
     half_open_successes: u32,
+    half_open_in_flight: u32,
+    /// Open duration set by an operator, replacing `timeout_duration` until the circuit leaves Open
+    forced_timeout: Option<Duration>,
+    /// Outcomes inside the sliding window, oldest first (`true` = failure)
+    outcomes: VecDeque<(Instant, bool)>,
 }
 
 impl CircuitBreaker {
@@ -85,38 +157,89 @@ impl CircuitBreaker {
             config,
             last_state_change: Instant::now(),
             half_open_successes: 0,
+            half_open_in_flight: 0,
+            forced_timeout: None,
+            outcomes: VecDeque::new(),
         }
     }
 
+    fn open_duration(&self) -> Duration {
+        self.forced_timeout.unwrap_or(self.config.timeout_duration)
+    }
+
     fn should_allow_request(&mut self) -> bool {
         match self.state {
             CircuitState::Closed => true,
             CircuitState::Open => {
                 // Check if timeout has passed
-                if self.last_state_change.elapsed() >= self.config.timeout_duration {
+                if self.last_state_change.elapsed() >= self.open_duration() {
                     self.transition_to(CircuitState::HalfOpen);
-                    true
+                    self.try_probe()
                 } else {
                     false
                 }
             }
-            CircuitState::HalfOpen => true,
+            CircuitState::HalfOpen => self.try_probe(),
+        }
+    }
+
+    /// Admit a trial request while half-open, up to the configured number in flight
+    fn try_probe(&mut self) -> bool {
+        if self.half_open_in_flight < self.config.half_open_max_probes.max(1) {
+            self.half_open_in_flight += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Add an outcome to the window and drop the ones that have aged out
+    fn push_outcome(&mut self, failed: bool) {
+        let now = Instant::now();
+        self.outcomes.push_back((now, failed));
+        while let Some(&(at, _)) = self.outcomes.front() {
+            if now.duration_since(at) <= self.config.window {
+                break;
+            }
+            self.outcomes.pop_front();
         }
+        self.stats.window_requests = self.outcomes.len() as u64;
+        self.stats.window_failures = self.outcomes.iter().filter(|(_, failed)| *failed).count() as u64;
+    }
+
+    fn snapshot(&self, service: &str) -> CircuitSnapshot {
+        let since_change = chrono::Duration::from_std(self.last_state_change.elapsed()).unwrap_or_default();
+        CircuitSnapshot {
+            service: service.to_string(),
+            state: self.state.as_str().to_string(),
+            changed_at: Utc::now() - since_change,
+            open_secs: self.open_duration().as_secs(),
+            total_requests: self.stats.total_requests,
+            failed_requests: self.stats.failed_requests,
+            successful_requests: self.stats.successful_requests,
+            consecutive_failures: self.stats.consecutive_failures,
+            state_changes: self.stats.state_changes,
+        }
+    }
+
+    fn clear_window(&mut self) {
+        self.outcomes.clear();
+        self.stats.window_requests = 0;
+        self.stats.window_failures = 0;
     }
 
     fn record_success(&mut self) {
         self.stats.total_requests += 1;
         self.stats.successful_requests += 1;
         self.stats.consecutive_failures = 0;
+        self.push_outcome(false);
 
-        match self.state {
-            CircuitState::HalfOpen => {
-                self.half_open_successes += 1;
-                if self.half_open_successes >= self.config.success_threshold {
-                    self.transition_to(CircuitState::Closed);
-                }
+        if self.state == CircuitState::HalfOpen {
+            self.half_open_in_flight = self.half_open_in_flight.saturating_sub(1);
+            self.half_open_successes += 1;
+            if self.half_open_successes >= self.config.success_threshold {
+                self.transition_to(CircuitState::Closed);
             }
-            _ => {}
         }
     }
 
@@ -125,14 +248,15 @@ impl CircuitBreaker {
         self.stats.failed_requests += 1;
         self.stats.consecutive_failures += 1;
         self.stats.last_failure_time = Some(Instant::now());
+        self.push_outcome(true);
 
         match self.state {
             CircuitState::Closed => {
                 // Check if we should open the circuit
                 if self.stats.consecutive_failures >= self.config.failure_threshold {
                     self.transition_to(CircuitState::Open);
-                } else if self.stats.total_requests >= self.config.min_requests {
-                    let failure_rate = self.stats.failed_requests as f64 / self.stats.total_requests as f64;
+                } else if self.stats.window_requests >= self.config.min_requests {
+                    let failure_rate = self.stats.window_failures as f64 / self.stats.window_requests as f64;
                     if failure_rate >= self.config.failure_rate_threshold {
                         self.transition_to(CircuitState::Open);
                     }
@@ -159,6 +283,12 @@ impl CircuitBreaker {
             if new_state == CircuitState::HalfOpen {
                 self.half_open_successes = 0;
             }
+            if new_state != CircuitState::Open {
+                self.forced_timeout = None;
+                // Recovery starts from a clean window; failures from before the outage don't count
+                self.half_open_in_flight = 0;
+                self.clear_window();
+            }
         }
     }
 }
@@ -167,6 +297,7 @@ impl CircuitBreaker {
 pub struct CircuitBreakerManager {
     breakers: Arc<RwLock<HashMap<String, CircuitBreaker>>>,
     default_config: CircuitBreakerConfig,
+    persist: Option<UnboundedSender<CircuitSnapshot>>,
 }
 
 impl CircuitBreakerManager {
@@ -174,7 +305,67 @@ impl CircuitBreakerManager {
         Self {
             breakers: Arc::new(RwLock::new(HashMap::new())),
             default_config: CircuitBreakerConfig::default(),
+            persist: None,
+        }
+    }
+
+    /// Send a snapshot on every state transition so it can be stored and restored after a restart
+    pub fn with_persistence(mut self, sender: UnboundedSender<CircuitSnapshot>) -> Self {
+        self.persist = Some(sender);
+        self
+    }
+
+    fn persist(&self, service: &str, breaker: &CircuitBreaker) {
+        if let Some(sender) = &self.persist {
+            // The receiver only goes away at shutdown
+            let _ = sender.send(breaker.snapshot(service));
+        }
+    }
+
+    /// Restore a persisted breaker. Snapshots older than `max_age` are ignored, and an open
+    /// circuit whose timeout ran out while the service was down comes back half-open.
+    pub fn restore(&self, snapshot: CircuitSnapshot, max_age: Duration) -> bool {
+        let Some(state) = CircuitState::parse(&snapshot.state) else {
+            warn!("Ignoring circuit breaker snapshot with unknown state: {}", snapshot.state);
+            return false;
+        };
+        let age = (Utc::now() - snapshot.changed_at).to_std().unwrap_or_default();
+        if age > max_age {
+            debug!("Ignoring stale circuit breaker snapshot for {} ({:?} old)", snapshot.service, age);
+            return false;
         }
+
+        let mut breakers = self.breakers.write();
+        let breaker = breakers
+            .entry(snapshot.service.clone())
+            .or_insert_with(|| CircuitBreaker::new(self.default_config.clone()));
+
+        breaker.stats.total_requests = snapshot.total_requests;
+        breaker.stats.failed_requests = snapshot.failed_requests;
+        breaker.stats.successful_requests = snapshot.successful_requests;
+        breaker.stats.consecutive_failures = snapshot.consecutive_failures;
+        breaker.stats.state_changes = snapshot.state_changes;
+
+        let open_for = Duration::from_secs(snapshot.open_secs);
+        breaker.state = match state {
+            CircuitState::Open if age >= open_for => CircuitState::HalfOpen,
+            state => state,
+        };
+        breaker.forced_timeout = (breaker.state == CircuitState::Open
+            && open_for != breaker.config.timeout_duration)
+            .then_some(open_for);
+        breaker.last_state_change = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+        breaker.half_open_successes = 0;
+        breaker.half_open_in_flight = 0;
+
+        info!(
+            "Restored circuit breaker for service {} as {} (saved {:?} ago)",
+            snapshot.service,
+            breaker.state.as_str(),
+            age
+        );
+        crate::metrics::record_circuit_breaker_state(&snapshot.service, breaker.state.as_str());
+        true
     }
 
     /// Configure a specific service with custom settings
@@ -191,18 +382,17 @@ impl CircuitBreakerManager {
             .entry(service.to_string())
             .or_insert_with(|| CircuitBreaker::new(self.default_config.clone()));
 
+        let before = breaker.state;
         let allowed = breaker.should_allow_request();
+        if breaker.state != before {
+            self.persist(service, breaker);
+        }
         
         if !allowed {
             warn!("Circuit breaker OPEN for service: {}", service);
             crate::metrics::record_circuit_breaker_state(service, "open");
         } else {
-            let state_str = match breaker.state {
-                CircuitState::Closed => "closed",
-                CircuitState::Open => "open",
-                CircuitState::HalfOpen => "half_open",
-            };
-            crate::metrics::record_circuit_breaker_state(service, state_str);
+            crate::metrics::record_circuit_breaker_state(service, breaker.state.as_str());
         }
 
         allowed
@@ -212,8 +402,12 @@ impl CircuitBreakerManager {
     pub fn record_success(&self, service: &str) {
         let mut breakers = self.breakers.write();
         if let Some(breaker) = breakers.get_mut(service) {
+            let before = breaker.state;
             breaker.record_success();
             debug!("Recorded success for service: {}", service);
+            if breaker.state != before {
+                self.persist(service, breaker);
+            }
         }
     }
 
@@ -221,11 +415,15 @@ impl CircuitBreakerManager {
     pub fn record_failure(&self, service: &str) {
         let mut breakers = self.breakers.write();
         if let Some(breaker) = breakers.get_mut(service) {
+            let before = breaker.state;
             breaker.record_failure();
             warn!(
                 "Recorded failure for service: {} (consecutive failures: {})",
                 service, breaker.stats.consecutive_failures
             );
+            if breaker.state != before {
+                self.persist(service, breaker);
+            }
         }
     }
 
@@ -241,6 +439,20 @@ impl CircuitBreakerManager {
         breakers.get(service).map(|b| b.state)
     }
 
+    /// Time left before an open circuit allows a trial request, or `None` if requests may flow.
+    /// Unlike `should_allow_request` this never changes the breaker's state.
+    pub fn open_remaining(&self, service: &str) -> Option<Duration> {
+        let breakers = self.breakers.read();
+        let breaker = breakers.get(service)?;
+        if breaker.state != CircuitState::Open {
+            return None;
+        }
+        breaker
+            .open_duration()
+            .checked_sub(breaker.last_state_change.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
     /// Get all services and their states
     pub fn get_all_states(&self) -> HashMap<String, CircuitState> {
         let breakers = self.breakers.read();
@@ -250,15 +462,56 @@ impl CircuitBreakerManager {
             .collect()
     }
 
-    /// Reset a circuit breaker for a service
-    pub fn reset(&self, service: &str) {
+    /// Get all services and their statistics
+    pub fn get_all_stats(&self) -> HashMap<String, (CircuitState, CircuitStats)> {
+        let breakers = self.breakers.read();
+        breakers
+            .iter()
+            .map(|(service, breaker)| (service.clone(), (breaker.state, breaker.stats.clone())))
+            .collect()
+    }
+
+    /// Reset a circuit breaker for a service. Returns false if the service is unknown.
+    pub fn reset(&self, service: &str) -> bool {
         let mut breakers = self.breakers.write();
-        if let Some(breaker) = breakers.get_mut(service) {
-            breaker.state = CircuitState::Closed;
-            breaker.stats.consecutive_failures = 0;
-            breaker.half_open_successes = 0;
-            breaker.last_state_change = Instant::now();
-            info!("Reset circuit breaker for service: {}", service);
+        match breakers.get_mut(service) {
+            Some(breaker) => {
+                breaker.state = CircuitState::Closed;
+                breaker.stats.consecutive_failures = 0;
+                breaker.half_open_successes = 0;
+                breaker.half_open_in_flight = 0;
+                breaker.forced_timeout = None;
+                breaker.clear_window();
+                breaker.last_state_change = Instant::now();
+                info!("Reset circuit breaker for service: {}", service);
+                crate::metrics::record_circuit_breaker_state(service, "closed");
+                self.persist(service, breaker);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Open a circuit by hand, for `duration` or the configured timeout, e.g. while a
+    /// provider is known to be down. Returns false if the service is unknown.
+    pub fn force_open(&self, service: &str, duration: Option<Duration>) -> bool {
+        let mut breakers = self.breakers.write();
+        match breakers.get_mut(service) {
+            Some(breaker) => {
+                breaker.transition_to(CircuitState::Open);
+                // Restart the timer even if the circuit was already open
+                breaker.last_state_change = Instant::now();
+                breaker.forced_timeout = duration;
+                warn!(
+                    "Circuit breaker for service {} forced open for {:?}",
+                    service,
+                    breaker.open_duration()
+                );
+                crate::metrics::record_circuit_breaker_state(service, "open");
+                self.persist(service, breaker);
+                true
+            }
+            None => false,
         }
     }
 }
@@ -284,6 +537,8 @@ macro_rules! with_circuit_breaker {
                     $manager.record_success($service);
                     Ok(result)
                 }
+                // A bad input says nothing about the provider's health, though a hung request does
+                Err(e) if e.is_record_specific() && !matches!(e, $crate::error::EmbedError::Timeout(_)) => Err(e),
                 Err(e) => {
                     $manager.record_failure($service);
                     Err(e)
@@ -346,4 +601,171 @@ mod tests {
         breaker.record_success();
         assert_eq!(breaker.state, CircuitState::Closed);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_record_specific_errors_do_not_trip_breaker() {
+        use crate::error::EmbedError;
+
+        let manager = CircuitBreakerManager::new();
+        manager.configure_service(
+            "test",
+            CircuitBreakerConfig {
+                failure_threshold: 2,
+                ..Default::default()
+            },
+        );
+
+        for _ in 0..5 {
+            let result: Result<(), EmbedError> =
+                crate::with_circuit_breaker!(manager, "test", Err(EmbedError::InputTooLong("long".to_string())));
+            assert!(result.is_err());
+        }
+        assert_eq!(manager.get_state("test"), Some(CircuitState::Closed));
+
+        for _ in 0..2 {
+            let _: Result<(), EmbedError> = crate::with_circuit_breaker!(
+                manager,
+                "test",
+                Err(EmbedError::ServerError {
+                    status: 502,
+                    message: "bad gateway".to_string(),
+                })
+            );
+        }
+        assert_eq!(manager.get_state("test"), Some(CircuitState::Open));
+    }
+
+    #[test]
+    fn test_open_remaining() {
+        let manager = CircuitBreakerManager::new();
+        manager.configure_service(
+            "provider",
+            CircuitBreakerConfig {
+                failure_threshold: 1,
+                timeout_duration: Duration::from_millis(100),
+                ..Default::default()
+            },
+        );
+
+        assert!(manager.open_remaining("provider").is_none());
+        assert!(manager.open_remaining("unknown").is_none());
+
+        manager.record_failure("provider");
+        assert!(manager.open_remaining("provider").is_some());
+        assert_eq!(manager.get_state("provider"), Some(CircuitState::Open));
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(manager.open_remaining("provider").is_none());
+    }
+
+    #[test]
+    fn test_failure_rate_uses_sliding_window() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 100,
+            failure_rate_threshold: 0.5,
+            min_requests: 4,
+            window: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut breaker = CircuitBreaker::new(config);
+
+        // Plenty of old successes must not dilute a fresh burst of failures
+        for _ in 0..50 {
+            breaker.record_success();
+        }
+        std::thread::sleep(Duration::from_millis(150));
+
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state, CircuitState::Closed);
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.stats.window_requests, 4);
+        assert_eq!(breaker.state, CircuitState::Open);
+    }
+
+    #[test]
+    fn test_half_open_probe_limit() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 1,
+            timeout_duration: Duration::from_millis(50),
+            success_threshold: 2,
+            half_open_max_probes: 2,
+            ..Default::default()
+        };
+        let mut breaker = CircuitBreaker::new(config);
+
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(80));
+
+        assert!(breaker.should_allow_request());
+        assert!(breaker.should_allow_request());
+        assert!(!breaker.should_allow_request());
+
+        // A finished probe frees its slot
+        breaker.record_success();
+        assert!(breaker.should_allow_request());
+    }
+
+    #[test]
+    fn test_force_open_and_reset() {
+        let manager = CircuitBreakerManager::new();
+        manager.configure_service("provider", CircuitBreakerConfig::default());
+        assert!(!manager.force_open("unknown", None));
+
+        assert!(manager.force_open("provider", Some(Duration::from_millis(50))));
+        assert_eq!(manager.get_state("provider"), Some(CircuitState::Open));
+        assert!(manager.open_remaining("provider").unwrap() <= Duration::from_millis(50));
+
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(manager.open_remaining("provider").is_none());
+
+        assert!(manager.force_open("provider", None));
+        assert!(manager.reset("provider"));
+        assert_eq!(manager.get_state("provider"), Some(CircuitState::Closed));
+        assert!(manager.open_remaining("provider").is_none());
+    }
+
+    #[test]
+    fn test_snapshot_restore_with_decay() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = CircuitBreakerManager::new().with_persistence(tx);
+        manager.configure_service(
+            "provider",
+            CircuitBreakerConfig {
+                failure_threshold: 1,
+                timeout_duration: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+
+        manager.record_failure("provider");
+        let snapshot = rx.try_recv().expect("transition should be persisted");
+        assert_eq!(snapshot.state, "open");
+        assert_eq!(snapshot.open_secs, 60);
+
+        // A fresh open snapshot keeps the circuit open
+        let restarted = CircuitBreakerManager::new();
+        assert!(restarted.restore(snapshot.clone(), Duration::from_secs(3600)));
+        assert_eq!(restarted.get_state("provider"), Some(CircuitState::Open));
+        assert!(restarted.open_remaining("provider").is_some());
+
+        // Once the open timeout has passed it comes back half-open
+        let expired = CircuitSnapshot {
+            changed_at: Utc::now() - chrono::Duration::seconds(120),
+            ..snapshot.clone()
+        };
+        let restarted = CircuitBreakerManager::new();
+        assert!(restarted.restore(expired, Duration::from_secs(3600)));
+        assert_eq!(restarted.get_state("provider"), Some(CircuitState::HalfOpen));
+
+        // Snapshots older than the max age are ignored
+        let stale = CircuitSnapshot {
+            changed_at: Utc::now() - chrono::Duration::hours(2),
+            ..snapshot
+        };
+        let restarted = CircuitBreakerManager::new();
+        assert!(!restarted.restore(stale, Duration::from_secs(3600)));
+        assert_eq!(restarted.get_state("provider"), None);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    ab, batch_jobs, clustering, config::Config, config_check, eval, history, import, service, stats, text_sizes,
+    user_embeddings, verify,
+};
+use clap::Subcommand;
+use std::{path::PathBuf, time::Duration};
+
+/// One-off maintenance commands; without a subcommand the embedding service runs
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Audit stored embeddings for dimension, NaN/Inf and magnitude problems
+    Verify {
+        /// Clear invalid embeddings so the service re-embeds those repos
+        #[arg(long)]
+        mark_invalid: bool,
+
+        /// Number of stored embeddings fetched per query
+        #[arg(long, default_value = "500")]
+        page_size: usize,
+    },
+
+    /// Print embedding coverage: totals, per-language and per-model counts, backlog age
+    /// percentiles and average dimensions
+    Stats,
+
+    /// Report estimated token counts of the texts prepared for embedding and list the longest
+    /// repos over TOKEN_LIMIT, for tuning the limit
+    TextSizes {
+        /// Repos over the limit listed, longest first
+        #[arg(long, default_value = "20")]
+        top: usize,
+
+        /// Repos fetched per query
+        #[arg(long, default_value = "500")]
+        page_size: usize,
+    },
+
+    /// Bulk-load precomputed embeddings (id, model, vector) from a JSONL or Parquet file
+    Import {
+        /// File to read; the format is taken from the extension unless --format is given
+        path: PathBuf,
+
+        /// jsonl or parquet (Parquet needs the `parquet` feature)
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Embeddings written per database round trip
+        #[arg(long, default_value = "500")]
+        batch_size: usize,
+
+        /// Validate the file without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Recompute per-user embeddings from the embeddings of the repos each user starred
+    UserEmbeddings {
+        /// Recompute every user rather than only those affected since the last refresh
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Group stored embeddings into topics with k-means, label each topic with the most
+    /// distinctive terms from its repos' descriptions and write every repo's cluster back
+    Cluster {
+        /// Number of clusters
+        #[arg(long, default_value = "50")]
+        k: usize,
+
+        /// Maximum k-means iterations
+        #[arg(long, default_value = "25")]
+        iterations: usize,
+
+        /// Print the clusters without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Measure how well stored embeddings rank labelled similar repos (MRR, recall@k),
+    /// optionally comparing several models stored in the `repo_embedding` table
+    Eval {
+        /// JSONL file of {"query", "candidate", "similar"} pairs of repo ids
+        path: PathBuf,
+
+        /// Cutoffs for recall@k
+        #[arg(long, value_delimiter = ',', default_value = "1,5,10")]
+        k: Vec<usize>,
+
+        /// Models to compare (default: the configured model)
+        #[arg(long, value_delimiter = ',')]
+        models: Vec<String>,
+    },
+
+    /// Compare AB_MODEL's embeddings of the sampled repos with the configured model's: rank
+    /// correlation of pairwise similarities and overlap of nearest neighbours
+    AbReport {
+        /// Neighbours compared per repo
+        #[arg(long, default_value = "10")]
+        k: usize,
+    },
+
+    /// Restore each repo's most recently archived embedding of a model from
+    /// `repo_embedding_history` (kept when EMBEDDING_HISTORY is set), e.g. after a model
+    /// change made results worse
+    RollbackEmbeddings {
+        /// Model whose archived embeddings are restored
+        #[arg(long)]
+        model: String,
+
+        /// Count the repos that would be restored without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Backfill embeddings through OpenAI's asynchronous Batch API at half the price, with
+    /// results arriving within 24 hours; needs EMBEDDING_PROVIDER=openai
+    OpenaiBatch {
+        #[command(subcommand)]
+        action: BatchCommand,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Check every setting and print a report; exits non-zero if any check fails
+    Validate {
+        /// Connect and authenticate to the database and check its schema
+        #[arg(long)]
+        connect: bool,
+
+        /// Embed a short probe text with the configured provider
+        #[arg(long)]
+        probe: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BatchCommand {
+    /// Write pending repos not already in an open batch to a batch file and submit it
+    Submit {
+        /// Requests in the batch (OpenAI accepts at most 50000)
+        #[arg(long, default_value = "50000")]
+        max_requests: usize,
+    },
+
+    /// Check open batches once and ingest the ones that have finished
+    Poll,
+
+    /// Submit and poll until every pending repo has been embedded
+    Run {
+        /// Requests per batch
+        #[arg(long, default_value = "50000")]
+        max_requests: usize,
+
+        /// Batches kept in flight at once
+        #[arg(long, default_value = "4")]
+        max_open: usize,
+
+        /// Seconds between polls
+        #[arg(long, default_value = "60")]
+        poll_secs: u64,
+    },
+}
+
+/// Run the subcommand selected on the command line, or the service when none was given
+pub async fn dispatch(config: Config) -> anyhow::Result<()> {
+    match config.command.clone() {
+        Some(Command::Verify { mark_invalid, page_size }) => {
+            let report = verify::run_verify(config, mark_invalid, page_size).await?;
+            println!("{}", report);
+            if report.invalid > 0 && !mark_invalid {
+                anyhow::bail!("{} invalid embeddings found", report.invalid);
+            }
+            Ok(())
+        }
+        Some(Command::Stats) => {
+            let stats = stats::run_stats(config).await?;
+            println!("{}", stats);
+            Ok(())
+        }
+        Some(Command::TextSizes { top, page_size }) => {
+            let report = text_sizes::run_text_sizes(config, top, page_size).await?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::Import { path, format, batch_size, dry_run }) => {
+            let format = format
+                .map(|f| f.parse::<import::ImportFormat>())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))?;
+            let report = import::run_import(config, &path, format, batch_size, dry_run).await?;
+            println!("{}", report);
+            if report.rejected + report.failed > 0 {
+                anyhow::bail!("{} embeddings were not imported", report.rejected + report.failed);
+            }
+            Ok(())
+        }
+        Some(Command::UserEmbeddings { full }) => {
+            let report = user_embeddings::run_user_embeddings(config, full).await?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::Cluster { k, iterations, dry_run }) => {
+            let report = clustering::run_cluster(config, k, iterations, dry_run).await?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::Eval { path, k, models }) => {
+            let report = eval::run_eval(config, &path, &k, models).await?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::AbReport { k }) => {
+            let report = ab::run_ab_report(config, k).await?;
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::RollbackEmbeddings { model, dry_run }) => {
+            let report = history::run_rollback(config, model, dry_run).await?;
+            println!("{}", report);
+            if report.failed > 0 {
+                anyhow::bail!("{} embeddings could not be restored", report.failed);
+            }
+            Ok(())
+        }
+        Some(Command::OpenaiBatch { action }) => {
+            let report = match action {
+                BatchCommand::Submit { max_requests } => batch_jobs::run_submit(config, max_requests).await?,
+                BatchCommand::Poll => batch_jobs::run_poll(config).await?,
+                BatchCommand::Run { max_requests, max_open, poll_secs } => {
+                    batch_jobs::run_backfill(config, max_requests, max_open, Duration::from_secs(poll_secs)).await?
+                }
+            };
+            println!("{}", report);
+            Ok(())
+        }
+        Some(Command::Config { action: ConfigCommand::Validate { connect, probe } }) => {
+            let report = config_check::run_config_validate(config, connect, probe).await?;
+            println!("{}", report);
+            if report.failures() > 0 {
+                anyhow::bail!("{} configuration checks failed", report.failures());
+            }
+            Ok(())
+        }
+        None => service::run_with_config(config).await,
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    pool::create_pool,
+    surreal_client::{ClusterSummary, SurrealClient},
+};
+use prometheus::Registry;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+use tracing::info;
+
+/// Page size used when reading embeddings to cluster
+const CLUSTER_PAGE_SIZE: usize = 1000;
+
+/// Terms kept per cluster label
+const LABEL_TERMS: usize = 5;
+
+/// Fixed seed so re-running on the same data gives the same clusters
+const KMEANS_SEED: u64 = 0xc1_0573;
+
+/// Words too common in repo descriptions to say anything about a topic
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "that", "this", "from", "your", "you", "are", "can", "into",
+    "using", "use", "based", "written", "simple", "tool", "library", "project", "repository",
+    "implementation", "not", "all", "any", "its", "via", "more", "other", "which", "has", "have",
+];
+
+/// Spherical k-means result: vectors are compared by cosine similarity
+#[derive(Debug, Clone)]
+pub struct KMeans {
+    /// Cluster of each input vector
+    pub assignments: Vec<usize>,
+    /// Unit-length centroid of each cluster
+    pub centroids: Vec<Vec<f32>>,
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let magnitude = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        vector.iter().map(|x| x / magnitude).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Cluster `vectors` (all of the same dimension) into at most `k` groups, seeding with
+/// k-means++ and stopping after `iterations` rounds or once no vector changes cluster
+pub fn kmeans(vectors: &[Vec<f32>], k: usize, iterations: usize) -> KMeans {
+    let points: Vec<Vec<f32>> = vectors.iter().map(|v| normalized(v)).collect();
+    let k = k.min(points.len());
+    if k == 0 {
+        return KMeans {
+            assignments: vec![0; points.len()],
+            centroids: Vec::new(),
+        };
+    }
+
+    // k-means++: each further centroid is picked with probability proportional to its
+    // squared distance from the nearest one already chosen
+    let mut rng = StdRng::seed_from_u64(KMEANS_SEED);
+    let mut centroids = vec![points[rng.gen_range(0..points.len())].clone()];
+    let mut distances: Vec<f32> = points.iter().map(|p| 2.0 - 2.0 * dot(p, &centroids[0])).collect();
+    while centroids.len() < k {
+        let total: f32 = distances.iter().map(|d| d.max(0.0)).sum();
+        let next = if total > 0.0 {
+            let mut target = rng.gen_range(0.0..total);
+            distances
+                .iter()
+                .position(|d| {
+                    target -= d.max(0.0);
+                    target <= 0.0
+                })
+                .unwrap_or(points.len() - 1)
+        } else {
+            rng.gen_range(0..points.len())
+        };
+        centroids.push(points[next].clone());
+        for (distance, point) in distances.iter_mut().zip(&points) {
+            *distance = distance.min(2.0 - 2.0 * dot(point, &points[next]));
+        }
+    }
+
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0..iterations.max(1) {
+        let mut changed = false;
+        for (assignment, point) in assignments.iter_mut().zip(&points) {
+            let nearest = (0..centroids.len())
+                .max_by(|&a, &b| dot(point, &centroids[a]).total_cmp(&dot(point, &centroids[b])))
+                .unwrap_or(0);
+            if *assignment != nearest {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let dimensions = points[0].len();
+        let mut sums = vec![vec![0.0f32; dimensions]; centroids.len()];
+        for (&cluster, point) in assignments.iter().zip(&points) {
+            for (sum, value) in sums[cluster].iter_mut().zip(point) {
+                *sum += value;
+            }
+        }
+        for (centroid, sum) in centroids.iter_mut().zip(sums) {
+            // An emptied cluster keeps its previous centroid
+            if sum.iter().any(|&x| x != 0.0) {
+                *centroid = normalized(&sum);
+            }
+        }
+    }
+
+    KMeans {
+        assignments,
+        centroids,
+    }
+}
+
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '-' && c != '+' && c != '#')
+        .map(|word| word.trim_matches('-').to_lowercase())
+        .filter(|word| word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+}
+
+/// Label each cluster with the terms that best set its descriptions apart from the other
+/// clusters' (class-based TF-IDF: frequency in the cluster times the log of clusters / clusters
+/// using the term)
+pub fn top_terms(descriptions_by_cluster: &[Vec<&str>], count: usize) -> Vec<Vec<String>> {
+    let frequencies: Vec<HashMap<String, usize>> = descriptions_by_cluster
+        .iter()
+        .map(|descriptions| {
+            let mut frequency = HashMap::new();
+            for description in descriptions {
+                // Count each term once per description so one repetitive text can't dominate
+                for term in terms(description).collect::<HashSet<_>>() {
+                    *frequency.entry(term).or_insert(0) += 1;
+                }
+            }
+            frequency
+        })
+        .collect();
+
+    let mut clusters_using: HashMap<&str, usize> = HashMap::new();
+    for frequency in &frequencies {
+        for term in frequency.keys() {
+            *clusters_using.entry(term.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    let clusters = frequencies.len() as f32;
+    frequencies
+        .iter()
+        .map(|frequency| {
+            let mut scored: Vec<(&String, f32)> = frequency
+                .iter()
+                .map(|(term, &tf)| {
+                    let idf = (1.0 + clusters / clusters_using[term.as_str()] as f32).ln();
+                    (term, tf as f32 * idf)
+                })
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            scored.into_iter().take(count).map(|(term, _)| term.clone()).collect()
+        })
+        .collect()
+}
+
+/// Summary of a clustering run
+#[derive(Debug, Default)]
+pub struct ClusterReport {
+    pub clustered: usize,
+    pub clusters: Vec<ClusterSummary>,
+    pub dry_run: bool,
+}
+
+impl fmt::Display for ClusterReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Clustering report:")?;
+        writeln!(f, "  Repos clustered: {}", self.clustered)?;
+        writeln!(f, "  Clusters: {}", self.clusters.len())?;
+        let mut clusters: Vec<&ClusterSummary> = self.clusters.iter().collect();
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.size));
+        for cluster in clusters {
+            writeln!(f, "    #{} ({} repos): {}", cluster.cluster, cluster.size, cluster.terms.join(", "))?;
+        }
+        if self.dry_run {
+            writeln!(f, "  Dry run: nothing was written")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the `cluster` command: k-means over every stored embedding of the configured model,
+/// labelled from repo descriptions, with each repo's `cluster` field and the `repo_cluster`
+/// table rewritten unless `dry_run`
+pub async fn run_cluster(config: Config, k: usize, iterations: usize, dry_run: bool) -> anyhow::Result<ClusterReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+    if k == 0 {
+        anyhow::bail!("--k must be greater than 0");
+    }
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool)
+        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone())
+        .with_write_chunk_size(config.db_write_chunk_size);
+
+    let mut rows = client.get_all_stored_embeddings(CLUSTER_PAGE_SIZE).await?;
+    // Leftovers from another model can't be compared
+    if let Some(dimensions) = rows.first().map(|row| row.embedding.len()) {
+        rows.retain(|row| row.embedding.len() == dimensions);
+    }
+    info!(repos = rows.len(), k, "Clustering stored embeddings");
+
+    let vectors: Vec<Vec<f32>> = rows.iter().map(|row| row.embedding.clone()).collect();
+    let result = tokio::task::spawn_blocking(move || kmeans(&vectors, k, iterations)).await?;
+
+    let descriptions = client.get_repo_descriptions().await?;
+    let mut by_cluster: Vec<Vec<&str>> = vec![Vec::new(); result.centroids.len()];
+    for (row, &cluster) in rows.iter().zip(&result.assignments) {
+        if let Some(description) = descriptions.get(&row.id.to_string()) {
+            by_cluster[cluster].push(description);
+        }
+    }
+    let labels = top_terms(&by_cluster, LABEL_TERMS);
+
+    let mut sizes = vec![0; result.centroids.len()];
+    for &cluster in &result.assignments {
+        sizes[cluster] += 1;
+    }
+    let clusters: Vec<ClusterSummary> = labels
+        .into_iter()
+        .enumerate()
+        .filter(|(cluster, _)| sizes[*cluster] > 0)
+        .map(|(cluster, terms)| ClusterSummary {
+            cluster,
+            size: sizes[cluster],
+            terms,
+        })
+        .collect();
+
+    if !dry_run {
+        let assignments: Vec<_> = rows
+            .iter()
+            .map(|row| row.id.clone())
+            .zip(result.assignments.iter().copied())
+            .collect();
+        client.replace_clusters(&assignments, &clusters).await?;
+    }
+
+    Ok(ClusterReport {
+        clustered: rows.len(),
+        clusters,
+        dry_run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_and_labels() {
+        // Two tight groups around orthogonal directions
+        let mut vectors = Vec::new();
+        for i in 0..10 {
+            let jitter = i as f32 * 0.01;
+            vectors.push(vec![1.0, jitter, 0.0]);
+            vectors.push(vec![0.0, jitter, 1.0]);
+        }
+        let result = kmeans(&vectors, 2, 20);
+        assert_eq!(result.centroids.len(), 2);
+        for pair in result.assignments.chunks(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+        assert!(result.assignments.iter().step_by(2).all(|&c| c == result.assignments[0]));
+        assert_eq!(kmeans(&vectors[..1], 5, 10).centroids.len(), 1);
+
+        let labels = top_terms(
+            &[
+                vec!["A fast web framework for Rust", "Async web server in Rust"],
+                vec!["Deep learning models in Python", "Python deep learning toolkit"],
+            ],
+            2,
+        );
+        assert_eq!(labels[0], vec!["rust", "web"]);
+        assert_eq!(labels[1], vec!["deep", "learning"]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    config::Config,
+    embedder::Embedder,
+    metrics::Metrics,
+    migration,
+    pool::SurrealDBManager,
+};
+use deadpool::managed::Manager;
+use prometheus::Registry;
+use std::{fmt, sync::Arc, time::{Duration, Instant}};
+use surrealdb::{engine::any::Any, Surreal};
+use tokio::time::timeout;
+
+const PROBE_TEXT: &str = "config validation";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but something is worth a look (e.g. migrations the service will apply on start)
+    Warn,
+    Fail,
+    /// Not requested, or impossible after an earlier failure
+    Skipped,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Result of `config validate`
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub checks: Vec<Check>,
+}
+
+impl ConfigReport {
+    fn push(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count()
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Configuration validation report:")?;
+        for check in &self.checks {
+            writeln!(f, "  [{}] {}: {}", check.status.as_str(), check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validate the configuration and, when asked, check that the database and the embedding
+/// provider are reachable with it
+pub async fn run_config_validate(config: Config, connect: bool, probe: bool) -> anyhow::Result<ConfigReport> {
+    let config = Arc::new(config);
+    let mut report = ConfigReport::default();
+
+    if let Err(e) = config.validate() {
+        report.push("configuration", CheckStatus::Fail, e.to_string());
+        return Ok(report);
+    }
+    report.push("configuration", CheckStatus::Pass, "all settings are valid");
+
+    // Connection and embedding code record metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    if connect {
+        check_database(&config, &mut report).await;
+    } else {
+        report.push("database", CheckStatus::Skipped, "pass --connect to check");
+    }
+
+    if probe {
+        check_provider(&config, &mut report).await;
+    } else {
+        report.push("provider", CheckStatus::Skipped, "pass --probe to check");
+    }
+
+    Ok(report)
+}
+
+async fn check_database(config: &Arc<Config>, report: &mut ConfigReport) {
+    // One connection rather than a pool, so failures are reported instead of retried
+    let start = Instant::now();
+    let db = match SurrealDBManager::new(config.clone()).create().await {
+        Ok(db) => db,
+        Err(e) => {
+            report.push("database", CheckStatus::Fail, format!("{}: {}", config.db_url, e));
+            report.push("schema", CheckStatus::Skipped, "database unreachable");
+            return;
+        }
+    };
+    report.push(
+        "database",
+        CheckStatus::Pass,
+        format!(
+            "connected to {} ({}/{}) in {}ms",
+            config.db_url,
+            config.db_namespace,
+            config.db_database,
+            start.elapsed().as_millis()
+        ),
+    );
+
+    match check_schema(&db).await {
+        Ok((status, detail)) => report.push("schema", status, detail),
+        Err(e) => report.push("schema", CheckStatus::Fail, e.to_string()),
+    }
+}
+
+async fn check_schema(db: &Surreal<Any>) -> anyhow::Result<(CheckStatus, String)> {
+    let mut response = db.query("INFO FOR DB").await?;
+    let info: Option<serde_json::Value> = response.take(0)?;
+    let has_repo_table = info
+        .as_ref()
+        .and_then(|info| info.get("tables"))
+        .and_then(|tables| tables.get("repo"))
+        .is_some();
+    if !has_repo_table {
+        return Ok((CheckStatus::Fail, "table `repo` is not defined".to_string()));
+    }
+
+    let current = migration::current_version(db).await?;
+    let latest = migration::latest_version();
+    Ok(if current >= latest {
+        (CheckStatus::Pass, format!("table `repo` found, migrations at version {}", current))
+    } else {
+        (
+            CheckStatus::Warn,
+            format!(
+                "migrations at version {}, the service will apply up to {} on start",
+                current, latest
+            ),
+        )
+    })
+}
+
+async fn check_provider(config: &Arc<Config>, report: &mut ConfigReport) {
+    let embedder = match Embedder::new(config.clone()) {
+        Ok(embedder) => embedder,
+        Err(e) => {
+            report.push("provider", CheckStatus::Fail, e.to_string());
+            return;
+        }
+    };
+
+    let start = Instant::now();
+    let name = format!("{} ({})", embedder.provider_name(), embedder.model_name());
+    match timeout(PROBE_TIMEOUT, embedder.generate_embedding(PROBE_TEXT)).await {
+        Ok(Ok(embedding)) => report.push(
+            "provider",
+            CheckStatus::Pass,
+            format!(
+                "{} returned {} dimensions in {}ms",
+                name,
+                embedding.len(),
+                start.elapsed().as_millis()
+            ),
+        ),
+        Ok(Err(e)) => report.push("provider", CheckStatus::Fail, format!("{}: {}", name, e)),
+        Err(_) => report.push(
+            "provider",
+            CheckStatus::Fail,
+            format!("{}: timed out after {:?}", name, PROBE_TIMEOUT),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(report: &ConfigReport, name: &str) -> CheckStatus {
+        report.checks.iter().find(|check| check.name == name).unwrap().status
+    }
+
+    #[tokio::test]
+    async fn test_config_validate_report() {
+        let invalid = Config {
+            mock_dimensions: 0,
+            ..Config::for_tests()
+        };
+        let report = run_config_validate(invalid, true, true).await.unwrap();
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.failures(), 1);
+
+        // A fresh in-memory database has no schema yet, and nothing listens on the provider port
+        let config = Config {
+            embedding_provider: "ollama".to_string(),
+            ollama_url: "http://127.0.0.1:1".to_string(),
+            ..Config::for_tests()
+        };
+        let report = run_config_validate(config.clone(), true, true).await.unwrap();
+        assert_eq!(status(&report, "configuration"), CheckStatus::Pass);
+        assert_eq!(status(&report, "database"), CheckStatus::Pass);
+        assert_eq!(status(&report, "schema"), CheckStatus::Fail);
+        assert_eq!(status(&report, "provider"), CheckStatus::Fail);
+        assert_eq!(report.failures(), 2);
+        assert!(report.to_string().contains("[FAIL] schema: table `repo` is not defined"));
+
+        let report = run_config_validate(config, false, false).await.unwrap();
+        assert_eq!(status(&report, "database"), CheckStatus::Skipped);
+        assert_eq!(status(&report, "provider"), CheckStatus::Skipped);
+        assert_eq!(report.failures(), 0);
+    }
+}
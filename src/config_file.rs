@@ -0,0 +1,186 @@
+use crate::{
+    config::Config,
+    secrets::{self, VaultSource},
+};
+use anyhow::Context;
+use clap::{Command, CommandFactory, FromArgMatches};
+use serde_json::Value;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Parse `Config` from the command line and environment, using the values in the file given by
+/// `--config` / `EMBED_STAR_CONFIG` as defaults. Secrets from `<ENV>_FILE` files and Vault are
+/// layered in the same way. Precedence: CLI > env > `<ENV>_FILE` > Vault > file > built-in defaults.
+/// Exits with clap's usage message on invalid arguments.
+pub async fn load_config() -> anyhow::Result<Config> {
+    match load_config_from(std::env::args_os().collect()).await {
+        Err(e) => match e.downcast::<clap::Error>() {
+            Ok(clap_error) => clap_error.exit(),
+            Err(e) => Err(e),
+        },
+        config => config,
+    }
+}
+
+/// Load the configuration again with the process' original arguments, returning errors
+/// instead of exiting. Used for hot reloads.
+pub async fn reload_config() -> anyhow::Result<Config> {
+    load_config_from(std::env::args_os().collect()).await
+}
+
+pub async fn load_config_from(args: Vec<OsString>) -> anyhow::Result<Config> {
+    let mut command = Config::command();
+
+    if let Some(path) = config_path(&args) {
+        for (key, value) in read_config_file(&path)? {
+            command = with_default(command, &key, value)
+                .with_context(|| format!("Unknown setting '{}' in {}", key, path.display()))?;
+        }
+    }
+
+    // Later defaults win, so secret files override Vault
+    let mut secret_values = match VaultSource::from_env()? {
+        Some(vault) => vault.fetch().await?,
+        None => Vec::new(),
+    };
+    secret_values.extend(secrets::read_secret_files(&command)?);
+    for (key, value) in secret_values {
+        command = with_default(command, &key, value)?;
+    }
+
+    let matches = command.try_get_matches_from(args)?;
+    Ok(Config::from_arg_matches(&matches)?)
+}
+
+/// Use `value` as the default of the setting `key`; env vars and flags still take precedence
+fn with_default(command: Command, key: &str, value: String) -> anyhow::Result<Command> {
+    let id = command
+        .get_arguments()
+        .find(|arg| arg.get_id().as_str() == key && arg.get_long().is_some())
+        .map(|arg| arg.get_id().clone())
+        .ok_or_else(|| anyhow::anyhow!("Unknown setting '{}'", key))?;
+    Ok(command.mut_arg(id, |arg| arg.default_value(value)))
+}
+
+/// `--config <path>`, `--config=<path>` or `EMBED_STAR_CONFIG`
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("EMBED_STAR_CONFIG").map(PathBuf::from)
+}
+
+/// Read a TOML or YAML file (by extension) into `(setting, value)` pairs
+fn read_config_file(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+    let value: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)
+            .with_context(|| format!("Invalid YAML in {}", path.display()))?,
+        _ => toml::from_str(&contents)
+            .with_context(|| format!("Invalid TOML in {}", path.display()))?,
+    };
+
+    let mut settings = Vec::new();
+    flatten("", &value, &mut settings)?;
+    Ok(settings)
+}
+
+/// Flatten nested sections into setting names: `[monitoring] port = 9090` is `monitoring_port`.
+/// Lists become comma-separated values.
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) -> anyhow::Result<()> {
+    match value {
+        Value::Object(table) => {
+            for (key, value) in table {
+                let key = key.to_lowercase().replace('-', "_");
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}_{}", prefix, key)
+                };
+                flatten(&key, value, out)?;
+            }
+        }
+        Value::Null => {}
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| scalar(prefix, item))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            out.push((prefix.to_string(), items.join(",")));
+        }
+        scalar_value => out.push((prefix.to_string(), scalar(prefix, scalar_value)?)),
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => anyhow::bail!("Setting '{}' must be a string, number, boolean or list of those", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_sections_and_lists() {
+        let value: Value = toml::from_str(
+            r#"
+                batch_size = 25
+                [monitoring]
+                port = 9191
+                [openai]
+                api-key = ["sk-a", "sk-b"]
+            "#,
+        )
+        .unwrap();
+
+        let mut settings = Vec::new();
+        flatten("", &value, &mut settings).unwrap();
+        settings.sort();
+
+        assert_eq!(
+            settings,
+            vec![
+                ("batch_size".to_string(), "25".to_string()),
+                ("monitoring_port".to_string(), "9191".to_string()),
+                ("openai_api_key".to_string(), "sk-a,sk-b".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_values_are_defaults_under_cli_flags() {
+        let path = std::env::temp_dir().join(format!("embed_star_{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "token_limit = 1234\n[cb]\npersist_max_age_secs = 60\n").unwrap();
+
+        let config = load_config_from(vec![
+            "embed_star".into(),
+            "--config".into(),
+            path.clone().into(),
+            "--token-limit".into(),
+            "999".into(),
+        ])
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.token_limit, 999);
+        assert_eq!(config.cb_persist_max_age_secs, 60);
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{Datelike, NaiveDate, Utc};
+use parking_lot::Mutex;
+use std::fmt;
+use tracing::{error, info};
+
+/// Published list prices in USD per million input tokens
+const PRICE_TABLE: &[(&str, f64)] = &[
+    ("text-embedding-3-small", 0.02),
+    ("text-embedding-3-large", 0.13),
+    ("text-embedding-ada-002", 0.10),
+    ("togethercomputer/m2-bert-80M-8k-retrieval", 0.008),
+    ("togethercomputer/m2-bert-80M-32k-retrieval", 0.008),
+    ("intfloat/multilingual-e5-large-instruct", 0.02),
+    ("BAAI/bge-large-en-v1.5", 0.016),
+    ("BAAI/bge-base-en-v1.5", 0.008),
+];
+
+/// Price per million tokens for a model; local Ollama models are free
+pub fn price_per_million_tokens(provider: &str, model: &str) -> Option<f64> {
+    if provider == "ollama" {
+        return Some(0.0);
+    }
+    PRICE_TABLE
+        .iter()
+        .find(|(name, _)| *name == model)
+        .map(|(_, price)| *price)
+}
+
+/// Budget period that has been exhausted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetPeriod {
+    Daily,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Monthly => "monthly",
+        }
+    }
+}
+
+impl fmt::Display for BudgetPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Spend accumulated in the current UTC day and month
+#[derive(Debug, Clone, Copy)]
+struct Spend {
+    day: NaiveDate,
+    daily: f64,
+    monthly: f64,
+    exceeded: Option<BudgetPeriod>,
+}
+
+impl Spend {
+    fn new(today: NaiveDate) -> Self {
+        Self {
+            day: today,
+            daily: 0.0,
+            monthly: 0.0,
+            exceeded: None,
+        }
+    }
+
+    /// Reset counters when the day or month changes
+    fn roll_over(&mut self, today: NaiveDate) {
+        if today == self.day {
+            return;
+        }
+        if (today.year(), today.month()) != (self.day.year(), self.day.month()) {
+            self.monthly = 0.0;
+        }
+        self.daily = 0.0;
+        self.day = today;
+    }
+}
+
+/// Estimates embedding spend and enforces optional daily/monthly budgets (UTC periods, in-process only)
+pub struct CostTracker {
+    provider: String,
+    model: String,
+    price_per_million: f64,
+    daily_budget: Option<f64>,
+    monthly_budget: Option<f64>,
+    spend: Mutex<Spend>,
+}
+
+impl CostTracker {
+    pub fn new(provider: &str, model: &str, price_per_million: f64) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            price_per_million,
+            daily_budget: None,
+            monthly_budget: None,
+            spend: Mutex::new(Spend::new(Utc::now().date_naive())),
+        }
+    }
+
+    pub fn with_budgets(mut self, daily: Option<f64>, monthly: Option<f64>) -> Self {
+        self.daily_budget = daily;
+        self.monthly_budget = monthly;
+        self
+    }
+
+    /// Record the tokens of one request and return its estimated cost in USD
+    pub fn record(&self, tokens: u64) -> f64 {
+        let cost = tokens as f64 * self.price_per_million / 1_000_000.0;
+        crate::metrics::record_embedding_cost(&self.provider, &self.model, cost);
+
+        let mut spend = self.spend.lock();
+        spend.roll_over(Utc::now().date_naive());
+        spend.daily += cost;
+        spend.monthly += cost;
+
+        let exceeded = self.check(&spend);
+        if let (Some(period), None) = (exceeded, spend.exceeded) {
+            error!(
+                period = %period,
+                daily_spend = spend.daily,
+                monthly_spend = spend.monthly,
+                "Embedding budget exceeded, pausing processing"
+            );
+        }
+        spend.exceeded = exceeded;
+        crate::metrics::set_budget_exceeded(exceeded);
+
+        cost
+    }
+
+    /// The budget period currently exhausted, if any; clears itself once the period rolls over
+    pub fn budget_exceeded(&self) -> Option<BudgetPeriod> {
+        let mut spend = self.spend.lock();
+        spend.roll_over(Utc::now().date_naive());
+
+        let exceeded = self.check(&spend);
+        if exceeded.is_none() && spend.exceeded.is_some() {
+            info!("New budget period started, resuming processing");
+            crate::metrics::set_budget_exceeded(None);
+        }
+        spend.exceeded = exceeded;
+        exceeded
+    }
+
+    /// Estimated spend for the current day and month in USD
+    pub fn spend(&self) -> (f64, f64) {
+        let mut spend = self.spend.lock();
+        spend.roll_over(Utc::now().date_naive());
+        (spend.daily, spend.monthly)
+    }
+
+    fn check(&self, spend: &Spend) -> Option<BudgetPeriod> {
+        if self.daily_budget.is_some_and(|budget| spend.daily >= budget) {
+            Some(BudgetPeriod::Daily)
+        } else if self.monthly_budget.is_some_and(|budget| spend.monthly >= budget) {
+            Some(BudgetPeriod::Monthly)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_lookup() {
+        assert_eq!(price_per_million_tokens("openai", "text-embedding-3-small"), Some(0.02));
+        assert_eq!(price_per_million_tokens("ollama", "nomic-embed-text"), Some(0.0));
+        assert_eq!(price_per_million_tokens("openai", "unknown-model"), None);
+    }
+
+    #[test]
+    fn test_budget_enforcement() {
+        let tracker = CostTracker::new("openai", "text-embedding-3-small", 1.0)
+            .with_budgets(Some(0.5), None);
+
+        let cost = tracker.record(250_000);
+        assert!((cost - 0.25).abs() < 1e-9);
+        assert_eq!(tracker.budget_exceeded(), None);
+
+        tracker.record(250_000);
+        assert_eq!(tracker.budget_exceeded(), Some(BudgetPeriod::Daily));
+    }
+
+    #[test]
+    fn test_roll_over_resets_daily_spend() {
+        let mut spend = Spend::new(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap());
+        spend.daily = 1.0;
+        spend.monthly = 5.0;
+
+        spend.roll_over(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap());
+        assert_eq!(spend.daily, 1.0);
+
+        spend.roll_over(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert_eq!(spend.daily, 0.0);
+        assert_eq!(spend.monthly, 0.0);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    metrics,
+    surreal_client::{DuplicatePair, StoredEmbeddingRow, SurrealClient},
+    validation::{EmbeddingValidator, ValidationConfig},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+
+/// Page size used when reading embeddings for a scan
+const SCAN_PAGE_SIZE: usize = 1000;
+
+/// Random-hyperplane LSH: a pair is a candidate when all `ROWS_PER_BAND` signature bits of any
+/// band agree. With 20 bands of 16 bits a pair at cosine 0.95 is found ~96% of the time, while
+/// unrelated (orthogonal) pairs collide in about 0.03% of cases.
+const BANDS: usize = 20;
+const ROWS_PER_BAND: usize = 16;
+
+/// Buckets larger than this (typically many empty-description repos embedding alike) are
+/// skipped rather than compared pairwise
+const MAX_BUCKET_SIZE: usize = 1000;
+
+/// Fixed seed so repeated scans hash the same way
+const HYPERPLANE_SEED: u64 = 0x5eed_d0b1e;
+
+/// Approximate nearest-neighbour index over embeddings, bucketing by random hyperplane
+/// signatures so only likely-similar pairs are compared exactly
+pub struct LshIndex {
+    planes: Vec<Vec<f32>>,
+    buckets: HashMap<(usize, u16), Vec<usize>>,
+}
+
+impl LshIndex {
+    pub fn new(dimensions: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(HYPERPLANE_SEED);
+        // Gaussian components (Box-Muller) make the hyperplanes uniformly oriented
+        let mut gaussian = move || {
+            let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+            let u2: f32 = rng.gen();
+            (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+        };
+        let planes = (0..BANDS * ROWS_PER_BAND)
+            .map(|_| (0..dimensions).map(|_| gaussian()).collect())
+            .collect();
+        Self {
+            planes,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Add the embedding at `index` of the scanned rows
+    pub fn insert(&mut self, index: usize, embedding: &[f32]) {
+        for (band, planes) in self.planes.chunks(ROWS_PER_BAND).enumerate() {
+            let key = planes.iter().enumerate().fold(0u16, |key, (bit, plane)| {
+                let dot: f32 = plane.iter().zip(embedding).map(|(p, v)| p * v).sum();
+                if dot >= 0.0 { key | (1 << bit) } else { key }
+            });
+            self.buckets.entry((band, key)).or_default().push(index);
+        }
+    }
+
+    /// Each candidate pair once, lower index first
+    pub fn candidate_pairs(&self) -> HashSet<(usize, usize)> {
+        let mut pairs = HashSet::new();
+        let mut oversized = 0;
+        for members in self.buckets.values() {
+            if members.len() > MAX_BUCKET_SIZE {
+                oversized += 1;
+                continue;
+            }
+            for (i, &a) in members.iter().enumerate() {
+                for &b in &members[i + 1..] {
+                    pairs.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+        if oversized > 0 {
+            warn!(buckets = oversized, "Skipped oversized LSH buckets during duplicate scan");
+        }
+        pairs
+    }
+}
+
+/// Pairs of `rows` whose cosine similarity is at least `threshold`, most similar first
+pub fn find_duplicates(rows: &[StoredEmbeddingRow], threshold: f32) -> Vec<DuplicatePair> {
+    let Some(dimensions) = rows.first().map(|row| row.embedding.len()) else {
+        return Vec::new();
+    };
+    let validator = EmbeddingValidator::new(ValidationConfig::default());
+
+    let mut index = LshIndex::new(dimensions);
+    for (i, row) in rows.iter().enumerate() {
+        // Leftovers from another model can't be compared
+        if row.embedding.len() == dimensions {
+            index.insert(i, &row.embedding);
+        }
+    }
+
+    let mut duplicates: Vec<DuplicatePair> = index
+        .candidate_pairs()
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let similarity = validator
+                .cosine_similarity(&rows[a].embedding, &rows[b].embedding)
+                .ok()?;
+            (similarity >= threshold).then(|| DuplicatePair {
+                repo_a: rows[a].id.clone(),
+                repo_b: rows[b].id.clone(),
+                similarity,
+            })
+        })
+        .collect();
+    duplicates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    duplicates
+}
+
+/// Summary of a duplicate scan
+#[derive(Debug, Default)]
+pub struct DuplicateReport {
+    pub scanned: usize,
+    pub pairs: usize,
+    /// Distinct repos appearing in at least one pair
+    pub repos: usize,
+}
+
+/// Scan every stored embedding for near-duplicate pairs (forks, mirrors, renamed copies) and
+/// replace the model's rows in `repo_duplicate` with the result. All vectors are held in memory
+/// for the scan; hashing runs on a blocking thread.
+pub async fn scan_duplicates(client: &SurrealClient, threshold: f32) -> anyhow::Result<DuplicateReport> {
+    let rows = client.get_all_stored_embeddings(SCAN_PAGE_SIZE).await?;
+    let scanned = rows.len();
+    let pairs = tokio::task::spawn_blocking(move || find_duplicates(&rows, threshold)).await?;
+    client.replace_duplicates(&pairs).await?;
+
+    let repos = pairs
+        .iter()
+        .flat_map(|pair| [pair.repo_a.to_string(), pair.repo_b.to_string()])
+        .collect::<HashSet<_>>()
+        .len();
+    metrics::set_duplicates(pairs.len(), repos);
+    info!(scanned, pairs = pairs.len(), repos, "Duplicate scan finished");
+
+    Ok(DuplicateReport {
+        scanned,
+        pairs: pairs.len(),
+        repos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use surrealdb::RecordId;
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut rows: Vec<StoredEmbeddingRow> = (0..200)
+            .map(|i| StoredEmbeddingRow {
+                id: RecordId::from(("repo", format!("r{}", i).as_str())),
+                embedding: (0..64).map(|_| rng.gen_range(-1.0..1.0)).collect(),
+            })
+            .collect();
+        // A fork whose embedding barely differs from its parent
+        let mut fork = rows[42].embedding.clone();
+        fork[0] += 0.01;
+        rows.push(StoredEmbeddingRow {
+            id: RecordId::from(("repo", "fork")),
+            embedding: fork,
+        });
+
+        let duplicates = find_duplicates(&rows, 0.95);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].repo_a, RecordId::from(("repo", "r42")));
+        assert_eq!(duplicates[0].repo_b, RecordId::from(("repo", "fork")));
+        assert!(duplicates[0].similarity > 0.99);
+        assert!(find_duplicates(&[], 0.95).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
+use crate::recording::fnv1a;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, VecDeque},
     sync::Arc,
@@ -9,19 +11,28 @@ use tracing::{debug, info};
 /// Cache entry containing embedding data and metadata
 #[derive(Debug, Clone)]
 struct CacheEntry {
-    embedding: Vec<f32>,
+    embedding: Arc<[f32]>,
     model: String,
     created_at: Instant,
     last_accessed: Instant,
     access_count: u64,
 }
 
+/// Cached provider failure, used to avoid re-submitting a repo that just failed
+#[derive(Debug, Clone)]
+struct FailureEntry {
+    reason: String,
+    created_at: Instant,
+}
+
 /// LRU cache for embeddings with TTL support
 pub struct EmbeddingCache {
     entries: Arc<RwLock<HashMap<String, CacheEntry>>>,
     access_order: Arc<RwLock<VecDeque<String>>>,
+    failures: Arc<RwLock<HashMap<String, FailureEntry>>>,
     max_size: usize,
     ttl: Duration,
+    negative_ttl: Duration,
 }
 
 impl EmbeddingCache {
@@ -29,18 +40,27 @@ impl EmbeddingCache {
         Self {
             entries: Arc::new(RwLock::new(HashMap::with_capacity(max_size))),
             access_order: Arc::new(RwLock::new(VecDeque::with_capacity(max_size))),
+            failures: Arc::new(RwLock::new(HashMap::new())),
             max_size,
             ttl: Duration::from_secs(ttl_seconds),
+            negative_ttl: Duration::ZERO,
         }
     }
 
-    /// Generate a cache key from repo information
-    pub fn cache_key(repo_full_name: &str, model: &str) -> String {
-        format!("{}:{}", repo_full_name, model)
+    /// Enable negative caching of provider failures for the given TTL (0 disables it)
+    pub fn with_negative_ttl(mut self, ttl_seconds: u64) -> Self {
+        self.negative_ttl = Duration::from_secs(ttl_seconds);
+        self
+    }
+
+    /// Cache key of `model`'s embedding of `text`, the repo's prepared text. Any change to the
+    /// text makes a new key, so an edited repo is never served its old vector.
+    pub fn cache_key(text: &str, model: &str) -> String {
+        format!("{}:{:016x}", model, fnv1a(text.as_bytes()))
     }
 
     /// Get an embedding from cache if it exists and is not expired
-    pub fn get(&self, key: &str) -> Option<(Vec<f32>, String)> {
+    pub fn get(&self, key: &str) -> Option<(Arc<[f32]>, String)> {
         let mut entries = self.entries.write();
         let mut access_order = self.access_order.write();
 
@@ -72,8 +92,8 @@ impl EmbeddingCache {
         }
     }
 
-    /// Put an embedding into the cache
-    pub fn put(&self, key: String, embedding: Vec<f32>, model: String) {
+    /// Put an embedding into the cache; pass an `Arc<[f32]>` to share it with pending updates
+    pub fn put(&self, key: String, embedding: impl Into<Arc<[f32]>>, model: String) {
         let mut entries = self.entries.write();
         let mut access_order = self.access_order.write();
 
@@ -87,7 +107,7 @@ impl EmbeddingCache {
 
         // Insert new entry
         let entry = CacheEntry {
-            embedding,
+            embedding: embedding.into(),
             model,
             created_at: Instant::now(),
             last_accessed: Instant::now(),
@@ -100,6 +120,65 @@ impl EmbeddingCache {
         debug!("Added cache entry: {} (cache size: {})", key, entries.len());
     }
 
+    /// Get the reason of a recent failure for this key, if one is still cached
+    pub fn get_failure(&self, key: &str) -> Option<String> {
+        if self.negative_ttl.is_zero() {
+            return None;
+        }
+
+        let mut failures = self.failures.write();
+        match failures.get(key) {
+            Some(entry) if entry.created_at.elapsed() <= self.negative_ttl => {
+                debug!("Negative cache hit for key: {}", key);
+                Some(entry.reason.clone())
+            }
+            Some(_) => {
+                failures.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remember that generating an embedding for this key failed
+    pub fn put_failure(&self, key: String, reason: String) {
+        if self.negative_ttl.is_zero() {
+            return;
+        }
+
+        let mut failures = self.failures.write();
+
+        // Failures are short-lived; bound the map to the same size as the main cache
+        if failures.len() >= self.max_size {
+            let negative_ttl = self.negative_ttl;
+            failures.retain(|_, entry| entry.created_at.elapsed() <= negative_ttl);
+        }
+
+        if failures.len() < self.max_size {
+            debug!("Added negative cache entry: {}", key);
+            failures.insert(
+                key,
+                FailureEntry {
+                    reason,
+                    created_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Drop a cached failure, e.g. after the repo was successfully embedded
+    pub fn clear_failure(&self, key: &str) {
+        self.failures.write().remove(key);
+    }
+
+    /// Forget everything cached for a key, e.g. when its repo was deleted
+    pub fn remove(&self, key: &str) {
+        if self.entries.write().remove(key).is_some() {
+            self.access_order.write().retain(|k| k != key);
+        }
+        self.failures.write().remove(key);
+    }
+
     /// Remove expired entries from the cache
     pub fn evict_expired(&self) {
         let mut entries = self.entries.write();
@@ -123,6 +202,11 @@ impl EmbeddingCache {
         if expired_count > 0 {
             info!("Evicted {} expired cache entries", expired_count);
         }
+
+        let negative_ttl = self.negative_ttl;
+        self.failures
+            .write()
+            .retain(|_, entry| now.duration_since(entry.created_at) <= negative_ttl);
     }
 
     /// Get cache statistics
@@ -142,6 +226,7 @@ impl EmbeddingCache {
             hit_count,
             max_size: self.max_size,
             ttl_seconds: self.ttl.as_secs(),
+            negative_entries: self.failures.read().len(),
         }
     }
 
@@ -152,18 +237,20 @@ impl EmbeddingCache {
         
         entries.clear();
         access_order.clear();
+        self.failures.write().clear();
         
         info!("Cache cleared");
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheStats {
     pub total_entries: usize,
     pub total_memory_bytes: usize,
     pub hit_count: u64,
     pub max_size: usize,
     pub ttl_seconds: u64,
+    pub negative_entries: usize,
 }
 
 impl Default for EmbeddingCache {
@@ -173,26 +260,6 @@ impl Default for EmbeddingCache {
     }
 }
 
-/// Background task to periodically clean up expired entries
-pub async fn cache_cleanup_task(
-    cache: Arc<EmbeddingCache>,
-    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
-) {
-    let mut interval = tokio::time::interval(Duration::from_secs(300)); // Every 5 minutes
-
-    loop {
-        tokio::select! {
-            _ = shutdown_rx.recv() => {
-                info!("Cache cleanup task shutting down");
-                break;
-            }
-            _ = interval.tick() => {
-                cache.evict_expired();
-            }
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,7 +275,7 @@ mod tests {
         let result = cache.get("key1");
         assert!(result.is_some());
         let (retrieved, model) = result.unwrap();
-        assert_eq!(retrieved, embedding1);
+        assert_eq!(&*retrieved, embedding1.as_slice());
         assert_eq!(model, "model1");
 
         // Test cache miss
@@ -224,6 +291,18 @@ mod tests {
         assert!(cache.get("key3").is_some());
     }
 
+    #[test]
+    fn test_cache_shares_embedding() {
+        let cache = EmbeddingCache::new(10, 60);
+        let embedding: Arc<[f32]> = vec![0.1, 0.2, 0.3].into();
+
+        // The cache and a pending update hold the same allocation, not a copy
+        cache.put("key".to_string(), embedding.clone(), "model".to_string());
+        let (cached, _) = cache.get("key").unwrap();
+        assert!(Arc::ptr_eq(&cached, &embedding));
+        assert_eq!(Arc::strong_count(&embedding), 3);
+    }
+
     #[test]
     fn test_cache_stats() {
         let cache = EmbeddingCache::new(100, 3600);
@@ -247,4 +326,30 @@ mod tests {
         assert_eq!(stats.hit_count, 3);
         assert_eq!(stats.max_size, 100);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_negative_cache() {
+        let cache = EmbeddingCache::new(10, 60).with_negative_ttl(60);
+
+        assert!(cache.get_failure("key1").is_none());
+
+        cache.put_failure("key1".to_string(), "INVALID_EMBEDDING".to_string());
+        assert_eq!(cache.get_failure("key1"), Some("INVALID_EMBEDDING".to_string()));
+        assert_eq!(cache.stats().negative_entries, 1);
+
+        cache.clear_failure("key1");
+        assert!(cache.get_failure("key1").is_none());
+
+        // Removing a key drops both the embedding and any cached failure
+        cache.put("key2".to_string(), vec![0.1], "model".to_string());
+        cache.put_failure("key2".to_string(), "error".to_string());
+        cache.remove("key2");
+        assert!(cache.get("key2").is_none());
+        assert!(cache.get_failure("key2").is_none());
+
+        // Disabled negative cache never stores failures
+        let disabled = EmbeddingCache::new(10, 60);
+        disabled.put_failure("key1".to_string(), "error".to_string());
+        assert!(disabled.get_failure("key1").is_none());
+    }
+}
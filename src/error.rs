@@ -2,11 +2,27 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum EmbedError {
+    /// Boxed so the large surrealdb error doesn't bloat every `Result<T>`
     #[error("Database error: {0}")]
-    Database(#[from] surrealdb::Error),
+    Database(Box<surrealdb::Error>),
     
-    #[error("Embedding provider error: {0}")]
-    EmbeddingProvider(String),
+    #[error("Provider rejected the credentials: {0}")]
+    AuthFailed(String),
+    
+    #[error("Provider quota exhausted: {0}")]
+    QuotaExceeded(String),
+    
+    #[error("Model not found: {0}")]
+    ModelNotFound(String),
+    
+    #[error("Input too long for the model: {0}")]
+    InputTooLong(String),
+    
+    #[error("Provider server error ({status}): {message}")]
+    ServerError { status: u16, message: String },
+    
+    #[error("Provider rejected the request ({status}): {message}")]
+    InvalidRequest { status: u16, message: String },
     
     #[error("Configuration error: {0}")]
     Configuration(String),
@@ -29,27 +45,66 @@ pub enum EmbedError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    #[error("Timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
 }
 
 pub type Result<T> = std::result::Result<T, EmbedError>;
 
+impl From<surrealdb::Error> for EmbedError {
+    fn from(error: surrealdb::Error) -> Self {
+        EmbedError::Database(Box::new(error))
+    }
+}
+
 impl EmbedError {
     pub fn is_retryable(&self) -> bool {
+        match self {
+            // Connection failures and timeouts have no status
+            EmbedError::Http(e) => e.status().is_none_or(is_transient_status),
+            EmbedError::Database(_)
+            | EmbedError::RateLimitExceeded { .. }
+            | EmbedError::ServiceUnavailable(_)
+            | EmbedError::ServerError { .. } => true,
+            _ => false,
+        }
+    }
+    
+    /// Whether the failure is down to this record's input rather than the provider or the
+    /// account, so skipping the record for a while is worthwhile
+    pub fn is_record_specific(&self) -> bool {
         matches!(
             self,
-            EmbedError::Database(_)
-                | EmbedError::Http(_)
-                | EmbedError::RateLimitExceeded { .. }
-                | EmbedError::ServiceUnavailable(_)
+            EmbedError::InputTooLong(_)
+                | EmbedError::InvalidRequest { .. }
+                | EmbedError::InvalidDimension { .. }
+                | EmbedError::InvalidEmbedding(_)
+                | EmbedError::ValidationError(_)
+                | EmbedError::Timeout(_)
+        )
+    }
+    
+    /// Whether every request will fail the same way until someone fixes the credentials, quota
+    /// or model, which is worth alerting on
+    pub fn is_account_wide(&self) -> bool {
+        matches!(
+            self,
+            EmbedError::AuthFailed(_) | EmbedError::QuotaExceeded(_) | EmbedError::ModelNotFound(_)
         )
     }
     
     pub fn error_code(&self) -> &'static str {
         match self {
             EmbedError::Database(_) => "DATABASE_ERROR",
-            EmbedError::EmbeddingProvider(_) => "EMBEDDING_ERROR",
+            EmbedError::AuthFailed(_) => "AUTH_FAILED",
+            EmbedError::QuotaExceeded(_) => "QUOTA_EXCEEDED",
+            EmbedError::ModelNotFound(_) => "MODEL_NOT_FOUND",
+            EmbedError::InputTooLong(_) => "INPUT_TOO_LONG",
+            EmbedError::ServerError { .. } => "SERVER_ERROR",
+            EmbedError::InvalidRequest { .. } => "INVALID_REQUEST",
             EmbedError::Configuration(_) => "CONFIG_ERROR",
             EmbedError::Http(_) => "HTTP_ERROR",
             EmbedError::RateLimitExceeded { .. } => "RATE_LIMIT",
@@ -57,7 +112,77 @@ impl EmbedError {
             EmbedError::InvalidEmbedding(_) => "INVALID_EMBEDDING",
             EmbedError::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
             EmbedError::ValidationError(_) => "VALIDATION_ERROR",
+            EmbedError::Timeout(_) => "TIMEOUT",
             EmbedError::Internal(_) => "INTERNAL_ERROR",
         }
     }
-}
\ No newline at end of file
+}
+
+/// Whether a provider answering with `status` may answer differently if asked again; other
+/// client errors (bad request, credentials, unknown model) never will
+pub fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    use reqwest::StatusCode;
+    status.is_server_error()
+        || matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::CONFLICT | StatusCode::TOO_MANY_REQUESTS
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    /// A reqwest error carrying `status`, from a local server that answers with it
+    async fn http_error(status: u16) -> EmbedError {
+        use axum::{extract::Path, http, routing::get, Router};
+
+        let app = Router::new().route(
+            "/:status",
+            get(|Path(status): Path<u16>| async move { http::StatusCode::from_u16(status).unwrap() }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let response = reqwest::get(format!("http://{}/{}", addr, status)).await.unwrap();
+        EmbedError::Http(response.error_for_status().unwrap_err())
+    }
+
+    #[test]
+    fn test_is_transient_status() {
+        let cases = [
+            (400, false),
+            (401, false),
+            (404, false),
+            (408, true),
+            (409, true),
+            (422, false),
+            (429, true),
+            (500, true),
+            (502, true),
+            (503, true),
+        ];
+        for (status, transient) in cases {
+            assert_eq!(is_transient_status(StatusCode::from_u16(status).unwrap()), transient, "{}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_is_retryable() {
+        for (status, retryable) in [(400, false), (408, true), (409, true), (429, true), (500, true), (503, true)] {
+            assert_eq!(http_error(status).await.is_retryable(), retryable, "{}", status);
+        }
+
+        // A connection failure has no status and may well succeed next time
+        let refused = reqwest::Client::new().get("http://127.0.0.1:1").send().await.unwrap_err();
+        assert!(refused.status().is_none());
+        assert!(EmbedError::Http(refused).is_retryable());
+
+        assert!(EmbedError::RateLimitExceeded { provider: "p".to_string() }.is_retryable());
+        assert!(EmbedError::ServerError { status: 502, message: String::new() }.is_retryable());
+        assert!(!EmbedError::AuthFailed(String::new()).is_retryable());
+        assert!(!EmbedError::InputTooLong(String::new()).is_retryable());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    config::Config,
+    import::parse_repo_id,
+    metrics::Metrics,
+    pool::create_pool,
+    surreal_client::{StorageMode, StoredEmbeddingRow, SurrealClient},
+};
+use anyhow::Context;
+use prometheus::Registry;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::Arc,
+};
+use surrealdb::RecordId;
+use tracing::info;
+
+/// Page size used when reading the corpus
+const EVAL_PAGE_SIZE: usize = 1000;
+
+/// One labelled pair: `candidate` should (or, with `similar: false`, should not) come up when
+/// searching for repos like `query`. Ids are repo record ids (`repo:abc` or just `abc`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabeledPair {
+    pub query: String,
+    pub candidate: String,
+    pub similar: bool,
+}
+
+/// Read a JSONL file of [`LabeledPair`]s
+pub fn read_pairs(path: &Path) -> anyhow::Result<Vec<(RecordId, RecordId, bool)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut pairs = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let pair: LabeledPair = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("line {}: {}", number + 1, e))?;
+        let query = parse_repo_id(&pair.query).map_err(|e| anyhow::anyhow!("line {}: query {}", number + 1, e))?;
+        let candidate = parse_repo_id(&pair.candidate)
+            .map_err(|e| anyhow::anyhow!("line {}: candidate {}", number + 1, e))?;
+        pairs.push((query, candidate, pair.similar));
+    }
+    Ok(pairs)
+}
+
+/// Ranking quality of one model on the labelled pairs
+#[derive(Debug, Clone, Default)]
+pub struct ModelEval {
+    pub model: String,
+    /// Stored embeddings searched
+    pub corpus: usize,
+    /// Queries with at least one similar candidate, both embedded
+    pub queries: usize,
+    /// Pairs skipped because the query or candidate has no stored embedding
+    pub missing: usize,
+    /// Mean reciprocal rank of the first similar candidate when searching the whole corpus
+    pub mrr: f64,
+    /// Share of similar candidates ranked within the top k, by k
+    pub recall: BTreeMap<usize, f64>,
+    pub similar_mean: Option<f64>,
+    pub dissimilar_mean: Option<f64>,
+    /// Share of (similar, dissimilar) candidate pairs for the same query ranked the right way
+    pub pair_accuracy: Option<f64>,
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let magnitude = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        vector.iter().map(|x| x / magnitude).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Score `model`'s stored vectors against the labelled pairs. Each query's similar candidates
+/// are looked for among every other stored repo, ranked by cosine similarity.
+pub fn evaluate(
+    model: &str,
+    pairs: &[(RecordId, RecordId, bool)],
+    corpus: &[StoredEmbeddingRow],
+    ks: &[usize],
+) -> ModelEval {
+    let dimensions = corpus.first().map_or(0, |row| row.embedding.len());
+    let rows: Vec<(String, Vec<f32>)> = corpus
+        .iter()
+        .filter(|row| row.embedding.len() == dimensions)
+        .map(|row| (row.id.to_string(), normalized(&row.embedding)))
+        .collect();
+    let index: HashMap<&str, usize> = rows.iter().enumerate().map(|(i, (id, _))| (id.as_str(), i)).collect();
+
+    let mut eval = ModelEval {
+        model: model.to_string(),
+        corpus: rows.len(),
+        ..Default::default()
+    };
+    let mut labels: BTreeMap<usize, (HashSet<usize>, HashSet<usize>)> = BTreeMap::new();
+    for (query, candidate, similar) in pairs {
+        match (index.get(query.to_string().as_str()), index.get(candidate.to_string().as_str())) {
+            (Some(&query), Some(&candidate)) => {
+                let (similar_set, dissimilar_set) = labels.entry(query).or_default();
+                if *similar {
+                    similar_set.insert(candidate);
+                } else {
+                    dissimilar_set.insert(candidate);
+                }
+            }
+            _ => eval.missing += 1,
+        }
+    }
+
+    let mut reciprocal_ranks = Vec::new();
+    let mut hits: BTreeMap<usize, (usize, usize)> = ks.iter().map(|&k| (k, (0, 0))).collect();
+    let (mut similar_scores, mut dissimilar_scores) = (Vec::new(), Vec::new());
+    let (mut ordered, mut compared) = (0usize, 0usize);
+
+    for (&query, (similar, dissimilar)) in &labels {
+        let scores: Vec<f32> = rows.iter().map(|(_, vector)| dot(&rows[query].1, vector)).collect();
+        similar_scores.extend(similar.iter().map(|&i| scores[i] as f64));
+        dissimilar_scores.extend(dissimilar.iter().map(|&i| scores[i] as f64));
+        for &s in similar {
+            for &d in dissimilar {
+                compared += 1;
+                ordered += (scores[s] > scores[d]) as usize;
+            }
+        }
+        if similar.is_empty() {
+            continue;
+        }
+
+        eval.queries += 1;
+        let mut ranking: Vec<usize> = (0..rows.len()).filter(|&i| i != query).collect();
+        ranking.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let first = ranking.iter().position(|i| similar.contains(i)).unwrap_or(ranking.len());
+        reciprocal_ranks.push(1.0 / (first + 1) as f64);
+        for (&k, (found, total)) in hits.iter_mut() {
+            *found += ranking.iter().take(k).filter(|i| similar.contains(i)).count();
+            *total += similar.len();
+        }
+    }
+
+    eval.mrr = mean(&reciprocal_ranks).unwrap_or(0.0);
+    eval.recall = hits
+        .into_iter()
+        .map(|(k, (found, total))| (k, if total > 0 { found as f64 / total as f64 } else { 0.0 }))
+        .collect();
+    eval.similar_mean = mean(&similar_scores);
+    eval.dissimilar_mean = mean(&dissimilar_scores);
+    eval.pair_accuracy = (compared > 0).then(|| ordered as f64 / compared as f64);
+    eval
+}
+
+/// Evaluation of every compared model
+#[derive(Debug, Default)]
+pub struct EvalReport {
+    pub pairs: usize,
+    pub models: Vec<ModelEval>,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.4}", v));
+        writeln!(f, "Embedding evaluation report ({} labelled pairs):", self.pairs)?;
+        for eval in &self.models {
+            writeln!(f, "  {}:", eval.model)?;
+            writeln!(f, "    Corpus: {} embeddings", eval.corpus)?;
+            writeln!(f, "    Queries: {} ({} pairs missing an embedding)", eval.queries, eval.missing)?;
+            writeln!(f, "    MRR: {:.4}", eval.mrr)?;
+            for (k, recall) in &eval.recall {
+                writeln!(f, "    Recall@{}: {:.4}", k, recall)?;
+            }
+            writeln!(f, "    Mean similarity: similar {}, dissimilar {}",
+                optional(eval.similar_mean),
+                optional(eval.dissimilar_mean)
+            )?;
+            writeln!(f, "    Pair accuracy: {}", optional(eval.pair_accuracy))?;
+        }
+        if self.models.len() > 1 {
+            if let Some(best) = self.models.iter().max_by(|a, b| a.mrr.total_cmp(&b.mrr)) {
+                writeln!(f, "  Best MRR: {}", best.model)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run the `eval` command over the configured model, or each of `models` (which must be stored
+/// in the `repo_embedding` table unless only the configured model is given)
+pub async fn run_eval(config: Config, path: &Path, ks: &[usize], models: Vec<String>) -> anyhow::Result<EvalReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+    let pairs = read_pairs(path)?;
+    let models = if models.is_empty() {
+        vec![config.embedding_model.clone()]
+    } else {
+        models
+    };
+    let storage = config.storage_mode()?;
+    if storage == StorageMode::Inline && models.iter().any(|model| *model != config.embedding_model) {
+        anyhow::bail!("Comparing models needs EMBEDDING_STORAGE=table; inline storage only holds {}", config.embedding_model);
+    }
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+    let pool = create_pool(config.clone()).await?;
+
+    let mut report = EvalReport {
+        pairs: pairs.len(),
+        models: Vec::new(),
+    };
+    for model in models {
+        let client = SurrealClient::new(pool.clone()).with_storage_mode(storage, model.clone());
+        let corpus = client.get_all_stored_embeddings(EVAL_PAGE_SIZE).await?;
+        info!(model = %model, corpus = corpus.len(), "Evaluating model");
+
+        let pairs = pairs.clone();
+        let ks = ks.to_vec();
+        let eval = tokio::task::spawn_blocking(move || evaluate(&model, &pairs, &corpus, &ks)).await?;
+        report.models.push(eval);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: &str, embedding: Vec<f32>) -> StoredEmbeddingRow {
+        StoredEmbeddingRow {
+            id: RecordId::from(("repo", id)),
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_ranking() {
+        let corpus = vec![
+            row("query", vec![1.0, 0.0]),
+            row("twin", vec![0.9, 0.1]),
+            row("close", vec![0.7, 0.3]),
+            row("far", vec![0.0, 1.0]),
+        ];
+        let id = |key: &str| RecordId::from(("repo", key));
+        let pairs = vec![
+            (id("query"), id("close"), true),
+            (id("query"), id("far"), false),
+            (id("query"), id("gone"), true),
+        ];
+
+        let eval = evaluate("m", &pairs, &corpus, &[1, 2]);
+        assert_eq!(eval.queries, 1);
+        assert_eq!(eval.missing, 1);
+        // "twin" outranks the labelled candidate
+        assert!((eval.mrr - 0.5).abs() < 1e-9);
+        assert_eq!(eval.recall[&1], 0.0);
+        assert_eq!(eval.recall[&2], 1.0);
+        assert_eq!(eval.pair_accuracy, Some(1.0));
+        assert!(eval.similar_mean > eval.dissimilar_mean);
+    }
+}
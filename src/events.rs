@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber; one that falls further behind skips the oldest
+const EVENT_BUFFER: usize = 1024;
+
+/// Progress event streamed by `/events`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PipelineEvent {
+    /// A batch finished, whether or not everything in it was stored
+    BatchCompleted {
+        batch_id: String,
+        tenant: String,
+        model: String,
+        repos: usize,
+        stored: usize,
+        failed: usize,
+        skipped: usize,
+        duration_ms: u64,
+        at: DateTime<Utc>,
+    },
+    /// Embedding, validation or the database write failed for a repo
+    EmbeddingFailed {
+        batch_id: String,
+        tenant: String,
+        repo: String,
+        error_code: Option<String>,
+        at: DateTime<Utc>,
+    },
+    /// Latest pending count from the stats reporter and repos waiting in the queues
+    Backlog {
+        pending_repos: usize,
+        queue_depth: usize,
+        at: DateTime<Utc>,
+    },
+}
+
+impl PipelineEvent {
+    /// Name sent as the SSE `event:` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BatchCompleted { .. } => "batch_completed",
+            Self::EmbeddingFailed { .. } => "embedding_failed",
+            Self::Backlog { .. } => "backlog",
+        }
+    }
+}
+
+static EVENTS: OnceLock<broadcast::Sender<PipelineEvent>> = OnceLock::new();
+
+fn sender() -> &'static broadcast::Sender<PipelineEvent> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+/// Send `event` to current subscribers; dropped when nobody is listening
+pub fn publish(event: PipelineEvent) {
+    let _ = sender().send(event);
+}
+
+/// Receive events published from now on
+pub fn subscribe() -> broadcast::Receiver<PipelineEvent> {
+    sender().subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_to_subscribers() {
+        publish(PipelineEvent::Backlog {
+            pending_repos: 1,
+            queue_depth: 0,
+            at: Utc::now(),
+        });
+        let mut rx = subscribe();
+        publish(PipelineEvent::EmbeddingFailed {
+            batch_id: "b".to_string(),
+            tenant: "default".to_string(),
+            repo: "repo:events_test".to_string(),
+            error_code: Some("VALIDATION_ERROR".to_string()),
+            at: Utc::now(),
+        });
+
+        // Only events published after subscribing arrive; other tests may publish concurrently
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if matches!(&event, PipelineEvent::EmbeddingFailed { repo, .. } if repo == "repo:events_test") {
+                break event;
+            }
+        };
+        assert_eq!(event.name(), "embedding_failed");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "embedding_failed");
+        assert_eq!(json["repo"], "repo:events_test");
+        assert_eq!(json["error_code"], "VALIDATION_ERROR");
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    pool::create_pool,
+    surreal_client::{EmbeddingUpdate, SurrealClient},
+};
+use chrono::Utc;
+use prometheus::Registry;
+use std::{fmt, sync::Arc};
+use tracing::info;
+
+/// Archived vectors read and restored per round trip
+const ROLLBACK_PAGE_SIZE: usize = 500;
+
+/// Summary of a rollback
+#[derive(Debug, Default)]
+pub struct RollbackReport {
+    pub model: String,
+    /// Repos with an archived vector of the model
+    pub repos: usize,
+    pub restored: usize,
+    pub failed: usize,
+    pub dry_run: bool,
+}
+
+impl fmt::Display for RollbackReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Embedding rollback to {}:", self.model)?;
+        writeln!(f, "  Repos with archived embeddings: {}", self.repos)?;
+        if self.dry_run {
+            writeln!(f, "  Dry run: nothing was restored")?;
+        } else {
+            writeln!(f, "  Restored: {}", self.restored)?;
+            writeln!(f, "  Failed: {}", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Restore the most recently archived vector of the client's model for every repo that has
+/// one. With history enabled the vectors they replace are archived in turn, so a rollback can
+/// itself be rolled back.
+pub async fn rollback_embeddings(client: &SurrealClient, model: &str, dry_run: bool) -> anyhow::Result<RollbackReport> {
+    let ids = client.get_latest_history_ids().await?;
+    let mut report = RollbackReport {
+        model: model.to_string(),
+        repos: ids.len(),
+        dry_run,
+        ..Default::default()
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    for chunk in ids.chunks(ROLLBACK_PAGE_SIZE) {
+        let updates: Vec<EmbeddingUpdate> = client
+            .get_history_embeddings(chunk)
+            .await?
+            .into_iter()
+            .map(|row| EmbeddingUpdate {
+                repo_id: row.repo,
+                embedding: row.embedding.into(),
+                normalized: row.normalized.unwrap_or(false),
+                repaired: false,
+                updated_at: Utc::now(),
+                model: None,
+                language: None,
+            })
+            .collect();
+        let result = client.batch_update_embeddings(updates).await?;
+        report.restored += result.successful;
+        report.failed += result.failed;
+        info!(restored = report.restored, repos = report.repos, "Restoring archived embeddings");
+    }
+    Ok(report)
+}
+
+/// Run the `rollback-embeddings` command, writing `model`'s archived vectors back where the
+/// configured storage mode keeps them
+pub async fn run_rollback(config: Config, model: String, dry_run: bool) -> anyhow::Result<RollbackReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool)
+        .with_quantization(config.quantization_mode()?, config.quantized_only)
+        .with_precision(config.storage_precision()?)
+        .with_storage_mode(config.storage_mode()?, model.clone())
+        .with_write_chunk_size(config.db_write_chunk_size)
+        .with_history(config.embedding_history, !config.embedding_history_metadata_only);
+
+    rollback_embeddings(&client, &model, dry_run).await
+}
@@ -0,0 +1,416 @@
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    pool::{create_pool, Pool},
+    quantization::QuantizationMode,
+    surreal_client::{EmbeddingUpdate, StorageMode, SurrealClient},
+    validation::{known_dimensions, EmbeddingValidator, ValidationConfig},
+};
+use anyhow::Context;
+use chrono::Utc;
+use prometheus::Registry;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+use surrealdb::RecordId;
+use tracing::{info, warn};
+
+/// Maximum number of individual failures kept for the report
+const MAX_SAMPLE_ERRORS: usize = 20;
+
+/// Input file format for `import`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Requires the `parquet` feature
+    Parquet,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "ndjson" | "json" => Ok(ImportFormat::Jsonl),
+            "parquet" => Ok(ImportFormat::Parquet),
+            other => Err(format!(
+                "Unknown import format '{}' (expected jsonl or parquet)",
+                other
+            )),
+        }
+    }
+}
+
+impl ImportFormat {
+    /// Guess the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+/// One precomputed embedding. `id` is a repo record id (`repo:abc` or just `abc`); `model`
+/// defaults to the configured embedding model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRecord {
+    pub id: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(alias = "embedding")]
+    pub vector: Vec<f32>,
+}
+
+/// Summary of an import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub read: usize,
+    pub imported: usize,
+    /// Records that failed validation and were not written
+    pub rejected: usize,
+    /// Records the database refused, e.g. because the repo doesn't exist
+    pub failed: usize,
+    pub models: BTreeMap<String, usize>,
+    pub sample_errors: Vec<(String, String)>,
+}
+
+impl ImportReport {
+    fn error(&mut self, id: impl Into<String>, error: impl Into<String>) {
+        if self.sample_errors.len() < MAX_SAMPLE_ERRORS {
+            self.sample_errors.push((id.into(), error.into()));
+        }
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Embedding import report:")?;
+        writeln!(f, "  Read: {}", self.read)?;
+        writeln!(f, "  Imported: {}", self.imported)?;
+        writeln!(f, "  Rejected: {}", self.rejected)?;
+        writeln!(f, "  Failed to write: {}", self.failed)?;
+        writeln!(f, "  Models:")?;
+        for (model, count) in &self.models {
+            writeln!(f, "    {}: {}", model, count)?;
+        }
+        if !self.sample_errors.is_empty() {
+            writeln!(f, "  Sample errors:")?;
+            for (id, error) in &self.sample_errors {
+                writeln!(f, "    {}: {}", id, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+type Records = Box<dyn Iterator<Item = anyhow::Result<ImportRecord>> + Send>;
+
+/// Bulk-load precomputed embeddings, `batch_size` writes per database round trip. With
+/// `dry_run` the file is only validated.
+pub async fn run_import(
+    config: Config,
+    path: &Path,
+    format: Option<ImportFormat>,
+    batch_size: usize,
+    dry_run: bool,
+) -> anyhow::Result<ImportReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    let format = match format.or_else(|| ImportFormat::from_path(path)) {
+        Some(format) => format,
+        None => anyhow::bail!(
+            "Can't tell the format of {} from its extension; pass --format",
+            path.display()
+        ),
+    };
+    let records = match format {
+        ImportFormat::Jsonl => jsonl_records(path)?,
+        ImportFormat::Parquet => parquet_records(path)?,
+    };
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let storage_mode = config.storage_mode()?;
+    let quantization = config.quantization_mode()?;
+    let pool = if dry_run {
+        None
+    } else {
+        Some(create_pool(config.clone()).await?)
+    };
+    // Quality checks only; dimensions are checked per model below
+    let validator = EmbeddingValidator::new(ValidationConfig {
+        min_dimension: 1,
+        max_dimension: usize::MAX,
+        normalize: config.normalize_embeddings,
+        precision: config.storage_precision()?,
+        ..Default::default()
+    });
+
+    let mut report = ImportReport::default();
+    let mut dimensions: HashMap<String, usize> = HashMap::new();
+    let mut pending: HashMap<String, Vec<EmbeddingUpdate>> = HashMap::new();
+    let mut clients: HashMap<String, SurrealClient> = HashMap::new();
+
+    for record in records {
+        report.read += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.rejected += 1;
+                report.error(format!("record {}", report.read), e.to_string());
+                continue;
+            }
+        };
+        let model = record
+            .model
+            .clone()
+            .unwrap_or_else(|| config.embedding_model.clone());
+
+        let update = match prepare_update(&config, storage_mode, &validator, &mut dimensions, &model, record) {
+            Ok(update) => update,
+            Err((id, e)) => {
+                report.rejected += 1;
+                report.error(id, e);
+                continue;
+            }
+        };
+        *report.models.entry(model.clone()).or_insert(0) += 1;
+
+        let Some(pool) = &pool else {
+            report.imported += 1;
+            continue;
+        };
+        let batch = pending.entry(model.clone()).or_default();
+        batch.push(update);
+        if batch.len() >= batch_size.max(1) {
+            let updates = std::mem::take(batch);
+            let client = clients
+                .entry(model.clone())
+                .or_insert_with(|| import_client(&config, pool.clone(), storage_mode, quantization, &model));
+            write_batch(client, updates, &mut report).await;
+            info!(read = report.read, imported = report.imported, "Import progress");
+        }
+    }
+
+    if let Some(pool) = &pool {
+        for (model, updates) in pending {
+            if updates.is_empty() {
+                continue;
+            }
+            let client = clients
+                .entry(model.clone())
+                .or_insert_with(|| import_client(&config, pool.clone(), storage_mode, quantization, &model));
+            write_batch(client, updates, &mut report).await;
+        }
+    }
+
+    if report.rejected + report.failed > 0 {
+        warn!(rejected = report.rejected, failed = report.failed, "Some embeddings were not imported");
+    }
+    Ok(report)
+}
+
+/// Validate one record and turn it into an update; errors carry the record id
+fn prepare_update(
+    config: &Config,
+    storage_mode: StorageMode,
+    validator: &EmbeddingValidator,
+    dimensions: &mut HashMap<String, usize>,
+    model: &str,
+    record: ImportRecord,
+) -> Result<EmbeddingUpdate, (String, String)> {
+    let id = record.id.clone();
+    if storage_mode == StorageMode::Inline && model != config.embedding_model {
+        return Err((
+            id,
+            format!(
+                "model {} differs from EMBEDDING_MODEL {}; inline storage holds one model, use EMBEDDING_STORAGE=table",
+                model, config.embedding_model
+            ),
+        ));
+    }
+    let repo_id = parse_repo_id(&record.id).map_err(|e| (id.clone(), e))?;
+
+    // The model registry decides; for unknown models the first vector sets the dimension
+    let expected = if model == config.embedding_model {
+        config.target_dimensions.or_else(|| known_dimensions(model))
+    } else {
+        known_dimensions(model)
+    };
+    let expected = *dimensions
+        .entry(model.to_string())
+        .or_insert_with(|| expected.unwrap_or(record.vector.len()));
+    if record.vector.len() != expected {
+        return Err((
+            id,
+            format!("{} dimensions, {} expects {}", record.vector.len(), model, expected),
+        ));
+    }
+
+    let mut embedding = record.vector;
+    let normalized = validator
+        .validate(&embedding, &id)
+        .and_then(|_| validator.prepare_for_storage(&mut embedding))
+        .map_err(|e| (id.clone(), e.to_string()))?;
+
+    Ok(EmbeddingUpdate {
+        repo_id,
+        embedding: embedding.into(),
+        normalized,
+        repaired: false,
+        updated_at: Utc::now(),
+        model: None,
+        language: None,
+    })
+}
+
+fn import_client(
+    config: &Config,
+    pool: Pool,
+    storage_mode: StorageMode,
+    quantization: QuantizationMode,
+    model: &str,
+) -> SurrealClient {
+    SurrealClient::new(pool)
+        .with_quantization(quantization, config.quantized_only)
+        .with_precision(config.storage_precision().unwrap_or_default())
+        .with_storage_mode(storage_mode, model.to_string())
+        .with_write_chunk_size(config.db_write_chunk_size)
+        .with_history(config.embedding_history, !config.embedding_history_metadata_only)
+}
+
+async fn write_batch(client: &SurrealClient, updates: Vec<EmbeddingUpdate>, report: &mut ImportReport) {
+    let count = updates.len();
+    match client.batch_update_embeddings(updates).await {
+        Ok(result) => {
+            report.imported += result.successful;
+            report.failed += result.failed;
+            for (id, error) in result.errors {
+                report.error(id.to_string(), error);
+            }
+        }
+        Err(e) => {
+            report.failed += count;
+            report.error("batch", e.to_string());
+        }
+    }
+}
+
+/// `repo:abc`, `repo:⟨owner/name⟩`, `repo:123` or a bare key
+pub(crate) fn parse_repo_id(id: &str) -> Result<RecordId, String> {
+    let key = match id.split_once(':') {
+        Some(("repo", key)) => key,
+        Some((table, _)) => return Err(format!("not a repo id (table {})", table)),
+        None => id,
+    };
+    let key = key.trim_start_matches(['⟨', '`']).trim_end_matches(['⟩', '`']);
+    if key.is_empty() {
+        return Err("empty id".to_string());
+    }
+    Ok(match key.parse::<i64>() {
+        Ok(number) => RecordId::from(("repo", number)),
+        Err(_) => RecordId::from(("repo", key)),
+    })
+}
+
+fn jsonl_records(path: &Path) -> anyhow::Result<Records> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let lines = BufReader::new(file).lines().enumerate().filter_map(|(number, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(anyhow::anyhow!("line {}: {}", number + 1, e))),
+        };
+        if line.trim().is_empty() {
+            return None;
+        }
+        Some(
+            serde_json::from_str::<ImportRecord>(&line)
+                .map_err(|e| anyhow::anyhow!("line {}: {}", number + 1, e)),
+        )
+    });
+    Ok(Box::new(lines))
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_records(path: &Path) -> anyhow::Result<Records> {
+    use parquet::file::reader::SerializedFileReader;
+
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let reader = SerializedFileReader::new(file)
+        .with_context(|| format!("Invalid Parquet file {}", path.display()))?;
+    Ok(Box::new(reader.into_iter().map(|row| parquet_record(&row?))))
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_record(row: &parquet::record::Row) -> anyhow::Result<ImportRecord> {
+    use parquet::record::Field;
+
+    let mut id = None;
+    let mut model = None;
+    let mut vector = None;
+    for (name, field) in row.get_column_iter() {
+        match (name.as_str(), field) {
+            ("id", Field::Str(value)) => id = Some(value.clone()),
+            ("model", Field::Str(value)) => model = Some(value.clone()),
+            ("vector" | "embedding", Field::ListInternal(list)) => {
+                let values = list
+                    .elements()
+                    .iter()
+                    .map(|value| match value {
+                        Field::Float(x) => Ok(*x),
+                        Field::Double(x) => Ok(*x as f32),
+                        other => Err(anyhow::anyhow!("non-numeric vector element {}", other)),
+                    })
+                    .collect::<anyhow::Result<Vec<f32>>>()?;
+                vector = Some(values);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ImportRecord {
+        id: id.ok_or_else(|| anyhow::anyhow!("missing string column `id`"))?,
+        model,
+        vector: vector.ok_or_else(|| anyhow::anyhow!("missing list column `vector`"))?,
+    })
+}
+
+#[cfg(not(feature = "parquet"))]
+fn parquet_records(_path: &Path) -> anyhow::Result<Records> {
+    anyhow::bail!("Parquet import requires building with `--features parquet`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_import_records() {
+        let record: ImportRecord =
+            serde_json::from_str(r#"{"id": "repo:abc", "embedding": [0.5, -0.5]}"#).unwrap();
+        assert_eq!(record.model, None);
+        assert_eq!(record.vector, vec![0.5, -0.5]);
+
+        assert_eq!(parse_repo_id("repo:abc").unwrap(), RecordId::from(("repo", "abc")));
+        assert_eq!(parse_repo_id("abc").unwrap(), RecordId::from(("repo", "abc")));
+        assert_eq!(parse_repo_id("repo:42").unwrap(), RecordId::from(("repo", 42i64)));
+        assert_eq!(
+            parse_repo_id("repo:⟨rust-lang/rust⟩").unwrap(),
+            RecordId::from(("repo", "rust-lang/rust"))
+        );
+        assert!(parse_repo_id("user:abc").is_err());
+
+        assert_eq!(ImportFormat::from_path(Path::new("vectors.jsonl")), Some(ImportFormat::Jsonl));
+        assert_eq!(ImportFormat::from_path(Path::new("vectors.parquet")), Some(ImportFormat::Parquet));
+        assert_eq!(known_dimensions("nomic-embed-text:latest"), Some(768));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::shutdown::ShutdownController;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tracing::{info, warn};
+
+/// Operator-controlled intake state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntakeMode {
+    /// Repos are queued and processed normally
+    Running,
+    /// Workers stop taking repos off the queue; nothing queued is lost
+    Paused,
+    /// No new repos are queued; the queue is finished and the service exits
+    Draining,
+}
+
+impl IntakeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntakeMode::Running => "running",
+            IntakeMode::Paused => "paused",
+            IntakeMode::Draining => "draining",
+        }
+    }
+}
+
+/// Shared pause/resume/drain switch for the pipeline, driven by the admin API
+pub struct IntakeControl {
+    mode: RwLock<IntakeMode>,
+    in_flight: AtomicUsize,
+    drained: AtomicBool,
+    shutdown: ShutdownController,
+}
+
+impl IntakeControl {
+    pub fn new(shutdown: ShutdownController) -> Self {
+        Self {
+            mode: RwLock::new(IntakeMode::Running),
+            in_flight: AtomicUsize::new(0),
+            drained: AtomicBool::new(false),
+            shutdown,
+        }
+    }
+
+    pub fn mode(&self) -> IntakeMode {
+        *self.mode.read()
+    }
+
+    pub fn pause(&self) -> IntakeMode {
+        self.set_mode(IntakeMode::Paused)
+    }
+
+    pub fn resume(&self) -> IntakeMode {
+        self.set_mode(IntakeMode::Running)
+    }
+
+    pub fn drain(&self) -> IntakeMode {
+        self.set_mode(IntakeMode::Draining)
+    }
+
+    fn set_mode(&self, mode: IntakeMode) -> IntakeMode {
+        if self.drained.load(Ordering::SeqCst) {
+            warn!("Drain already finished, ignoring request to switch intake to {}", mode.as_str());
+            return IntakeMode::Draining;
+        }
+        let previous = std::mem::replace(&mut *self.mode.write(), mode);
+        if previous != mode {
+            info!("Intake switched from {} to {}", previous.as_str(), mode.as_str());
+            crate::metrics::set_intake_paused(mode == IntakeMode::Paused);
+        }
+        mode
+    }
+
+    /// Whether producers should keep queueing repos
+    pub fn accepting(&self) -> bool {
+        self.mode() != IntakeMode::Draining
+    }
+
+    /// Number of batches currently being processed
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Mark a batch as taken off the queue. Call while holding the queue lock so an empty
+    /// queue with no batches in flight really means the drain is done.
+    pub fn begin_batch(&self) -> BatchGuard<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        BatchGuard { control: self }
+    }
+
+    /// Called by a worker that found the queue empty. Triggers shutdown once the drain is done.
+    pub fn queue_empty(&self) {
+        if self.mode() == IntakeMode::Draining
+            && self.in_flight() == 0
+            && !self.drained.swap(true, Ordering::SeqCst)
+        {
+            info!("Queue drained, shutting down");
+            self.shutdown.shutdown();
+        }
+    }
+}
+
+/// Keeps a batch counted as in flight until dropped
+pub struct BatchGuard<'a> {
+    control: &'a IntakeControl,
+}
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        self.control.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight_batches() {
+        let (controller, receiver) = ShutdownController::new();
+        let mut shutdown_rx = receiver.subscribe();
+        let intake = IntakeControl::new(controller);
+
+        assert_eq!(intake.pause(), IntakeMode::Paused);
+        assert!(intake.accepting());
+        assert_eq!(intake.resume(), IntakeMode::Running);
+
+        let batch = intake.begin_batch();
+        intake.drain();
+        assert!(!intake.accepting());
+
+        intake.queue_empty();
+        assert!(shutdown_rx.try_recv().is_err());
+
+        drop(batch);
+        intake.queue_empty();
+        assert!(shutdown_rx.try_recv().is_ok());
+
+        // A finished drain can't be undone
+        assert_eq!(intake.resume(), IntakeMode::Draining);
+    }
+}
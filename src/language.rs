@@ -0,0 +1,74 @@
+use crate::{embedder::Embedder, models::Repo, validation::EmbeddingValidator};
+use std::sync::Arc;
+use whatlang::Lang;
+
+/// Descriptions shorter than this rarely say enough to tell languages apart
+const MIN_DETECTION_CHARS: usize = 20;
+
+/// ISO 639-3 code of the language the repo's description is written in, when whatlang is
+/// confident about it
+pub fn description_language(repo: &Repo) -> Option<&'static str> {
+    detect(repo.description.as_deref()?).map(|lang| lang.code())
+}
+
+fn detect(text: &str) -> Option<Lang> {
+    if text.chars().filter(|c| c.is_alphabetic()).count() < MIN_DETECTION_CHARS {
+        return None;
+    }
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang())
+}
+
+/// Sends repos whose description is detectably not English to a multilingual model, so the
+/// primary model can be a cheaper English-only one. Repos without a detectable language stay
+/// with the primary model. The workers run the routed repos through `process_batch` with this
+/// embedder and the tenant's own sink, and each vector is stored with the model that made it.
+pub struct LanguageRouter {
+    embedder: Arc<Embedder>,
+    validator: Arc<EmbeddingValidator>,
+}
+
+impl LanguageRouter {
+    pub fn new(embedder: Arc<Embedder>, validator: Arc<EmbeddingValidator>) -> Self {
+        Self { embedder, validator }
+    }
+
+    pub fn embedder(&self) -> &Arc<Embedder> {
+        &self.embedder
+    }
+
+    /// Validator with the multilingual model's profile
+    pub fn validator(&self) -> &Arc<EmbeddingValidator> {
+        &self.validator
+    }
+
+    /// Split `batch` into the repos for the primary model and those for the multilingual one
+    pub fn split(&self, batch: &[Repo]) -> (Vec<Repo>, Vec<Repo>) {
+        batch.iter().cloned().partition(|repo| {
+            repo.description
+                .as_deref()
+                .and_then(detect)
+                .is_none_or(|lang| lang == Lang::Eng)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        let english = "A fast and friendly web framework for building reliable network services, \
+                       with routing, middleware and templates that are easy to learn and use";
+        assert_eq!(detect(english).map(|l| l.code()), Some("eng"));
+
+        let german = "Ein schnelles und freundliches Framework für Webanwendungen und Dienste, \
+                      das einfach zu lernen ist und sich gut mit anderen Bibliotheken verbinden lässt";
+        assert_eq!(detect(german).map(|l| l.code()), Some("deu"));
+
+        // Too short to tell
+        assert_eq!(detect("CLI tool"), None);
+    }
+}
@@ -1,30 +1,72 @@
+pub mod ab;
+pub mod api_keys;
+pub mod audit;
+pub mod autoscale;
+pub mod batch_jobs;
+pub mod chunking;
 pub mod circuit_breaker;
+pub mod cli;
+pub mod clustering;
 pub mod config;
+pub mod config_check;
+pub mod config_file;
+pub mod cost;
+pub mod duplicates;
 pub mod embedder;
 pub mod embedding_cache;
-pub mod embedding_validation;
 pub mod error;
+pub mod eval;
+pub mod events;
+pub mod history;
+pub mod import;
+pub mod intake;
+pub mod language;
 pub mod metrics;
 pub mod migration;
+#[cfg(feature = "mock")]
+pub mod mock_embedder;
 pub mod models;
+pub mod notifier;
+pub mod openmetrics;
+pub mod pipeline;
 pub mod pool;
 pub mod pool_metrics;
+pub mod precision;
 pub mod process_batch;
+pub mod provider_error;
+pub mod provider_probe;
+pub mod quantization;
 pub mod rate_limiter;
+pub mod recording;
+pub mod reload;
+pub mod repo_store;
 pub mod retry;
+pub mod runtime_metrics;
+pub mod sanitize;
+pub mod scheduler;
+pub mod secrets;
 pub mod server;
 pub mod service;
 pub mod shutdown;
+pub mod stats;
 pub mod surreal_client;
+pub mod telemetry;
+pub mod tenant;
+pub mod text_sizes;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tls;
+pub mod user_embeddings;
 pub mod validation;
-
-use clap::Parser;
+pub mod verify;
+pub mod wal;
+pub mod write_retry;
 
 /// Run the embed_star service
 pub async fn run_service() -> anyhow::Result<()> {
-    // Parse config from environment/CLI
-    let config = config::Config::parse();
+    // Parse config from the config file, environment and CLI
+    let config = config_file::load_config().await?;
     
-    // Run the service
-    service::run_with_config(config).await
+    // Run the selected command (the service by default)
+    cli::dispatch(config).await
 }
\ No newline at end of file
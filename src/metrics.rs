@@ -1,131 +1,404 @@
+use crate::tenant::current_tenant;
 use prometheus::{
-    register_counter_vec, register_histogram_vec, register_int_gauge, register_int_gauge_vec,
-    CounterVec, HistogramVec, IntGauge, IntGaugeVec, Registry,
+    core::Collector,
+    proto::{MetricFamily, MetricType},
+    CounterVec, Encoder, GaugeVec, HistogramVec, IntGauge, IntGaugeVec, Registry, TextEncoder,
 };
-use std::sync::OnceLock;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
 
 pub struct Metrics {
     pub embeddings_total: CounterVec,
     pub embeddings_errors: CounterVec,
     pub embedding_duration: HistogramVec,
-    pub repos_pending: IntGauge,
-    pub repos_processed: IntGauge,
+    pub repos_pending: IntGaugeVec,
+    pub repos_processed: IntGaugeVec,
     pub provider_requests: CounterVec,
     pub rate_limits: CounterVec,
     pub active_connections: IntGaugeVec,
     pub circuit_breaker_state: IntGaugeVec,
     pub retry_attempts: CounterVec,
-    pub pool_connections_active: IntGauge,
-    pub pool_connections_idle: IntGauge,
-    pub pool_connections_waiting: IntGauge,
+    pub pool_connections_active: IntGaugeVec,
+    pub pool_connections_idle: IntGaugeVec,
+    pub pool_connections_waiting: IntGaugeVec,
     pub pool_connections_created: CounterVec,
     pub pool_connections_recycled: CounterVec,
     pub pool_connection_errors: CounterVec,
     pub pool_health_check_failures: CounterVec,
     pub embedding_validations: CounterVec,
+    pub validation_repairs: CounterVec,
+    pub negative_cache_hits: CounterVec,
+    pub intake_paused: IntGauge,
+    pub backlog_paused: IntGauge,
+    pub api_key_requests: CounterVec,
+    pub api_keys_active: IntGaugeVec,
+    pub prompt_tokens: CounterVec,
+    pub estimated_cost: CounterVec,
+    pub budget_exceeded: IntGaugeVec,
+    pub retry_budget_exhausted: CounterVec,
+    pub provider_up: IntGaugeVec,
+    pub provider_probe_latency: GaugeVec,
+    pub provider_failure_rate: GaugeVec,
+    pub provider_average_magnitude: GaugeVec,
+    pub provider_dimension_consistency: GaugeVec,
+    pub runtime_workers: IntGauge,
+    pub runtime_alive_tasks: IntGauge,
+    pub runtime_global_queue_depth: IntGauge,
+    pub runtime_worker_busy_seconds: GaugeVec,
+    pub runtime_worker_polls: IntGaugeVec,
+    pub queue_depth: IntGauge,
+    pub worker_batch_items: IntGaugeVec,
+    pub batch_size: HistogramVec,
+    pub db_batch_update_duration: HistogramVec,
+    pub embedding_freshness_lag: HistogramVec,
+    pub oldest_pending_age: IntGaugeVec,
+    pub db_unavailable: IntGaugeVec,
+    pub repos_removed: CounterVec,
+    pub repo_fields_defaulted: CounterVec,
+    pub user_embeddings_refreshed: CounterVec,
+    pub duplicate_pairs: IntGaugeVec,
+    pub duplicate_repos: IntGaugeVec,
+    pub write_retry_queued: IntGaugeVec,
+    pub write_retry_updates: CounterVec,
+    pub workers: IntGauge,
+    pub desired_workers: IntGauge,
+    pub text_tokens: HistogramVec,
+    pub text_truncations: CounterVec,
 }
 
 static METRICS: OnceLock<Metrics> = OnceLock::new();
 
+const EMBEDDING_DURATION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
 impl Metrics {
-    pub fn new(_registry: &Registry) -> prometheus::Result<Self> {
+    /// Build the metrics without registering them anywhere; see [`Metrics::register`]
+    pub fn new() -> prometheus::Result<Self> {
         Ok(Self {
-            embeddings_total: register_counter_vec!(
+            embeddings_total: CounterVec::new(
                 prometheus::opts!("embed_star_embeddings_total", "Total number of embeddings generated"),
-                &["provider", "model"]
+                &["provider", "model", "tenant"]
             )?,
-            embeddings_errors: register_counter_vec!(
+            embeddings_errors: CounterVec::new(
                 prometheus::opts!("embed_star_embeddings_errors_total", "Total number of embedding errors"),
-                &["provider", "error_type"]
+                &["provider", "error_type", "tenant"]
             )?,
             embedding_duration: {
                 let opts = prometheus::HistogramOpts::new(
                     "embed_star_embedding_duration_seconds",
                     "Time taken to generate embeddings"
-                ).buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]);
-                register_histogram_vec!(opts, &["provider", "model"])?
+                ).buckets(EMBEDDING_DURATION_BUCKETS.to_vec());
+                HistogramVec::new(opts, &["provider", "model"])?
             },
-            repos_pending: register_int_gauge!(
-                prometheus::opts!("embed_star_repos_pending", "Number of repos pending embedding generation")
+            repos_pending: IntGaugeVec::new(
+                prometheus::opts!("embed_star_repos_pending", "Number of repos pending embedding generation"),
+                &["tenant"]
             )?,
-            repos_processed: register_int_gauge!(
-                prometheus::opts!("embed_star_repos_processed", "Total number of repos processed")
+            repos_processed: IntGaugeVec::new(
+                prometheus::opts!("embed_star_repos_processed", "Total number of repos processed"),
+                &["tenant"]
             )?,
-            provider_requests: register_counter_vec!(
+            provider_requests: CounterVec::new(
                 prometheus::opts!("embed_star_provider_requests_total", "Total requests to embedding providers"),
-                &["provider", "status"]
+                &["provider", "status", "tenant"]
             )?,
-            rate_limits: register_counter_vec!(
+            rate_limits: CounterVec::new(
                 prometheus::opts!("embed_star_rate_limits_total", "Total number of rate limit hits"),
-                &["provider"]
+                &["provider", "tenant"]
             )?,
-            active_connections: register_int_gauge_vec!(
+            active_connections: IntGaugeVec::new(
                 prometheus::opts!("embed_star_active_connections", "Number of active connections"),
                 &["type"]
             )?,
-            circuit_breaker_state: register_int_gauge_vec!(
+            circuit_breaker_state: IntGaugeVec::new(
                 prometheus::opts!("embed_star_circuit_breaker_state", "Circuit breaker state (0=closed, 1=open, 2=half-open)"),
                 &["service"]
             )?,
-            retry_attempts: register_counter_vec!(
+            retry_attempts: CounterVec::new(
                 prometheus::opts!("embed_star_retry_attempts_total", "Total retry attempts"),
-                &["operation"]
+                &["operation", "tenant"]
             )?,
-            pool_connections_active: register_int_gauge!(
-                prometheus::opts!("embed_star_pool_connections_active", "Number of active pool connections")
+            pool_connections_active: IntGaugeVec::new(
+                prometheus::opts!("embed_star_pool_connections_active", "Number of active pool connections"),
+                &["pool"]
             )?,
-            pool_connections_idle: register_int_gauge!(
-                prometheus::opts!("embed_star_pool_connections_idle", "Number of idle pool connections")
+            pool_connections_idle: IntGaugeVec::new(
+                prometheus::opts!("embed_star_pool_connections_idle", "Number of idle pool connections"),
+                &["pool"]
             )?,
-            pool_connections_waiting: register_int_gauge!(
-                prometheus::opts!("embed_star_pool_connections_waiting", "Number of requests waiting for a connection")
+            pool_connections_waiting: IntGaugeVec::new(
+                prometheus::opts!("embed_star_pool_connections_waiting", "Number of requests waiting for a connection"),
+                &["pool"]
             )?,
-            pool_connections_created: register_counter_vec!(
+            pool_connections_created: CounterVec::new(
                 prometheus::opts!("embed_star_pool_connections_created_total", "Total pool connections created"),
                 &["pool"]
             )?,
-            pool_connections_recycled: register_counter_vec!(
+            pool_connections_recycled: CounterVec::new(
                 prometheus::opts!("embed_star_pool_connections_recycled_total", "Total pool connections recycled"),
                 &["pool"]
             )?,
-            pool_connection_errors: register_counter_vec!(
+            pool_connection_errors: CounterVec::new(
                 prometheus::opts!("embed_star_pool_connection_errors_total", "Total pool connection errors"),
                 &["pool", "error_type"]
             )?,
-            pool_health_check_failures: register_counter_vec!(
+            pool_health_check_failures: CounterVec::new(
                 prometheus::opts!("embed_star_pool_health_check_failures_total", "Total pool health check failures"),
                 &["pool"]
             )?,
-            embedding_validations: register_counter_vec!(
+            embedding_validations: CounterVec::new(
                 prometheus::opts!("embed_star_embedding_validations_total", "Total embedding validation attempts"),
-                &["model", "status"]
+                &["model", "status", "tenant"]
+            )?,
+            validation_repairs: CounterVec::new(
+                prometheus::opts!("embed_star_validation_repairs_total", "Embeddings that failed validation with repair enabled, by whether rescaling repaired them"),
+                &["model", "result", "tenant"]
+            )?,
+            negative_cache_hits: CounterVec::new(
+                prometheus::opts!("embed_star_negative_cache_hits_total", "Repos skipped because of a recently cached provider failure"),
+                &["provider", "tenant"]
+            )?,
+            intake_paused: IntGauge::with_opts(
+                prometheus::opts!("embed_star_intake_paused", "Whether workers stopped pulling repos (operator pause, database outage, open provider circuit or budget; 1 = paused)")
+            )?,
+            backlog_paused: IntGauge::with_opts(
+                prometheus::opts!("embed_star_backlog_paused", "Whether backlog processing is held back by BACKLOG_WINDOWS or BACKLOG_MAX_LATENCY_MS (1 = paused)")
+            )?,
+            api_key_requests: CounterVec::new(
+                prometheus::opts!("embed_star_api_key_requests_total", "Provider requests per API key by outcome"),
+                &["provider", "key", "status"]
+            )?,
+            api_keys_active: IntGaugeVec::new(
+                prometheus::opts!("embed_star_api_keys_active", "API keys not disabled after auth failures"),
+                &["provider"]
+            )?,
+            prompt_tokens: CounterVec::new(
+                prometheus::opts!("embed_star_prompt_tokens_total", "Prompt tokens sent to embedding providers, as reported by the provider or estimated"),
+                &["provider", "model", "source", "tenant"]
+            )?,
+            estimated_cost: CounterVec::new(
+                prometheus::opts!("embed_star_estimated_cost_dollars_total", "Estimated embedding spend in USD"),
+                &["provider", "model", "tenant"]
+            )?,
+            budget_exceeded: IntGaugeVec::new(
+                prometheus::opts!("embed_star_budget_exceeded", "Whether the embedding budget for the period is exhausted (1 = processing paused)"),
+                &["period"]
+            )?,
+            retry_budget_exhausted: CounterVec::new(
+                prometheus::opts!("embed_star_retry_budget_exhausted_total", "Retries skipped because the service-wide retry budget was spent"),
+                &["operation"]
+            )?,
+            provider_up: IntGaugeVec::new(
+                prometheus::opts!("embed_star_provider_up", "Whether the last background probe of the embedding provider succeeded"),
+                &["provider", "model"]
+            )?,
+            provider_probe_latency: GaugeVec::new(
+                prometheus::opts!("embed_star_provider_probe_latency_seconds", "Latency of the last successful provider probe"),
+                &["provider", "model"]
+            )?,
+            provider_failure_rate: GaugeVec::new(
+                prometheus::opts!("embed_star_provider_validation_failure_rate", "Share of the provider's recent embeddings that failed validation"),
+                &["provider"]
+            )?,
+            provider_average_magnitude: GaugeVec::new(
+                prometheus::opts!("embed_star_provider_average_magnitude", "Mean magnitude of the provider's recent embeddings"),
+                &["provider"]
+            )?,
+            provider_dimension_consistency: GaugeVec::new(
+                prometheus::opts!("embed_star_provider_dimension_consistency", "Share of the provider's recent embeddings with its most common dimension"),
+                &["provider"]
+            )?,
+            runtime_workers: IntGauge::new(
+                "embed_star_runtime_workers",
+                "Number of tokio runtime worker threads"
+            )?,
+            runtime_alive_tasks: IntGauge::new(
+                "embed_star_runtime_alive_tasks",
+                "Number of tokio tasks currently alive"
+            )?,
+            runtime_global_queue_depth: IntGauge::new(
+                "embed_star_runtime_global_queue_depth",
+                "Tasks waiting in the tokio runtime's global injection queue"
+            )?,
+            runtime_worker_busy_seconds: GaugeVec::new(
+                prometheus::opts!("embed_star_runtime_worker_busy_seconds", "Total time each tokio worker thread has spent polling tasks (tokio_unstable builds only)"),
+                &["worker"]
+            )?,
+            runtime_worker_polls: IntGaugeVec::new(
+                prometheus::opts!("embed_star_runtime_worker_polls", "Total task polls per tokio worker thread (tokio_unstable builds only)"),
+                &["worker"]
+            )?,
+            queue_depth: IntGauge::new(
+                "embed_star_queue_depth",
+                "Repos waiting in the processing channel"
+            )?,
+            worker_batch_items: IntGaugeVec::new(
+                prometheus::opts!("embed_star_worker_batch_items", "Repos in the batch each worker is currently processing"),
+                &["worker"]
+            )?,
+            batch_size: {
+                let opts = prometheus::HistogramOpts::new(
+                    "embed_star_batch_size",
+                    "Number of repos in each batch taken off the queue"
+                ).buckets(prometheus::exponential_buckets(1.0, 2.0, 10)?);
+                HistogramVec::new(opts, &["worker", "tenant"])?
+            },
+            db_batch_update_duration: {
+                let opts = prometheus::HistogramOpts::new(
+                    "embed_star_db_batch_update_duration_seconds",
+                    "Time taken to write a batch of embeddings to the database"
+                ).buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]);
+                HistogramVec::new(opts, &["outcome", "tenant"])?
+            },
+            embedding_freshness_lag: {
+                let opts = prometheus::HistogramOpts::new(
+                    "embed_star_embedding_freshness_lag_seconds",
+                    "Time from a repo's updated_at to its embedding being written"
+                ).buckets(vec![1.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0, 21600.0, 86400.0, 604800.0]);
+                HistogramVec::new(opts, &["model", "tenant"])?
+            },
+            oldest_pending_age: IntGaugeVec::new(
+                prometheus::opts!("embed_star_oldest_pending_age_seconds", "Seconds since the least recently updated repo still waiting for an embedding was updated (0 when none are pending)"),
+                &["tenant"]
+            )?,
+            db_unavailable: IntGaugeVec::new(
+                prometheus::opts!("embed_star_db_unavailable", "Whether SurrealDB is unreachable and the pool is reconnecting with backoff (1 = unavailable)"),
+                &["pool"]
+            )?,
+            repos_removed: CounterVec::new(
+                prometheus::opts!("embed_star_repos_removed_total", "Repos whose cached and sink embeddings were dropped because they were deleted or made private"),
+                &["reason", "tenant"]
+            )?,
+            repo_fields_defaulted: CounterVec::new(
+                prometheus::opts!("embed_star_repo_fields_defaulted_total", "Repo fields missing from a fetched record and filled with a default"),
+                &["field", "tenant"]
+            )?,
+            user_embeddings_refreshed: CounterVec::new(
+                prometheus::opts!("embed_star_user_embeddings_refreshed_total", "User aggregate embeddings recomputed, by whether any starred repo was embedded"),
+                &["result", "tenant"]
+            )?,
+            duplicate_pairs: IntGaugeVec::new(
+                prometheus::opts!("embed_star_duplicate_pairs", "Near-duplicate repo pairs found by the latest duplicates scan"),
+                &["tenant"]
+            )?,
+            duplicate_repos: IntGaugeVec::new(
+                prometheus::opts!("embed_star_duplicate_repos", "Distinct repos in at least one near-duplicate pair in the latest duplicates scan"),
+                &["tenant"]
+            )?,
+            write_retry_queued: IntGaugeVec::new(
+                prometheus::opts!("embed_star_write_retry_queued", "Generated embeddings waiting to be written again after a failed database write"),
+                &["tenant"]
+            )?,
+            write_retry_updates: CounterVec::new(
+                prometheus::opts!("embed_star_write_retry_updates_total", "Queued embeddings written on retry (replayed) or discarded because the queue was full (dropped)"),
+                &["result", "tenant"]
+            )?,
+            workers: IntGauge::with_opts(
+                prometheus::opts!("embed_star_workers", "Batch processor workers running")
+            )?,
+            desired_workers: IntGauge::with_opts(
+                prometheus::opts!("embed_star_desired_workers", "Workers that would absorb new repos and clear the backlog within AUTOSCALE_DRAIN_TARGET_SECS, given the current provider latency")
+            )?,
+            text_tokens: {
+                let opts = prometheus::HistogramOpts::new(
+                    "embed_star_text_tokens",
+                    "Estimated tokens in each prepared text before truncation or chunking"
+                ).buckets(prometheus::exponential_buckets(16.0, 2.0, 12)?);
+                HistogramVec::new(opts, &["model", "tenant"])?
+            },
+            text_truncations: CounterVec::new(
+                prometheus::opts!("embed_star_text_truncations_total", "Texts cut to TOKEN_LIMIT before being sent to the provider"),
+                &["model", "tenant"]
             )?,
         })
     }
     
+    /// Every metric, for registering
+    fn collectors(&self) -> Vec<Box<dyn Collector>> {
+        vec![
+            Box::new(self.embeddings_total.clone()),
+            Box::new(self.embeddings_errors.clone()),
+            Box::new(self.embedding_duration.clone()),
+            Box::new(self.repos_pending.clone()),
+            Box::new(self.repos_processed.clone()),
+            Box::new(self.provider_requests.clone()),
+            Box::new(self.rate_limits.clone()),
+            Box::new(self.active_connections.clone()),
+            Box::new(self.circuit_breaker_state.clone()),
+            Box::new(self.retry_attempts.clone()),
+            Box::new(self.pool_connections_active.clone()),
+            Box::new(self.pool_connections_idle.clone()),
+            Box::new(self.pool_connections_waiting.clone()),
+            Box::new(self.pool_connections_created.clone()),
+            Box::new(self.pool_connections_recycled.clone()),
+            Box::new(self.pool_connection_errors.clone()),
+            Box::new(self.pool_health_check_failures.clone()),
+            Box::new(self.embedding_validations.clone()),
+            Box::new(self.validation_repairs.clone()),
+            Box::new(self.negative_cache_hits.clone()),
+            Box::new(self.intake_paused.clone()),
+            Box::new(self.backlog_paused.clone()),
+            Box::new(self.api_key_requests.clone()),
+            Box::new(self.api_keys_active.clone()),
+            Box::new(self.prompt_tokens.clone()),
+            Box::new(self.estimated_cost.clone()),
+            Box::new(self.budget_exceeded.clone()),
+            Box::new(self.retry_budget_exhausted.clone()),
+            Box::new(self.provider_up.clone()),
+            Box::new(self.provider_probe_latency.clone()),
+            Box::new(self.provider_failure_rate.clone()),
+            Box::new(self.provider_average_magnitude.clone()),
+            Box::new(self.provider_dimension_consistency.clone()),
+            Box::new(self.runtime_workers.clone()),
+            Box::new(self.runtime_alive_tasks.clone()),
+            Box::new(self.runtime_global_queue_depth.clone()),
+            Box::new(self.runtime_worker_busy_seconds.clone()),
+            Box::new(self.runtime_worker_polls.clone()),
+            Box::new(self.queue_depth.clone()),
+            Box::new(self.worker_batch_items.clone()),
+            Box::new(self.batch_size.clone()),
+            Box::new(self.db_batch_update_duration.clone()),
+            Box::new(self.embedding_freshness_lag.clone()),
+            Box::new(self.oldest_pending_age.clone()),
+            Box::new(self.db_unavailable.clone()),
+            Box::new(self.repos_removed.clone()),
+            Box::new(self.repo_fields_defaulted.clone()),
+            Box::new(self.user_embeddings_refreshed.clone()),
+            Box::new(self.duplicate_pairs.clone()),
+            Box::new(self.duplicate_repos.clone()),
+            Box::new(self.write_retry_queued.clone()),
+            Box::new(self.write_retry_updates.clone()),
+            Box::new(self.workers.clone()),
+            Box::new(self.desired_workers.clone()),
+            Box::new(self.text_tokens.clone()),
+            Box::new(self.text_truncations.clone()),
+        ]
+    }
+
+    /// Register the process-wide metrics on `registry`, creating them on first use. Calling it
+    /// again, with the same or another registry (an embedding application's, or a test's), is
+    /// fine: every registry shares the same metrics.
     pub fn register(registry: &Registry) -> prometheus::Result<()> {
-        let metrics = Self::new(registry)?;
-        
-        registry.register(Box::new(metrics.embeddings_total.clone()))?;
-        registry.register(Box::new(metrics.embeddings_errors.clone()))?;
-        registry.register(Box::new(metrics.embedding_duration.clone()))?;
-        registry.register(Box::new(metrics.repos_pending.clone()))?;
-        registry.register(Box::new(metrics.repos_processed.clone()))?;
-        registry.register(Box::new(metrics.provider_requests.clone()))?;
-        registry.register(Box::new(metrics.rate_limits.clone()))?;
-        registry.register(Box::new(metrics.active_connections.clone()))?;
-        registry.register(Box::new(metrics.circuit_breaker_state.clone()))?;
-        registry.register(Box::new(metrics.retry_attempts.clone()))?;
-        registry.register(Box::new(metrics.pool_connections_active.clone()))?;
-        registry.register(Box::new(metrics.pool_connections_idle.clone()))?;
-        registry.register(Box::new(metrics.pool_connections_waiting.clone()))?;
-        registry.register(Box::new(metrics.pool_connections_created.clone()))?;
-        registry.register(Box::new(metrics.pool_connections_recycled.clone()))?;
-        registry.register(Box::new(metrics.pool_connection_errors.clone()))?;
-        registry.register(Box::new(metrics.pool_health_check_failures.clone()))?;
-        registry.register(Box::new(metrics.embedding_validations.clone()))?;
-        
-        METRICS.set(metrics).map_err(|_| prometheus::Error::Msg("Metrics already initialized".to_string()))?;
+        let metrics = match METRICS.get() {
+            Some(metrics) => metrics,
+            None => {
+                // A concurrent caller may win the race; its metrics are used instead
+                let _ = METRICS.set(Self::new()?);
+                Self::get()
+            }
+        };
+
+        for collector in metrics.collectors() {
+            match registry.register(collector) {
+                Ok(()) | Err(prometheus::Error::AlreadyReg) => {}
+                Err(e) => return Err(e),
+            }
+        }
         Ok(())
     }
     
@@ -134,32 +407,52 @@ impl Metrics {
     }
 }
 
+// Metrics about a tenant's repos are labelled with the tenant the calling task works for
+// (`tenant::in_tenant`); the pool label is the tenant name as each tenant has its own pool
+
 pub fn record_embedding_generated(provider: &str, model: &str, duration: f64) {
     let metrics = Metrics::get();
-    metrics.embeddings_total.with_label_values(&[provider, model]).inc();
+    let tenant = current_tenant();
+    metrics.embeddings_total.with_label_values(&[provider, model, &tenant]).inc();
     metrics.embedding_duration.with_label_values(&[provider, model]).observe(duration);
-    metrics.repos_processed.inc();
+    // Slow requests in a dashboard link straight to their trace
+    if let Some(trace_id) = crate::telemetry::current_trace_id() {
+        crate::openmetrics::exemplars().observe(
+            "embed_star_embedding_duration_seconds",
+            &[("provider", provider), ("model", model)],
+            EMBEDDING_DURATION_BUCKETS,
+            duration,
+            trace_id,
+        );
+    }
+    metrics.repos_processed.with_label_values(&[&tenant]).inc();
 }
 
 pub fn record_embedding_error(provider: &str, error_type: &str) {
     let metrics = Metrics::get();
-    metrics.embeddings_errors.with_label_values(&[provider, error_type]).inc();
+    metrics
+        .embeddings_errors
+        .with_label_values(&[provider, error_type, &current_tenant()])
+        .inc();
 }
 
 pub fn record_provider_request(provider: &str, success: bool) {
     let metrics = Metrics::get();
     let status = if success { "success" } else { "failure" };
-    metrics.provider_requests.with_label_values(&[provider, status]).inc();
+    metrics
+        .provider_requests
+        .with_label_values(&[provider, status, &current_tenant()])
+        .inc();
 }
 
 pub fn record_rate_limit(provider: &str) {
     let metrics = Metrics::get();
-    metrics.rate_limits.with_label_values(&[provider]).inc();
+    metrics.rate_limits.with_label_values(&[provider, &current_tenant()]).inc();
 }
 
 pub fn set_pending_repos(count: i64) {
     let metrics = Metrics::get();
-    metrics.repos_pending.set(count);
+    metrics.repos_pending.with_label_values(&[&current_tenant()]).set(count);
 }
 
 pub fn update_active_connections(conn_type: &str, delta: i64) {
@@ -168,58 +461,650 @@ pub fn update_active_connections(conn_type: &str, delta: i64) {
 }
 
 pub fn record_circuit_breaker_state(service: &str, state: &str) {
-    let metrics = Metrics::get();
-    let value = match state {
-        "closed" => 0,
-        "open" => 1,
-        "half_open" => 2,
-        _ => 0,
-    };
-    metrics.circuit_breaker_state.with_label_values(&[service]).set(value);
+    if let Some(metrics) = METRICS.get() {
+        let value = match state {
+            "closed" => 0,
+            "open" => 1,
+            "half_open" => 2,
+            _ => 0,
+        };
+        metrics.circuit_breaker_state.with_label_values(&[service]).set(value);
+    }
 }
 
 pub fn record_retry(operation: &str) {
-    let metrics = Metrics::get();
-    metrics.retry_attempts.with_label_values(&[operation]).inc();
+    if let Some(metrics) = METRICS.get() {
+        metrics.retry_attempts.with_label_values(&[operation, &current_tenant()]).inc();
+    }
 }
 
 pub fn set_pool_connections_active(count: i64) {
     let metrics = Metrics::get();
-    metrics.pool_connections_active.set(count);
+    metrics.pool_connections_active.with_label_values(&[&current_tenant()]).set(count);
 }
 
 pub fn set_pool_connections_idle(count: i64) {
     let metrics = Metrics::get();
-    metrics.pool_connections_idle.set(count);
+    metrics.pool_connections_idle.with_label_values(&[&current_tenant()]).set(count);
 }
 
 pub fn set_pool_connections_waiting(count: i64) {
     let metrics = Metrics::get();
-    metrics.pool_connections_waiting.set(count);
+    metrics.pool_connections_waiting.with_label_values(&[&current_tenant()]).set(count);
 }
 
 pub fn increment_pool_connections_created() {
     let metrics = Metrics::get();
-    metrics.pool_connections_created.with_label_values(&["surrealdb"]).inc();
+    metrics.pool_connections_created.with_label_values(&[&current_tenant()]).inc();
 }
 
 pub fn increment_pool_connections_recycled() {
     let metrics = Metrics::get();
-    metrics.pool_connections_recycled.with_label_values(&["surrealdb"]).inc();
+    metrics.pool_connections_recycled.with_label_values(&[&current_tenant()]).inc();
 }
 
 pub fn increment_pool_connection_errors() {
     let metrics = Metrics::get();
-    metrics.pool_connection_errors.with_label_values(&["surrealdb", "create"]).inc();
+    metrics.pool_connection_errors.with_label_values(&[&current_tenant(), "create"]).inc();
 }
 
 pub fn increment_pool_health_check_failures() {
     let metrics = Metrics::get();
-    metrics.pool_health_check_failures.with_label_values(&["surrealdb"]).inc();
+    metrics.pool_health_check_failures.with_label_values(&[&current_tenant()]).inc();
 }
 
 pub fn record_embedding_validation(model: &str, success: bool) {
     let metrics = Metrics::get();
     let status = if success { "pass" } else { "fail" };
-    metrics.embedding_validations.with_label_values(&[model, status]).inc();
-}
\ No newline at end of file
+    metrics
+        .embedding_validations
+        .with_label_values(&[model, status, &current_tenant()])
+        .inc();
+}
+
+pub fn record_validation_repair(model: &str, repaired: bool) {
+    if let Some(metrics) = METRICS.get() {
+        let result = if repaired { "repaired" } else { "rejected" };
+        metrics
+            .validation_repairs
+            .with_label_values(&[model, result, &current_tenant()])
+            .inc();
+    }
+}
+
+pub fn record_negative_cache_hit(provider: &str) {
+    let metrics = Metrics::get();
+    metrics.negative_cache_hits.with_label_values(&[provider, &current_tenant()]).inc();
+}
+
+pub fn set_intake_paused(paused: bool) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.intake_paused.set(paused as i64);
+    }
+}
+
+pub fn set_backlog_paused(paused: bool) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.backlog_paused.set(paused as i64);
+    }
+}
+
+// Key pools are also built by standalone embedders (tests, examples) where metrics may not be registered
+
+pub fn record_api_key_request(provider: &str, key: &str, status: &str) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.api_key_requests.with_label_values(&[provider, key, status]).inc();
+    }
+}
+
+pub fn set_api_keys_active(provider: &str, count: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.api_keys_active.with_label_values(&[provider]).set(count as i64);
+    }
+}
+
+pub fn record_embedding_cost(provider: &str, model: &str, cost: f64) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .estimated_cost
+            .with_label_values(&[provider, model, &current_tenant()])
+            .inc_by(cost);
+    }
+}
+
+pub fn record_prompt_tokens(provider: &str, model: &str, tokens: u64, reported: bool) {
+    if let Some(metrics) = METRICS.get() {
+        let source = if reported { "reported" } else { "estimated" };
+        metrics
+            .prompt_tokens
+            .with_label_values(&[provider, model, source, &current_tenant()])
+            .inc_by(tokens as f64);
+    }
+}
+
+pub fn record_text_tokens(model: &str, tokens: u32) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .text_tokens
+            .with_label_values(&[model, &current_tenant()])
+            .observe(tokens as f64);
+    }
+}
+
+pub fn record_text_truncated(model: &str) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .text_truncations
+            .with_label_values(&[model, &current_tenant()])
+            .inc();
+    }
+}
+
+pub fn set_budget_exceeded(period: Option<crate::cost::BudgetPeriod>) {
+    if let Some(metrics) = METRICS.get() {
+        for candidate in [crate::cost::BudgetPeriod::Daily, crate::cost::BudgetPeriod::Monthly] {
+            let value = (period == Some(candidate)) as i64;
+            metrics.budget_exceeded.with_label_values(&[candidate.as_str()]).set(value);
+        }
+    }
+}
+
+pub fn record_retry_budget_exhausted(operation: &str) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.retry_budget_exhausted.with_label_values(&[operation]).inc();
+    }
+}
+
+pub fn set_provider_probe(provider: &str, model: &str, available: bool, latency: Option<std::time::Duration>) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.provider_up.with_label_values(&[provider, model]).set(available as i64);
+        if let Some(latency) = latency {
+            metrics
+                .provider_probe_latency
+                .with_label_values(&[provider, model])
+                .set(latency.as_secs_f64());
+        }
+    }
+}
+
+/// Rolling quality statistics of a provider, kept by `validation::ProviderQualityTracker`
+pub fn set_provider_quality(provider: &str, failure_rate: f32, average_magnitude: Option<f32>, consistency: Option<f32>) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.provider_failure_rate.with_label_values(&[provider]).set(failure_rate as f64);
+        if let Some(magnitude) = average_magnitude {
+            metrics.provider_average_magnitude.with_label_values(&[provider]).set(magnitude as f64);
+        }
+        if let Some(consistency) = consistency {
+            metrics.provider_dimension_consistency.with_label_values(&[provider]).set(consistency as f64);
+        }
+    }
+}
+
+/// Snapshot of the tokio runtime, exported by `runtime_metrics::monitor_runtime_metrics`
+pub fn set_runtime_stats(workers: usize, alive_tasks: usize, global_queue_depth: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.runtime_workers.set(workers as i64);
+        metrics.runtime_alive_tasks.set(alive_tasks as i64);
+        metrics.runtime_global_queue_depth.set(global_queue_depth as i64);
+    }
+}
+
+pub fn set_runtime_worker_stats(worker: usize, busy: std::time::Duration, polls: u64) {
+    if let Some(metrics) = METRICS.get() {
+        let worker = worker.to_string();
+        metrics
+            .runtime_worker_busy_seconds
+            .with_label_values(&[&worker])
+            .set(busy.as_secs_f64());
+        metrics.runtime_worker_polls.with_label_values(&[&worker]).set(polls as i64);
+    }
+}
+
+pub fn set_queue_depth(depth: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.queue_depth.set(depth as i64);
+    }
+}
+
+pub fn set_worker_batch_items(worker: usize, items: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .worker_batch_items
+            .with_label_values(&[&worker.to_string()])
+            .set(items as i64);
+    }
+}
+
+pub fn set_workers(workers: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.workers.set(workers as i64);
+    }
+}
+
+pub fn set_desired_workers(workers: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.desired_workers.set(workers as i64);
+    }
+}
+
+pub fn record_batch_size(worker: usize, size: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .batch_size
+            .with_label_values(&[&worker.to_string(), &current_tenant()])
+            .observe(size as f64);
+    }
+}
+
+pub fn record_db_batch_update(duration: std::time::Duration, success: bool) {
+    if let Some(metrics) = METRICS.get() {
+        let outcome = if success { "success" } else { "error" };
+        metrics
+            .db_batch_update_duration
+            .with_label_values(&[outcome, &current_tenant()])
+            .observe(duration.as_secs_f64());
+    }
+}
+
+pub fn record_embedding_freshness_lag(model: &str, lag: chrono::Duration) {
+    if let Some(metrics) = METRICS.get() {
+        // Clock skew between SurrealDB and the service can make the lag slightly negative
+        let seconds = lag.num_milliseconds().max(0) as f64 / 1000.0;
+        metrics
+            .embedding_freshness_lag
+            .with_label_values(&[model, &current_tenant()])
+            .observe(seconds);
+    }
+}
+
+pub fn set_oldest_pending_age(age: Option<chrono::Duration>) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .oldest_pending_age
+            .with_label_values(&[&current_tenant()])
+            .set(age.map_or(0, |age| age.num_seconds().max(0)));
+    }
+}
+
+pub fn set_db_unavailable(unavailable: bool) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .db_unavailable
+            .with_label_values(&[&current_tenant()])
+            .set(unavailable as i64);
+    }
+}
+
+pub fn record_repo_removed(reason: &str) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .repos_removed
+            .with_label_values(&[reason, &current_tenant()])
+            .inc();
+    }
+}
+
+pub fn set_write_retry_queued(queued: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .write_retry_queued
+            .with_label_values(&[&current_tenant()])
+            .set(queued as i64);
+    }
+}
+
+pub fn record_write_retry_replayed(count: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .write_retry_updates
+            .with_label_values(&["replayed", &current_tenant()])
+            .inc_by(count as f64);
+    }
+}
+
+pub fn record_write_retry_dropped(count: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .write_retry_updates
+            .with_label_values(&["dropped", &current_tenant()])
+            .inc_by(count as f64);
+    }
+}
+
+pub fn record_repo_field_defaulted(field: &str) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .repo_fields_defaulted
+            .with_label_values(&[field, &current_tenant()])
+            .inc();
+    }
+}
+
+pub fn record_user_embeddings_refreshed(updated: usize, cleared: usize) {
+    if let Some(metrics) = METRICS.get() {
+        let tenant = current_tenant();
+        metrics
+            .user_embeddings_refreshed
+            .with_label_values(&["updated", &tenant])
+            .inc_by(updated as f64);
+        metrics
+            .user_embeddings_refreshed
+            .with_label_values(&["cleared", &tenant])
+            .inc_by(cleared as f64);
+    }
+}
+
+pub fn set_duplicates(pairs: usize, repos: usize) {
+    if let Some(metrics) = METRICS.get() {
+        let tenant = current_tenant();
+        metrics.duplicate_pairs.with_label_values(&[&tenant]).set(pairs as i64);
+        metrics.duplicate_repos.with_label_values(&[&tenant]).set(repos as i64);
+    }
+}
+
+/// Datagrams are kept under a typical MTU so they aren't fragmented
+const STATSD_MAX_PACKET: usize = 1432;
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wire format for StatsD pushes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsdFormat {
+    /// Labels as DogStatsD tags (`name:1|g|#provider:openai`), as the Datadog agent expects
+    DogStatsd,
+    /// Label values folded into the metric name (`name.openai:1|g`) for plain StatsD servers
+    Plain,
+}
+
+impl FromStr for StatsdFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dogstatsd" | "datadog" => Ok(StatsdFormat::DogStatsd),
+            "plain" | "statsd" => Ok(StatsdFormat::Plain),
+            other => Err(format!("Unknown StatsD format '{}'. Expected dogstatsd or plain", other)),
+        }
+    }
+}
+
+/// Turns gathered metric families into StatsD lines. Prometheus counters are cumulative while
+/// StatsD counters are increments, so the last value of every counter series is remembered and
+/// only the increase is sent. Histograms and summaries are sent as their `_count` and `_sum`.
+pub struct StatsdEncoder {
+    format: StatsdFormat,
+    previous: HashMap<String, f64>,
+}
+
+impl StatsdEncoder {
+    pub fn new(format: StatsdFormat) -> Self {
+        Self {
+            format,
+            previous: HashMap::new(),
+        }
+    }
+
+    pub fn encode(&mut self, families: &[MetricFamily]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for family in families {
+            let name = family.get_name();
+            for metric in family.get_metric() {
+                let labels: Vec<(&str, &str)> = metric
+                    .get_label()
+                    .iter()
+                    .map(|label| (label.get_name(), label.get_value()))
+                    .collect();
+                match family.get_field_type() {
+                    MetricType::COUNTER => {
+                        self.counter(&mut lines, name, &labels, metric.get_counter().get_value())
+                    }
+                    MetricType::GAUGE => self.gauge(&mut lines, name, &labels, metric.get_gauge().get_value()),
+                    MetricType::UNTYPED => {
+                        self.gauge(&mut lines, name, &labels, metric.get_untyped().get_value())
+                    }
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        let count = histogram.get_sample_count() as f64;
+                        self.counter(&mut lines, &format!("{}_count", name), &labels, count);
+                        self.counter(&mut lines, &format!("{}_sum", name), &labels, histogram.get_sample_sum());
+                    }
+                    MetricType::SUMMARY => {
+                        let summary = metric.get_summary();
+                        let count = summary.get_sample_count() as f64;
+                        self.counter(&mut lines, &format!("{}_count", name), &labels, count);
+                        self.counter(&mut lines, &format!("{}_sum", name), &labels, summary.get_sample_sum());
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn counter(&mut self, lines: &mut Vec<String>, name: &str, labels: &[(&str, &str)], value: f64) {
+        let key = self.key(name, labels);
+        let previous = self.previous.insert(key, value).unwrap_or(0.0);
+        // A counter below its last value was reset (e.g. the registry was rebuilt)
+        let increase = if value >= previous { value - previous } else { value };
+        if increase > 0.0 {
+            lines.push(self.line(name, labels, increase, "c"));
+        }
+    }
+
+    fn gauge(&self, lines: &mut Vec<String>, name: &str, labels: &[(&str, &str)], value: f64) {
+        // A signed gauge value is an adjustment in StatsD, so negative values start from zero
+        if value < 0.0 {
+            lines.push(self.line(name, labels, 0.0, "g"));
+        }
+        lines.push(self.line(name, labels, value, "g"));
+    }
+
+    fn key(&self, name: &str, labels: &[(&str, &str)]) -> String {
+        let mut key = name.to_string();
+        for (label, value) in labels {
+            key.push_str(&format!(",{}={}", label, value));
+        }
+        key
+    }
+
+    fn line(&self, name: &str, labels: &[(&str, &str)], value: f64, kind: &str) -> String {
+        let clean = |value: &str, keep: &[char]| -> String {
+            value
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || keep.contains(&c) { c } else { '_' })
+                .collect()
+        };
+        match self.format {
+            StatsdFormat::DogStatsd if !labels.is_empty() => {
+                let tags: Vec<String> = labels
+                    .iter()
+                    .map(|(label, value)| format!("{}:{}", label, clean(value, &['-', '_', '.', '/', ':'])))
+                    .collect();
+                format!("{}:{}|{}|#{}", name, value, kind, tags.join(","))
+            }
+            StatsdFormat::DogStatsd => format!("{}:{}|{}", name, value, kind),
+            StatsdFormat::Plain => {
+                let mut name = name.to_string();
+                for (_, value) in labels {
+                    name.push('.');
+                    name.push_str(&clean(value, &['-', '_']));
+                }
+                format!("{}:{}|{}", name, value, kind)
+            }
+        }
+    }
+}
+
+/// Where and how often metrics are pushed, in addition to being served on `/metrics`
+#[derive(Debug, Clone)]
+pub struct PushSettings {
+    pub interval: Duration,
+    /// Pushgateway base URL; metrics go to `/metrics/job/<job>/instance/<instance>`
+    pub pushgateway_url: Option<String>,
+    pub job: String,
+    pub instance: String,
+    /// `host:port` of a StatsD or DogStatsD agent (UDP)
+    pub statsd_addr: Option<String>,
+    pub statsd_format: StatsdFormat,
+}
+
+/// Pushes the registry's metrics to the configured targets
+pub struct MetricsPusher {
+    registry: Arc<Registry>,
+    settings: PushSettings,
+    client: reqwest::Client,
+    statsd: Option<(UdpSocket, StatsdEncoder)>,
+}
+
+impl MetricsPusher {
+    pub async fn new(registry: Arc<Registry>, settings: PushSettings) -> anyhow::Result<Self> {
+        let statsd = match &settings.statsd_addr {
+            Some(addr) => {
+                let target = tokio::net::lookup_host(addr.as_str())
+                    .await?
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("STATSD_ADDR {} did not resolve", addr))?;
+                let local = if target.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+                let socket = UdpSocket::bind(local).await?;
+                socket.connect(target).await?;
+                Some((socket, StatsdEncoder::new(settings.statsd_format)))
+            }
+            None => None,
+        };
+        Ok(Self {
+            registry,
+            client: reqwest::Client::builder().timeout(PUSH_TIMEOUT).build()?,
+            settings,
+            statsd,
+        })
+    }
+
+    /// Push the current values to every target once
+    pub async fn push(&mut self) -> anyhow::Result<()> {
+        let families = self.registry.gather();
+
+        if let Some(base) = &self.settings.pushgateway_url {
+            let mut url = reqwest::Url::parse(base)?;
+            url.path_segments_mut()
+                .map_err(|_| anyhow::anyhow!("Invalid Pushgateway URL {}", base))?
+                .pop_if_empty()
+                .extend(["metrics", "job", &self.settings.job, "instance", &self.settings.instance]);
+            let encoder = TextEncoder::new();
+            let mut body = Vec::new();
+            encoder.encode(&families, &mut body)?;
+            // PUT replaces the whole group, so series that disappeared don't linger
+            let response = self
+                .client
+                .put(url)
+                .header(reqwest::header::CONTENT_TYPE, encoder.format_type())
+                .body(body)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                anyhow::bail!("Pushgateway returned {}", response.status());
+            }
+        }
+
+        if let Some((socket, encoder)) = &mut self.statsd {
+            let mut packet = String::new();
+            for line in encoder.encode(&families) {
+                if !packet.is_empty() && packet.len() + 1 + line.len() > STATSD_MAX_PACKET {
+                    socket.send(packet.as_bytes()).await?;
+                    packet.clear();
+                }
+                if !packet.is_empty() {
+                    packet.push('\n');
+                }
+                packet.push_str(&line);
+            }
+            if !packet.is_empty() {
+                socket.send(packet.as_bytes()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Push on the configured interval until shutdown, then once more so the final values
+    /// aren't lost
+    pub async fn run(mut self, mut shutdown_rx: tokio::sync::broadcast::Receiver<()>) {
+        info!("Pushing metrics every {:?}", self.settings.interval);
+        let mut ticker = tokio::time::interval(self.settings.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    if let Err(e) = self.push().await {
+                        warn!("Final metrics push failed: {}", e);
+                    }
+                    info!("Metrics pusher shutting down");
+                    return;
+                }
+                _ = ticker.tick() => {
+                    if let Err(e) = self.push().await {
+                        warn!("Metrics push failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Gauge, Opts};
+
+    #[test]
+    fn test_statsd_encoding() {
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests_total", "requests"), &["provider"]).unwrap();
+        let gauge = Gauge::new("queue_depth", "depth").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+
+        counter.with_label_values(&["openai"]).inc_by(3.0);
+        gauge.set(-2.0);
+        let mut encoder = StatsdEncoder::new(StatsdFormat::DogStatsd);
+        assert_eq!(
+            encoder.encode(&registry.gather()),
+            vec!["queue_depth:0|g", "queue_depth:-2|g", "requests_total:3|c|#provider:openai"]
+        );
+
+        // Only the increase since the last push is sent
+        counter.with_label_values(&["openai"]).inc_by(2.0);
+        gauge.set(5.0);
+        assert_eq!(
+            encoder.encode(&registry.gather()),
+            vec!["queue_depth:5|g", "requests_total:2|c|#provider:openai"]
+        );
+
+        let mut plain = StatsdEncoder::new(StatsdFormat::Plain);
+        assert!(plain
+            .encode(&registry.gather())
+            .contains(&"requests_total.openai:5|c".to_string()));
+        assert!("statsd".parse::<StatsdFormat>().is_ok());
+        assert!("graphite".parse::<StatsdFormat>().is_err());
+    }
+
+    #[test]
+    fn test_register_is_idempotent_across_registries() {
+        let first = Registry::new();
+        let second = Registry::new();
+        Metrics::register(&first).unwrap();
+        Metrics::register(&first).unwrap();
+        Metrics::register(&second).unwrap();
+
+        set_backlog_paused(true);
+        let paused = |registry: &Registry| {
+            registry
+                .gather()
+                .iter()
+                .find(|family| family.get_name() == "embed_star_backlog_paused")
+                .map(|family| family.get_metric()[0].get_gauge().get_value())
+        };
+        assert_eq!(paused(&first), Some(1.0));
+        assert_eq!(paused(&second), Some(1.0));
+        // Nothing leaks into the default registry
+        assert!(prometheus::gather().iter().all(|family| !family.get_name().starts_with("embed_star_")));
+        set_backlog_paused(false);
+    }
+}
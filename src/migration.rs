@@ -1,5 +1,6 @@
 use crate::pool::Pool;
 use anyhow::Result;
+use surrealdb::{engine::any::Any, Surreal};
 use tracing::{info, warn};
 
 pub struct Migration {
@@ -32,8 +33,256 @@ const MIGRATIONS: &[Migration] = &[
             REMOVE INDEX idx_repo_embedding_generated_at ON TABLE repo;
         "#,
     },
+    Migration {
+        version: 3,
+        name: "add_embedding_normalized_field",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding_normalized ON TABLE repo TYPE option<bool>;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding_normalized ON TABLE repo;
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "add_quantized_embedding_fields",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding_quantization ON TABLE repo TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS embedding_quantized ON TABLE repo TYPE option<array<int>>;
+            DEFINE FIELD IF NOT EXISTS embedding_scale ON TABLE repo TYPE option<float>;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding_quantization ON TABLE repo;
+            REMOVE FIELD embedding_quantized ON TABLE repo;
+            REMOVE FIELD embedding_scale ON TABLE repo;
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "add_repo_embedding_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS repo_embedding SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS repo ON TABLE repo_embedding TYPE record<repo>;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE repo_embedding TYPE string;
+            DEFINE FIELD IF NOT EXISTS embedding ON TABLE repo_embedding TYPE option<array<float>>;
+            DEFINE FIELD IF NOT EXISTS normalized ON TABLE repo_embedding TYPE option<bool>;
+            DEFINE FIELD IF NOT EXISTS quantization ON TABLE repo_embedding TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS quantized ON TABLE repo_embedding TYPE option<array<int>>;
+            DEFINE FIELD IF NOT EXISTS scale ON TABLE repo_embedding TYPE option<float>;
+            DEFINE FIELD IF NOT EXISTS generated_at ON TABLE repo_embedding TYPE datetime;
+            DEFINE INDEX IF NOT EXISTS idx_repo_embedding_repo_model ON TABLE repo_embedding COLUMNS repo, model UNIQUE;
+            DEFINE INDEX IF NOT EXISTS idx_repo_embedding_model ON TABLE repo_embedding COLUMNS model;
+        "#,
+        down: r#"
+            REMOVE TABLE repo_embedding;
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "add_circuit_breaker_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS circuit_breaker SCHEMALESS;
+        "#,
+        down: r#"
+            REMOVE TABLE circuit_breaker;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "add_embedding_audit_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS embedding_audit SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS repo ON TABLE embedding_audit TYPE record<repo>;
+            DEFINE FIELD IF NOT EXISTS batch_id ON TABLE embedding_audit TYPE string;
+            DEFINE FIELD IF NOT EXISTS provider ON TABLE embedding_audit TYPE string;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE embedding_audit TYPE string;
+            DEFINE FIELD IF NOT EXISTS duration_ms ON TABLE embedding_audit TYPE int;
+            DEFINE FIELD IF NOT EXISTS cache_hit ON TABLE embedding_audit TYPE bool;
+            DEFINE FIELD IF NOT EXISTS outcome ON TABLE embedding_audit TYPE string;
+            DEFINE FIELD IF NOT EXISTS error_code ON TABLE embedding_audit TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS created_at ON TABLE embedding_audit TYPE datetime;
+            DEFINE INDEX IF NOT EXISTS idx_embedding_audit_repo ON TABLE embedding_audit COLUMNS repo, created_at;
+            DEFINE INDEX IF NOT EXISTS idx_embedding_audit_created_at ON TABLE embedding_audit COLUMNS created_at;
+        "#,
+        down: r#"
+            REMOVE TABLE embedding_audit;
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "add_user_embedding_fields",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding ON TABLE user TYPE option<array<float>>;
+            DEFINE FIELD IF NOT EXISTS embedding_model ON TABLE user TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS embedding_repo_count ON TABLE user TYPE option<int>;
+            DEFINE FIELD IF NOT EXISTS embedding_updated_at ON TABLE user TYPE option<datetime>;
+            DEFINE TABLE IF NOT EXISTS user_embedding_state SCHEMALESS;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding ON TABLE user;
+            REMOVE FIELD embedding_model ON TABLE user;
+            REMOVE FIELD embedding_repo_count ON TABLE user;
+            REMOVE FIELD embedding_updated_at ON TABLE user;
+            REMOVE TABLE user_embedding_state;
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "add_repo_duplicate_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS repo_duplicate SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS repo_a ON TABLE repo_duplicate TYPE record<repo>;
+            DEFINE FIELD IF NOT EXISTS repo_b ON TABLE repo_duplicate TYPE record<repo>;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE repo_duplicate TYPE string;
+            DEFINE FIELD IF NOT EXISTS similarity ON TABLE repo_duplicate TYPE float;
+            DEFINE FIELD IF NOT EXISTS detected_at ON TABLE repo_duplicate TYPE datetime DEFAULT time::now();
+            DEFINE INDEX IF NOT EXISTS idx_repo_duplicate_model ON TABLE repo_duplicate COLUMNS model;
+            DEFINE INDEX IF NOT EXISTS idx_repo_duplicate_repo_a ON TABLE repo_duplicate COLUMNS repo_a;
+            DEFINE INDEX IF NOT EXISTS idx_repo_duplicate_repo_b ON TABLE repo_duplicate COLUMNS repo_b;
+        "#,
+        down: r#"
+            REMOVE TABLE repo_duplicate;
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "add_cluster_fields",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS cluster ON TABLE repo TYPE option<int>;
+            DEFINE FIELD IF NOT EXISTS cluster_model ON TABLE repo TYPE option<string>;
+            DEFINE INDEX IF NOT EXISTS idx_repo_cluster ON TABLE repo COLUMNS cluster_model, cluster;
+            DEFINE TABLE IF NOT EXISTS repo_cluster SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE repo_cluster TYPE string;
+            DEFINE FIELD IF NOT EXISTS cluster ON TABLE repo_cluster TYPE int;
+            DEFINE FIELD IF NOT EXISTS size ON TABLE repo_cluster TYPE int;
+            DEFINE FIELD IF NOT EXISTS terms ON TABLE repo_cluster TYPE array<string>;
+            DEFINE FIELD IF NOT EXISTS created_at ON TABLE repo_cluster TYPE datetime DEFAULT time::now();
+            DEFINE INDEX IF NOT EXISTS idx_repo_cluster_model ON TABLE repo_cluster COLUMNS model, cluster UNIQUE;
+        "#,
+        down: r#"
+            REMOVE INDEX idx_repo_cluster ON TABLE repo;
+            REMOVE FIELD cluster ON TABLE repo;
+            REMOVE FIELD cluster_model ON TABLE repo;
+            REMOVE TABLE repo_cluster;
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "add_embedding_repaired_fields",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding_repaired ON TABLE repo TYPE option<bool>;
+            DEFINE FIELD IF NOT EXISTS repaired ON TABLE repo_embedding TYPE option<bool>;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding_repaired ON TABLE repo;
+            REMOVE FIELD repaired ON TABLE repo_embedding;
+        "#,
+    },
+    Migration {
+        version: 12,
+        name: "add_embedding_history",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding_model ON TABLE repo TYPE option<string>;
+            DEFINE TABLE IF NOT EXISTS repo_embedding_history SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS repo ON TABLE repo_embedding_history TYPE record<repo>;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE repo_embedding_history TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS embedding ON TABLE repo_embedding_history TYPE option<array<float>>;
+            DEFINE FIELD IF NOT EXISTS normalized ON TABLE repo_embedding_history TYPE option<bool>;
+            DEFINE FIELD IF NOT EXISTS generated_at ON TABLE repo_embedding_history TYPE option<datetime>;
+            DEFINE FIELD IF NOT EXISTS archived_at ON TABLE repo_embedding_history TYPE datetime DEFAULT time::now();
+            DEFINE INDEX IF NOT EXISTS idx_repo_embedding_history_repo ON TABLE repo_embedding_history COLUMNS repo, archived_at;
+            DEFINE INDEX IF NOT EXISTS idx_repo_embedding_history_model ON TABLE repo_embedding_history COLUMNS model;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding_model ON TABLE repo;
+            REMOVE TABLE repo_embedding_history;
+        "#,
+    },
+    Migration {
+        version: 13,
+        name: "add_batch_job_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS batch_job SCHEMALESS;
+            DEFINE INDEX IF NOT EXISTS idx_batch_job_model_done ON TABLE batch_job COLUMNS model, done;
+        "#,
+        down: r#"
+            REMOVE TABLE batch_job;
+        "#,
+    },
+    Migration {
+        version: 14,
+        name: "add_change_feed_cursor_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS change_feed_cursor SCHEMALESS;
+        "#,
+        down: r#"
+            REMOVE TABLE change_feed_cursor;
+        "#,
+    },
+    Migration {
+        version: 15,
+        name: "add_batch_run_table",
+        up: r#"
+            DEFINE TABLE IF NOT EXISTS batch_run SCHEMAFULL;
+            DEFINE FIELD IF NOT EXISTS batch_id ON TABLE batch_run TYPE string;
+            DEFINE FIELD IF NOT EXISTS worker ON TABLE batch_run TYPE option<int>;
+            DEFINE FIELD IF NOT EXISTS provider ON TABLE batch_run TYPE string;
+            DEFINE FIELD IF NOT EXISTS model ON TABLE batch_run TYPE string;
+            DEFINE FIELD IF NOT EXISTS size ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS stored ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS failed ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS skipped ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS cache_hits ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS duration_ms ON TABLE batch_run TYPE int;
+            DEFINE FIELD IF NOT EXISTS batch_size ON TABLE batch_run TYPE option<int>;
+            DEFINE FIELD IF NOT EXISTS batch_delay_ms ON TABLE batch_run TYPE option<int>;
+            DEFINE FIELD IF NOT EXISTS created_at ON TABLE batch_run TYPE datetime;
+            DEFINE INDEX IF NOT EXISTS idx_batch_run_created_at ON TABLE batch_run COLUMNS created_at;
+        "#,
+        down: r#"
+            REMOVE TABLE batch_run;
+        "#,
+    },
+    Migration {
+        version: 16,
+        name: "add_description_language_field",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS description_language ON TABLE repo TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS description_language ON TABLE repo_embedding TYPE option<string>;
+        "#,
+        down: r#"
+            REMOVE FIELD description_language ON TABLE repo;
+            REMOVE FIELD description_language ON TABLE repo_embedding;
+        "#,
+    },
+    Migration {
+        version: 17,
+        name: "add_embedding_precision_fields",
+        up: r#"
+            DEFINE FIELD IF NOT EXISTS embedding_precision ON TABLE repo TYPE option<string>;
+            DEFINE FIELD IF NOT EXISTS precision ON TABLE repo_embedding TYPE option<string>;
+        "#,
+        down: r#"
+            REMOVE FIELD embedding_precision ON TABLE repo;
+            REMOVE FIELD precision ON TABLE repo_embedding;
+        "#,
+    },
 ];
 
+/// Version of the newest migration this build knows about
+pub fn latest_version() -> u32 {
+    MIGRATIONS.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Version of the newest migration applied to the database (0 when none are)
+pub async fn current_version(db: &Surreal<Any>) -> Result<u32> {
+    let mut response = db
+        .query("SELECT VALUE version FROM migration ORDER BY version DESC LIMIT 1")
+        .await?;
+    let current_version: Option<u32> = response.take(0)?;
+    Ok(current_version.unwrap_or(0))
+}
+
 pub async fn run_migrations(pool: &Pool) -> Result<()> {
     info!("Running database migrations...");
     
@@ -52,12 +301,7 @@ pub async fn run_migrations(pool: &Pool) -> Result<()> {
     .await?;
     
     // Get current version
-    let mut response = db
-        .query("SELECT VALUE version FROM migration ORDER BY version DESC LIMIT 1")
-        .await?;
-    let current_version: Option<u32> = response.take(0)?;
-    
-    let current_version = current_version.unwrap_or(0);
+    let current_version = current_version(&db).await?;
     info!("Current migration version: {}", current_version);
     
     // Apply pending migrations
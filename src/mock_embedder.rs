@@ -0,0 +1,103 @@
+use crate::embedder::EmbeddingProvider;
+use crate::recording::fnv1a;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Offline provider for tests and local development (`EMBEDDING_PROVIDER=mock`). The same text
+/// always produces the same unit-length vector, so cache hits and re-embeds can be asserted.
+pub struct MockEmbedder {
+    model: String,
+    dimensions: usize,
+    latency: Duration,
+    failure_rate: f64,
+}
+
+impl MockEmbedder {
+    pub fn new(model: String, dimensions: usize) -> Self {
+        Self {
+            model,
+            dimensions,
+            latency: Duration::ZERO,
+            failure_rate: 0.0,
+        }
+    }
+
+    /// Sleep this long before answering each request
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Fail this fraction (0.0-1.0) of requests, chosen at random
+    pub fn with_failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The vector for `text`, independent of latency and failures
+    pub fn embedding_for(&self, text: &str) -> Vec<f32> {
+        let mut state = fnv1a(self.model.as_bytes()) ^ fnv1a(text.as_bytes());
+        let mut embedding: Vec<f32> = (0..self.dimensions)
+            .map(|_| {
+                // Top 24 bits mapped onto [-1, 1)
+                let bits = (splitmix64(&mut state) >> 40) as f32;
+                bits / (1u32 << 23) as f32 - 1.0
+            })
+            .collect();
+
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MockEmbedder {
+    async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        if self.failure_rate > 0.0 && rand::random::<f64>() < self.failure_rate {
+            return Err(anyhow::anyhow!("Mock embedding failure"));
+        }
+        Ok(self.embedding_for(text))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_embeddings_are_deterministic() {
+        let embedder = MockEmbedder::new("mock".to_string(), 64);
+
+        let first = embedder.generate_embedding("rust-lang/rust").await.unwrap();
+        let second = embedder.generate_embedding("rust-lang/rust").await.unwrap();
+        let other = embedder.generate_embedding("tokio-rs/tokio").await.unwrap();
+
+        assert_eq!(first.len(), 64);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        let norm = first.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+
+        let failing = MockEmbedder::new("mock".to_string(), 64).with_failure_rate(1.0);
+        assert!(failing.generate_embedding("rust-lang/rust").await.is_err());
+    }
+}
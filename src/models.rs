@@ -1,29 +1,81 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use surrealdb::RecordId;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Upstream owns the repo schema, so every field but `id` and `full_name` falls back to a
+// default when missing rather than failing the whole fetch. Each fallback is counted so
+// drift shows up in `embed_star_repo_fields_defaulted_total` instead of stalling the pipeline.
+mod defaults {
+    use chrono::{DateTime, Utc};
+
+    macro_rules! defaulted {
+        ($($name:ident: $ty:ty = $field:literal;)*) => {
+            $(
+                pub fn $name() -> $ty {
+                    crate::metrics::record_repo_field_defaulted($field);
+                    Default::default()
+                }
+            )*
+        };
+    }
+
+    defaulted! {
+        github_id: i64 = "github_id";
+        name: String = "name";
+        url: String = "url";
+        stars: u32 = "stars";
+        owner: super::RepoOwner = "owner";
+        owner_login: String = "owner.login";
+        owner_avatar_url: String = "owner.avatar_url";
+        is_private: bool = "is_private";
+        created_at: DateTime<Utc> = "created_at";
+        updated_at: DateTime<Utc> = "updated_at";
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RepoOwner {
+    #[serde(default = "defaults::owner_login")]
     pub login: String,
+    #[serde(default = "defaults::owner_avatar_url")]
     pub avatar_url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Repo {
     pub id: RecordId,
+    #[serde(default = "defaults::github_id")]
     pub github_id: i64,
+    #[serde(default = "defaults::name")]
     pub name: String,
     pub full_name: String,
+    #[serde(default)]
     pub description: Option<String>,
+    #[serde(default = "defaults::url")]
     pub url: String,
+    #[serde(default = "defaults::stars")]
     pub stars: u32,
+    #[serde(default)]
     pub language: Option<String>,
+    /// README text, when the upstream importer stores it; embedded after the summary fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readme: Option<String>,
+    #[serde(default = "defaults::owner")]
     pub owner: RepoOwner,
+    #[serde(default = "defaults::is_private")]
     pub is_private: bool,
+    #[serde(default = "defaults::created_at")]
     pub created_at: DateTime<Utc>,
+    #[serde(default = "defaults::updated_at")]
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
     pub embedding: Option<Vec<f32>>,
+    #[serde(default)]
     pub embedding_generated_at: Option<DateTime<Utc>>,
+    /// Fields this version doesn't know about, kept so writing the repo back doesn't drop them
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 impl Repo {
@@ -49,6 +101,10 @@ impl Repo {
         parts.push(format!("Stars: {}", self.stars));
         parts.push(format!("Owner: {}", self.owner.login));
 
+        if let Some(readme) = self.readme.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+            parts.push(format!("README:\n{}", readme));
+        }
+
         parts.join("\n")
     }
 }
@@ -65,4 +121,57 @@ pub enum LiveAction {
     Create,
     Update,
     Delete,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repo_tolerates_schema_drift() {
+        let now = Utc::now();
+        let repo = Repo {
+            id: RecordId::from(("repo", "drift")),
+            github_id: 42,
+            name: "drift".to_string(),
+            full_name: "owner/drift".to_string(),
+            description: None,
+            url: "https://github.com/owner/drift".to_string(),
+            stars: 7,
+            language: None,
+            readme: None,
+            owner: RepoOwner {
+                login: "owner".to_string(),
+                avatar_url: "https://github.com/owner.png".to_string(),
+            },
+            is_private: false,
+            created_at: now,
+            updated_at: now,
+            embedding: None,
+            embedding_generated_at: None,
+            extra: Default::default(),
+        };
+
+        // Upstream dropped some fields and added one this version doesn't know
+        let mut record = serde_json::to_value(&repo).unwrap();
+        let fields = record.as_object_mut().unwrap();
+        fields.remove("stars");
+        fields.remove("url");
+        fields["owner"].as_object_mut().unwrap().remove("avatar_url");
+        fields.insert("topics".to_string(), json!(["rust", "search"]));
+
+        let drifted: Repo = serde_json::from_value(record).unwrap();
+        assert_eq!(drifted.full_name, "owner/drift");
+        assert_eq!(drifted.github_id, 42);
+        assert_eq!(drifted.stars, 0);
+        assert_eq!(drifted.url, "");
+        assert_eq!(drifted.owner.login, "owner");
+        assert_eq!(drifted.owner.avatar_url, "");
+        assert_eq!(drifted.extra.get("topics"), Some(&json!(["rust", "search"])));
+
+        // Unknown fields survive being written back
+        let written = serde_json::to_value(&drifted).unwrap();
+        assert_eq!(written["topics"], json!(["rust", "search"]));
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    circuit_breaker::{CircuitBreakerManager, CircuitState},
+    embedder::Embedder,
+    pipeline::PipelineState,
+};
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+/// Windows with fewer requests than this are too small to judge a failure rate
+const MIN_WINDOW_REQUESTS: u64 = 10;
+
+/// Payload shape expected by the webhook receiver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Slack,
+    Discord,
+    /// Plain JSON object with the alert fields, for custom receivers
+    Generic,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "slack" => Ok(WebhookFormat::Slack),
+            "discord" => Ok(WebhookFormat::Discord),
+            "generic" | "" => Ok(WebhookFormat::Generic),
+            other => Err(format!(
+                "Unknown webhook format '{}' (expected slack, discord or generic)",
+                other
+            )),
+        }
+    }
+}
+
+/// When each condition becomes worth alerting on
+#[derive(Debug, Clone)]
+pub struct AlertThresholds {
+    /// How long a provider circuit may stay open (or half-open) before alerting
+    pub circuit_open: Duration,
+    /// Window failure rate (0.0-1.0) that alerts once sustained
+    pub failure_rate: f64,
+    /// Pending repo count that alerts once sustained; `None` disables the backlog alert
+    pub backlog: Option<u64>,
+    /// How long the failure rate and backlog conditions must hold before alerting
+    pub sustain: Duration,
+}
+
+/// A condition as observed on one evaluation
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub key: String,
+    pub active: bool,
+    pub message: String,
+    /// How long it must stay active before an alert fires
+    pub sustain: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl AlertStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertStatus::Firing => "firing",
+            AlertStatus::Resolved => "resolved",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub key: String,
+    pub status: AlertStatus,
+    pub message: String,
+}
+
+/// Turns observed conditions into alerts, firing once a condition has held for its sustain
+/// period and resolving once it clears. Each alert fires at most once per episode.
+#[derive(Default)]
+pub struct AlertEvaluator {
+    active_since: HashMap<String, Instant>,
+    firing: HashSet<String>,
+}
+
+impl AlertEvaluator {
+    pub fn evaluate(&mut self, conditions: Vec<Condition>, now: Instant) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+
+        for condition in conditions {
+            if condition.active {
+                let since = *self.active_since.entry(condition.key.clone()).or_insert(now);
+                if now.duration_since(since) >= condition.sustain
+                    && self.firing.insert(condition.key.clone())
+                {
+                    alerts.push(Alert {
+                        key: condition.key,
+                        status: AlertStatus::Firing,
+                        message: condition.message,
+                    });
+                }
+            } else {
+                self.active_since.remove(&condition.key);
+                if self.firing.remove(&condition.key) {
+                    alerts.push(Alert {
+                        key: condition.key,
+                        status: AlertStatus::Resolved,
+                        message: condition.message,
+                    });
+                }
+            }
+        }
+
+        alerts
+    }
+}
+
+/// Posts alerts to a webhook
+pub struct Notifier {
+    client: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+}
+
+impl Notifier {
+    pub fn new(url: String, format: WebhookFormat) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Self { client, url, format })
+    }
+
+    fn payload(&self, alert: &Alert) -> serde_json::Value {
+        let text = format!(
+            "[embed_star] {} {}: {}",
+            alert.status.as_str().to_uppercase(),
+            alert.key,
+            alert.message
+        );
+        match self.format {
+            WebhookFormat::Slack => json!({ "text": text }),
+            WebhookFormat::Discord => json!({ "content": text }),
+            WebhookFormat::Generic => json!({
+                "service": "embed_star",
+                "alert": alert.key,
+                "status": alert.status.as_str(),
+                "message": alert.message,
+                "timestamp": chrono::Utc::now(),
+            }),
+        }
+    }
+
+    pub async fn send(&self, alert: &Alert) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&self.payload(alert))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!("Webhook returned {}", response.status());
+        }
+        Ok(())
+    }
+}
+
+/// Current state of every alert condition
+fn observe(
+    thresholds: &AlertThresholds,
+    circuit_breaker: &CircuitBreakerManager,
+    embedder: &Embedder,
+    pipeline: &PipelineState,
+) -> Vec<Condition> {
+    let mut conditions = Vec::new();
+
+    for (service, (state, stats)) in circuit_breaker.get_all_stats() {
+        conditions.push(Condition {
+            key: format!("circuit_open:{}", service),
+            active: state != CircuitState::Closed,
+            message: format!(
+                "circuit breaker for {} has been {} for over {}s",
+                service,
+                state.as_str(),
+                thresholds.circuit_open.as_secs()
+            ),
+            sustain: thresholds.circuit_open,
+        });
+
+        let failure_rate = if stats.window_requests >= MIN_WINDOW_REQUESTS {
+            stats.window_failures as f64 / stats.window_requests as f64
+        } else {
+            0.0
+        };
+        conditions.push(Condition {
+            key: format!("failure_rate:{}", service),
+            active: failure_rate > thresholds.failure_rate,
+            message: format!(
+                "{} failure rate is {:.0}% (threshold {:.0}%)",
+                service,
+                failure_rate * 100.0,
+                thresholds.failure_rate * 100.0
+            ),
+            sustain: thresholds.sustain,
+        });
+    }
+
+    if let Some(threshold) = thresholds.backlog {
+        let pending = pipeline.status().pending_repos.unwrap_or(0);
+        conditions.push(Condition {
+            key: "backlog".to_string(),
+            active: pending > threshold,
+            message: format!("{} repos pending (threshold {})", pending, threshold),
+            sustain: thresholds.sustain,
+        });
+    }
+
+    let rejection = embedder.rejection();
+    conditions.push(Condition {
+        key: format!("provider_rejecting:{}", embedder.provider_name()),
+        active: rejection.is_some(),
+        message: match rejection {
+            Some(reason) => format!("{} is rejecting requests: {}", embedder.provider_name(), reason),
+            None => format!("{} is accepting requests again", embedder.provider_name()),
+        },
+        sustain: Duration::ZERO,
+    });
+
+    let exceeded = embedder.budget_exceeded();
+    conditions.push(Condition {
+        key: "budget_exhausted".to_string(),
+        active: exceeded.is_some(),
+        message: match exceeded {
+            Some(period) => format!("{} budget exhausted, processing is paused", period),
+            None => "budget available again".to_string(),
+        },
+        sustain: Duration::ZERO,
+    });
+
+    conditions
+}
+
+/// Evaluate alert conditions every 30s and post transitions to the webhook until shutdown
+pub async fn run_notifier(
+    notifier: Notifier,
+    thresholds: AlertThresholds,
+    circuit_breaker: Arc<CircuitBreakerManager>,
+    embedder: Arc<Embedder>,
+    pipeline: Arc<PipelineState>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    let mut evaluator = AlertEvaluator::default();
+    let mut interval = interval(Duration::from_secs(30));
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                info!("Notifier received shutdown signal");
+                break;
+            }
+            _ = interval.tick() => {
+                let conditions = observe(&thresholds, &circuit_breaker, &embedder, &pipeline);
+                for alert in evaluator.evaluate(conditions, Instant::now()) {
+                    warn!(alert = %alert.key, status = alert.status.as_str(), "{}", alert.message);
+                    if let Err(e) = notifier.send(&alert).await {
+                        error!(alert = %alert.key, "Failed to send alert webhook: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(active: bool, sustain: Duration) -> Condition {
+        Condition {
+            key: "backlog".to_string(),
+            active,
+            message: "backlog".to_string(),
+            sustain,
+        }
+    }
+
+    #[test]
+    fn test_alert_fires_once_after_sustain_and_resolves() {
+        let mut evaluator = AlertEvaluator::default();
+        let sustain = Duration::from_secs(60);
+        let start = Instant::now();
+
+        assert!(evaluator.evaluate(vec![condition(true, sustain)], start).is_empty());
+
+        let alerts = evaluator.evaluate(vec![condition(true, sustain)], start + sustain);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].status, AlertStatus::Firing);
+
+        // Still active: no repeat
+        assert!(evaluator
+            .evaluate(vec![condition(true, sustain)], start + sustain * 2)
+            .is_empty());
+
+        let alerts = evaluator.evaluate(vec![condition(false, sustain)], start + sustain * 3);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].status, AlertStatus::Resolved);
+    }
+
+    #[test]
+    fn test_flapping_condition_restarts_sustain_period() {
+        let mut evaluator = AlertEvaluator::default();
+        let sustain = Duration::from_secs(60);
+        let start = Instant::now();
+
+        evaluator.evaluate(vec![condition(true, sustain)], start);
+        // Clearing before the sustain period resolves nothing and resets the timer
+        assert!(evaluator
+            .evaluate(vec![condition(false, sustain)], start + Duration::from_secs(30))
+            .is_empty());
+        assert!(evaluator
+            .evaluate(vec![condition(true, sustain)], start + Duration::from_secs(70))
+            .is_empty());
+    }
+}
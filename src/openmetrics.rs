@@ -0,0 +1,200 @@
+use parking_lot::Mutex;
+use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Content type of [`encode`]'s output; scrapers ask for it in `Accept` to receive exemplars
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// A sampled observation pointing at the trace that produced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub value: f64,
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+}
+
+/// Latest exemplar of each histogram bucket. The prometheus crate has no exemplar support, so
+/// they are kept here and merged in when metrics are encoded as OpenMetrics.
+#[derive(Default)]
+pub struct ExemplarStore {
+    /// By metric name, `name=value` label pairs sorted by name, and bucket upper bound
+    exemplars: Mutex<HashMap<(String, String, u64), Exemplar>>,
+}
+
+fn series_key(labels: &mut Vec<(&str, &str)>) -> String {
+    labels.sort_by(|a, b| a.0.cmp(b.0));
+    labels
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl ExemplarStore {
+    /// Remember `trace_id` as the exemplar of the bucket of `buckets` (the histogram's upper
+    /// bounds) that `value` falls into
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], buckets: &[f64], value: f64, trace_id: String) {
+        let bound = buckets.iter().copied().find(|bound| value <= *bound).unwrap_or(f64::INFINITY);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64())
+            .unwrap_or_default();
+        let key = (name.to_string(), series_key(&mut labels.to_vec()), bound.to_bits());
+        self.exemplars.lock().insert(
+            key,
+            Exemplar {
+                trace_id,
+                value,
+                timestamp,
+            },
+        );
+    }
+
+    fn get(&self, name: &str, labels: &[LabelPair], bound: f64) -> Option<Exemplar> {
+        let mut labels: Vec<(&str, &str)> = labels.iter().map(|l| (l.get_name(), l.get_value())).collect();
+        let key = (name.to_string(), series_key(&mut labels), bound.to_bits());
+        self.exemplars.lock().get(&key).cloned()
+    }
+}
+
+static EXEMPLARS: OnceLock<ExemplarStore> = OnceLock::new();
+
+/// Exemplars recorded by the metric helpers
+pub fn exemplars() -> &'static ExemplarStore {
+    EXEMPLARS.get_or_init(ExemplarStore::default)
+}
+
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+fn labels(pairs: &[LabelPair], extra: Option<(&str, String)>) -> String {
+    let mut rendered: Vec<String> = pairs
+        .iter()
+        .map(|pair| format!("{}=\"{}\"", pair.get_name(), escape(pair.get_value())))
+        .collect();
+    if let Some((name, value)) = extra {
+        rendered.push(format!("{}=\"{}\"", name, escape(&value)));
+    }
+    if rendered.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", rendered.join(","))
+    }
+}
+
+/// Encode `families` in the OpenMetrics text format, attaching exemplars from `store` to
+/// histogram buckets
+pub fn encode(families: &[MetricFamily], store: &ExemplarStore) -> String {
+    let mut out = String::new();
+    for family in families {
+        let full_name = family.get_name();
+        let (name, kind) = match family.get_field_type() {
+            // OpenMetrics names the counter family without its `_total` sample suffix
+            MetricType::COUNTER => (full_name.strip_suffix("_total").unwrap_or(full_name), "counter"),
+            MetricType::GAUGE => (full_name, "gauge"),
+            MetricType::HISTOGRAM => (full_name, "histogram"),
+            MetricType::SUMMARY => (full_name, "summary"),
+            MetricType::UNTYPED => (full_name, "unknown"),
+        };
+        let help = family.get_help().replace('\\', r"\\").replace('\n', r"\n");
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+
+        for metric in family.get_metric() {
+            let pairs = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    let _ = writeln!(out, "{}_total{} {}", name, labels(pairs, None), number(metric.get_counter().get_value()));
+                }
+                MetricType::GAUGE => {
+                    let _ = writeln!(out, "{}{} {}", name, labels(pairs, None), number(metric.get_gauge().get_value()));
+                }
+                MetricType::UNTYPED => {
+                    let _ = writeln!(out, "{}{} {}", name, labels(pairs, None), number(metric.get_untyped().get_value()));
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let mut bounds: Vec<(f64, u64)> = histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|bucket| (bucket.get_upper_bound(), bucket.get_cumulative_count()))
+                        .collect();
+                    if bounds.last().is_none_or(|(bound, _)| bound.is_finite()) {
+                        bounds.push((f64::INFINITY, histogram.get_sample_count()));
+                    }
+                    for (bound, count) in bounds {
+                        let _ = write!(out, "{}_bucket{} {}", name, labels(pairs, Some(("le", number(bound)))), count);
+                        if let Some(exemplar) = store.get(full_name, pairs, bound) {
+                            let _ = write!(
+                                out,
+                                " # {{trace_id=\"{}\"}} {} {}",
+                                escape(&exemplar.trace_id),
+                                number(exemplar.value),
+                                exemplar.timestamp
+                            );
+                        }
+                        out.push('\n');
+                    }
+                    let _ = writeln!(out, "{}_count{} {}", name, labels(pairs, None), histogram.get_sample_count());
+                    let _ = writeln!(out, "{}_sum{} {}", name, labels(pairs, None), number(histogram.get_sample_sum()));
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let quantile_label = Some(("quantile", number(quantile.get_quantile())));
+                        let _ = writeln!(out, "{}{} {}", name, labels(pairs, quantile_label), number(quantile.get_value()));
+                    }
+                    let _ = writeln!(out, "{}_count{} {}", name, labels(pairs, None), summary.get_sample_count());
+                    let _ = writeln!(out, "{}_sum{} {}", name, labels(pairs, None), number(summary.get_sample_sum()));
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{CounterVec, HistogramOpts, HistogramVec, Opts, Registry};
+
+    #[test]
+    fn test_encode_with_exemplars() {
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests_total", "Requests"), &["provider"]).unwrap();
+        let histogram = HistogramVec::new(HistogramOpts::new("duration_seconds", "Duration").buckets(vec![0.5, 1.0]), &["provider"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+
+        counter.with_label_values(&["openai"]).inc();
+        histogram.with_label_values(&["openai"]).observe(0.7);
+        let store = ExemplarStore::default();
+        store.observe("duration_seconds", &[("provider", "openai")], &[0.5, 1.0], 0.7, "abc123".to_string());
+
+        let text = encode(&registry.gather(), &store);
+        assert!(text.contains("# TYPE requests counter\n"));
+        assert!(text.contains("requests_total{provider=\"openai\"} 1\n"));
+        assert!(text.contains("duration_seconds_bucket{provider=\"openai\",le=\"0.5\"} 0\n"));
+        assert!(text.contains("duration_seconds_bucket{provider=\"openai\",le=\"1\"} 1 # {trace_id=\"abc123\"} 0.7 "));
+        assert!(text.contains("duration_seconds_bucket{provider=\"openai\",le=\"+Inf\"} 1\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    events::{self, PipelineEvent},
+    models::Repo,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+
+/// Live view of the processing pipeline, updated by the workers and served by `/status`
+pub struct PipelineState {
+    /// One queue per tenant; weak so the status view never keeps them open
+    queues: Vec<mpsc::WeakSender<Repo>>,
+    batch_size: AtomicUsize,
+    batch_delay_ms: AtomicU64,
+    /// One slot per running worker; resized when the worker count changes
+    worker_batches: RwLock<Vec<AtomicUsize>>,
+    /// Embeddings stored since startup
+    embeddings_stored: AtomicU64,
+    /// Unix milliseconds of the last stored embedding; 0 until one has been written
+    last_embedding_ms: AtomicI64,
+    /// Latest pending count from the stats reporter; -1 until the first report
+    pending_repos: AtomicI64,
+    /// Latest autoscaling hint; -1 until one has been computed
+    desired_workers: AtomicI64,
+}
+
+/// Serializable snapshot of [`PipelineState`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStatus {
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    pub batch_size: usize,
+    pub batch_delay_ms: u64,
+    /// Repos in the batch each worker is processing (0 = idle)
+    pub worker_batches: Vec<usize>,
+    /// Workers that would keep up with the backlog, as exported in `embed_star_desired_workers`
+    pub desired_workers: Option<usize>,
+    pub last_embedding_at: Option<DateTime<Utc>>,
+    pub pending_repos: Option<u64>,
+}
+
+impl PipelineState {
+    pub fn new(queue: &mpsc::Sender<Repo>, workers: usize, batch_size: usize, batch_delay_ms: u64) -> Self {
+        Self {
+            queues: vec![queue.downgrade()],
+            batch_size: AtomicUsize::new(batch_size),
+            batch_delay_ms: AtomicU64::new(batch_delay_ms),
+            worker_batches: RwLock::new((0..workers).map(|_| AtomicUsize::new(0)).collect()),
+            embeddings_stored: AtomicU64::new(0),
+            last_embedding_ms: AtomicI64::new(0),
+            pending_repos: AtomicI64::new(-1),
+            desired_workers: AtomicI64::new(-1),
+        }
+    }
+
+    /// Also count this queue, for services processing more than one tenant
+    pub fn with_queue(mut self, queue: &mpsc::Sender<Repo>) -> Self {
+        self.queues.push(queue.downgrade());
+        self
+    }
+
+    /// Batch settings after a configuration reload
+    pub fn set_batch_settings(&self, batch_size: usize, batch_delay_ms: u64) {
+        self.batch_size.store(batch_size, Ordering::Relaxed);
+        self.batch_delay_ms.store(batch_delay_ms, Ordering::Relaxed);
+    }
+
+    /// Worker count after workers were added or drained
+    pub fn set_workers(&self, workers: usize) {
+        self.worker_batches.write().resize_with(workers, || AtomicUsize::new(0));
+        crate::metrics::set_workers(workers);
+    }
+
+    /// Record the size of the batch a worker is processing; 0 when it finishes
+    pub fn set_worker_batch(&self, worker_id: usize, size: usize) {
+        if let Some(slot) = self.worker_batches.read().get(worker_id) {
+            slot.store(size, Ordering::Relaxed);
+            crate::metrics::set_worker_batch_items(worker_id, size);
+        }
+    }
+
+    /// Repos waiting in the processing channels
+    pub fn queue_depth(&self) -> usize {
+        self.queue_usage().0
+    }
+
+    /// Queued repos and total capacity across the tenant queues
+    fn queue_usage(&self) -> (usize, usize) {
+        self.queues
+            .iter()
+            .filter_map(|queue| queue.upgrade())
+            .fold((0, 0), |(depth, capacity), queue| {
+                (
+                    depth + queue.max_capacity() - queue.capacity(),
+                    capacity + queue.max_capacity(),
+                )
+            })
+    }
+
+    pub fn record_embeddings_stored(&self, count: usize) {
+        self.embeddings_stored.fetch_add(count as u64, Ordering::Relaxed);
+        if count > 0 {
+            self.last_embedding_ms
+                .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+        }
+    }
+
+    /// Embeddings stored since startup
+    pub fn embeddings_stored(&self) -> u64 {
+        self.embeddings_stored.load(Ordering::Relaxed)
+    }
+
+    pub fn set_desired_workers(&self, workers: usize) {
+        self.desired_workers.store(workers as i64, Ordering::Relaxed);
+        crate::metrics::set_desired_workers(workers);
+    }
+
+    pub fn set_pending_repos(&self, count: usize) {
+        self.pending_repos.store(count as i64, Ordering::Relaxed);
+        events::publish(PipelineEvent::Backlog {
+            pending_repos: count,
+            queue_depth: self.queue_depth(),
+            at: Utc::now(),
+        });
+    }
+
+    pub fn status(&self) -> PipelineStatus {
+        let last_ms = self.last_embedding_ms.load(Ordering::Relaxed);
+        let pending = self.pending_repos.load(Ordering::Relaxed);
+        let desired = self.desired_workers.load(Ordering::Relaxed);
+        let (queue_depth, queue_capacity) = self.queue_usage();
+
+        PipelineStatus {
+            queue_depth,
+            queue_capacity,
+            batch_size: self.batch_size.load(Ordering::Relaxed),
+            batch_delay_ms: self.batch_delay_ms.load(Ordering::Relaxed),
+            worker_batches: self
+                .worker_batches
+                .read()
+                .iter()
+                .map(|slot| slot.load(Ordering::Relaxed))
+                .collect(),
+            desired_workers: (desired >= 0).then_some(desired as usize),
+            last_embedding_at: (last_ms > 0)
+                .then(|| Utc.timestamp_millis_opt(last_ms).single())
+                .flatten(),
+            pending_repos: (pending >= 0).then_some(pending as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_status() {
+        let (tx, _rx) = mpsc::channel(8);
+        let pipeline = PipelineState::new(&tx, 2, 10, 100);
+
+        let status = pipeline.status();
+        assert_eq!(status.queue_capacity, 8);
+        assert_eq!(status.worker_batches, vec![0, 0]);
+        assert!(status.last_embedding_at.is_none());
+        assert!(status.pending_repos.is_none());
+
+        pipeline.set_worker_batch(1, 7);
+        pipeline.set_worker_batch(5, 3); // unknown workers are ignored
+        pipeline.record_embeddings_stored(7);
+        pipeline.set_pending_repos(42);
+
+        let status = pipeline.status();
+        assert_eq!(status.worker_batches, vec![0, 7]);
+        assert!(status.last_embedding_at.is_some());
+        assert_eq!(status.pending_repos, Some(42));
+
+        pipeline.set_workers(3);
+        pipeline.set_desired_workers(4);
+        let status = pipeline.status();
+        assert_eq!(status.worker_batches, vec![0, 7, 0]);
+        assert_eq!(status.desired_workers, Some(4));
+        assert_eq!(pipeline.embeddings_stored(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_queue_and_worker_gauges() {
+        crate::metrics::Metrics::register(&prometheus::Registry::new()).unwrap();
+        let now = Utc::now();
+        let repo = Repo {
+            id: surrealdb::RecordId::from(("repo", "queued")),
+            github_id: 1,
+            name: "queued".to_string(),
+            full_name: "owner/queued".to_string(),
+            description: None,
+            url: String::new(),
+            stars: 0,
+            language: None,
+            readme: None,
+            owner: crate::models::RepoOwner {
+                login: "owner".to_string(),
+                avatar_url: String::new(),
+            },
+            is_private: false,
+            created_at: now,
+            updated_at: now,
+            embedding: None,
+            embedding_generated_at: None,
+            extra: Default::default(),
+        };
+
+        // Depth and capacity add up across tenant queues
+        let (first, _first_rx) = mpsc::channel(8);
+        let (second, _second_rx) = mpsc::channel(4);
+        let pipeline = PipelineState::new(&first, 10, 10, 100).with_queue(&second);
+        first.send(repo.clone()).await.unwrap();
+        first.send(repo.clone()).await.unwrap();
+        second.send(repo).await.unwrap();
+        assert_eq!(pipeline.queue_depth(), 3);
+        assert_eq!(pipeline.status().queue_capacity, 12);
+
+        let items = crate::metrics::Metrics::get().worker_batch_items.with_label_values(&["9"]);
+        pipeline.set_worker_batch(9, 5);
+        assert_eq!(items.get(), 5);
+        pipeline.set_worker_batch(9, 0);
+        assert_eq!(items.get(), 0);
+
+        let tenant = crate::tenant::current_tenant();
+        let sizes = crate::metrics::Metrics::get().batch_size.with_label_values(&["9", &tenant]);
+        let before = sizes.get_sample_count();
+        crate::metrics::record_batch_size(9, 5);
+        assert_eq!(sizes.get_sample_count(), before + 1);
+    }
+}
@@ -1,16 +1,19 @@
-use crate::config::Config;
+use crate::{config::Config, tls::TlsSettings};
 use anyhow::Result;
 use deadpool::{
     managed::{self, Manager, Metrics, Object, RecycleError, RecycleResult},
 };
+use parking_lot::Mutex;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use surrealdb::{
     engine::any::{connect, Any},
     Surreal,
 };
-use surrealdb::opt::auth::Root;
-use tokio::time::timeout;
+use surrealdb::opt::auth::{Database, Jwt, Namespace, Record, Root};
+use tokio::{sync::OnceCell, time::timeout};
 use tracing::{debug, error, info, warn};
 
 /// Type alias for a pooled SurrealDB connection
@@ -19,22 +22,184 @@ pub type Connection = Object<SurrealDBManager>;
 /// Type alias for the connection pool
 pub type Pool = managed::Pool<SurrealDBManager>;
 
+/// How connections sign in to SurrealDB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DbAuth {
+    /// Root user (`DB_USER` / `DB_PASS`)
+    #[default]
+    Root,
+    /// User defined on the namespace
+    Namespace,
+    /// User defined on the database
+    Database,
+    /// Record access method (`DB_ACCESS`), signing in with `DB_USER` / `DB_PASS` as the
+    /// `user` and `pass` parameters
+    Record,
+    /// Pre-issued JWT (`DB_TOKEN`)
+    Token,
+}
+
+impl FromStr for DbAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "root" | "" => Ok(DbAuth::Root),
+            "namespace" | "ns" => Ok(DbAuth::Namespace),
+            "database" | "db" => Ok(DbAuth::Database),
+            "record" => Ok(DbAuth::Record),
+            "token" => Ok(DbAuth::Token),
+            other => Err(format!(
+                "Unknown database auth '{}' (expected root, namespace, database, record or token)",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether `url` is an embedded in-memory datastore (`mem://`, needs the `kv-mem` engine)
+pub fn is_memory_url(url: &str) -> bool {
+    url == "memory" || url.starts_with("mem://")
+}
+
+/// Whether `url` runs SurrealDB inside this process: `mem://`, or `rocksdb://path` with the
+/// `rocksdb` feature. Embedded datastores have no server to sign in to.
+pub fn is_embedded_url(url: &str) -> bool {
+    is_memory_url(url) || url.starts_with("rocksdb://")
+}
+
+/// First reconnect delay after SurrealDB becomes unreachable; doubles per failed attempt
+const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Whether SurrealDB is reachable, shared by every connection of a pool. A failed connect marks
+/// the database unavailable; until the backoff elapses new connects and recycles fail fast
+/// instead of each hitting the server, and workers pause. The next successful connect clears it.
+#[derive(Debug, Default)]
+pub struct DbHealth {
+    unavailable: AtomicBool,
+    outage: Mutex<Outage>,
+}
+
+#[derive(Debug, Default)]
+struct Outage {
+    failed_attempts: u32,
+    started: Option<Instant>,
+    retry_at: Option<Instant>,
+}
+
+impl DbHealth {
+    pub fn is_unavailable(&self) -> bool {
+        self.unavailable.load(Ordering::Relaxed)
+    }
+
+    /// Time until the next reconnect attempt is allowed, while in an outage
+    pub fn backoff_remaining(&self) -> Option<Duration> {
+        let retry_at = self.outage.lock().retry_at?;
+        retry_at.checked_duration_since(Instant::now()).filter(|remaining| !remaining.is_zero())
+    }
+
+    fn record_success(&self) {
+        if !self.unavailable.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let outage = std::mem::take(&mut *self.outage.lock());
+        info!(
+            failed_attempts = outage.failed_attempts,
+            "Database reachable again after {:?}",
+            outage.started.map(|started| started.elapsed()).unwrap_or_default()
+        );
+        crate::metrics::set_db_unavailable(false);
+    }
+
+    fn record_failure(&self, error: &surrealdb::Error) {
+        let mut outage = self.outage.lock();
+        outage.failed_attempts += 1;
+        let backoff = RECONNECT_BACKOFF_BASE
+            .saturating_mul(1 << (outage.failed_attempts - 1).min(16))
+            .min(RECONNECT_BACKOFF_MAX);
+        outage.retry_at = Some(Instant::now() + backoff);
+
+        if self.unavailable.swap(true, Ordering::Relaxed) {
+            debug!(attempt = outage.failed_attempts, "Reconnect failed, next attempt in {:?}: {}", backoff, error);
+        } else {
+            outage.started = Some(Instant::now());
+            error!("Database unavailable, reconnecting with backoff (next attempt in {:?}): {}", backoff, error);
+            crate::metrics::set_db_unavailable(true);
+        }
+    }
+}
+
+fn unavailable_error(remaining: Duration) -> surrealdb::Error {
+    surrealdb::Error::Api(surrealdb::error::Api::InternalError(format!(
+        "Database unavailable, next reconnect attempt in {:?}",
+        remaining
+    )))
+}
+
 /// Manager for SurrealDB connections that implements deadpool's Manager trait
 pub struct SurrealDBManager {
     config: Arc<Config>,
+    /// Every `mem://` connect creates a new empty datastore and a `rocksdb://` path can only be
+    /// opened once per process, so pooled connections to an embedded engine share one
+    embedded: OnceCell<Surreal<Any>>,
+    health: Arc<DbHealth>,
 }
 
 impl SurrealDBManager {
     pub fn new(config: Arc<Config>) -> Self {
-        Self { config }
+        Self {
+            config,
+            embedded: OnceCell::new(),
+            health: Arc::new(DbHealth::default()),
+        }
+    }
+
+    pub fn health(&self) -> Arc<DbHealth> {
+        self.health.clone()
+    }
+
+    /// Why a pooled connection should be closed rather than reused, if it should
+    pub fn expiry_reason(&self, metrics: &Metrics) -> Option<&'static str> {
+        let exceeds = |limit: Option<u64>, elapsed: Duration| {
+            limit.is_some_and(|secs| elapsed >= Duration::from_secs(secs))
+        };
+        if exceeds(self.config.pool_max_lifetime_secs, metrics.age()) {
+            Some("max lifetime reached")
+        } else if exceeds(self.config.pool_idle_timeout_secs, metrics.last_used()) {
+            Some("idle timeout reached")
+        } else {
+            None
+        }
     }
 
     async fn create_connection(&self) -> Result<Surreal<Any>, surrealdb::Error> {
         let url = &self.config.db_url;
+        if is_embedded_url(url) {
+            // Embedded datastores run without authentication
+            let db = self
+                .embedded
+                .get_or_try_init(|| async {
+                    let db = connect(url.as_str()).await?;
+                    db.use_ns(&self.config.db_namespace)
+                        .use_db(&self.config.db_database)
+                        .await?;
+                    Ok::<_, surrealdb::Error>(db)
+                })
+                .await?;
+            return Ok(db.clone());
+        }
         let timeout_duration = Duration::from_secs(self.config.pool_create_timeout_secs);
         
-        // Create connection with timeout
-        let db: Surreal<Any> = match timeout(timeout_duration, connect(url)).await {
+        // Create connection with timeout, using custom TLS settings for wss:// and https://
+        let tls = TlsSettings::from_config(&self.config).rustls_config().map_err(|e| {
+            surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+        })?;
+        let connecting = match tls {
+            Some(tls) => connect((url.as_str(), surrealdb::opt::Config::new().rustls(tls))),
+            None => connect(url.as_str()),
+        };
+        let db: Surreal<Any> = match timeout(timeout_duration, connecting).await {
             Ok(Ok(db)) => db,
             Ok(Err(e)) => return Err(e),
             Err(_) => {
@@ -47,13 +212,7 @@ impl SurrealDBManager {
         };
 
         // Authenticate with timeout
-        match timeout(
-            Duration::from_secs(5),
-            db.signin(Root {
-                username: &self.config.db_user,
-                password: &self.config.db_pass,
-            })
-        ).await {
+        match timeout(Duration::from_secs(5), self.authenticate(&db)).await {
             Ok(Ok(_)) => {},
             Ok(Err(e)) => return Err(e),
             Err(_) => {
@@ -85,11 +244,64 @@ impl SurrealDBManager {
         Ok(db)
     }
 
+    async fn authenticate(&self, db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
+        let config = &self.config;
+        let auth = config.db_auth().map_err(|e| {
+            surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+        })?;
+
+        match auth {
+            DbAuth::Root => {
+                db.signin(Root {
+                    username: &config.db_user,
+                    password: &config.db_pass,
+                })
+                .await?;
+            }
+            DbAuth::Namespace => {
+                db.signin(Namespace {
+                    namespace: &config.db_namespace,
+                    username: &config.db_user,
+                    password: &config.db_pass,
+                })
+                .await?;
+            }
+            DbAuth::Database => {
+                db.signin(Database {
+                    namespace: &config.db_namespace,
+                    database: &config.db_database,
+                    username: &config.db_user,
+                    password: &config.db_pass,
+                })
+                .await?;
+            }
+            DbAuth::Record => {
+                db.signin(Record {
+                    namespace: &config.db_namespace,
+                    database: &config.db_database,
+                    access: config.db_access.as_deref().unwrap_or_default(),
+                    params: serde_json::json!({
+                        "user": config.db_user,
+                        "pass": config.db_pass,
+                    }),
+                })
+                .await?;
+            }
+            DbAuth::Token => {
+                let token = config.db_token.clone().unwrap_or_default();
+                db.authenticate(Jwt::from(token)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn health_check(&self, db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
-        // Perform a simple health check query with timeout
-        match timeout(Duration::from_secs(5), db.query("RETURN 1")).await {
-            Ok(Ok(mut response)) => {
-                let _: Option<i32> = response.take(0)?;
+        // Run the configured health check query with timeout; any result shape is fine
+        let limit = Duration::from_secs(self.config.pool_health_check_timeout_secs);
+        match timeout(limit, db.query(self.config.pool_health_check_query.as_str())).await {
+            Ok(Ok(response)) => {
+                response.check()?;
                 Ok(())
             }
             Ok(Err(e)) => Err(e),
@@ -109,6 +321,11 @@ impl Manager for SurrealDBManager {
     type Error = surrealdb::Error;
 
     async fn create(&self) -> Result<Self::Type, Self::Error> {
+        // During an outage only one connect per backoff period goes to the server
+        if let Some(remaining) = self.health.backoff_remaining() {
+            return Err(unavailable_error(remaining));
+        }
+
         debug!("Creating new SurrealDB connection");
         let start = std::time::Instant::now();
         
@@ -117,11 +334,12 @@ impl Manager for SurrealDBManager {
                 let elapsed = start.elapsed();
                 info!("Created new SurrealDB connection in {:?}", elapsed);
                 crate::metrics::increment_pool_connections_created();
+                self.health.record_success();
                 Ok(conn)
             }
             Err(e) => {
-                error!("Failed to create SurrealDB connection: {}", e);
                 crate::metrics::increment_pool_connection_errors();
+                self.health.record_failure(&e);
                 Err(e)
             }
         }
@@ -130,8 +348,26 @@ impl Manager for SurrealDBManager {
     async fn recycle(
         &self,
         conn: &mut Self::Type,
-        _: &Metrics,
+        metrics: &Metrics,
     ) -> RecycleResult<Self::Error> {
+        // Idle connections from before an outage are dead; drop them without a round trip
+        if self.health.is_unavailable() {
+            return Err(RecycleError::Message("Database unavailable".into()));
+        }
+
+        // Rotate connections before a proxy or load balancer kills them mid-query
+        if let Some(reason) = self.expiry_reason(metrics) {
+            debug!("Closing pooled connection: {}", reason);
+            return Err(RecycleError::Message(reason.into()));
+        }
+
+        // A connection checked out moments ago is almost certainly still alive
+        let skip_within = Duration::from_secs(self.config.pool_health_check_skip_secs);
+        if metrics.last_used() < skip_within {
+            crate::metrics::increment_pool_connections_recycled();
+            return Ok(());
+        }
+
         debug!("Recycling SurrealDB connection");
         
         match self.health_check(conn).await {
@@ -193,11 +429,11 @@ pub async fn create_pool(config: Arc<Config>) -> Result<Pool> {
     
     // Wait for all pre-warming tasks to complete with timeout
     let pre_warm_total_timeout = Duration::from_secs(config.pool_create_timeout_secs * 2);
-    if let Err(_) = timeout(pre_warm_total_timeout, async {
+    if timeout(pre_warm_total_timeout, async {
         for handle in handles {
             let _ = handle.await;
         }
-    }).await {
+    }).await.is_err() {
         warn!("Pre-warming phase timed out, continuing anyway");
     }
     
@@ -213,6 +449,13 @@ pub async fn create_pool(config: Arc<Config>) -> Result<Pool> {
 pub trait PoolExt {
     /// Get pool statistics
     fn stats(&self) -> PoolStats;
+
+    /// Outage state shared by the pool's connections
+    fn db_health(&self) -> Arc<DbHealth>;
+
+    /// Close idle connections past `POOL_IDLE_TIMEOUT_SECS` or `POOL_MAX_LIFETIME_SECS`;
+    /// returns how many were closed
+    fn reap_expired(&self) -> usize;
 }
 
 /// Pool statistics
@@ -228,12 +471,29 @@ impl PoolExt for Pool {
     fn stats(&self) -> PoolStats {
         let status = self.status();
         PoolStats {
-            size: status.size as usize,
-            available: status.available as usize,
-            waiting: status.waiting as usize,
-            max_size: status.max_size as usize,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+            max_size: status.max_size,
         }
     }
+
+    fn db_health(&self) -> Arc<DbHealth> {
+        self.manager().health()
+    }
+
+    fn reap_expired(&self) -> usize {
+        let manager = self.manager();
+        let reaped = std::cell::Cell::new(0);
+        self.retain(|_, metrics| {
+            let expired = manager.expiry_reason(&metrics).is_some();
+            if expired {
+                reaped.set(reaped.get() + 1);
+            }
+            !expired
+        });
+        reaped.get()
+    }
 }
 
 #[cfg(test)]
@@ -244,32 +504,57 @@ mod tests {
 
     fn test_config() -> Arc<Config> {
         Arc::new(Config {
-            db_url: "memory://test".to_string(),
-            db_user: "root".to_string(),
-            db_pass: "root".to_string(),
             db_namespace: "test_ns".to_string(),
             db_database: "test_db".to_string(),
             embedding_provider: "ollama".to_string(),
-            ollama_url: "http://localhost:11434".to_string(),
-            openai_api_key: None,
-            together_api_key: None,
             embedding_model: "test-model".to_string(),
-            batch_size: 10,
             pool_size: 2,
             retry_attempts: 3,
             retry_delay_ms: 100,
             batch_delay_ms: 100,
             monitoring_port: Some(9090),
-            parallel_workers: 1,
-            token_limit: 8000,
             pool_max_size: 5,
             pool_timeout_secs: 30,
             pool_wait_timeout_secs: 10,
             pool_create_timeout_secs: 30,
             pool_recycle_timeout_secs: 30,
+            price_per_million_tokens: None,
+            provider_probe_interval_secs: 300,
+            monitoring_bind_addr: "0.0.0.0".to_string(),
+            mock_dimensions: 768,
+            poll_interval_secs: 5,
+            shutdown_drain_secs: 20,
+            ..Config::for_tests()
         })
     }
 
+    #[test]
+    fn test_db_auth_parsing() {
+        assert_eq!("root".parse::<DbAuth>().unwrap(), DbAuth::Root);
+        assert_eq!("NS".parse::<DbAuth>().unwrap(), DbAuth::Namespace);
+        assert_eq!("database".parse::<DbAuth>().unwrap(), DbAuth::Database);
+        assert_eq!("record".parse::<DbAuth>().unwrap(), DbAuth::Record);
+        assert!("scope".parse::<DbAuth>().is_err());
+    }
+
+    #[test]
+    fn test_db_health_backoff() {
+        let health = DbHealth::default();
+        let error = unavailable_error(Duration::ZERO);
+
+        health.record_failure(&error);
+        assert!(health.is_unavailable());
+        let first = health.backoff_remaining().unwrap();
+        assert!(first <= RECONNECT_BACKOFF_BASE);
+
+        health.record_failure(&error);
+        assert!(health.backoff_remaining().unwrap() > first);
+
+        health.record_success();
+        assert!(!health.is_unavailable());
+        assert!(health.backoff_remaining().is_none());
+    }
+
     #[tokio::test]
     async fn test_surreal_manager_create_connection() {
         let config = test_config();
@@ -325,7 +610,7 @@ mod tests {
         let conn = conn_result.unwrap();
         
         // Verify the connection works
-        let mut response = conn.query("SELECT 1 as value").await.expect("Query failed");
+        let mut response = conn.query("RETURN 1").await.expect("Query failed");
         let result: Option<serde_json::Value> = response.take(0).expect("Failed to get result");
         assert!(result.is_some());
     }
@@ -351,7 +636,7 @@ mod tests {
                 tokio::time::sleep(Duration::from_millis(10)).await;
                 
                 // Perform a query
-                let mut response = conn.query("SELECT $id as id")
+                let mut response = conn.query("RETURN $id")
                     .bind(("id", i))
                     .await
                     .expect("Query failed");
@@ -405,6 +690,58 @@ mod tests {
         assert!(query_result.is_ok());
     }
 
+    #[test]
+    fn test_embedded_urls() {
+        assert!(is_embedded_url("mem://"));
+        assert!(is_embedded_url("rocksdb://data/embed_star.db"));
+        assert!(!is_embedded_url("ws://localhost:8000"));
+        assert!(!is_memory_url("rocksdb://data/embed_star.db"));
+    }
+
+    #[test]
+    fn test_connection_expiry() {
+        let manager = SurrealDBManager::new(Arc::new(Config {
+            pool_idle_timeout_secs: Some(60),
+            pool_max_lifetime_secs: Some(600),
+            ..test_config().as_ref().clone()
+        }));
+
+        // Recently recycled, so not idle, but created past the max lifetime
+        let mut metrics = deadpool::managed::Metrics {
+            created: Instant::now() - Duration::from_secs(900),
+            recycled: Some(Instant::now()),
+            ..Default::default()
+        };
+        assert_eq!(manager.expiry_reason(&metrics), Some("max lifetime reached"));
+
+        metrics.created = Instant::now() - Duration::from_secs(300);
+        metrics.recycled = Some(Instant::now() - Duration::from_secs(120));
+        assert_eq!(manager.expiry_reason(&metrics), Some("idle timeout reached"));
+
+        metrics.recycled = Some(Instant::now());
+        assert_eq!(manager.expiry_reason(&metrics), None);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_skipped_after_recent_use() {
+        let config = Config {
+            pool_health_check_query: "THROW 'unhealthy'".to_string(),
+            ..test_config().as_ref().clone()
+        };
+        let metrics = deadpool::managed::Metrics::default();
+
+        let manager = SurrealDBManager::new(Arc::new(config.clone()));
+        let mut conn = manager.create().await.expect("Failed to create connection");
+        assert!(manager.recycle(&mut conn, &metrics).await.is_err());
+
+        let manager = SurrealDBManager::new(Arc::new(Config {
+            pool_health_check_skip_secs: 60,
+            ..config
+        }));
+        let mut conn = manager.create().await.expect("Failed to create connection");
+        assert!(manager.recycle(&mut conn, &metrics).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_pool_timeout() {
         let config = Arc::new(Config {
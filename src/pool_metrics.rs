@@ -22,6 +22,12 @@ pub async fn monitor_pool_metrics(
 }
 
 async fn report_pool_metrics(pool: &Pool) {
+    // Close idle connections that outlived their idle timeout or lifetime
+    let reaped = pool.reap_expired();
+    if reaped > 0 {
+        debug!("Closed {} expired pooled connections", reaped);
+    }
+
     // Get pool statistics
     let stats = pool.stats();
     
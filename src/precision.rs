@@ -0,0 +1,113 @@
+use half::{bf16, f16};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Precision of the float vector written to the database. Half-precision vectors are rounded
+/// before writing, so every stored value converts to 16 bits without further loss and readers
+/// can pack them with [`StoragePrecision::encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoragePrecision {
+    /// Values as the provider returned them
+    #[default]
+    F32,
+    /// IEEE half precision: 11 significant bits, values up to 65504
+    F16,
+    /// bfloat16: the range of f32 with 8 significant bits
+    Bf16,
+}
+
+impl StoragePrecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StoragePrecision::F32 => "f32",
+            StoragePrecision::F16 => "f16",
+            StoragePrecision::Bf16 => "bf16",
+        }
+    }
+
+    /// Largest magnitude this precision can hold
+    pub fn max_value(&self) -> f32 {
+        match self {
+            StoragePrecision::F32 => f32::MAX,
+            StoragePrecision::F16 => f16::MAX.to_f32(),
+            StoragePrecision::Bf16 => bf16::MAX.to_f32(),
+        }
+    }
+
+    /// `embedding` with every value rounded to the nearest value of this precision
+    pub fn round(&self, embedding: &[f32]) -> Vec<f32> {
+        match self {
+            StoragePrecision::F32 => embedding.to_vec(),
+            StoragePrecision::F16 => embedding.iter().map(|&x| f16::from_f32(x).to_f32()).collect(),
+            StoragePrecision::Bf16 => embedding.iter().map(|&x| bf16::from_f32(x).to_f32()).collect(),
+        }
+    }
+
+    /// The 16-bit patterns of a vector, for readers that keep vectors in half precision;
+    /// `None` for f32
+    pub fn encode(&self, embedding: &[f32]) -> Option<Vec<u16>> {
+        match self {
+            StoragePrecision::F32 => None,
+            StoragePrecision::F16 => Some(embedding.iter().map(|&x| f16::from_f32(x).to_bits()).collect()),
+            StoragePrecision::Bf16 => Some(embedding.iter().map(|&x| bf16::from_f32(x).to_bits()).collect()),
+        }
+    }
+
+    /// Float values of 16-bit patterns produced by [`Self::encode`]; f32 has no 16-bit form
+    pub fn decode(&self, bits: &[u16]) -> Option<Vec<f32>> {
+        match self {
+            StoragePrecision::F32 => None,
+            StoragePrecision::F16 => Some(bits.iter().map(|&b| f16::from_bits(b).to_f32()).collect()),
+            StoragePrecision::Bf16 => Some(bits.iter().map(|&b| bf16::from_bits(b).to_f32()).collect()),
+        }
+    }
+}
+
+impl FromStr for StoragePrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "f32" | "float32" | "" => Ok(StoragePrecision::F32),
+            "f16" | "float16" | "half" => Ok(StoragePrecision::F16),
+            "bf16" | "bfloat16" => Ok(StoragePrecision::Bf16),
+            other => Err(format!(
+                "Unknown storage precision '{}' (expected f32, f16 or bf16)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StoragePrecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_precision_round_trip() {
+        let embedding = vec![0.1, -0.333_333, 1.0, 0.0];
+
+        for precision in [StoragePrecision::F16, StoragePrecision::Bf16] {
+            let rounded = precision.round(&embedding);
+            let bits = precision.encode(&rounded).unwrap();
+            // Rounded values survive the trip through 16 bits exactly
+            assert_eq!(precision.decode(&bits).unwrap(), rounded);
+            for (original, rounded) in embedding.iter().zip(&rounded) {
+                assert!((original - rounded).abs() < 0.002, "{} {}", precision, rounded);
+            }
+        }
+
+        assert_eq!(StoragePrecision::F32.round(&embedding), embedding);
+        assert!(StoragePrecision::F32.encode(&embedding).is_none());
+        assert_eq!(StoragePrecision::F16.max_value(), 65504.0);
+        assert_eq!("BF16".parse::<StoragePrecision>().unwrap(), StoragePrecision::Bf16);
+        assert!("f8".parse::<StoragePrecision>().is_err());
+    }
+}
@@ -1,33 +1,91 @@
 use crate::{
+    audit::{AuditOutcome, AuditRecord, BatchAudit, BatchRun},
     circuit_breaker::CircuitBreakerManager,
     embedder::Embedder,
     embedding_cache::EmbeddingCache,
     error::EmbedError,
+    events::{self, PipelineEvent},
+    language::description_language,
     metrics,
     models::Repo,
-    rate_limiter::RateLimiterManager,
+    rate_limiter::{estimate_tokens, RateLimiterManager},
+    repo_store::EmbeddingSink,
     retry::{with_retry, RetryConfig},
-    surreal_client::{EmbeddingUpdate, SurrealClient},
-    validation::EmbeddingValidator,
+    surreal_client::EmbeddingUpdate,
+    telemetry,
+    tenant::current_tenant,
+    validation::{EmbeddingValidator, ValidationOutcome},
     with_circuit_breaker,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::time::Instant;
-use tracing::{debug, error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
-pub async fn process_batch(
+/// Audit code for repos left unprocessed because `cancel` fired
+const CANCELLED: &str = "CANCELLED";
+
+/// Audit code for stored embeddings that validation repair had to rescale
+const REPAIRED: &str = "REPAIRED";
+
+/// Embed a batch of repos and store the results. Returns the batch's summary, including how
+/// many embeddings were stored.
+/// Once `cancel` fires the in-flight provider request is aborted and the remaining repos are
+/// left pending; embeddings generated before that are still written.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_batch<S: EmbeddingSink + ?Sized>(
     batch: &[Repo],
-    client: &Arc<SurrealClient>,
+    client: &Arc<S>,
     embedder: &Arc<Embedder>,
     rate_limiter: &Arc<RateLimiterManager>,
     circuit_breaker: &Arc<CircuitBreakerManager>,
     validator: &Arc<EmbeddingValidator>,
     cache: &Arc<EmbeddingCache>,
     retry_config: &RetryConfig,
-) {
+    cancel: &CancellationToken,
+) -> BatchRun {
     let batch_id = Uuid::new_v4();
+    // Each batch is its own trace root, so traces stay bounded while workers run indefinitely
+    let span = info_span!(
+        parent: None,
+        "process_batch",
+        batch_id = %batch_id,
+        session_id = %telemetry::session_id(),
+        batch_size = batch.len(),
+    );
+
+    process_batch_inner(
+        batch_id,
+        batch,
+        client,
+        embedder,
+        rate_limiter,
+        circuit_breaker,
+        validator,
+        cache,
+        retry_config,
+        cancel,
+    )
+    .instrument(span)
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_batch_inner<S: EmbeddingSink + ?Sized>(
+    batch_id: Uuid,
+    batch: &[Repo],
+    client: &Arc<S>,
+    embedder: &Arc<Embedder>,
+    rate_limiter: &Arc<RateLimiterManager>,
+    circuit_breaker: &Arc<CircuitBreakerManager>,
+    validator: &Arc<EmbeddingValidator>,
+    cache: &Arc<EmbeddingCache>,
+    retry_config: &RetryConfig,
+    cancel: &CancellationToken,
+) -> BatchRun {
     let batch_size = batch.len();
+    let batch_start = Instant::now();
     
     // Create a cleaner log with just the essential info
     info!(
@@ -45,8 +103,21 @@ pub async fn process_batch(
 
     // Collect successful updates for batch processing
     let mut pending_updates = Vec::new();
+    let mut audit = BatchAudit::new(batch_id, embedder.provider_name(), embedder.model_name());
 
     for (idx, repo) in batch.iter().enumerate() {
+        if cancel.is_cancelled() {
+            warn!(
+                batch_id = %batch_id,
+                remaining = batch_size - idx,
+                "Shutdown deadline reached, leaving the rest of the batch pending"
+            );
+            for repo in &batch[idx..] {
+                audit.record(&repo.id, AuditOutcome::Skipped, Some(CANCELLED), Duration::ZERO, false);
+            }
+            break;
+        }
+
         // Process each repo with a clean span
         let repo_span = tracing::debug_span!(
             "process_repo",
@@ -60,66 +131,173 @@ pub async fn process_batch(
         debug!("Processing repository");
 
         let text = repo.prepare_text_for_embedding();
-        let provider = embedder.model_name();
-        let cache_key = EmbeddingCache::cache_key(&repo.full_name, provider);
+        // Limits and the breaker are shared by every model the provider serves
+        let provider = embedder.provider_name();
+        let cache_key = EmbeddingCache::cache_key(&text, embedder.model_name());
+        let language = description_language(repo);
         
         // Check cache first
         if let Some((cached_embedding, _cached_model)) = cache.get(&cache_key) {
             info!("Using cached embedding");
             
             // Add to pending updates with cached embedding
+            // Cached vectors were stored under the same policy, so they are already normalized
             pending_updates.push(EmbeddingUpdate {
                 repo_id: repo.id.clone(),
                 embedding: cached_embedding,
+                normalized: validator.normalizes(),
+                repaired: false,
+                updated_at: repo.updated_at,
+                model: Some(embedder.model_name().to_string()),
+                language: language.map(str::to_string),
             });
+            audit.record(&repo.id, AuditOutcome::Stored, None, Duration::ZERO, true);
+            continue;
+        }
+
+        // Skip repos that failed recently instead of sending them to the provider again
+        if let Some(reason) = cache.get_failure(&cache_key) {
+            debug!(reason = %reason, "Skipping repo with recently cached failure");
+            metrics::record_negative_cache_hit(provider);
+            audit.record(&repo.id, AuditOutcome::Skipped, Some(reason.as_str()), Duration::ZERO, false);
             continue;
         }
 
         // Wait for rate limit permit
-        if let Err(e) = rate_limiter.wait_for_permit(&provider).await {
+        if let Err(e) = rate_limiter.wait_for_permit(provider).await {
             error!(error = %e, "Rate limit error, skipping repo");
-            metrics::record_rate_limit(&provider);
+            metrics::record_rate_limit(provider);
+            audit.record(&repo.id, AuditOutcome::Skipped, Some(e.error_code()), Duration::ZERO, false);
             continue;
         }
 
+        // Providers also cap tokens per minute, which request counting alone can't capture
+        if let Err(e) = rate_limiter.wait_for_tokens(provider, estimate_tokens(&text)).await {
+            error!(error = %e, "Token limit error, skipping repo");
+            metrics::record_rate_limit(provider);
+            audit.record(&repo.id, AuditOutcome::Skipped, Some(e.error_code()), Duration::ZERO, false);
+            continue;
+        }
+
+        // Bound concurrent requests so scaling workers up can't flood a slow provider
+        let _slot = match rate_limiter.acquire_slot(provider).await {
+            Ok(slot) => slot,
+            Err(e) => {
+                error!(error = %e, "Concurrency limit error, skipping repo");
+                audit.record(&repo.id, AuditOutcome::Skipped, Some(e.error_code()), Duration::ZERO, false);
+                continue;
+            }
+        };
+
         // Generate embedding with circuit breaker
         let start = Instant::now();
         
-        let embedding_result = with_circuit_breaker!(
-            circuit_breaker,
-            provider,
-            with_retry(
-                &format!("generate_embedding_{}", repo.full_name),
-                retry_config,
-                || async {
-                    embedder.generate_embedding(&text).await
-                        .map_err(|e| EmbedError::EmbeddingProvider(e.to_string()))
-                },
-            ).await
-        );
+        let embedding_result = tokio::select! {
+            // Dropping the request future aborts the HTTP call; the repo stays pending
+            _ = cancel.cancelled() => {
+                warn!("Shutdown deadline reached, aborted in-flight embedding request");
+                audit.record(&repo.id, AuditOutcome::Skipped, Some(CANCELLED), start.elapsed(), false);
+                continue;
+            }
+            result = async {
+                with_circuit_breaker!(
+                    circuit_breaker,
+                    provider,
+                    {
+                        let mut attempt = 0;
+                        let attempts = with_retry(
+                            "generate_embedding",
+                            retry_config,
+                            || {
+                                let retry = attempt > 0;
+                                attempt += 1;
+                                let text = &text;
+                                async move {
+                                    // A retry waits out any backoff the provider asked for, like the
+                                    // first attempt did above
+                                    if retry {
+                                        rate_limiter.wait_for_permit(provider).await?;
+                                    }
+                                    // Failures the provider didn't explain are treated as transient; this
+                                    // is the only layer that retries them
+                                    let result = embedder.generate_embedding(text).await.map_err(|e| {
+                                        e.downcast::<EmbedError>()
+                                            .unwrap_or_else(|e| EmbedError::ServiceUnavailable(e.to_string()))
+                                    });
+                                    // Let the provider's own quota and Retry-After drive the limiter
+                                    // before anything else is sent
+                                    if let Some(hint) = embedder.take_rate_limit_hint() {
+                                        if let Err(e) = rate_limiter.apply_hint(provider, &hint).await {
+                                            warn!(error = %e, "Failed to apply provider rate limit hint");
+                                        }
+                                    }
+                                    result
+                                }
+                            },
+                        );
+                        // A hung request counts against the breaker like any other failure, but
+                        // running out of time while waiting out a provider backoff says nothing
+                        // about this record
+                        match retry_config.record_timeout {
+                            Some(limit) => match tokio::time::timeout(limit, attempts).await {
+                                Ok(result) => result,
+                                Err(_) if rate_limiter.blocked_for(provider).await.is_some() => {
+                                    Err(EmbedError::RateLimitExceeded {
+                                        provider: provider.to_string(),
+                                    })
+                                }
+                                Err(_) => Err(EmbedError::Timeout(limit)),
+                            },
+                            None => attempts.await,
+                        }
+                    }
+                )
+            } => result,
+        };
         
         match embedding_result {
-            Ok(embedding) => {
+            Ok(mut embedding) => {
                 let duration = start.elapsed().as_secs_f64();
                 
-                // Validate the embedding
-                match validator.validate(&embedding, &repo.full_name) {
-                    Ok(_) => {
+                // Validate the embedding and apply the storage policy
+                let validated = info_span!("validate_embedding").in_scope(|| {
+                    let outcome = validator.validate_or_repair(&mut embedding, &repo.full_name)?;
+                    let normalized = validator.prepare_for_storage(&mut embedding)?;
+                    Ok::<_, EmbedError>((normalized, outcome == ValidationOutcome::Repaired))
+                });
+                match validated {
+                    Ok((normalized, repaired)) => {
                         metrics::record_embedding_generated(provider, embedder.model_name(), duration);
                         metrics::record_provider_request(provider, true);
                         
-                        // Cache the embedding
-                        cache.put(
-                            cache_key,
-                            embedding.clone(),
-                            embedder.model_name().to_string(),
-                        );
+                        // Cache the embedding; the cache and the update share one allocation.
+                        // Repaired vectors aren't cached so a cache hit never hides the repair.
+                        let embedding: Arc<[f32]> = embedding.into();
+                        if !repaired {
+                            cache.put(
+                                cache_key,
+                                embedding.clone(),
+                                embedder.model_name().to_string(),
+                            );
+                        }
                         
-                        // Add to pending updates
-                        pending_updates.push(EmbeddingUpdate {
+                        // Add to pending updates, logging it first so a crash before the
+                        // write doesn't waste the provider call
+                        let update = EmbeddingUpdate {
                             repo_id: repo.id.clone(),
                             embedding,
-                        });
+                            normalized,
+                            repaired,
+                            updated_at: repo.updated_at,
+                            model: Some(embedder.model_name().to_string()),
+                            language: language.map(str::to_string),
+                        };
+                        if let Err(e) = client.record_generated(&update).await {
+                            warn!(error = %e, "Failed to record embedding in the write-ahead log");
+                        }
+                        pending_updates.push(update);
+                        let code = repaired.then_some(REPAIRED);
+                        audit.record(&repo.id, AuditOutcome::Stored, code, start.elapsed(), false);
                         
                         info!(
                             duration_ms = (duration * 1000.0) as u64,
@@ -129,6 +307,8 @@ pub async fn process_batch(
                     Err(e) => {
                         error!(error = %e, "Embedding validation failed");
                         metrics::record_provider_request(provider, false);
+                        audit.record(&repo.id, AuditOutcome::Failed, Some(e.error_code()), start.elapsed(), false);
+                        cache.put_failure(cache_key, e.error_code().to_string());
                     }
                 }
             }
@@ -136,14 +316,25 @@ pub async fn process_batch(
                 error!(error = %e, "Failed to generate embedding");
                 metrics::record_embedding_error(provider, e.error_code());
                 metrics::record_provider_request(provider, false);
+                audit.record(&repo.id, AuditOutcome::Failed, Some(e.error_code()), start.elapsed(), false);
+
+                // Transient failures (open circuit, rate limits) should be retried on the next poll, and
+                // account-wide ones (credentials, quota, model) aren't this repo's fault; inputs the
+                // provider can't take, timeouts included, are skipped until the negative cache expires
+                if e.is_record_specific() {
+                    cache.put_failure(cache_key, e.error_code().to_string());
+                }
             }
         }
     }
 
     // Batch update embeddings if any were generated
-    if !pending_updates.is_empty() {
+    let stored = if !pending_updates.is_empty() {
         let update_count = pending_updates.len();
-        match client.batch_update_embeddings(pending_updates).await {
+        let write_start = Instant::now();
+        let write_result = client.store_embeddings(pending_updates).await;
+        metrics::record_db_batch_update(write_start.elapsed(), write_result.is_ok());
+        match write_result {
             Ok(result) => {
                 info!(
                     batch_id = %batch_id,
@@ -152,6 +343,8 @@ pub async fn process_batch(
                     duration_ms = result.duration.as_millis(),
                     "Batch update completed"
                 );
+                audit.settle_write(Some(&result.errors));
+                result.successful
             }
             Err(e) => {
                 error!(
@@ -160,6 +353,8 @@ pub async fn process_batch(
                     error = %e,
                     "Failed to batch update embeddings"
                 );
+                audit.settle_write(None);
+                0
             }
         }
     } else {
@@ -167,7 +362,43 @@ pub async fn process_batch(
             batch_id = %batch_id,
             "No embeddings were generated in this batch"
         );
+        0
+    };
+
+    let run = audit.summary(stored, batch_start.elapsed());
+    let records = audit.into_records();
+    publish_batch_events(&run, &records);
+
+    if let Err(e) = client.write_audit_records(records).await {
+        warn!(batch_id = %batch_id, error = %e, "Failed to write audit records");
     }
+
+    run
+}
+
+/// Stream the batch's failures and its summary to `/events` subscribers
+fn publish_batch_events(run: &BatchRun, records: &[AuditRecord]) {
+    let tenant = current_tenant().to_string();
+    for record in records.iter().filter(|record| record.outcome == AuditOutcome::Failed) {
+        events::publish(PipelineEvent::EmbeddingFailed {
+            batch_id: run.batch_id.clone(),
+            tenant: tenant.clone(),
+            repo: record.repo.to_string(),
+            error_code: record.error_code.clone(),
+            at: run.created_at,
+        });
+    }
+    events::publish(PipelineEvent::BatchCompleted {
+        batch_id: run.batch_id.clone(),
+        tenant,
+        model: run.model.clone(),
+        repos: run.size,
+        stored: run.stored,
+        failed: run.failed,
+        skipped: run.skipped,
+        duration_ms: run.duration_ms,
+        at: run.created_at,
+    });
 }
 
 #[cfg(test)]
@@ -194,30 +425,27 @@ mod tests {
         Arc<EmbeddingCache>,
         RetryConfig,
     ) {
+        crate::metrics::Metrics::register(&prometheus::Registry::new()).expect("Failed to register metrics");
         let config = Arc::new(Config {
-            db_url: "memory://test".to_string(),
-            db_user: "root".to_string(),
-            db_pass: "root".to_string(),
             db_namespace: "test_ns".to_string(),
             db_database: "test_db".to_string(),
             embedding_provider: "ollama".to_string(),
-            ollama_url: "http://localhost:11434".to_string(),
-            openai_api_key: None,
-            together_api_key: None,
             embedding_model: "test-model".to_string(),
-            batch_size: 10,
             pool_size: 2,
-            retry_attempts: 1,
-            retry_delay_ms: 10,
             batch_delay_ms: 100,
             monitoring_port: Some(9090),
-            parallel_workers: 1,
-            token_limit: 8000,
             pool_max_size: 5,
             pool_timeout_secs: 30,
             pool_wait_timeout_secs: 10,
             pool_create_timeout_secs: 30,
             pool_recycle_timeout_secs: 30,
+            price_per_million_tokens: None,
+            provider_probe_interval_secs: 300,
+            monitoring_bind_addr: "0.0.0.0".to_string(),
+            mock_dimensions: 768,
+            poll_interval_secs: 5,
+            shutdown_drain_secs: 20,
+            ..Config::for_tests()
         });
 
         let pool = create_pool(config.clone()).await.expect("Failed to create pool");
@@ -230,7 +458,7 @@ mod tests {
         let circuit_breaker = Arc::new(CircuitBreakerManager::new());
         let validator = Arc::new(EmbeddingValidator::new(ValidationConfig::default()));
         let cache = Arc::new(EmbeddingCache::new(100, 3600));
-        let retry_config = RetryConfig::default();
+        let retry_config = RetryConfig::from_config(&config);
 
         (client, embedder, rate_limiter, circuit_breaker, validator, cache, retry_config)
     }
@@ -246,6 +474,7 @@ mod tests {
             url: format!("https://github.com/owner/test-{}", id),
             stars: 42,
             language: Some("Rust".to_string()),
+            readme: None,
             owner: RepoOwner {
                 login: "owner".to_string(),
                 avatar_url: "https://github.com/owner.png".to_string(),
@@ -255,6 +484,7 @@ mod tests {
             updated_at: now,
             embedding: None,
             embedding_generated_at: None,
+            extra: Default::default(),
         }
     }
 
@@ -275,6 +505,7 @@ mod tests {
             &validator,
             &cache,
             &retry_config,
+            &CancellationToken::new(),
         ).await;
     }
 
@@ -284,10 +515,16 @@ mod tests {
             setup_test_environment().await;
 
         let repo = create_test_repo("cached");
+        let conn = client.get_connection().await.expect("Failed to get connection");
+        let _: Option<Repo> = conn
+            .create(("repo", "cached"))
+            .content(repo.clone())
+            .await
+            .expect("Failed to create repo");
         let batch = vec![repo.clone()];
         
         // Pre-populate cache
-        let cache_key = EmbeddingCache::cache_key(&repo.full_name, embedder.model_name());
+        let cache_key = EmbeddingCache::cache_key(&repo.prepare_text_for_embedding(), embedder.model_name());
         cache.put(cache_key, vec![0.1, 0.2, 0.3], embedder.model_name().to_string());
         
         // Process batch - should use cached embedding
@@ -300,10 +537,10 @@ mod tests {
             &validator,
             &cache,
             &retry_config,
+            &CancellationToken::new(),
         ).await;
         
         // Verify the update was made
-        let conn = client.get_connection().await.expect("Failed to get connection");
         let updated: Option<Repo> = conn.select(&repo.id).await.expect("Failed to select repo");
         
         assert!(updated.is_some());
@@ -320,7 +557,7 @@ mod tests {
         let conn = client.get_connection().await.expect("Failed to get connection");
         let _: Option<Repo> = conn
             .create(("repo", "single"))
-            .content(&repo)
+            .content(repo.clone())
             .await
             .expect("Failed to create repo");
 
@@ -338,6 +575,7 @@ mod tests {
             &validator,
             &cache,
             &retry_config,
+            &CancellationToken::new(),
         ).await;
     }
 
@@ -354,7 +592,7 @@ mod tests {
             let repo = create_test_repo(&format!("multi{}", i));
             let _: Option<Repo> = conn
                 .create(("repo", format!("multi{}", i)))
-                .content(&repo)
+                .content(repo.clone())
                 .await
                 .expect("Failed to create repo");
             batch.push(repo);
@@ -369,9 +607,219 @@ mod tests {
             &validator,
             &cache,
             &retry_config,
+            &CancellationToken::new(),
         ).await;
     }
 
+    struct HangingProvider;
+
+    #[async_trait::async_trait]
+    impl crate::embedder::EmbeddingProvider for HangingProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            std::future::pending().await
+        }
+
+        fn model_name(&self) -> &str {
+            "hanging"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hung_record_times_out() {
+        let (client, _embedder, rate_limiter, circuit_breaker, validator, _cache, retry_config) =
+            setup_test_environment().await;
+        let embedder = Arc::new(Embedder::builder("ollama", Box::new(HangingProvider)).build());
+        let cache = Arc::new(EmbeddingCache::new(100, 3600).with_negative_ttl(300));
+        let retry_config = RetryConfig {
+            record_timeout: Some(Duration::from_millis(50)),
+            ..retry_config
+        };
+
+        let repo = create_test_repo("hanging");
+        let batch = vec![repo.clone()];
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            process_batch(
+                &batch,
+                &client,
+                &embedder,
+                &rate_limiter,
+                &circuit_breaker,
+                &validator,
+                &cache,
+                &retry_config,
+                &CancellationToken::new(),
+            ),
+        )
+        .await
+        .expect("A hung provider call should not stall the batch");
+
+        let cache_key = EmbeddingCache::cache_key(&repo.prepare_text_for_embedding(), embedder.model_name());
+        assert_eq!(cache.get_failure(&cache_key).as_deref(), Some("TIMEOUT"));
+    }
+
+    /// Answers the first request with a 429 carrying `Retry-After`, then succeeds
+    struct RateLimitedProvider {
+        calls: std::sync::atomic::AtomicUsize,
+        hint: parking_lot::Mutex<Option<crate::rate_limiter::RateLimitHint>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::embedder::EmbeddingProvider for RateLimitedProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                *self.hint.lock() = Some(crate::rate_limiter::RateLimitHint {
+                    retry_after: Some(Duration::from_millis(300)),
+                    ..Default::default()
+                });
+                return Err(EmbedError::RateLimitExceeded {
+                    provider: "limited".to_string(),
+                }
+                .into());
+            }
+            Ok((1..=128).map(|i| i as f32 / 128.0).collect())
+        }
+
+        fn model_name(&self) -> &str {
+            "limited"
+        }
+
+        fn take_rate_limit_hint(&self) -> Option<crate::rate_limiter::RateLimitHint> {
+            self.hint.lock().take()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_waits_for_retry_after() {
+        let (client, _embedder, rate_limiter, circuit_breaker, validator, cache, retry_config) =
+            setup_test_environment().await;
+        let embedder = Arc::new(
+            Embedder::builder(
+                "ollama",
+                Box::new(RateLimitedProvider {
+                    calls: Default::default(),
+                    hint: Default::default(),
+                }),
+            )
+            .build(),
+        );
+        let retry_config = RetryConfig {
+            max_retries: 2,
+            jitter: false,
+            ..retry_config
+        };
+
+        let repo = create_test_repo("limited");
+        let conn = client.get_connection().await.expect("Failed to get connection");
+        let _: Option<Repo> = conn
+            .create(("repo", "limited"))
+            .content(repo.clone())
+            .await
+            .expect("Failed to create repo");
+
+        let start = Instant::now();
+        let run = process_batch(
+            &[repo],
+            &client,
+            &embedder,
+            &rate_limiter,
+            &circuit_breaker,
+            &validator,
+            &cache,
+            &retry_config,
+            &CancellationToken::new(),
+        )
+        .await;
+
+        // The retry went out only after the provider's Retry-After, not after the usual ~100ms
+        assert!(start.elapsed() >= Duration::from_millis(280), "{:?}", start.elapsed());
+        assert_eq!(run.stored, 1);
+    }
+
+    struct StaticProvider;
+
+    #[async_trait::async_trait]
+    impl crate::embedder::EmbeddingProvider for StaticProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok((1..=128).map(|i| i as f32 / 128.0).collect())
+        }
+
+        fn model_name(&self) -> &str {
+            "static"
+        }
+    }
+
+    /// Records the name and fields of every span opened while it is the default subscriber
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<parking_lot::Mutex<Vec<(String, String)>>>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Fields(String);
+            impl tracing::field::Visit for Fields {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                    self.0.push_str(&format!("{}={:?} ", field.name(), value));
+                }
+            }
+            let mut fields = Fields(String::new());
+            attrs.record(&mut fields);
+            self.0.lock().push((attrs.metadata().name().to_string(), fields.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_spans() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (client, _embedder, rate_limiter, circuit_breaker, validator, cache, retry_config) =
+            setup_test_environment().await;
+        let embedder = Arc::new(Embedder::builder("ollama", Box::new(StaticProvider)).build());
+        let repo = create_test_repo("traced");
+        let conn = client.get_connection().await.expect("Failed to get connection");
+        let _: Option<Repo> = conn
+            .create(("repo", "traced"))
+            .content(repo.clone())
+            .await
+            .expect("Failed to create repo");
+
+        let recorder = SpanRecorder::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+        let run = process_batch(
+            &[repo],
+            &client,
+            &embedder,
+            &rate_limiter,
+            &circuit_breaker,
+            &validator,
+            &cache,
+            &retry_config,
+            &CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(run.stored, 1);
+
+        let spans = recorder.0.lock().clone();
+        let fields = |name: &str| {
+            spans
+                .iter()
+                .find(|(span, _)| span == name)
+                .map(|(_, fields)| fields.clone())
+                .unwrap_or_else(|| panic!("no {} span in {:?}", name, spans))
+        };
+        let batch = fields("process_batch");
+        assert!(batch.contains(&format!("batch_id={}", run.batch_id)), "{}", batch);
+        assert!(batch.contains(&format!("session_id={}", telemetry::session_id())), "{}", batch);
+        assert!(fields("provider.embed").contains("provider=\"ollama\""));
+        fields("validate_embedding");
+        fields("db.write_embeddings");
+    }
+
     #[tokio::test]
     async fn test_batch_update_reporting() {
         let (client, embedder, rate_limiter, circuit_breaker, validator, cache, retry_config) = 
@@ -381,13 +829,13 @@ mod tests {
         let conn = client.get_connection().await.expect("Failed to get connection");
         
         let repo1 = create_test_repo("update1");
-        let _: Option<Repo> = conn.create(("repo", "update1")).content(&repo1).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "update1")).content(repo1.clone()).await.expect("Failed to create repo");
         
         let repo2 = create_test_repo("update2");
-        let _: Option<Repo> = conn.create(("repo", "update2")).content(&repo2).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "update2")).content(repo2.clone()).await.expect("Failed to create repo");
         
         // Pre-cache one to simulate mixed processing
-        let cache_key = EmbeddingCache::cache_key(&repo1.full_name, embedder.model_name());
+        let cache_key = EmbeddingCache::cache_key(&repo1.prepare_text_for_embedding(), embedder.model_name());
         cache.put(cache_key, vec![0.1, 0.2, 0.3], embedder.model_name().to_string());
         
         let batch = vec![repo1, repo2];
@@ -401,6 +849,7 @@ mod tests {
             &validator,
             &cache,
             &retry_config,
+            &CancellationToken::new(),
         ).await;
     }
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+use crate::error::{is_transient_status, EmbedError};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+/// Error payload of OpenAI and the OpenAI-compatible Together AI API:
+/// `{"error": {"message": "...", "type": "...", "code": "..."}}`
+#[derive(Debug, Default, Deserialize)]
+struct OpenAIErrorBody {
+    #[serde(default)]
+    error: OpenAIErrorDetail,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OpenAIErrorDetail {
+    message: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    /// A string for OpenAI, sometimes a number or null elsewhere
+    code: Option<serde_json::Value>,
+}
+
+/// Ollama's error payload: `{"error": "..."}`
+#[derive(Debug, Deserialize)]
+struct OllamaErrorBody {
+    error: String,
+}
+
+/// Whether a provider message says the input exceeds the model's context
+fn mentions_input_too_long(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["context length", "context_length", "too long", "too many tokens", "token limit"]
+        .iter()
+        .any(|phrase| message.contains(phrase))
+}
+
+/// Fallback for a response whose payload didn't say more than its status
+fn from_status(status: StatusCode, message: String) -> EmbedError {
+    if status.is_server_error() {
+        EmbedError::ServerError {
+            status: status.as_u16(),
+            message,
+        }
+    } else if is_transient_status(status) {
+        EmbedError::ServiceUnavailable(format!("{} ({})", message, status))
+    } else {
+        EmbedError::InvalidRequest {
+            status: status.as_u16(),
+            message,
+        }
+    }
+}
+
+/// The typed error for a failed OpenAI-compatible response
+pub fn from_openai_response(provider: &str, status: StatusCode, body: &str, request_id: &str) -> EmbedError {
+    let detail = serde_json::from_str::<OpenAIErrorBody>(body).unwrap_or_default().error;
+    let code = detail.code.as_ref().and_then(|code| code.as_str()).unwrap_or_default();
+    let kind = detail.kind.as_deref().unwrap_or_default();
+    let text = detail.message.as_deref().unwrap_or(body.trim());
+    let message = format!("{} (request {})", text, request_id);
+
+    match status {
+        // Billing quota and rate limits share 429; only the former outlasts a backoff
+        _ if code == "insufficient_quota" || kind == "insufficient_quota" => EmbedError::QuotaExceeded(message),
+        StatusCode::TOO_MANY_REQUESTS => EmbedError::RateLimitExceeded {
+            provider: provider.to_string(),
+        },
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => EmbedError::AuthFailed(message),
+        _ if code == "invalid_api_key" => EmbedError::AuthFailed(message),
+        _ if code == "model_not_found" || status == StatusCode::NOT_FOUND => EmbedError::ModelNotFound(message),
+        _ if code == "context_length_exceeded" || mentions_input_too_long(text) => EmbedError::InputTooLong(message),
+        status => from_status(status, message),
+    }
+}
+
+/// The typed error for a failed Together AI response. Together uses the OpenAI payload but its
+/// own status codes: 402 when the account is out of credit, and 403 when the input exceeds the
+/// model's context.
+pub fn from_together_response(status: StatusCode, body: &str, request_id: &str) -> EmbedError {
+    let detail = serde_json::from_str::<OpenAIErrorBody>(body).unwrap_or_default().error;
+    let text = detail.message.as_deref().unwrap_or(body.trim());
+    let message = format!("{} (request {})", text, request_id);
+
+    match status {
+        StatusCode::PAYMENT_REQUIRED => EmbedError::QuotaExceeded(message),
+        StatusCode::FORBIDDEN if mentions_input_too_long(text) => EmbedError::InputTooLong(message),
+        status => from_openai_response("together", status, body, request_id),
+    }
+}
+
+/// The typed error for a failed Ollama response
+pub fn from_ollama_response(status: StatusCode, body: &str) -> EmbedError {
+    let message = serde_json::from_str::<OllamaErrorBody>(body)
+        .map(|body| body.error)
+        .unwrap_or_else(|_| body.trim().to_string());
+    let lower = message.to_lowercase();
+
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => EmbedError::RateLimitExceeded {
+            provider: "ollama".to_string(),
+        },
+        // Only a proxy in front of Ollama checks credentials
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => EmbedError::AuthFailed(message),
+        _ if status == StatusCode::NOT_FOUND || (lower.contains("model") && lower.contains("not found")) => {
+            EmbedError::ModelNotFound(message)
+        }
+        // Ollama answers an over-long input with a 500, so check before treating it as a server fault
+        _ if mentions_input_too_long(&message) || lower.contains("input length exceeds") => {
+            EmbedError::InputTooLong(message)
+        }
+        status => from_status(status, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_responses_map_to_typed_errors() {
+        let quota = r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        let error = from_openai_response("openai", StatusCode::TOO_MANY_REQUESTS, quota, "r1");
+        assert_eq!(error.error_code(), "QUOTA_EXCEEDED");
+        assert!(!error.is_retryable());
+
+        let error = from_openai_response("openai", StatusCode::TOO_MANY_REQUESTS, "{}", "r2");
+        assert!(matches!(error, EmbedError::RateLimitExceeded { .. }));
+
+        let key = r#"{"error": {"message": "Incorrect API key provided", "code": "invalid_api_key"}}"#;
+        let error = from_openai_response("openai", StatusCode::UNAUTHORIZED, key, "r3");
+        assert_eq!(error.error_code(), "AUTH_FAILED");
+        assert!(error.to_string().contains("Incorrect API key provided (request r3)"));
+
+        let context = r#"{"error": {"message": "This model's maximum context length is 8192 tokens", "code": "context_length_exceeded"}}"#;
+        let error = from_openai_response("openai", StatusCode::BAD_REQUEST, context, "r4");
+        assert_eq!(error.error_code(), "INPUT_TOO_LONG");
+
+        let error = from_openai_response("together", StatusCode::BAD_GATEWAY, "upstream down", "r5");
+        assert!(matches!(error, EmbedError::ServerError { status: 502, .. }));
+        assert!(error.is_retryable());
+
+        // Otherwise the status decides whether asking again could help
+        let error = from_openai_response("openai", StatusCode::BAD_REQUEST, "{}", "r6");
+        assert_eq!(error.error_code(), "INVALID_REQUEST");
+        assert!(!error.is_retryable());
+        assert!(from_openai_response("together", StatusCode::REQUEST_TIMEOUT, "", "r7").is_retryable());
+
+        let credit = r#"{"error": {"message": "Credit limit exceeded", "type": "credit_limit"}}"#;
+        let error = from_together_response(StatusCode::PAYMENT_REQUIRED, credit, "r8");
+        assert_eq!(error.error_code(), "QUOTA_EXCEEDED");
+        let context = r#"{"error": {"message": "Input token count exceeds the model's context length"}}"#;
+        let error = from_together_response(StatusCode::FORBIDDEN, context, "r9");
+        assert_eq!(error.error_code(), "INPUT_TOO_LONG");
+        let error = from_together_response(StatusCode::SERVICE_UNAVAILABLE, "", "r10");
+        assert!(matches!(error, EmbedError::ServerError { status: 503, .. }));
+
+        let missing = r#"{"error": "model \"nomic-embed-text\" not found, try pulling it first"}"#;
+        let error = from_ollama_response(StatusCode::NOT_FOUND, missing);
+        assert_eq!(error.error_code(), "MODEL_NOT_FOUND");
+
+        let long = r#"{"error": "the input length exceeds the context length"}"#;
+        let error = from_ollama_response(StatusCode::INTERNAL_SERVER_ERROR, long);
+        assert_eq!(error.error_code(), "INPUT_TOO_LONG");
+
+        let error = from_ollama_response(StatusCode::INTERNAL_SERVER_ERROR, r#"{"error": "llama runner crashed"}"#);
+        assert!(matches!(error, EmbedError::ServerError { status: 500, .. }));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{
+    circuit_breaker::CircuitBreakerManager,
+    embedder::Embedder,
+    server::ProviderHealth,
+};
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::{sync::Arc, time::{Duration, Instant}};
+use tokio::time::{interval, timeout, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// Fixed, tiny input so a probe costs as little as possible
+const PROBE_TEXT: &str = "health check";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Periodically probes the embedding provider and keeps the latest result for `/health`,
+/// so health checks never make an embedding call themselves
+pub struct ProviderProber {
+    embedder: Arc<Embedder>,
+    circuit_breaker: Arc<CircuitBreakerManager>,
+    interval: Duration,
+    latest: RwLock<Option<ProviderHealth>>,
+}
+
+impl ProviderProber {
+    pub fn new(
+        embedder: Arc<Embedder>,
+        circuit_breaker: Arc<CircuitBreakerManager>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            embedder,
+            circuit_breaker,
+            interval,
+            latest: RwLock::new(None),
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("{} ({})", self.embedder.provider_name(), self.embedder.model_name())
+    }
+
+    /// Latest probe result; reported as unavailable until the first probe completes
+    pub fn latest(&self) -> Vec<ProviderHealth> {
+        let health = self.latest.read().clone().unwrap_or_else(|| ProviderHealth {
+            name: self.name(),
+            available: false,
+            latency_ms: None,
+            last_checked: None,
+            error: Some("not probed yet".to_string()),
+            model_available: None,
+        });
+        vec![health]
+    }
+
+    /// Probe the provider once. While its circuit is open the provider is reported down
+    /// without being called.
+    pub async fn probe(&self) -> ProviderHealth {
+        let provider = self.embedder.provider_name();
+        let model = self.embedder.model_name();
+
+        // A missing model is reported as such instead of as a failed embedding
+        let model_available = match self.embedder.model_available().await {
+            Ok(available) => available,
+            Err(e) => {
+                debug!("Could not check model availability: {}", e);
+                None
+            }
+        };
+
+        let (available, latency, error) =
+            if let Some(remaining) = self.circuit_breaker.open_remaining(self.embedder.provider_name()) {
+                (false, None, Some(format!("circuit open for another {}s", remaining.as_secs())))
+            } else if model_available == Some(false) {
+                (false, None, Some(format!("model {} is not installed", model)))
+            } else {
+                let start = Instant::now();
+                match timeout(PROBE_TIMEOUT, self.embedder.generate_embedding(PROBE_TEXT)).await {
+                    Ok(Ok(_)) => (true, Some(start.elapsed()), None),
+                    Ok(Err(e)) => (false, None, Some(e.to_string())),
+                    Err(_) => (false, None, Some(format!("timed out after {:?}", PROBE_TIMEOUT))),
+                }
+            };
+
+        crate::metrics::set_provider_probe(provider, model, available, latency);
+
+        ProviderHealth {
+            name: self.name(),
+            available,
+            latency_ms: latency.map(|l| l.as_millis() as u64),
+            last_checked: Some(Utc::now()),
+            error,
+            model_available,
+        }
+    }
+
+    /// Probe on a fixed interval until shutdown
+    pub async fn run(self: Arc<Self>, mut shutdown_rx: tokio::sync::broadcast::Receiver<()>) {
+        info!("Probing embedding provider every {:?}", self.interval);
+        let mut ticker = interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    info!("Provider prober received shutdown signal");
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let health = self.probe().await;
+                    match &health.error {
+                        Some(error) => warn!(provider = %health.name, error = %error, "Provider probe failed"),
+                        None => debug!(provider = %health.name, latency_ms = ?health.latency_ms, "Provider probe succeeded"),
+                    }
+                    *self.latest.write() = Some(health);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit_breaker::CircuitBreakerConfig, embedder::EmbeddingProvider};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![0.5; 8])
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_results_are_cached() {
+        crate::metrics::Metrics::register(&prometheus::Registry::new()).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let embedder = Arc::new(Embedder::builder("probed", Box::new(CountingProvider(calls.clone()))).build());
+        let circuit_breaker = Arc::new(CircuitBreakerManager::new());
+        let prober = Arc::new(ProviderProber::new(embedder, circuit_breaker.clone(), Duration::from_millis(10)));
+
+        assert_eq!(prober.latest()[0].error.as_deref(), Some("not probed yet"));
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+        let run = tokio::spawn(prober.clone().run(shutdown_rx));
+        while prober.latest()[0].last_checked.is_none() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        shutdown_tx.send(()).unwrap();
+        run.await.unwrap();
+
+        // Reading the cached result doesn't probe again
+        let probes = calls.load(Ordering::SeqCst);
+        let health = &prober.latest()[0];
+        assert!(health.available && health.latency_ms.is_some());
+        assert_eq!(health.name, "probed (counting)");
+        let up = &crate::metrics::Metrics::get().provider_up;
+        assert_eq!(up.with_label_values(&["probed", "counting"]).get(), 1);
+        prober.latest();
+        assert_eq!(calls.load(Ordering::SeqCst), probes);
+
+        // An open circuit is reported without calling the provider
+        circuit_breaker.configure_service(
+            "probed",
+            CircuitBreakerConfig { failure_threshold: 1, ..CircuitBreakerConfig::default() },
+        );
+        circuit_breaker.record_failure("probed");
+        let health = prober.probe().await;
+        assert!(!health.available);
+        assert!(health.error.unwrap().starts_with("circuit open"));
+        assert_eq!(up.with_label_values(&["probed", "counting"]).get(), 0);
+        assert_eq!(calls.load(Ordering::SeqCst), probes);
+    }
+}
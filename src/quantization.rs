@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// How embeddings are quantized when written to the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantizationMode {
+    /// Store only the float vector
+    #[default]
+    None,
+    /// Symmetric scalar quantization to i8 with a per-vector scale factor
+    Int8,
+    /// One sign bit per dimension, packed into bytes
+    Binary,
+}
+
+impl QuantizationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuantizationMode::None => "none",
+            QuantizationMode::Int8 => "int8",
+            QuantizationMode::Binary => "binary",
+        }
+    }
+}
+
+impl FromStr for QuantizationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "" => Ok(QuantizationMode::None),
+            "int8" => Ok(QuantizationMode::Int8),
+            "binary" => Ok(QuantizationMode::Binary),
+            other => Err(format!(
+                "Unknown quantization mode '{}' (expected none, int8 or binary)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QuantizationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A quantized representation of an embedding
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantizedEmbedding {
+    Int8 { values: Vec<i8>, scale: f32 },
+    Binary { bits: Vec<u8>, dimensions: usize },
+}
+
+impl QuantizedEmbedding {
+    pub fn mode(&self) -> QuantizationMode {
+        match self {
+            QuantizedEmbedding::Int8 { .. } => QuantizationMode::Int8,
+            QuantizedEmbedding::Binary { .. } => QuantizationMode::Binary,
+        }
+    }
+
+    /// Values as stored in SurrealDB (`array<int>`)
+    pub fn stored_values(&self) -> Vec<i64> {
+        match self {
+            QuantizedEmbedding::Int8 { values, .. } => values.iter().map(|&v| v as i64).collect(),
+            QuantizedEmbedding::Binary { bits, .. } => bits.iter().map(|&b| b as i64).collect(),
+        }
+    }
+
+    /// Scale factor needed to reconstruct the float vector, if any
+    pub fn scale(&self) -> Option<f32> {
+        match self {
+            QuantizedEmbedding::Int8 { scale, .. } => Some(*scale),
+            QuantizedEmbedding::Binary { .. } => None,
+        }
+    }
+}
+
+/// Quantize an embedding with the given mode
+pub fn quantize(mode: QuantizationMode, embedding: &[f32]) -> Option<QuantizedEmbedding> {
+    match mode {
+        QuantizationMode::None => None,
+        QuantizationMode::Int8 => {
+            let (values, scale) = quantize_int8(embedding);
+            Some(QuantizedEmbedding::Int8 { values, scale })
+        }
+        QuantizationMode::Binary => Some(QuantizedEmbedding::Binary {
+            bits: quantize_binary(embedding),
+            dimensions: embedding.len(),
+        }),
+    }
+}
+
+/// Symmetric scalar quantization: `value ≈ q * scale` with `q` in [-127, 127]
+pub fn quantize_int8(embedding: &[f32]) -> (Vec<i8>, f32) {
+    let max_abs = embedding.iter().fold(0.0f32, |acc, &x| acc.max(x.abs()));
+    if max_abs == 0.0 {
+        return (vec![0; embedding.len()], 0.0);
+    }
+
+    let scale = max_abs / 127.0;
+    let values = embedding
+        .iter()
+        .map(|&x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+        .collect();
+
+    (values, scale)
+}
+
+/// Reconstruct an approximate float vector from int8 values
+pub fn dequantize_int8(values: &[i8], scale: f32) -> Vec<f32> {
+    values.iter().map(|&q| q as f32 * scale).collect()
+}
+
+/// Pack the sign of each dimension into bits (1 = positive), most significant bit first
+pub fn quantize_binary(embedding: &[f32]) -> Vec<u8> {
+    let mut bits = vec![0u8; embedding.len().div_ceil(8)];
+    for (i, &value) in embedding.iter().enumerate() {
+        if value > 0.0 {
+            bits[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    bits
+}
+
+/// Hamming distance between two packed binary embeddings
+pub fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int8_round_trip() {
+        let embedding = vec![0.5, -1.0, 0.25, 0.0];
+        let (values, scale) = quantize_int8(&embedding);
+
+        assert_eq!(values[1], -127);
+        let restored = dequantize_int8(&values, scale);
+        for (original, restored) in embedding.iter().zip(restored.iter()) {
+            assert!((original - restored).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_binary_quantization() {
+        let embedding = vec![0.1, -0.2, 0.3, -0.4, 0.5, 0.6, -0.7, 0.8, 0.9];
+        let bits = quantize_binary(&embedding);
+
+        assert_eq!(bits.len(), 2);
+        assert_eq!(bits[0], 0b1010_1101);
+        assert_eq!(bits[1], 0b1000_0000);
+        assert_eq!(hamming_distance(&bits, &bits), 0);
+    }
+
+    #[test]
+    fn test_mode_parsing() {
+        assert_eq!("int8".parse::<QuantizationMode>().unwrap(), QuantizationMode::Int8);
+        assert_eq!("BINARY".parse::<QuantizationMode>().unwrap(), QuantizationMode::Binary);
+        assert_eq!("none".parse::<QuantizationMode>().unwrap(), QuantizationMode::None);
+        assert!("fp4".parse::<QuantizationMode>().is_err());
+        assert!(quantize(QuantizationMode::None, &[0.1]).is_none());
+    }
+}
@@ -1,27 +1,176 @@
 use governor::{Quota, RateLimiter as GovernorRateLimiter};
 use governor::clock::{QuantaClock, QuantaInstant};
 use governor::state::{InMemoryState, NotKeyed};
+use reqwest::header::HeaderMap;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use tokio::time::Instant;
 use std::num::NonZeroU32;
+use tracing::{info, warn};
 use crate::error::{EmbedError, Result};
 
 type RateLimiterInstance = GovernorRateLimiter<NotKeyed, InMemoryState, QuantaClock, governor::middleware::NoOpMiddleware<QuantaInstant>>;
 
+/// Rate-limit information reported by a provider in its response headers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitHint {
+    /// Wait requested by a `Retry-After` header (usually on 429/503)
+    pub retry_after: Option<Duration>,
+    /// Request quota granted to this key, normalized to requests per minute
+    pub requests_per_minute: Option<u32>,
+    /// Requests left in the current window
+    pub remaining_requests: Option<u32>,
+    /// Time until the request window resets
+    pub reset_requests: Option<Duration>,
+    /// Token quota granted to this key, normalized to tokens per minute
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimitHint {
+    /// Parse OpenAI's `x-ratelimit-*-requests` headers (per-minute limits, reset as "1m2.5s")
+    pub fn from_openai_headers(headers: &HeaderMap) -> Option<Self> {
+        let hint = Self {
+            retry_after: parse_retry_after(headers),
+            requests_per_minute: header_u32(headers, "x-ratelimit-limit-requests"),
+            remaining_requests: header_u32(headers, "x-ratelimit-remaining-requests"),
+            reset_requests: header_str(headers, "x-ratelimit-reset-requests").and_then(parse_duration_hint),
+            tokens_per_minute: header_u32(headers, "x-ratelimit-limit-tokens"),
+        };
+        (hint != Self::default()).then_some(hint)
+    }
+
+    /// Parse Together AI's `x-ratelimit-*` headers (per-second limits, reset in seconds)
+    pub fn from_together_headers(headers: &HeaderMap) -> Option<Self> {
+        let hint = Self {
+            retry_after: parse_retry_after(headers),
+            requests_per_minute: header_u32(headers, "x-ratelimit-limit").map(|rps| rps.saturating_mul(60)),
+            remaining_requests: header_u32(headers, "x-ratelimit-remaining"),
+            reset_requests: header_str(headers, "x-ratelimit-reset").and_then(parse_duration_hint),
+            tokens_per_minute: header_u32(headers, "x-tokenlimit-limit").map(|tps| tps.saturating_mul(60)),
+        };
+        (hint != Self::default()).then_some(hint)
+    }
+
+    /// How long the provider asked us to stop sending requests, if at all
+    pub fn backoff(&self) -> Option<Duration> {
+        self.retry_after.or(match self.remaining_requests {
+            Some(0) => self.reset_requests,
+            _ => None,
+        })
+    }
+}
+
+/// Rough token estimate for rate limiting (~4 characters per token for English/code)
+pub fn estimate_tokens(text: &str) -> u32 {
+    let chars = text.chars().count() as u32;
+    chars.div_ceil(4).max(1)
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+fn header_u32(headers: &HeaderMap, name: &str) -> Option<u32> {
+    header_str(headers, name).and_then(|v| v.parse().ok())
+}
+
+/// `Retry-After` is either delta-seconds or an HTTP date
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = header_str(headers, "retry-after-ms")
+        .and_then(|ms| ms.parse::<f64>().ok())
+        .map(|ms| Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+    if value.is_some() {
+        return value;
+    }
+
+    let value = header_str(headers, "retry-after")?;
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+/// Parse durations such as "20ms", "1.5s", "6m0s" or plain seconds ("30")
+pub fn parse_duration_hint(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            'h' => amount * 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                amount / 1000.0
+            }
+            'm' => amount * 60.0,
+            's' => amount,
+            _ => return None,
+        };
+    }
+
+    number.is_empty().then(|| Duration::from_secs_f64(total))
+}
+
+/// Record an operator-configured limit (0 removes it)
+async fn set_cap(caps: &RwLock<HashMap<String, u32>>, provider: &str, limit: u32) {
+    let mut caps = caps.write().await;
+    if limit == 0 {
+        caps.remove(provider);
+    } else {
+        caps.insert(provider.to_string(), limit);
+    }
+}
+
+/// Per-provider token limiters with the tokens-per-minute quota each was built for
+type TokenLimiters = HashMap<String, (Arc<RateLimiterInstance>, u32)>;
+
 pub struct RateLimiterManager {
     limiters: Arc<RwLock<HashMap<String, Arc<RateLimiterInstance>>>>,
+    quotas: Arc<RwLock<HashMap<String, u32>>>,
+    token_limiters: Arc<RwLock<TokenLimiters>>,
+    /// Operator-configured limits, which provider hints may tighten but never exceed
+    request_caps: Arc<RwLock<HashMap<String, u32>>>,
+    token_caps: Arc<RwLock<HashMap<String, u32>>>,
+    concurrency: Arc<RwLock<HashMap<String, Arc<Semaphore>>>>,
+    blocked_until: Arc<RwLock<HashMap<String, Instant>>>,
 }
 
 impl RateLimiterManager {
     pub fn new() -> Self {
         Self {
             limiters: Arc::new(RwLock::new(HashMap::new())),
+            quotas: Arc::new(RwLock::new(HashMap::new())),
+            token_limiters: Arc::new(RwLock::new(HashMap::new())),
+            request_caps: Arc::new(RwLock::new(HashMap::new())),
+            token_caps: Arc::new(RwLock::new(HashMap::new())),
+            concurrency: Arc::new(RwLock::new(HashMap::new())),
+            blocked_until: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
+    /// Limit the number of requests sent to a provider per minute (0 removes the limit)
     pub async fn configure_provider(&self, provider: &str, requests_per_minute: u32) -> Result<()> {
+        set_cap(&self.request_caps, provider, requests_per_minute).await;
+        self.set_request_quota(provider, requests_per_minute).await
+    }
+
+    async fn set_request_quota(&self, provider: &str, requests_per_minute: u32) -> Result<()> {
         if requests_per_minute == 0 {
+            self.limiters.write().await.remove(provider);
+            self.quotas.write().await.remove(provider);
             return Ok(());
         }
         
@@ -30,11 +179,124 @@ impl RateLimiterManager {
         
         let mut limiters = self.limiters.write().await;
         limiters.insert(provider.to_string(), limiter);
+        self.quotas.write().await.insert(provider.to_string(), requests_per_minute);
         
         Ok(())
     }
+
+    /// Limit the number of tokens sent to a provider per minute (0 removes the limit)
+    pub async fn configure_provider_tokens(&self, provider: &str, tokens_per_minute: u32) -> Result<()> {
+        set_cap(&self.token_caps, provider, tokens_per_minute).await;
+        self.set_token_quota(provider, tokens_per_minute).await
+    }
+
+    async fn set_token_quota(&self, provider: &str, tokens_per_minute: u32) -> Result<()> {
+        let mut token_limiters = self.token_limiters.write().await;
+        match NonZeroU32::new(tokens_per_minute) {
+            Some(tpm) => {
+                let limiter = Arc::new(GovernorRateLimiter::direct(Quota::per_minute(tpm)));
+                token_limiters.insert(provider.to_string(), (limiter, tokens_per_minute));
+            }
+            None => {
+                token_limiters.remove(provider);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cap the number of requests in flight to a provider at once (0 removes the cap)
+    pub async fn configure_provider_concurrency(&self, provider: &str, max_in_flight: usize) -> Result<()> {
+        let mut concurrency = self.concurrency.write().await;
+        if max_in_flight == 0 {
+            concurrency.remove(provider);
+        } else {
+            concurrency.insert(provider.to_string(), Arc::new(Semaphore::new(max_in_flight)));
+        }
+
+        Ok(())
+    }
+
+    /// Wait for an in-flight slot; the request may proceed while the returned permit is held.
+    /// Returns `None` when no concurrency cap is configured for the provider.
+    pub async fn acquire_slot(&self, provider: &str) -> Result<Option<OwnedSemaphorePermit>> {
+        let semaphore = self.concurrency.read().await.get(provider).cloned();
+
+        match semaphore {
+            Some(semaphore) => semaphore
+                .acquire_owned()
+                .await
+                .map(Some)
+                .map_err(|e| EmbedError::Internal(anyhow::anyhow!("Concurrency limiter closed for {}: {}", provider, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Currently enforced tokens-per-minute quota for a provider
+    pub async fn tokens_per_minute(&self, provider: &str) -> Option<u32> {
+        self.token_limiters.read().await.get(provider).map(|(_, tpm)| *tpm)
+    }
+
+    /// Currently enforced requests-per-minute quota for a provider
+    pub async fn requests_per_minute(&self, provider: &str) -> Option<u32> {
+        self.quotas.read().await.get(provider).copied()
+    }
+
+    /// Tune the limiter from what the provider reported: adopt its quota, never above what was
+    /// configured, and honour any requested backoff
+    pub async fn apply_hint(&self, provider: &str, hint: &RateLimitHint) -> Result<()> {
+        if let Some(backoff) = hint.backoff().filter(|d| !d.is_zero()) {
+            let until = Instant::now() + backoff;
+            let mut blocked = self.blocked_until.write().await;
+            let entry = blocked.entry(provider.to_string()).or_insert(until);
+            if *entry < until {
+                *entry = until;
+            }
+            warn!("Provider {} asked to back off for {:?}", provider, backoff);
+        }
+
+        if let Some(reported) = hint.requests_per_minute.filter(|&rpm| rpm > 0) {
+            let rpm = match self.request_caps.read().await.get(provider) {
+                Some(&cap) => cap.min(reported),
+                None => reported,
+            };
+            if self.requests_per_minute(provider).await != Some(rpm) {
+                info!("Adjusting rate limit for {} to {} requests/minute as reported by the provider", provider, rpm);
+                self.set_request_quota(provider, rpm).await?;
+            }
+        }
+
+        // Token limits only apply where one was configured; unlimited providers stay unlimited
+        if let Some(reported) = hint.tokens_per_minute.filter(|&tpm| tpm > 0) {
+            let cap = self.token_caps.read().await.get(provider).copied();
+            if let Some(tpm) = cap.map(|cap| cap.min(reported)) {
+                if self.tokens_per_minute(provider).await != Some(tpm) {
+                    info!("Adjusting token limit for {} to {} tokens/minute as reported by the provider", provider, tpm);
+                    self.set_token_quota(provider, tpm).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remaining provider-requested backoff, if any
+    pub async fn blocked_for(&self, provider: &str) -> Option<Duration> {
+        let blocked = self.blocked_until.read().await;
+        blocked
+            .get(provider)
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
     
     pub async fn check_rate_limit(&self, provider: &str) -> Result<()> {
+        if self.blocked_for(provider).await.is_some() {
+            crate::metrics::record_rate_limit(provider);
+            return Err(EmbedError::RateLimitExceeded {
+                provider: provider.to_string(),
+            });
+        }
+
         let limiters = self.limiters.read().await;
         
         if let Some(limiter) = limiters.get(provider) {
@@ -54,15 +316,36 @@ impl RateLimiterManager {
     }
     
     pub async fn wait_for_permit(&self, provider: &str) -> Result<()> {
-        let limiters = self.limiters.read().await;
+        if let Some(remaining) = self.blocked_for(provider).await {
+            tokio::time::sleep(remaining).await;
+        }
+
+        // Clone the limiter so a quota update from `apply_hint` isn't blocked while we wait
+        let limiter = self.limiters.read().await.get(provider).cloned();
         
-        if let Some(limiter) = limiters.get(provider) {
+        if let Some(limiter) = limiter {
             limiter.until_ready().await;
             Ok(())
         } else {
             Ok(())
         }
     }
+
+    /// Wait until `tokens` fit in the provider's tokens-per-minute budget
+    pub async fn wait_for_tokens(&self, provider: &str, tokens: u32) -> Result<()> {
+        let limiter = self.token_limiters.read().await.get(provider).cloned();
+
+        if let Some((limiter, tokens_per_minute)) = limiter {
+            // A single text larger than the whole budget can only wait for a full bucket
+            let cells = NonZeroU32::new(tokens.clamp(1, tokens_per_minute)).unwrap();
+            limiter
+                .until_n_ready(cells)
+                .await
+                .map_err(|e| EmbedError::Internal(anyhow::anyhow!("Token limit error for {}: {}", provider, e)))?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for RateLimiterManager {
@@ -100,4 +383,123 @@ mod tests {
         // Third request should fail (rate limit exceeded)
         assert!(limiter.check().is_err());
     }
+
+    #[test]
+    fn test_parse_duration_hint() {
+        assert_eq!(parse_duration_hint("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration_hint("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration_hint("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration_hint("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_duration_hint("1h2m"), Some(Duration::from_secs(3720)));
+        assert_eq!(parse_duration_hint("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_provider_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit-requests", "5000".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-requests", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset-requests", "12ms".parse().unwrap());
+
+        let hint = RateLimitHint::from_openai_headers(&headers).unwrap();
+        assert_eq!(hint.requests_per_minute, Some(5000));
+        assert_eq!(hint.backoff(), Some(Duration::from_millis(12)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", "10".parse().unwrap());
+        headers.insert("retry-after", "2".parse().unwrap());
+
+        let hint = RateLimitHint::from_together_headers(&headers).unwrap();
+        assert_eq!(hint.requests_per_minute, Some(600));
+        assert_eq!(hint.backoff(), Some(Duration::from_secs(2)));
+
+        assert!(RateLimitHint::from_openai_headers(&HeaderMap::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_token_limiter() {
+        assert_eq!(estimate_tokens(""), 1);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("abcdefghi"), 3);
+
+        let manager = RateLimiterManager::new();
+
+        // Unconfigured providers are not limited
+        manager.wait_for_tokens("test", 1_000_000).await.unwrap();
+
+        manager.configure_provider_tokens("test", 100).await.unwrap();
+        assert_eq!(manager.tokens_per_minute("test").await, Some(100));
+
+        // Oversized requests are clamped to the bucket size instead of failing
+        manager.wait_for_tokens("test", 500).await.unwrap();
+
+        let (limiter, _) = manager.token_limiters.read().await.get("test").cloned().unwrap();
+        assert!(limiter.check().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let manager = RateLimiterManager::new();
+        assert!(manager.acquire_slot("test").await.unwrap().is_none());
+
+        manager.configure_provider_concurrency("test", 1).await.unwrap();
+        let permit = manager.acquire_slot("test").await.unwrap();
+        assert!(permit.is_some());
+
+        // The second request has to wait until the first slot is released
+        let blocked = tokio::time::timeout(Duration::from_millis(50), manager.acquire_slot("test")).await;
+        assert!(blocked.is_err());
+
+        drop(permit);
+        let next = tokio::time::timeout(Duration::from_millis(50), manager.acquire_slot("test")).await;
+        assert!(next.unwrap().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_apply_hint() {
+        let manager = RateLimiterManager::new();
+        manager.configure_provider("test", 1000).await.unwrap();
+
+        let hint = RateLimitHint {
+            requests_per_minute: Some(3000),
+            retry_after: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        manager.apply_hint("test", &hint).await.unwrap();
+
+        // A higher reported quota doesn't lift the configured limit
+        assert_eq!(manager.requests_per_minute("test").await, Some(1000));
+        assert!(manager.blocked_for("test").await.is_some());
+
+        let start = std::time::Instant::now();
+        manager.wait_for_permit("test").await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_apply_hint_never_exceeds_configured_limits() {
+        let manager = RateLimiterManager::new();
+        manager.configure_provider("test", 1000).await.unwrap();
+        manager.configure_provider_tokens("test", 10_000).await.unwrap();
+
+        let hint = |rpm, tpm| RateLimitHint {
+            requests_per_minute: Some(rpm),
+            tokens_per_minute: Some(tpm),
+            ..Default::default()
+        };
+
+        manager.apply_hint("test", &hint(500, 4_000)).await.unwrap();
+        assert_eq!(manager.requests_per_minute("test").await, Some(500));
+        assert_eq!(manager.tokens_per_minute("test").await, Some(4_000));
+
+        // Once the provider grants more again, the limits return to the configured caps
+        manager.apply_hint("test", &hint(5000, 40_000)).await.unwrap();
+        assert_eq!(manager.requests_per_minute("test").await, Some(1000));
+        assert_eq!(manager.tokens_per_minute("test").await, Some(10_000));
+
+        // Without a configured limit the reported request quota is adopted, but tokens stay unlimited
+        manager.apply_hint("other", &hint(300, 4_000)).await.unwrap();
+        assert_eq!(manager.requests_per_minute("other").await, Some(300));
+        assert_eq!(manager.tokens_per_minute("other").await, None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use crate::embedder::EmbeddingProvider;
+use crate::rate_limiter::RateLimitHint;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, str::FromStr};
+use tracing::warn;
+
+/// What [`RecordingProvider`] does with the recordings directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// Call the provider and save every request and response
+    Record,
+    /// Answer from saved responses only; the provider is never called
+    Replay,
+}
+
+impl FromStr for RecordingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "record" => Ok(RecordingMode::Record),
+            "replay" => Ok(RecordingMode::Replay),
+            other => Err(format!("Unknown provider recording mode '{}'. Expected record or replay", other)),
+        }
+    }
+}
+
+/// One provider request and its outcome, stored as `<hash>.json`
+#[derive(Debug, Serialize, Deserialize)]
+struct Recording {
+    model: String,
+    text: String,
+    embedding: Option<Vec<f32>>,
+    prompt_tokens: Option<u64>,
+    /// The provider's error, replayed as a failure
+    error: Option<String>,
+    recorded_at: DateTime<Utc>,
+}
+
+/// Saves provider responses to disk and serves them back, so CI runs are deterministic and a
+/// validation failure can be debugged offline from exactly the vector the provider returned,
+/// without paying for it again. Recordings are keyed by model and the text as sent, after
+/// truncation and chunking.
+pub struct RecordingProvider {
+    /// `None` when replaying
+    inner: Option<Box<dyn EmbeddingProvider>>,
+    model: String,
+    dir: PathBuf,
+}
+
+impl RecordingProvider {
+    /// Pass requests to `inner`, saving each response in `dir`
+    pub fn record(inner: Box<dyn EmbeddingProvider>, dir: PathBuf) -> Self {
+        Self {
+            model: inner.model_name().to_string(),
+            inner: Some(inner),
+            dir,
+        }
+    }
+
+    /// Answer requests for `model` from the recordings in `dir`; texts never recorded fail
+    pub fn replay(model: String, dir: PathBuf) -> Self {
+        Self { inner: None, model, dir }
+    }
+
+    fn path_for(&self, text: &str) -> PathBuf {
+        let key = fnv1a(self.model.as_bytes()) ^ fnv1a(text.as_bytes()).rotate_left(1);
+        self.dir.join(format!("{:016x}.json", key))
+    }
+
+    fn save(&self, text: &str, outcome: &Result<(Vec<f32>, Option<u64>)>) -> Result<()> {
+        let (embedding, prompt_tokens, error) = match outcome {
+            Ok((embedding, tokens)) => (Some(embedding.clone()), *tokens, None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+        let recording = Recording {
+            model: self.model.clone(),
+            text: text.to_string(),
+            embedding,
+            prompt_tokens,
+            error,
+            recorded_at: Utc::now(),
+        };
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path_for(text);
+        fs::write(&path, serde_json::to_vec_pretty(&recording)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn load(&self, text: &str) -> Result<(Vec<f32>, Option<u64>)> {
+        let path = self.path_for(text);
+        let recording: Recording = fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?))
+            .with_context(|| format!("No recorded response for this text ({})", path.display()))?;
+        // Guard against hash collisions
+        if recording.model != self.model || recording.text != text {
+            anyhow::bail!("No recorded response for this text ({} holds another request)", path.display());
+        }
+        match (recording.embedding, recording.error) {
+            (Some(embedding), _) => Ok((embedding, recording.prompt_tokens)),
+            (None, error) => Err(anyhow::anyhow!(error.unwrap_or_else(|| "Recorded request failed".to_string()))),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RecordingProvider {
+    async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.generate_embedding_with_usage(text).await?.0)
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    async fn generate_embedding_with_usage(&self, text: &str) -> Result<(Vec<f32>, Option<u64>)> {
+        let Some(inner) = &self.inner else {
+            return self.load(text);
+        };
+        let outcome = inner.generate_embedding_with_usage(text).await;
+        if let Err(e) = self.save(text, &outcome) {
+            warn!("Failed to record provider response: {:#}", e);
+        }
+        outcome
+    }
+
+    async fn preload(&self) -> Result<()> {
+        match &self.inner {
+            Some(inner) => inner.preload().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn model_available(&self) -> Result<Option<bool>> {
+        match &self.inner {
+            Some(inner) => inner.model_available().await,
+            None => Ok(None),
+        }
+    }
+
+    async fn pull_model(&self) -> Result<()> {
+        match &self.inner {
+            Some(inner) => inner.pull_model().await,
+            None => Err(anyhow::anyhow!("Models can't be pulled while replaying recorded responses")),
+        }
+    }
+
+    fn take_rate_limit_hint(&self) -> Option<RateLimitHint> {
+        self.inner.as_ref().and_then(|inner| inner.take_rate_limit_hint())
+    }
+}
+
+/// Stable across platforms and Rust versions, unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingProvider(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            if text.is_empty() {
+                anyhow::bail!("empty input");
+            }
+            Ok(vec![0.6, 0.8])
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_responses_replay_without_the_provider() {
+        let dir = std::env::temp_dir().join(format!("embed_star_recordings_{}", uuid::Uuid::new_v4()));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let recorder = RecordingProvider::record(Box::new(CountingProvider(calls.clone())), dir.clone());
+        assert_eq!(recorder.generate_embedding("rust-lang/rust").await.unwrap(), vec![0.6, 0.8]);
+        assert!(recorder.generate_embedding("").await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        let replayer = RecordingProvider::replay("counting".to_string(), dir.clone());
+        assert_eq!(replayer.generate_embedding("rust-lang/rust").await.unwrap(), vec![0.6, 0.8]);
+        let error = replayer.generate_embedding("").await.unwrap_err();
+        assert_eq!(error.to_string(), "empty input");
+        assert!(replayer.generate_embedding("tokio-rs/tokio").await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // Another model's recordings don't answer
+        let other = RecordingProvider::replay("other".to_string(), dir.clone());
+        assert!(other.generate_embedding("rust-lang/rust").await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    config::Config, config_file, pipeline::PipelineState, rate_limiter::RateLimiterManager,
+    telemetry,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tracing::{error, info};
+
+/// Settings that can change without a restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tunables {
+    /// Batch processor workers; the pool spawns or drains workers to match
+    pub parallel_workers: usize,
+    pub batch_size: usize,
+    pub batch_delay_ms: u64,
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+    pub provider_max_in_flight: Option<usize>,
+    pub log_filter: Option<String>,
+}
+
+impl Tunables {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            parallel_workers: config.parallel_workers,
+            batch_size: config.batch_size,
+            batch_delay_ms: config.batch_delay_ms,
+            requests_per_minute: config.requests_per_minute(),
+            tokens_per_minute: config.tokens_per_minute,
+            provider_max_in_flight: config.provider_max_in_flight,
+            log_filter: config.log_filter.clone(),
+        }
+    }
+}
+
+/// Re-reads the configuration and pushes the reloadable settings to the rate limiter, the
+/// log filter and (through a watch channel) the workers
+pub struct ConfigReloader {
+    tunables: watch::Sender<Tunables>,
+    rate_limiter: Arc<RateLimiterManager>,
+    /// Key the rate limiter uses for the provider (the model name, as in process_batch)
+    provider_key: String,
+    pipeline: Arc<PipelineState>,
+    /// Serializes reloads from SIGHUP and the admin API
+    reloading: Mutex<()>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        initial: Tunables,
+        rate_limiter: Arc<RateLimiterManager>,
+        provider_key: String,
+        pipeline: Arc<PipelineState>,
+    ) -> Self {
+        let (tunables, _) = watch::channel(initial);
+        Self {
+            tunables,
+            rate_limiter,
+            provider_key,
+            pipeline,
+            reloading: Mutex::new(()),
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Tunables> {
+        self.tunables.subscribe()
+    }
+
+    pub fn current(&self) -> Tunables {
+        self.tunables.borrow().clone()
+    }
+
+    /// Re-read the config file and environment and apply the reloadable settings.
+    /// Other settings in the file are validated but only take effect after a restart.
+    pub async fn reload(&self) -> anyhow::Result<Tunables> {
+        let config = config_file::reload_config().await?;
+        config.validate()?;
+        self.apply(Tunables::from_config(&config)).await
+    }
+
+    /// Change only the worker count, keeping the other settings
+    pub async fn set_workers(&self, workers: usize) -> anyhow::Result<Tunables> {
+        let next = Tunables {
+            parallel_workers: workers,
+            ..self.current()
+        };
+        self.apply(next).await
+    }
+
+    pub async fn apply(&self, next: Tunables) -> anyhow::Result<Tunables> {
+        if next.parallel_workers == 0 {
+            anyhow::bail!("Parallel workers must be greater than 0");
+        }
+        let _reloading = self.reloading.lock().await;
+        let previous = self.current();
+        let provider = self.provider_key.as_str();
+
+        if next.log_filter != previous.log_filter {
+            telemetry::set_log_filter(next.log_filter.as_deref())?;
+        }
+        if next.requests_per_minute != previous.requests_per_minute {
+            self.rate_limiter
+                .configure_provider(provider, next.requests_per_minute.unwrap_or(0))
+                .await?;
+        }
+        if next.tokens_per_minute != previous.tokens_per_minute {
+            self.rate_limiter
+                .configure_provider_tokens(provider, next.tokens_per_minute.unwrap_or(0))
+                .await?;
+        }
+        if next.provider_max_in_flight != previous.provider_max_in_flight {
+            self.rate_limiter
+                .configure_provider_concurrency(provider, next.provider_max_in_flight.unwrap_or(0))
+                .await?;
+        }
+        self.pipeline.set_batch_settings(next.batch_size, next.batch_delay_ms);
+
+        if next != previous {
+            info!(?previous, current = ?next, "Configuration reloaded");
+        } else {
+            info!("Configuration reloaded, no reloadable settings changed");
+        }
+        self.tunables.send_replace(next.clone());
+        Ok(next)
+    }
+}
+
+/// Reload the configuration on SIGHUP until shutdown
+pub async fn reload_on_sighup(
+    reloader: Arc<ConfigReloader>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP, reloading configuration");
+                    if let Err(e) = reloader.reload().await {
+                        error!("Configuration reload failed, keeping current settings: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = reloader;
+        let _ = shutdown_rx.recv().await;
+    }
+}
+
+/// Add a worker on SIGUSR1 and remove one on SIGUSR2 until shutdown, never going below one
+pub async fn resize_workers_on_signals(
+    reloader: Arc<ConfigReloader>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (mut grow, mut shrink) = match (signal(SignalKind::user_defined1()), signal(SignalKind::user_defined2())) {
+            (Ok(grow), Ok(shrink)) => (grow, shrink),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to install SIGUSR1/SIGUSR2 handlers: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            let workers = tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = grow.recv() => reloader.current().parallel_workers + 1,
+                _ = shrink.recv() => {
+                    let current = reloader.current().parallel_workers;
+                    if current <= 1 {
+                        info!("Received SIGUSR2, keeping the last worker");
+                        continue;
+                    }
+                    current - 1
+                }
+            };
+            info!(workers, "Received worker resize signal");
+            if let Err(e) = reloader.set_workers(workers).await {
+                error!("Failed to resize workers: {}", e);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = reloader;
+        let _ = shutdown_rx.recv().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_updates_limits_and_notifies_workers() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(8);
+        let pipeline = Arc::new(PipelineState::new(&tx, 1, 10, 100));
+        let rate_limiter = Arc::new(RateLimiterManager::new());
+        let initial = Tunables {
+            parallel_workers: 1,
+            batch_size: 10,
+            batch_delay_ms: 100,
+            requests_per_minute: Some(60),
+            tokens_per_minute: None,
+            provider_max_in_flight: None,
+            log_filter: None,
+        };
+        rate_limiter.configure_provider("model", 60).await.unwrap();
+        let reloader = ConfigReloader::new(
+            initial.clone(),
+            rate_limiter.clone(),
+            "model".to_string(),
+            pipeline.clone(),
+        );
+        let mut workers = reloader.subscribe();
+
+        reloader
+            .apply(Tunables {
+                batch_size: 25,
+                requests_per_minute: None,
+                tokens_per_minute: Some(5000),
+                ..initial
+            })
+            .await
+            .unwrap();
+
+        assert!(workers.has_changed().unwrap());
+        assert_eq!(workers.borrow_and_update().batch_size, 25);
+        assert_eq!(rate_limiter.requests_per_minute("model").await, None);
+        assert_eq!(rate_limiter.tokens_per_minute("model").await, Some(5000));
+        assert_eq!(pipeline.status().batch_size, 25);
+
+        assert!(reloader.set_workers(0).await.is_err());
+        assert_eq!(reloader.set_workers(4).await.unwrap().parallel_workers, 4);
+        assert_eq!(workers.borrow_and_update().batch_size, 25);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_worker_resize_signals() {
+        use std::time::Duration;
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // Registered before anything is raised, so the signals never take their default action
+        // (terminating the test process)
+        let _grow = signal(SignalKind::user_defined1()).unwrap();
+        let _shrink = signal(SignalKind::user_defined2()).unwrap();
+        let raise = |name: &str| {
+            let status = std::process::Command::new("kill")
+                .args([format!("-{}", name), std::process::id().to_string()])
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(8);
+        let pipeline = Arc::new(PipelineState::new(&tx, 1, 10, 100));
+        let initial = Tunables {
+            parallel_workers: 1,
+            batch_size: 10,
+            batch_delay_ms: 100,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            provider_max_in_flight: None,
+            log_filter: None,
+        };
+        let reloader = Arc::new(ConfigReloader::new(
+            initial,
+            Arc::new(RateLimiterManager::new()),
+            "model".to_string(),
+            pipeline,
+        ));
+        let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+        let resizer = tokio::spawn(resize_workers_on_signals(reloader.clone(), shutdown_rx));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let workers_become = |expected: usize| {
+            let reloader = reloader.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    while reloader.current().parallel_workers != expected {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .unwrap_or_else(|_| panic!("workers never became {}", expected));
+            }
+        };
+
+        raise("USR1");
+        workers_become(2).await;
+
+        raise("USR2");
+        workers_become(1).await;
+
+        // The last worker stays
+        raise("USR2");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(reloader.current().parallel_workers, 1);
+
+        shutdown_tx.send(()).unwrap();
+        resizer.await.unwrap();
+    }
+}
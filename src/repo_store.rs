@@ -0,0 +1,113 @@
+use crate::{
+    audit::{AuditRecord, BatchRun},
+    error::Result,
+    models::{LiveQueryNotification, Repo},
+    stats::CoverageStats,
+    surreal_client::{BatchUpdateResult, EmbeddingUpdate, SurrealClient},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+use tokio::sync::mpsc;
+
+/// Where the service finds repos that need embeddings
+#[async_trait]
+pub trait RepoSource: Send + Sync {
+    /// Up to `limit` repos without a current embedding
+    async fn fetch_pending(&self, limit: usize) -> Result<Vec<Repo>>;
+
+    /// Repos that need embedding as they appear, until the receiver is dropped
+    async fn watch(&self) -> Result<mpsc::Receiver<Repo>>;
+
+    async fn pending_count(&self) -> Result<usize>;
+
+    /// `updated_at` of the repo that has waited longest, for the freshness metrics
+    async fn oldest_pending(&self) -> Result<Option<DateTime<Utc>>> {
+        Ok(None)
+    }
+
+    /// Coverage breakdown for the periodic report; `None` when the source can't provide one
+    async fn coverage(&self) -> Result<Option<CoverageStats>> {
+        Ok(None)
+    }
+
+    /// Repos deleted or made private, as they happen, until the receiver is dropped; `None`
+    /// when the source can't report them
+    async fn watch_removals(&self) -> Result<Option<mpsc::Receiver<LiveQueryNotification>>> {
+        Ok(None)
+    }
+}
+
+/// Where the service writes generated embeddings
+#[async_trait]
+pub trait EmbeddingSink: Send + Sync {
+    async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult>;
+
+    /// Called as soon as an embedding is generated, before the batch is written; sinks
+    /// without a write-ahead log ignore it
+    async fn record_generated(&self, _update: &EmbeddingUpdate) -> Result<()> {
+        Ok(())
+    }
+
+    /// Persist the per-repo audit trail of a batch; sinks without an audit log ignore it
+    async fn write_audit_records(&self, _records: Vec<AuditRecord>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Persist a batch's summary; sinks without an audit log ignore it
+    async fn write_batch_run(&self, _run: &BatchRun) -> Result<()> {
+        Ok(())
+    }
+
+    /// Drop the embeddings of repos that were deleted or made private. External vector stores
+    /// implement this, as does the SurrealDB client for its `repo_embedding` rows.
+    async fn remove_embeddings(&self, _ids: Vec<RecordId>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RepoSource for SurrealClient {
+    async fn fetch_pending(&self, limit: usize) -> Result<Vec<Repo>> {
+        self.get_repos_needing_embeddings(limit).await
+    }
+
+    async fn watch(&self) -> Result<mpsc::Receiver<Repo>> {
+        self.setup_live_query().await
+    }
+
+    async fn pending_count(&self) -> Result<usize> {
+        self.get_pending_repos_count().await
+    }
+
+    async fn oldest_pending(&self) -> Result<Option<DateTime<Utc>>> {
+        self.get_oldest_pending_updated_at().await
+    }
+
+    async fn coverage(&self) -> Result<Option<CoverageStats>> {
+        Ok(Some(self.get_coverage_stats().await?))
+    }
+
+    async fn watch_removals(&self) -> Result<Option<mpsc::Receiver<LiveQueryNotification>>> {
+        Ok(Some(self.watch_repo_removals().await?))
+    }
+}
+
+#[async_trait]
+impl EmbeddingSink for SurrealClient {
+    async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+        self.batch_update_embeddings(updates).await
+    }
+
+    async fn write_audit_records(&self, records: Vec<AuditRecord>) -> Result<()> {
+        SurrealClient::write_audit_records(self, records).await
+    }
+
+    async fn write_batch_run(&self, run: &BatchRun) -> Result<()> {
+        SurrealClient::write_batch_run(self, run).await
+    }
+
+    async fn remove_embeddings(&self, ids: Vec<RecordId>) -> Result<()> {
+        self.delete_table_embeddings(&ids).await
+    }
+}
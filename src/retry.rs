@@ -1,12 +1,43 @@
+use governor::{clock::DefaultClock, state::{InMemoryState, NotKeyed}, Quota, RateLimiter};
+use rand::Rng;
+use std::num::NonZeroU32;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, warn};
+use crate::config::Config;
 use crate::error::{EmbedError, Result};
 
+/// Service-wide cap on retries per minute, shared by every worker
+pub struct RetryBudget {
+    limiter: RateLimiter<NotKeyed, InMemoryState, DefaultClock>,
+}
+
+impl RetryBudget {
+    pub fn per_minute(retries: NonZeroU32) -> Self {
+        Self {
+            limiter: RateLimiter::direct(Quota::per_minute(retries)),
+        }
+    }
+
+    /// Take one retry from the budget; false once it's spent for now
+    pub fn try_acquire(&self) -> bool {
+        self.limiter.check().is_ok()
+    }
+}
+
+#[derive(Clone)]
 pub struct RetryConfig {
     pub max_retries: u32,
     pub initial_interval: Duration,
     pub max_interval: Duration,
     pub multiplier: f64,
+    /// Sleep a random duration up to the backoff interval ("full jitter") so workers
+    /// that failed together don't retry together
+    pub jitter: bool,
+    pub budget: Option<Arc<RetryBudget>>,
+    /// Hard deadline for one repo's embedding, retries included, so a text that makes the
+    /// provider hang can't stall the rest of its batch
+    pub record_timeout: Option<Duration>,
 }
 
 impl Default for RetryConfig {
@@ -16,10 +47,41 @@ impl Default for RetryConfig {
             initial_interval: Duration::from_millis(100),
             max_interval: Duration::from_secs(10),
             multiplier: 2.0,
+            jitter: true,
+            budget: None,
+            record_timeout: None,
         }
     }
 }
 
+impl RetryConfig {
+    /// `RETRY_ATTEMPTS` counts the first try, so it allows one fewer retry.
+    /// Build once and share it so workers draw from the same retry budget.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_retries: config.retry_attempts.saturating_sub(1),
+            initial_interval: Duration::from_millis(config.retry_delay_ms),
+            budget: config
+                .retry_budget_per_minute
+                .and_then(NonZeroU32::new)
+                .map(|retries| Arc::new(RetryBudget::per_minute(retries))),
+            record_timeout: (config.record_timeout_secs > 0)
+                .then(|| Duration::from_secs(config.record_timeout_secs)),
+            ..Default::default()
+        }
+    }
+
+    fn delay(&self, interval: Duration) -> Duration {
+        if self.jitter && !interval.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=interval)
+        } else {
+            interval
+        }
+    }
+}
+
+/// The single retry layer for provider calls. `operation_name` is used as a metric label,
+/// so keep it low-cardinality (no repo names).
 pub async fn with_retry<F, Fut, T>(
     operation_name: &str,
     config: &RetryConfig,
@@ -31,10 +93,12 @@ where
 {
     use backoff::{backoff::Backoff, ExponentialBackoff};
     
+    // Jitter is applied on top, so the base intervals are deterministic
     let mut backoff = ExponentialBackoff {
         initial_interval: config.initial_interval,
         max_interval: config.max_interval,
         multiplier: config.multiplier,
+        randomization_factor: 0.0,
         max_elapsed_time: None,
         ..Default::default()
     };
@@ -63,10 +127,22 @@ where
                     return Err(error);
                 }
                 
+                if let Some(budget) = &config.budget {
+                    if !budget.try_acquire() {
+                        warn!(
+                            "Operation '{}' failed and the retry budget is exhausted, not retrying: {:?}",
+                            operation_name, error
+                        );
+                        crate::metrics::record_retry_budget_exhausted(operation_name);
+                        return Err(error);
+                    }
+                }
+                
                 retry_count += 1;
                 last_error = Some(error);
+                crate::metrics::record_retry(operation_name);
                 
-                if let Some(duration) = backoff.next_backoff() {
+                if let Some(duration) = backoff.next_backoff().map(|interval| config.delay(interval)) {
                     warn!(
                         "Operation '{}' failed (attempt {}/{}), retrying in {:?}",
                         operation_name, retry_count, config.max_retries, duration
@@ -91,7 +167,6 @@ where
 mod tests {
     use super::*;
     use std::sync::atomic::{AtomicU32, Ordering};
-    use std::sync::Arc;
     
     #[tokio::test]
     async fn test_retry_success_after_failures() {
@@ -103,6 +178,7 @@ mod tests {
             initial_interval: Duration::from_millis(10),
             max_interval: Duration::from_millis(100),
             multiplier: 2.0,
+            ..Default::default()
         };
         
         let result = with_retry("test_operation", &config, || {
@@ -141,4 +217,51 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(attempts.load(Ordering::SeqCst), 1); // Should not retry
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_from_config_counts_first_attempt() {
+        use clap::Parser;
+
+        let config = Config::parse_from(["embed_star", "--retry-attempts", "3", "--retry-delay-ms", "250"]);
+        let retry_config = RetryConfig::from_config(&config);
+        assert_eq!(retry_config.max_retries, 2);
+        assert_eq!(retry_config.initial_interval, Duration::from_millis(250));
+    }
+    
+    #[test]
+    fn test_full_jitter_stays_within_interval() {
+        let config = RetryConfig::default();
+        let interval = Duration::from_millis(200);
+        for _ in 0..100 {
+            assert!(config.delay(interval) <= interval);
+        }
+
+        let config = RetryConfig { jitter: false, ..Default::default() };
+        assert_eq!(config.delay(interval), interval);
+    }
+    
+    #[tokio::test]
+    async fn test_retry_budget_stops_retries() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let attempts_clone = attempts.clone();
+        
+        let config = RetryConfig {
+            initial_interval: Duration::from_millis(1),
+            budget: Some(Arc::new(RetryBudget::per_minute(NonZeroU32::new(1).unwrap()))),
+            ..Default::default()
+        };
+        
+        let result: Result<()> = with_retry("test_operation", &config, || {
+            let attempts = attempts_clone.clone();
+            async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(EmbedError::ServiceUnavailable("test error".to_string()))
+            }
+        })
+        .await;
+        
+        assert!(result.is_err());
+        // One retry from the budget, then it's spent
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
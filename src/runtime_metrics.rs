@@ -0,0 +1,74 @@
+use crate::{metrics, pipeline::PipelineState};
+use std::sync::Arc;
+use tokio::{
+    runtime::Handle,
+    time::{interval, Duration},
+};
+
+/// Export tokio runtime statistics and the processing channel depth
+pub async fn monitor_runtime_metrics(
+    pipeline: Arc<PipelineState>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    let mut interval = interval(Duration::from_secs(15));
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                break;
+            }
+            _ = interval.tick() => {
+                report_runtime_metrics(&pipeline);
+            }
+        }
+    }
+}
+
+fn report_runtime_metrics(pipeline: &PipelineState) {
+    let runtime = Handle::current().metrics();
+    metrics::set_runtime_stats(
+        runtime.num_workers(),
+        runtime.num_alive_tasks(),
+        runtime.global_queue_depth(),
+    );
+
+    // Per-worker poll statistics are only available with RUSTFLAGS="--cfg tokio_unstable"
+    #[cfg(tokio_unstable)]
+    for worker in 0..runtime.num_workers() {
+        metrics::set_runtime_worker_stats(
+            worker,
+            runtime.worker_total_busy_duration(worker),
+            runtime.worker_poll_count(worker),
+        );
+    }
+
+    metrics::set_queue_depth(pipeline.queue_depth());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::{broadcast, mpsc};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+    async fn test_runtime_metrics_exported() {
+        metrics::Metrics::register(&prometheus::Registry::new()).unwrap();
+        let (queue, _rx) = mpsc::channel(10);
+        let pipeline = Arc::new(PipelineState::new(&queue, 1, 10, 100));
+        let parked: Vec<_> = (0..5).map(|_| tokio::spawn(std::future::pending::<()>())).collect();
+
+        // The first tick fires straight away
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let monitor = tokio::spawn(monitor_runtime_metrics(pipeline, shutdown_rx));
+        let exported = metrics::Metrics::get();
+        while exported.runtime_workers.get() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(exported.runtime_workers.get(), 3);
+        assert!(exported.runtime_alive_tasks.get() >= parked.len() as i64);
+
+        shutdown_tx.send(()).unwrap();
+        monitor.await.unwrap();
+        parked.iter().for_each(|task| task.abort());
+    }
+}
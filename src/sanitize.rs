@@ -0,0 +1,166 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Cleans repo text before it is embedded: strips control and zero-width characters, normalizes
+/// unicode to NFC and collapses runs of whitespace, so providers don't reject the input and
+/// formatting noise doesn't move the vector. Tags and emoji can optionally be dropped too, since
+/// descriptions and READMEs are full of both, and non-Latin scripts transliterated to ASCII for
+/// English-only models.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextSanitizer {
+    strip_html: bool,
+    strip_emoji: bool,
+    transliterate: bool,
+}
+
+impl TextSanitizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove HTML tags and decode the common entities
+    pub fn with_strip_html(mut self, strip_html: bool) -> Self {
+        self.strip_html = strip_html;
+        self
+    }
+
+    pub fn with_strip_emoji(mut self, strip_emoji: bool) -> Self {
+        self.strip_emoji = strip_emoji;
+        self
+    }
+
+    /// Replace non-ASCII text with its closest ASCII spelling ("Привет" becomes "Privet")
+    pub fn with_transliterate(mut self, transliterate: bool) -> Self {
+        self.transliterate = transliterate;
+        self
+    }
+
+    pub fn sanitize(&self, text: &str) -> String {
+        let text = if self.strip_html { strip_html(text) } else { text.to_string() };
+        let cleaned: String = text
+            .nfc()
+            .filter(|&c| !(is_invisible(c) || self.strip_emoji && is_emoji(c)))
+            .collect();
+        let cleaned = if self.transliterate { deunicode::deunicode(&cleaned) } else { cleaned };
+        collapse_whitespace(&cleaned)
+    }
+}
+
+/// Control and zero-width characters; newlines and tabs are left for whitespace collapsing
+fn is_invisible(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t')
+        || matches!(c, '\u{200B}' | '\u{200C}' | '\u{200E}' | '\u{200F}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Emoji, pictographs and the joiners and modifiers that combine them
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B50}'..='\u{2B55}'
+            | '\u{200D}'
+            | '\u{20E3}'
+            | '\u{FE0E}'..='\u{FE0F}'
+    )
+}
+
+/// Tags that start a new line when removed
+const BLOCK_TAGS: &[&str] = &["br", "p", "div", "li", "tr", "h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// `text` without tags, comments and the most common entities, with block-level tags kept as line breaks
+fn strip_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let opens_tag = tag[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        let end = if tag.starts_with("<!--") {
+            tag.find("-->").map(|end| end + 3)
+        } else if opens_tag {
+            tag.find('>').map(|end| end + 1)
+        } else {
+            None
+        };
+        match end {
+            Some(end) => {
+                let name = tag[1..end - 1]
+                    .trim_matches('/')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_lowercase();
+                if BLOCK_TAGS.contains(&name.as_str()) {
+                    out.push('\n');
+                }
+                rest = &tag[end..];
+            }
+            // A lone '<' is text, as in "a < b"
+            None => {
+                out.push('<');
+                rest = &tag[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    [("&nbsp;", " "), ("&lt;", "<"), ("&gt;", ">"), ("&quot;", "\""), ("&#39;", "'"), ("&apos;", "'"), ("&amp;", "&")]
+        .iter()
+        .fold(out, |text, (entity, replacement)| text.replace(entity, replacement))
+}
+
+/// Single spaces within lines, no trailing whitespace and at most one blank line in a row
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        blank_lines = 0;
+        out.push_str(&line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let sanitizer = TextSanitizer::new();
+        // "e" + combining acute accent becomes the single precomposed character
+        let text = "Repository:  owner/cafe\u{301}\u{0}\u{200B}\r\nDescription:\tFast \u{1F680}\n\n\n\nREADME:  <b>bold</b>  ";
+        assert_eq!(
+            sanitizer.sanitize(text),
+            "Repository: owner/caf\u{e9}\nDescription: Fast \u{1F680}\n\nREADME: <b>bold</b>"
+        );
+
+        let sanitizer = sanitizer.with_strip_html(true).with_strip_emoji(true);
+        assert_eq!(
+            sanitizer.sanitize("Fast \u{1F680}\u{FE0F} <b>lib</b> &amp; tools<br/>for a < b<!-- note -->"),
+            "Fast lib & tools\nfor a < b"
+        );
+    }
+
+    #[test]
+    fn test_transliterate() {
+        let sanitizer = TextSanitizer::new().with_strip_emoji(true).with_transliterate(true);
+        assert_eq!(
+            sanitizer.sanitize("Description: Быстрый веб-сервер \u{1F680} für Café\u{200B}"),
+            "Description: Bystryi veb-server fur Cafe"
+        );
+
+        // Off by default
+        assert_eq!(TextSanitizer::new().sanitize("Café"), "Caf\u{e9}");
+    }
+}
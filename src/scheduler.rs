@@ -0,0 +1,507 @@
+use crate::{
+    config::Config,
+    duplicates::scan_duplicates,
+    embedder::Embedder,
+    embedding_cache::EmbeddingCache,
+    repo_store::RepoSource,
+    surreal_client::SurrealClient,
+    user_embeddings::refresh_user_embeddings,
+    verify::{audit_validator, verify_embeddings},
+};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+/// Page size used by the scheduled `verify` job
+const VERIFY_PAGE_SIZE: usize = 500;
+
+/// How far ahead to look for the next run; expressions like `0 0 30 2 *` never fire
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 4;
+
+/// How often the backlog gate re-checks the windows and provider latency
+const BACKLOG_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A five-field cron expression (minute hour day-of-month month day-of-week), evaluated in UTC.
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps (`*/10`, `5-55/10`);
+/// day-of-week runs 0-7 with both 0 and 7 meaning Sunday. `@hourly`, `@daily`, `@weekly` and
+/// `@monthly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// As in cron, a restricted day-of-month and day-of-week match if either does
+    day_of_month_any: bool,
+    day_of_week_any: bool,
+}
+
+impl CronSchedule {
+    fn matches_day(&self, time: &DateTime<Utc>) -> bool {
+        let dom = self.days_of_month & (1 << time.day()) != 0;
+        let dow = self.days_of_week & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.day_of_month_any, self.day_of_week_any) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+
+    /// First matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = after + ChronoDuration::days(MAX_LOOKAHEAD_DAYS);
+
+        while time <= limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = if time.month() == 12 {
+                    (time.year() + 1, 1)
+                } else {
+                    (time.year(), time.month() + 1)
+                };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.matches_day(&time) {
+                time = time.with_hour(0)?.with_minute(0)? + ChronoDuration::days(1);
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += ChronoDuration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim().to_lowercase().as_str() {
+            "@hourly" => "0 * * * *".to_string(),
+            "@daily" | "@midnight" => "0 0 * * *".to_string(),
+            "@weekly" => "0 0 * * 0".to_string(),
+            "@monthly" => "0 0 1 * *".to_string(),
+            other => other.to_string(),
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(format!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day-of-month month day-of-week)",
+                s.trim()
+            ));
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7, "day-of-week")?;
+        // 7 is Sunday as well
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(dom, 1, 31, "day-of-month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            day_of_month_any: dom == "*",
+            day_of_week_any: dow == "*",
+        })
+    }
+}
+
+/// Bitmask of the values selected by one cron field
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = |reason: &str| format!("Invalid cron {} field '{}': {}", name, field, reason);
+    let mut mask = 0u64;
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| invalid("step is not a number"))?;
+                if step == 0 {
+                    return Err(invalid("step must be greater than 0"));
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+
+        let number = |value: &str| -> Result<u32, String> {
+            let value: u32 = value.parse().map_err(|_| invalid("not a number"))?;
+            if value < min || value > max {
+                return Err(invalid(&format!("values must be between {} and {}", min, max)));
+            }
+            Ok(value)
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/15` means every 15 from 5
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let value = number(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(invalid("range start is after its end"));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+/// Built-in maintenance jobs that can be scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Log the coverage report (what `stats` prints)
+    Stats,
+    /// Validate every stored embedding and log the summary (what `verify` prints)
+    Verify,
+    /// Drop expired embedding cache entries
+    CacheCleanup,
+    /// Delete audit records older than `AUDIT_RETENTION_DAYS`
+    AuditPrune,
+    /// Refresh per-user aggregate embeddings affected by newly embedded repos
+    UserEmbeddings,
+    /// Find near-duplicate repos by embedding similarity and record them in `repo_duplicate`
+    Duplicates,
+}
+
+impl Job {
+    async fn run(self, context: &JobContext) -> anyhow::Result<()> {
+        match self {
+            Job::Stats => {
+                if let Some(stats) = context.source.coverage().await? {
+                    stats.log();
+                }
+            }
+            Job::Verify => {
+                let validator = audit_validator(&context.config);
+                let report =
+                    verify_embeddings(&context.client, &validator, false, VERIFY_PAGE_SIZE).await?;
+                info!(
+                    scanned = report.scanned,
+                    valid = report.valid,
+                    invalid = report.invalid,
+                    "Verification report"
+                );
+            }
+            Job::CacheCleanup => context.cache.evict_expired(),
+            Job::AuditPrune => {
+                if !context.config.audit_log || context.config.audit_retention_days == 0 {
+                    debug!("Audit log pruning is disabled; skipping");
+                    return Ok(());
+                }
+                let retention = Duration::from_secs(context.config.audit_retention_days * 86_400);
+                let deleted = context.client.prune_audit_records(retention).await?;
+                if deleted > 0 {
+                    info!("Pruned {} audit records", deleted);
+                }
+            }
+            Job::UserEmbeddings => {
+                refresh_user_embeddings(&context.client, &context.config, false).await?;
+            }
+            Job::Duplicates => {
+                scan_duplicates(&context.client, context.config.duplicate_threshold).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Job {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "stats" => Ok(Job::Stats),
+            "verify" => Ok(Job::Verify),
+            "cache_cleanup" => Ok(Job::CacheCleanup),
+            "audit_prune" => Ok(Job::AuditPrune),
+            "user_embeddings" => Ok(Job::UserEmbeddings),
+            "duplicates" => Ok(Job::Duplicates),
+            other => Err(format!(
+                "Unknown scheduled job '{}'. Expected stats, verify, cache_cleanup, audit_prune, user_embeddings or duplicates",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Job::Stats => "stats",
+            Job::Verify => "verify",
+            Job::CacheCleanup => "cache_cleanup",
+            Job::AuditPrune => "audit_prune",
+            Job::UserEmbeddings => "user_embeddings",
+            Job::Duplicates => "duplicates",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledJob {
+    pub job: Job,
+    pub schedule: CronSchedule,
+}
+
+/// Parse `SCHEDULE`: `job=cron` entries separated by `;`, e.g.
+/// `stats=*/10 * * * *; verify=@daily`. An empty string schedules nothing.
+pub fn parse_schedule(spec: &str) -> Result<Vec<ScheduledJob>, String> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (job, expression) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid schedule entry '{}': expected job=cron", entry))?;
+            Ok(ScheduledJob {
+                job: job.parse()?,
+                schedule: expression.parse()?,
+            })
+        })
+        .collect()
+}
+
+/// A daily UTC time range, `HH:MM-HH:MM`. The end is exclusive; a range ending before it starts
+/// wraps past midnight (`22:00-06:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessingWindow {
+    /// Minutes after midnight
+    start: u32,
+    end: u32,
+}
+
+impl ProcessingWindow {
+    pub fn contains(&self, time: &DateTime<Utc>) -> bool {
+        let minute = time.hour() * 60 + time.minute();
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl FromStr for ProcessingWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid processing window '{}': {}", s.trim(), reason);
+        let minutes = |time: &str| -> Result<u32, String> {
+            let (hour, minute) = time.trim().split_once(':').ok_or_else(|| invalid("expected HH:MM-HH:MM"))?;
+            let hour: u32 = hour.parse().map_err(|_| invalid("hour is not a number"))?;
+            let minute: u32 = minute.parse().map_err(|_| invalid("minute is not a number"))?;
+            // 24:00 is accepted as the end of the day
+            if minute > 59 || hour > 24 || (hour == 24 && minute > 0) {
+                return Err(invalid("time out of range"));
+            }
+            Ok(hour * 60 + minute)
+        };
+        let (start, end) = s.split_once('-').ok_or_else(|| invalid("expected HH:MM-HH:MM"))?;
+        let (start, end) = (minutes(start)?, minutes(end)?);
+        if start == end {
+            return Err(invalid("window is empty"));
+        }
+        Ok(Self { start: start % (24 * 60), end })
+    }
+}
+
+/// When the backlog lane (the scan for repos needing embeddings) may feed the queue. Live query
+/// updates are urgent and never held back.
+#[derive(Debug, Clone, Default)]
+pub struct BacklogPolicy {
+    /// Run only inside one of these; empty means any time
+    pub windows: Vec<ProcessingWindow>,
+    /// Pause while the provider's recent latency is above this
+    pub max_latency: Option<Duration>,
+}
+
+impl BacklogPolicy {
+    /// Parse `BACKLOG_WINDOWS` (comma-separated `HH:MM-HH:MM` UTC ranges) and the latency limit
+    pub fn parse(windows: &str, max_latency: Option<Duration>) -> Result<Self, String> {
+        let windows = windows
+            .split(',')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { windows, max_latency })
+    }
+
+    /// Whether the policy can ever hold the backlog back
+    pub fn is_restricted(&self) -> bool {
+        !self.windows.is_empty() || self.max_latency.is_some()
+    }
+
+    /// Why the backlog has to wait at `now`, or `None` when it may run
+    pub fn pause_reason(&self, now: &DateTime<Utc>, latency: Option<Duration>) -> Option<String> {
+        if !self.windows.is_empty() && !self.windows.iter().any(|window| window.contains(now)) {
+            return Some("outside the processing windows".to_string());
+        }
+        match (self.max_latency, latency) {
+            (Some(max), Some(latency)) if latency > max => Some(format!(
+                "provider latency {}ms is above {}ms",
+                latency.as_millis(),
+                max.as_millis()
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Open and close the backlog lane as `policy` dictates until shutdown. `gate` holds whether
+/// the backlog may run; the initial batch processors wait while it is closed.
+pub async fn run_backlog_gate(
+    policy: BacklogPolicy,
+    embedder: Arc<Embedder>,
+    gate: watch::Sender<bool>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    let mut ticker = tokio::time::interval(BACKLOG_CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                info!("Backlog gate shutting down");
+                return;
+            }
+            _ = ticker.tick() => {
+                let reason = policy.pause_reason(&Utc::now(), embedder.recent_latency());
+                let open = reason.is_none();
+                if *gate.borrow() != open {
+                    match &reason {
+                        Some(reason) => info!("Pausing backlog processing: {}", reason),
+                        None => info!("Resuming backlog processing"),
+                    }
+                    crate::metrics::set_backlog_paused(!open);
+                }
+                gate.send_replace(open);
+            }
+        }
+    }
+}
+
+/// What the jobs operate on
+pub struct JobContext {
+    pub config: Arc<Config>,
+    pub source: Arc<dyn RepoSource>,
+    pub client: Arc<SurrealClient>,
+    pub cache: Arc<EmbeddingCache>,
+}
+
+/// Run each job at its scheduled minutes until shutdown. Jobs run one at a time; a run that is
+/// still going when the next one is due delays it rather than overlapping, and missed runs are
+/// not caught up.
+pub async fn run_scheduler(
+    jobs: Vec<ScheduledJob>,
+    context: JobContext,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    loop {
+        let now = Utc::now();
+        let upcoming: Vec<(DateTime<Utc>, Job)> = jobs
+            .iter()
+            .filter_map(|scheduled| scheduled.schedule.next_after(now).map(|at| (at, scheduled.job)))
+            .collect();
+        let Some(next) = upcoming.iter().map(|(at, _)| *at).min() else {
+            warn!("No scheduled job will run again; scheduler stopping");
+            return;
+        };
+        let wait = (next - now).to_std().unwrap_or_default();
+        debug!(next = %next, "Scheduler sleeping for {:?}", wait);
+
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                info!("Scheduler shutting down");
+                return;
+            }
+            _ = tokio::time::sleep(wait) => {}
+        }
+
+        for job in upcoming.into_iter().filter(|(at, _)| *at == next).map(|(_, job)| job) {
+            let started = std::time::Instant::now();
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    info!(job = %job, "Scheduler shutting down during a job");
+                    return;
+                }
+                result = job.run(&context) => match result {
+                    Ok(()) => debug!(job = %job, "Scheduled job finished in {:?}", started.elapsed()),
+                    Err(e) => error!(job = %job, "Scheduled job failed: {}", e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cron_next_run() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        let every_ten: CronSchedule = "*/10 * * * *".parse().unwrap();
+        assert_eq!(every_ten.next_after(at("2024-03-01T12:03:30Z")), Some(at("2024-03-01T12:10:00Z")));
+        assert_eq!(every_ten.next_after(at("2024-03-01T12:10:00Z")), Some(at("2024-03-01T12:20:00Z")));
+
+        let daily: CronSchedule = "@daily".parse().unwrap();
+        assert_eq!(daily.next_after(at("2024-12-31T23:59:00Z")), Some(at("2025-01-01T00:00:00Z")));
+
+        // Weekdays at 03:30; 2024-03-02 is a Saturday
+        let weekdays: CronSchedule = "30 3 * * 1-5".parse().unwrap();
+        assert_eq!(weekdays.next_after(at("2024-03-02T10:00:00Z")), Some(at("2024-03-04T03:30:00Z")));
+
+        let never: CronSchedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(never.next_after(at("2024-01-01T00:00:00Z")), None);
+
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+
+        let jobs = parse_schedule("stats=*/10 * * * *; verify=@daily;").unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[1].job, Job::Verify);
+        assert!(parse_schedule("reindex=@daily").is_err());
+        assert!(parse_schedule("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backlog_policy() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        let policy = BacklogPolicy::parse("22:00-06:00, 12:00-13:00", Some(Duration::from_millis(500))).unwrap();
+        assert!(policy.is_restricted());
+        assert_eq!(policy.pause_reason(&at("2024-03-01T23:30:00Z"), None), None);
+        assert_eq!(policy.pause_reason(&at("2024-03-01T05:59:00Z"), None), None);
+        assert!(policy.pause_reason(&at("2024-03-01T06:00:00Z"), None).is_some());
+        assert_eq!(policy.pause_reason(&at("2024-03-01T12:30:00Z"), Some(Duration::from_millis(200))), None);
+        assert!(policy
+            .pause_reason(&at("2024-03-01T12:30:00Z"), Some(Duration::from_secs(2)))
+            .unwrap()
+            .contains("latency"));
+
+        let all_day: ProcessingWindow = "00:00-24:00".parse().unwrap();
+        assert!(all_day.contains(&at("2024-03-01T23:59:00Z")));
+        assert!(!BacklogPolicy::parse("", None).unwrap().is_restricted());
+        assert!(BacklogPolicy::parse("6-8", None).is_err());
+        assert!(BacklogPolicy::parse("05:00-05:00", None).is_err());
+        assert!(BacklogPolicy::parse("25:00-06:00", None).is_err());
+    }
+}
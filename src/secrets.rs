@@ -0,0 +1,174 @@
+use anyhow::Context;
+use clap::Command;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Settings that may be read from `<ENV>_FILE` or Vault instead of the environment
+pub const SECRET_SETTINGS: &[&str] = &[
+    "db_pass",
+    "db_token",
+    "openai_api_key",
+    "together_api_key",
+    "ollama_token",
+    "admin_token",
+    "monitoring_token",
+    "alert_webhook_url",
+];
+
+/// Secret values from files named by `<ENV>_FILE` variables (e.g. `OPENAI_API_KEY_FILE`), as
+/// mounted by Kubernetes or Docker secrets. Trailing newlines are trimmed.
+pub fn read_secret_files(command: &Command) -> anyhow::Result<Vec<(String, String)>> {
+    let mut secrets = Vec::new();
+
+    for arg in command.get_arguments() {
+        let setting = arg.get_id().as_str();
+        if !SECRET_SETTINGS.contains(&setting) {
+            continue;
+        }
+        let Some(env) = arg.get_env() else {
+            continue;
+        };
+        let file_var = format!("{}_FILE", env.to_string_lossy());
+        if let Some(path) = std::env::var_os(&file_var) {
+            let value = read_secret_file(&file_var, &path)?;
+            secrets.push((setting.to_string(), value));
+        }
+    }
+
+    Ok(secrets)
+}
+
+fn read_secret_file(var: &str, path: &std::ffi::OsStr) -> anyhow::Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {} ({})", var, path.to_string_lossy()))?;
+    let value = contents.trim_end_matches(['\r', '\n']).to_string();
+    if value.is_empty() {
+        anyhow::bail!("{} ({}) is empty", var, path.to_string_lossy());
+    }
+    Ok(value)
+}
+
+/// A HashiCorp Vault KV secret read once at startup (and on reload)
+///
+/// Configured from the environment like the Vault CLI: `VAULT_ADDR`, `VAULT_TOKEN` (or
+/// `VAULT_TOKEN_FILE`, e.g. written by a Vault Agent sidecar) and `VAULT_SECRET_PATH`, the API
+/// path of the secret such as `secret/data/embed_star`. Both KV v1 and v2 are supported.
+#[derive(Debug, Clone)]
+pub struct VaultSource {
+    addr: String,
+    token: String,
+    path: String,
+    namespace: Option<String>,
+}
+
+impl VaultSource {
+    /// `None` unless `VAULT_SECRET_PATH` is set
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(path) = std::env::var("VAULT_SECRET_PATH").ok().filter(|p| !p.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let addr = std::env::var("VAULT_ADDR")
+            .context("VAULT_ADDR is required when VAULT_SECRET_PATH is set")?;
+        let token = match std::env::var_os("VAULT_TOKEN_FILE") {
+            Some(file) => read_secret_file("VAULT_TOKEN_FILE", &file)?,
+            None => std::env::var("VAULT_TOKEN").context(
+                "VAULT_TOKEN or VAULT_TOKEN_FILE is required when VAULT_SECRET_PATH is set",
+            )?,
+        };
+
+        Ok(Some(Self {
+            addr: addr.trim_end_matches('/').to_string(),
+            token,
+            path: path.trim_matches('/').to_string(),
+            namespace: std::env::var("VAULT_NAMESPACE").ok(),
+        }))
+    }
+
+    /// Fetch the secret and return the values of known secret settings. Keys may be setting
+    /// names (`openai_api_key`) or environment variable names (`OPENAI_API_KEY`); other keys
+    /// are ignored so a secret can be shared with other services.
+    pub async fn fetch(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let url = format!("{}/v1/{}", self.addr, self.path);
+
+        let mut request = client.get(&url).header("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach Vault at {}", self.addr))?;
+        if !response.status().is_success() {
+            anyhow::bail!("Vault returned {} for {}", response.status(), self.path);
+        }
+
+        let body: Value = response.json().await.context("Invalid response from Vault")?;
+        Ok(secret_values(&body))
+    }
+}
+
+/// Known secret settings in a Vault read response (`data.data` for KV v2, `data` for KV v1)
+fn secret_values(body: &Value) -> Vec<(String, String)> {
+    let data = match body.pointer("/data/data") {
+        Some(Value::Object(data)) => data,
+        _ => match body.get("data") {
+            Some(Value::Object(data)) => data,
+            _ => return Vec::new(),
+        },
+    };
+
+    data.iter()
+        .filter_map(|(key, value)| {
+            let setting = key.to_lowercase();
+            if !SECRET_SETTINGS.contains(&setting.as_str()) {
+                return None;
+            }
+            let value = match value {
+                Value::String(s) => s.clone(),
+                // Several keys for rotation
+                Value::Array(items) => items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                _ => return None,
+            };
+            Some((setting, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_secret_values_from_kv_v1_and_v2() {
+        let v2 = json!({
+            "data": {
+                "data": { "OPENAI_API_KEY": "sk-a", "db_pass": "hunter2", "unrelated": "x" },
+                "metadata": { "version": 3 }
+            }
+        });
+        let mut values = secret_values(&v2);
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                ("db_pass".to_string(), "hunter2".to_string()),
+                ("openai_api_key".to_string(), "sk-a".to_string()),
+            ]
+        );
+
+        let v1 = json!({ "data": { "together_api_key": ["k1", "k2"] } });
+        assert_eq!(
+            secret_values(&v1),
+            vec![("together_api_key".to_string(), "k1,k2".to_string())]
+        );
+    }
+}
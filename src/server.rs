@@ -1,24 +1,55 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::get,
+    extract::{Request, State},
+    http::{header::{ACCEPT, AUTHORIZATION}, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{get, post},
     Json, Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
+use futures::{stream, Stream, StreamExt};
 use prometheus::{Encoder, Registry, TextEncoder};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::time::{timeout, Duration};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{timeout, Duration},
+};
 use crate::{
+    circuit_breaker::{CircuitBreakerManager, CircuitState, CircuitStats},
+    config::Config,
     embedder::Embedder,
+    embedding_cache::{CacheStats, EmbeddingCache},
+    events::{self, PipelineEvent},
+    intake::{IntakeControl, IntakeMode},
+    openmetrics,
+    pipeline::{PipelineState, PipelineStatus},
     pool::{Pool, PoolExt},
+    provider_probe::ProviderProber,
+    reload::{ConfigReloader, Tunables},
+    validation::{ProviderQuality, ProviderQualityTracker},
 };
 
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Arc<Config>,
     pub db_pool: Pool,
     pub registry: Arc<Registry>,
     pub embedder: Arc<Embedder>,
+    pub circuit_breaker: Arc<CircuitBreakerManager>,
+    /// Bearer token required by admin endpoints; `None` disables them
+    pub admin_token: Option<Arc<str>>,
+    /// Bearer token required by all other endpoints except the k8s probes; `None` leaves them open
+    pub monitoring_token: Option<Arc<str>>,
+    pub intake: Arc<IntakeControl>,
+    pub pipeline: Arc<PipelineState>,
+    pub cache: Arc<EmbeddingCache>,
+    pub provider_prober: Arc<ProviderProber>,
+    pub reloader: Arc<ConfigReloader>,
+    pub quality: Arc<ProviderQualityTracker>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -45,10 +76,95 @@ pub struct PoolStats {
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub status: String,
+    pub intake: IntakeMode,
+    pub paused: bool,
+    pub resumes_in_secs: Option<u64>,
+    pub budget_exceeded: Option<String>,
+    pub daily_spend_usd: f64,
+    pub monthly_spend_usd: f64,
+    pub circuit_breakers: HashMap<String, String>,
+    pub pipeline: PipelineStatus,
+    pub cache: CacheStats,
+    /// Rolling validation statistics of each provider
+    pub provider_quality: Vec<ProviderQuality>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IntakeResponse {
+    pub intake: IntakeMode,
+    pub batches_in_flight: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CircuitBreakerStatus {
+    pub state: String,
+    pub total_requests: u64,
+    pub failed_requests: u64,
+    pub successful_requests: u64,
+    pub consecutive_failures: u32,
+    pub window_requests: u64,
+    pub window_failures: u64,
+    pub state_changes: u64,
+    pub secs_since_last_failure: Option<u64>,
+    pub resumes_in_secs: Option<u64>,
+}
+
+impl CircuitBreakerStatus {
+    fn new(state: CircuitState, stats: CircuitStats, resumes_in: Option<Duration>) -> Self {
+        Self {
+            state: state.as_str().to_string(),
+            total_requests: stats.total_requests,
+            failed_requests: stats.failed_requests,
+            successful_requests: stats.successful_requests,
+            consecutive_failures: stats.consecutive_failures,
+            window_requests: stats.window_requests,
+            window_failures: stats.window_failures,
+            state_changes: stats.state_changes,
+            secs_since_last_failure: stats.last_failure_time.map(|t| t.elapsed().as_secs()),
+            resumes_in_secs: resumes_in.map(|r| r.as_secs()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitBreakerAction {
+    Reset,
+    Open,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CircuitBreakerCommand {
+    pub service: String,
+    pub action: CircuitBreakerAction,
+    /// How long to hold a forced-open circuit; defaults to the breaker's timeout
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkersCommand {
+    pub workers: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub database: bool,
+    pub configuration: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ProviderHealth {
     pub name: String,
     pub available: bool,
     pub latency_ms: Option<u64>,
+    pub last_checked: Option<chrono::DateTime<chrono::Utc>>,
+    pub error: Option<String>,
+    /// Whether the model is installed, for providers that host models themselves (Ollama)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_available: Option<bool>,
 }
 
 
@@ -58,10 +174,7 @@ pub async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRe
     let db_connected = match state.db_pool.get().await {
         Ok(conn) => {
             // Perform a simple health check query
-            match conn.query("SELECT 1 as health_check").await {
-                Ok(_) => true,
-                Err(_) => false,
-            }
+            conn.query("RETURN 1").await.is_ok()
         }
         Err(_) => false,
     };
@@ -83,7 +196,7 @@ pub async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRe
                 max_size: pool_stats.max_size,
             }),
         },
-        embedding_providers: check_provider_health(&state.embedder).await,
+        embedding_providers: state.provider_prober.latest(),
     };
     
     if db_connected {
@@ -93,9 +206,22 @@ pub async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRe
     }
 }
 
-pub async fn metrics_handler(State(state): State<AppState>) -> Result<Response, StatusCode> {
-    let encoder = TextEncoder::new();
+/// Prometheus text format, or OpenMetrics with exemplars when the scraper asks for it
+pub async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> Result<Response, StatusCode> {
     let metric_families = state.registry.gather();
+    let openmetrics = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/openmetrics-text"));
+    if openmetrics {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", openmetrics::CONTENT_TYPE)
+            .body(openmetrics::encode(&metric_families, openmetrics::exemplars()).into())
+            .unwrap());
+    }
+
+    let encoder = TextEncoder::new();
     let mut buffer = Vec::new();
     
     encoder
@@ -109,6 +235,251 @@ pub async fn metrics_handler(State(state): State<AppState>) -> Result<Response,
         .unwrap())
 }
 
+/// Server-sent events: batch completions, failed repos and backlog counts as they happen.
+/// Opens with the current backlog so clients don't wait for the next stats report.
+pub async fn events_handler(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let status = state.pipeline.status();
+    let snapshot = status.pending_repos.map(|pending| PipelineEvent::Backlog {
+        pending_repos: pending as usize,
+        queue_depth: status.queue_depth,
+        at: chrono::Utc::now(),
+    });
+    let updates = stream::unfold(events::subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((event, rx)),
+                Err(RecvError::Lagged(skipped)) => tracing::debug!(skipped, "Event subscriber fell behind"),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let stream = stream::iter(snapshot)
+        .chain(updates)
+        .map(|event| Event::default().event(event.name()).json_data(&event));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub async fn status_handler(State(state): State<AppState>) -> Json<StatusResponse> {
+    // Workers pause intake while an operator paused it, the breaker they use (keyed by
+    // provider name) is open or the spending budget is exhausted
+    let intake = state.intake.mode();
+    let remaining = state.circuit_breaker.open_remaining(state.embedder.provider_name());
+    let budget_exceeded = state.embedder.budget_exceeded();
+    let paused = intake == IntakeMode::Paused || remaining.is_some() || budget_exceeded.is_some();
+    let (daily_spend_usd, monthly_spend_usd) = state.embedder.cost_tracker().spend();
+    let circuit_breakers = state
+        .circuit_breaker
+        .get_all_states()
+        .into_iter()
+        .map(|(service, circuit)| (service, circuit.as_str().to_string()))
+        .collect();
+
+    let status = if intake == IntakeMode::Draining {
+        "draining"
+    } else if paused {
+        "paused"
+    } else {
+        "running"
+    };
+
+    Json(StatusResponse {
+        status: status.to_string(),
+        intake,
+        paused,
+        resumes_in_secs: remaining.map(|r| r.as_secs()),
+        budget_exceeded: budget_exceeded.map(|period| period.to_string()),
+        daily_spend_usd,
+        monthly_spend_usd,
+        circuit_breakers,
+        pipeline: state.pipeline.status(),
+        cache: state.cache.stats(),
+        provider_quality: state.quality.snapshot(),
+    })
+}
+
+pub async fn circuit_breakers_handler(
+    State(state): State<AppState>,
+) -> Json<HashMap<String, CircuitBreakerStatus>> {
+    let breakers = state
+        .circuit_breaker
+        .get_all_stats()
+        .into_iter()
+        .map(|(service, (circuit, stats))| {
+            let resumes_in = state.circuit_breaker.open_remaining(&service);
+            (service, CircuitBreakerStatus::new(circuit, stats, resumes_in))
+        })
+        .collect();
+    Json(breakers)
+}
+
+pub async fn circuit_breaker_command_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(command): Json<CircuitBreakerCommand>,
+) -> Result<Json<CircuitBreakerStatus>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+
+    let breaker = &state.circuit_breaker;
+    let found = match command.action {
+        CircuitBreakerAction::Reset => breaker.reset(&command.service),
+        CircuitBreakerAction::Open => breaker.force_open(
+            &command.service,
+            command.duration_secs.map(Duration::from_secs),
+        ),
+    };
+    if !found {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::warn!(service = %command.service, action = ?command.action, "Circuit breaker changed via admin API");
+
+    let circuit = breaker.get_state(&command.service).ok_or(StatusCode::NOT_FOUND)?;
+    let stats = breaker.get_stats(&command.service).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(CircuitBreakerStatus::new(
+        circuit,
+        stats,
+        breaker.open_remaining(&command.service),
+    )))
+}
+
+pub async fn admin_pause_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<IntakeResponse>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+    state.intake.pause();
+    Ok(intake_response(&state))
+}
+
+pub async fn admin_resume_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<IntakeResponse>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+    state.intake.resume();
+    Ok(intake_response(&state))
+}
+
+/// Stop queueing repos, finish what is queued and exit
+pub async fn admin_drain_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<IntakeResponse>, StatusCode> {
+    authorize_admin(&state, &headers)?;
+    state.intake.drain();
+    Ok(intake_response(&state))
+}
+
+/// Re-read the configuration and apply the reloadable settings, like SIGHUP.
+/// An invalid configuration is rejected and the current settings are kept.
+pub async fn admin_reload_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Tunables>, Response> {
+    authorize_admin(&state, &headers).map_err(IntoResponse::into_response)?;
+    match state.reloader.reload().await {
+        Ok(tunables) => Ok(Json(tunables)),
+        Err(e) => {
+            tracing::error!("Configuration reload failed, keeping current settings: {}", e);
+            Err((StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response())
+        }
+    }
+}
+
+/// Change the number of batch processor workers until the next reload or restart. Extra
+/// workers finish their current batch before stopping.
+pub async fn admin_workers_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(command): Json<WorkersCommand>,
+) -> Result<Json<Tunables>, Response> {
+    authorize_admin(&state, &headers).map_err(IntoResponse::into_response)?;
+    match state.reloader.set_workers(command.workers).await {
+        Ok(tunables) => {
+            tracing::warn!(workers = command.workers, "Worker count changed via admin API");
+            Ok(Json(tunables))
+        }
+        Err(e) => Err((StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response()),
+    }
+}
+
+fn intake_response(state: &AppState) -> Json<IntakeResponse> {
+    Json(IntakeResponse {
+        intake: state.intake.mode(),
+        batches_in_flight: state.intake.in_flight(),
+    })
+}
+
+/// Admin endpoints need `Authorization: Bearer <ADMIN_TOKEN>` and are refused outright
+/// when no token is configured
+fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let expected = state.admin_token.as_deref().ok_or(StatusCode::FORBIDDEN)?;
+    let provided = bearer_token(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Guards every route it wraps with `MONITORING_TOKEN` when one is configured. The admin
+/// token is accepted too, so admin calls need only one `Authorization` header.
+async fn require_monitoring_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if let Some(expected) = state.monitoring_token.as_deref() {
+        let authorized = bearer_token(request.headers()).is_some_and(|provided| {
+            constant_time_eq(provided.as_bytes(), expected.as_bytes())
+                || state
+                    .admin_token
+                    .as_deref()
+                    .is_some_and(|admin| constant_time_eq(provided.as_bytes(), admin.as_bytes()))
+        });
+        if !authorized {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Readiness probe: only the database pool and configuration, never the embedding provider,
+/// so probes stay cheap and don't flap with provider latency
+pub async fn readiness_check(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let database = match timeout(Duration::from_secs(2), state.db_pool.get()).await {
+        Ok(Ok(conn)) => matches!(
+            timeout(Duration::from_secs(2), conn.query("RETURN 1")).await,
+            Ok(Ok(_))
+        ),
+        _ => false,
+    };
+    let configuration = state.config.validate().is_ok();
+    let ready = database && configuration;
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(ReadinessResponse { ready, database, configuration }))
+}
+
+/// Static operator dashboard. It holds no data itself: the page reads `/status` and `/events`
+/// with the monitoring token the operator enters, so it is served without one.
+pub async fn dashboard_handler() -> Html<&'static str> {
+    Html(include_str!("dashboard.html"))
+}
+
 pub async fn liveness_check() -> impl IntoResponse {
     Json(serde_json::json!({
         "status": "alive",
@@ -116,49 +487,306 @@ pub async fn liveness_check() -> impl IntoResponse {
     }))
 }
 
-async fn check_provider_health(embedder: &Arc<Embedder>) -> Vec<ProviderHealth> {
-    let provider_name = embedder.provider_name();
-    let model_name = embedder.model_name();
-    
-    // Test the provider by trying to generate an embedding for a short test text
-    let test_text = "health check";
-    let start = std::time::Instant::now();
-    
-    let health_check_result = timeout(
-        Duration::from_secs(5),
-        embedder.generate_embedding(test_text)
-    ).await;
-    
-    let (available, latency_ms) = match health_check_result {
-        Ok(Ok(_)) => {
-            let latency = start.elapsed().as_millis() as u64;
-            (true, Some(latency))
-        }
-        Ok(Err(_)) => (false, None),
-        Err(_) => (false, None), // Timeout
-    };
-    
-    vec![ProviderHealth {
-        name: format!("{} ({})", provider_name, model_name),
-        available,
-        latency_ms,
-    }]
-}
-
 pub fn create_monitoring_router(state: AppState) -> Router {
+    // Probes stay unauthenticated so kubelets and load balancers can reach them, as does the
+    // dashboard page, which authenticates its own requests
+    let public = Router::new()
+        .route("/livez", get(liveness_check))
+        .route("/readyz", get(readiness_check))
+        .route("/dashboard", get(dashboard_handler));
+
     Router::new()
         .route("/health", get(health_check))
         .route("/metrics", get(metrics_handler))
-        .route("/livez", get(liveness_check))
+        .route("/status", get(status_handler))
+        .route("/events", get(events_handler))
+        .route(
+            "/circuit-breakers",
+            get(circuit_breakers_handler).post(circuit_breaker_command_handler),
+        )
+        .route("/admin/pause", post(admin_pause_handler))
+        .route("/admin/resume", post(admin_resume_handler))
+        .route("/admin/drain", post(admin_drain_handler))
+        .route("/admin/reload", post(admin_reload_handler))
+        .route("/admin/workers", post(admin_workers_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_monitoring_token))
+        .merge(public)
         .with_state(state)
 }
 
+/// Serve the monitoring router on `addr` (`host:port`, or `unix:<path>` for a unix domain socket)
 pub async fn run_monitoring_server(addr: &str, state: AppState) -> anyhow::Result<()> {
+    if let Some(path) = addr.strip_prefix("unix:") {
+        return serve_unix_socket(path, create_monitoring_router(state)).await;
+    }
+
+    let tls = match (&state.config.monitoring_tls_cert, &state.config.monitoring_tls_key) {
+        (Some(cert), Some(key)) => Some(RustlsConfig::from_config(Arc::new(crate::tls::server_config(cert, key)?))),
+        _ => None,
+    };
     let app = create_monitoring_router(state);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    tracing::info!("Monitoring server listening on {}", addr);
-    
-    axum::serve(listener, app).await?;
+
+    match tls {
+        Some(tls) => {
+            let addr: SocketAddr = addr.parse()?;
+            tracing::info!("Monitoring server listening on https://{}", addr);
+            axum_server::bind_rustls(addr, tls)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!("Monitoring server listening on {}", addr);
+            axum::serve(listener, app).await?;
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(unix)]
+async fn serve_unix_socket(path: &str, app: Router) -> anyhow::Result<()> {
+    use hyper_util::{
+        rt::{TokioExecutor, TokioIo},
+        server::conn::auto,
+        service::TowerToHyperService,
+    };
+
+    // A socket left behind by a previous run would make bind fail
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    tracing::info!("Monitoring server listening on unix:{}", path);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Monitoring connection error: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve_unix_socket(_path: &str, _app: Router) -> anyhow::Result<()> {
+    anyhow::bail!("Unix domain sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{embedder::EmbeddingProvider, service::ServiceBuilder};
+
+    struct StaticProvider;
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for StaticProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![0.5; 128])
+        }
+
+        fn model_name(&self) -> &str {
+            "static"
+        }
+    }
+
+    /// Counts its calls, so tests can tell whether a handler reached the provider
+    struct CountingProvider(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(vec![0.5; 128])
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+    }
+
+    /// Serve the router of a service built over an in-memory database on a local port
+    async fn serve(config: Config) -> String {
+        serve_with(config, Box::new(StaticProvider)).await
+    }
+
+    async fn serve_with(config: Config, provider: Box<dyn EmbeddingProvider>) -> String {
+        let service = ServiceBuilder::new(config)
+            .with_embedder(Arc::new(Embedder::builder("static", provider).build()))
+            .with_signal_handling(false)
+            .build()
+            .await
+            .unwrap();
+        let app = service.monitoring_router();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    async fn status(request: reqwest::RequestBuilder, token: Option<&str>) -> StatusCode {
+        let request = match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        StatusCode::from_u16(request.send().await.unwrap().status().as_u16()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_monitoring_and_admin_tokens() {
+        let base = serve(Config {
+            monitoring_token: Some("monitor".to_string()),
+            admin_token: Some("admin".to_string()),
+            ..Config::for_tests()
+        })
+        .await;
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{}{}", base, path));
+        let post = |path: &str| client.post(format!("{}{}", base, path));
+
+        assert_eq!(status(get("/status"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(get("/status"), Some("wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(get("/status"), Some("monitor")).await, StatusCode::OK);
+        // The admin token opens the monitoring endpoints too
+        assert_eq!(status(get("/status"), Some("admin")).await, StatusCode::OK);
+
+        assert_eq!(status(post("/admin/pause"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(post("/admin/pause"), Some("monitor")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(post("/admin/pause"), Some("admin")).await, StatusCode::OK);
+
+        // Probes and the dashboard page stay open
+        for path in ["/livez", "/readyz", "/dashboard"] {
+            assert_ne!(status(get(path), None).await, StatusCode::UNAUTHORIZED, "{}", path);
+        }
+        assert_eq!(status(get("/livez"), None).await, StatusCode::OK);
+        assert_eq!(status(get("/dashboard"), None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_endpoints_refused_without_admin_token() {
+        let base = serve(Config::for_tests()).await;
+        let client = reqwest::Client::new();
+
+        assert_eq!(status(client.get(format!("{}/status", base)), None).await, StatusCode::OK);
+        assert_eq!(
+            status(client.post(format!("{}/admin/pause", base)), Some("anything")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_reads_status_fields() {
+        let base = serve(Config {
+            monitoring_token: Some("monitor".to_string()),
+            ..Config::for_tests()
+        })
+        .await;
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{}/dashboard", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+        assert!(content_type.starts_with("text/html"), "{}", content_type);
+        let page = response.text().await.unwrap();
+        assert!(page.contains("fetch('/status'") && page.contains("fetch('/events'"));
+
+        // The fields the page renders
+        let status: serde_json::Value = client
+            .get(format!("{}/status", base))
+            .bearer_auth("monitor")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(status["status"].is_string());
+        assert!(status["pipeline"]["queue_depth"].is_u64());
+        assert!(status["pipeline"]["queue_capacity"].is_u64());
+        assert!(status["provider_quality"].is_array());
+        assert!(status["circuit_breakers"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_probes_never_call_the_provider() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let base = serve_with(Config::for_tests(), Box::new(CountingProvider(calls.clone()))).await;
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{}/readyz", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let readiness: ReadinessResponse = response.json().await.unwrap();
+        assert!(readiness.ready && readiness.database && readiness.configuration);
+
+        // /health reports the prober's cached result, which doesn't exist before its first run
+        let response = client.get(format!("{}/health", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let health: HealthResponse = response.json().await.unwrap();
+        assert!(health.database.connected);
+        assert!(!health.embedding_providers[0].available);
+        assert_eq!(health.embedding_providers[0].error.as_deref(), Some("not probed yet"));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_invalid_tls_paths_fail_validation() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let with_tls = |cert: Option<&str>, key: Option<&str>| Config {
+            monitoring_tls_cert: cert.map(|name| fixtures.join(name)),
+            monitoring_tls_key: key.map(|name| fixtures.join(name)),
+            ..Config::for_tests()
+        };
+
+        assert!(with_tls(Some("localhost.crt"), Some("localhost.key")).validate().is_ok());
+        assert!(with_tls(Some("missing.crt"), Some("localhost.key")).validate().is_err());
+        assert!(with_tls(Some("localhost.crt"), Some("missing.key")).validate().is_err());
+        assert!(with_tls(Some("localhost.crt"), None).validate().is_err());
+
+        let unix = Config {
+            monitoring_bind_addr: "unix:/tmp/embed_star.sock".to_string(),
+            ..with_tls(Some("localhost.crt"), Some("localhost.key"))
+        };
+        assert!(unix.validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_over_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let path = std::env::temp_dir().join(format!("embed_star_{}.sock", uuid::Uuid::new_v4()));
+        let service = ServiceBuilder::new(Config::for_tests())
+            .with_embedder(Arc::new(Embedder::builder("static", Box::new(StaticProvider)).build()))
+            .with_signal_handling(false)
+            .build()
+            .await
+            .unwrap();
+        let app = service.monitoring_router();
+        let socket = path.to_string_lossy().into_owned();
+        let server = tokio::spawn(async move { serve_unix_socket(&socket, app).await });
+
+        let mut stream = loop {
+            match tokio::net::UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        stream
+            .write_all(b"GET /livez HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+}
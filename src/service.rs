@@ -1,276 +1,935 @@
 use crate::{
-    circuit_breaker::{CircuitBreakerConfig, CircuitBreakerManager},
+    ab::AbShadow,
+    autoscale::ScalingEstimator,
+    circuit_breaker::{CircuitBreakerManager, CircuitSnapshot},
     config::Config,
     embedder::Embedder,
-    embedding_cache::{cache_cleanup_task, EmbeddingCache},
+    embedding_cache::EmbeddingCache,
     error::Result,
-    metrics::Metrics,
+    intake::{IntakeControl, IntakeMode},
+    language::LanguageRouter,
+    metrics::{Metrics, MetricsPusher},
     migration::run_migrations,
-    models::Repo,
-    pool::create_pool,
+    models::{LiveAction, LiveQueryNotification, Repo},
+    notifier::{run_notifier, Notifier},
+    pipeline::{PipelineState, PipelineStatus},
+    pool::{create_pool, Pool, PoolExt},
     pool_metrics::monitor_pool_metrics,
     process_batch::process_batch,
+    provider_probe::ProviderProber,
     rate_limiter::RateLimiterManager,
+    reload::{reload_on_sighup, resize_workers_on_signals, ConfigReloader, Tunables},
+    repo_store::{EmbeddingSink, RepoSource},
     retry::RetryConfig,
-    server::{run_monitoring_server, AppState},
-    shutdown::{setup_signal_handlers, GracefulShutdown, ShutdownController},
-    surreal_client::SurrealClient,
-    validation::{EmbeddingValidator, ValidationConfig},
+    runtime_metrics::monitor_runtime_metrics,
+    scheduler::{run_backlog_gate, run_scheduler, JobContext},
+    server::{create_monitoring_router, run_monitoring_server, AppState},
+    shutdown::{
+        listen_for_signals, GracefulShutdown, ShutdownController, ShutdownPhase, ShutdownReceiver,
+        SHUTDOWN_TIMEOUT,
+    },
+    surreal_client::{StorageMode, SurrealClient},
+    telemetry,
+    tenant::{in_tenant, Dispatcher, TenantTarget},
+    validation::{EmbeddingValidator, ProviderQualityTracker},
+    wal::WalSink,
+    write_retry::RetryingSink,
 };
 use prometheus::Registry;
+use serde::Serialize;
 use std::{sync::Arc, time::Duration};
 use tokio::{
-    sync::mpsc,
+    sync::{mpsc, watch},
     task::JoinHandle,
     time::{interval, sleep},
 };
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
-use uuid::Uuid;
 
 /// Run the embed_star service with the given configuration
 pub async fn run_with_config(config: Config) -> anyhow::Result<()> {
-    let session_id = Uuid::new_v4();
-    info!(session_id = %session_id, "Starting embed_star service");
-
-    // Validate configuration
-    let config = Arc::new(config);
-    config.validate()?;
-    info!("Configuration loaded: {}", config);
-
-    // Initialize metrics
-    let registry = Arc::new(Registry::new());
-    Metrics::register(&registry)?;
-    info!("Metrics initialized");
-
-    // Create database pool
-    let pool = create_pool(config.clone()).await?;
-    info!("Database connection pool created");
-
-    // Run migrations
-    run_migrations(&pool).await?;
-    info!("Database migrations completed");
-
-    // Initialize components
-    let client = Arc::new(SurrealClient::new(pool.clone()));
-    let embedder = Arc::new(Embedder::new(config.clone())?);
-    let rate_limiter = Arc::new(RateLimiterManager::new());
-    let circuit_breaker = Arc::new(CircuitBreakerManager::new());
-    let validator = Arc::new(EmbeddingValidator::new(ValidationConfig::default()));
-    let cache = Arc::new(EmbeddingCache::new(10_000, 3600)); // 10k entries, 1 hour TTL
-
-    // Configure circuit breakers for each provider
-    match config.embedding_provider.as_str() {
-        "openai" => {
-            rate_limiter.configure_provider("openai", 3000).await?;
-            circuit_breaker.configure_service(
-                "openai",
-                CircuitBreakerConfig {
-                    failure_threshold: 5,
-                    timeout_duration: Duration::from_secs(120),
-                    success_threshold: 3,
-                    failure_rate_threshold: 0.5,
-                    min_requests: 10,
-                },
-            );
+    ServiceBuilder::new(config).build().await?.run().await
+}
+
+/// Assembles the service for crates that embed it. Components that aren't supplied are built
+/// from the configuration exactly as `run_with_config` does. The database pool is always used
+/// for migrations, the monitoring server and the audit log, even with a custom source and sink.
+/// A custom pool, source or sink needs a single tenant (`TENANTS` unset or one entry).
+pub struct ServiceBuilder {
+    config: Config,
+    pool: Option<Pool>,
+    embedder: Option<Arc<Embedder>>,
+    cache: Option<Arc<EmbeddingCache>>,
+    source: Option<Arc<dyn RepoSource>>,
+    sink: Option<Arc<dyn EmbeddingSink>>,
+    handle_signals: bool,
+}
+
+impl ServiceBuilder {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            pool: None,
+            embedder: None,
+            cache: None,
+            source: None,
+            sink: None,
+            handle_signals: true,
         }
-        "together" => {
-            rate_limiter.configure_provider("together", 1000).await?;
-            circuit_breaker.configure_service(
-                "together",
-                CircuitBreakerConfig {
-                    failure_threshold: 10,
-                    timeout_duration: Duration::from_secs(60),
-                    success_threshold: 5,
-                    failure_rate_threshold: 0.6,
-                    min_requests: 20,
-                },
-            );
+    }
+
+    pub fn with_pool(mut self, pool: Pool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Use this embedder instead of the one selected by `EMBEDDING_PROVIDER`
+    pub fn with_embedder(mut self, embedder: Arc<Embedder>) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Arc<EmbeddingCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Read repos needing embeddings from here instead of SurrealDB
+    pub fn with_source(mut self, source: Arc<dyn RepoSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Write embeddings here instead of SurrealDB
+    pub fn with_sink(mut self, sink: Arc<dyn EmbeddingSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Shut down on SIGINT/SIGTERM (the default); disable when the host application owns signals
+    pub fn with_signal_handling(mut self, enabled: bool) -> Self {
+        self.handle_signals = enabled;
+        self
+    }
+
+    /// Validate the configuration, connect, run migrations and create every component. Nothing
+    /// is processed until [`ServiceHandle::start`].
+    pub async fn build(self) -> anyhow::Result<ServiceHandle> {
+        let session_id = telemetry::session_id();
+        info!(session_id = %session_id, "Starting embed_star service");
+
+        // Validate configuration
+        let config = Arc::new(self.config);
+        config.validate()?;
+        info!("Configuration loaded: {}", config);
+
+        // Initialize metrics
+        let registry = Arc::new(Registry::new());
+        Metrics::register(&registry)?;
+        info!("Metrics initialized");
+
+        let tenant_list = config.tenants()?;
+        if tenant_list.len() > 1 && (self.pool.is_some() || self.source.is_some() || self.sink.is_some()) {
+            anyhow::bail!("A custom pool, source or sink can only be used with a single tenant");
         }
-        "ollama" => {
-            circuit_breaker.configure_service(
-                "ollama",
-                CircuitBreakerConfig {
-                    failure_threshold: 3,
-                    timeout_duration: Duration::from_secs(30),
-                    success_threshold: 2,
-                    failure_rate_threshold: 0.3,
-                    min_requests: 5,
-                },
-            );
+
+        // Every tenant gets its own pool, migrations and client
+        let (mut pool, mut source, mut sink) = (self.pool, self.source, self.sink);
+        let mut tenants = Vec::with_capacity(tenant_list.len());
+        for tenant in &tenant_list {
+            let name: Arc<str> = Arc::from(tenant.name.as_str());
+            let tenant_config = Arc::new(config.for_tenant(tenant));
+            let components = in_tenant(name.clone(), async {
+                let pool = match pool.take() {
+                    Some(pool) => pool,
+                    None => create_pool(tenant_config.clone()).await?,
+                };
+                info!(tenant = %name, "Database connection pool created");
+
+                run_migrations(&pool).await?;
+                info!(tenant = %name, "Database migrations completed");
+
+                let client = Arc::new(
+                    SurrealClient::new(pool.clone())
+                        .with_quantization(config.quantization_mode()?, config.quantized_only)
+                        .with_precision(config.storage_precision()?)
+                        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone())
+                        .with_write_chunk_size(config.db_write_chunk_size)
+                        .with_history(config.embedding_history, !config.embedding_history_metadata_only)
+                        .with_polling(Duration::from_secs(config.poll_interval_secs), config.fetch_batch_size)
+                        .with_change_feed(config.change_feed_retention_days)
+                        .with_language_metadata(config.detect_language)
+                        .with_routed_model(config.multilingual_model.clone())
+                        .with_audit(config.audit_log),
+                );
+                let source: Arc<dyn RepoSource> = source.take().unwrap_or_else(|| client.clone());
+                let sink: Arc<dyn EmbeddingSink> = sink.take().unwrap_or_else(|| client.clone());
+                // Generated embeddings are logged until written, and what a crash or outage left
+                // in the log is written before any new work starts
+                let sink = match &config.wal_dir {
+                    Some(dir) => {
+                        let wal = WalSink::open(sink, dir.join(format!("{}.wal", name)))?;
+                        match wal.recover().await {
+                            Ok(0) => {}
+                            Ok(stored) => info!(tenant = %name, stored, "Recovered embeddings from the write-ahead log"),
+                            Err(e) => warn!(tenant = %name, pending = wal.pending(), "Failed to write embeddings from the write-ahead log: {}", e),
+                        }
+                        Arc::new(wal) as Arc<dyn EmbeddingSink>
+                    }
+                    None => sink,
+                };
+                // Embeddings from writes that fail outright are queued and written again later
+                let (sink, write_retry) = if config.write_retry_queue_size > 0 {
+                    let mut retrying = RetryingSink::new(sink, config.write_retry_queue_size);
+                    if let Some(dir) = &config.write_retry_spill_dir {
+                        retrying = retrying.with_spill_file(dir.join(format!("{}.jsonl", name)))?;
+                    }
+                    let retrying = Arc::new(retrying);
+                    (retrying.clone() as Arc<dyn EmbeddingSink>, Some(retrying))
+                } else {
+                    (sink, None)
+                };
+                // A/B model vectors always go to the repo_embedding table, keyed by that model
+                let shadow = config.ab_model.as_ref().map(|model| {
+                    Arc::new(
+                        SurrealClient::new(pool.clone())
+                            .with_storage_mode(StorageMode::Table, model.clone())
+                            .with_write_chunk_size(config.db_write_chunk_size),
+                    ) as Arc<dyn EmbeddingSink>
+                });
+                // Multilingual vectors live in another vector space, so they get their own
+                // repo_embedding rows instead of the primary model's field
+                let routed = match &config.multilingual_model {
+                    Some(model) => Some(Arc::new(
+                        SurrealClient::new(pool.clone())
+                            .with_quantization(config.quantization_mode()?, config.quantized_only)
+                            .with_precision(config.storage_precision()?)
+                            .with_storage_mode(StorageMode::Table, model.clone())
+                            .with_write_chunk_size(config.db_write_chunk_size)
+                            .with_language_metadata(config.detect_language),
+                    ) as Arc<dyn EmbeddingSink>),
+                    None => None,
+                };
+                Ok::<_, anyhow::Error>(TenantComponents {
+                    name: name.clone(),
+                    weight: tenant.weight,
+                    pool,
+                    client,
+                    source,
+                    sink,
+                    shadow,
+                    routed,
+                    write_retry,
+                })
+            })
+            .await?;
+            tenants.push(components);
         }
-        _ => {}
-    }
-
-    // Get initial statistics
-    let total_repos = client.get_total_repos_count().await?;
-    let embedded_repos = client.get_embedded_repos_count().await?;
-    let pending_repos = client.get_pending_repos_count().await?;
-
-    info!(
-        total_repos = total_repos,
-        embedded_repos = embedded_repos,
-        pending_repos = pending_repos,
-        "Database statistics"
-    );
-    
-    crate::metrics::set_pending_repos(pending_repos as i64);
-
-    // Setup shutdown handling
-    let shutdown_receiver = setup_signal_handlers().await;
-    let (shutdown_controller, _) = ShutdownController::new();
-    let mut graceful_shutdown = GracefulShutdown::new(shutdown_controller.clone());
-
-    // Create processing channel with larger buffer for parallel workers
-    let (tx, rx) = mpsc::channel::<Repo>(config.batch_size * config.parallel_workers * 2);
-
-    // Start monitoring server
-    let monitoring_addr = format!("0.0.0.0:{}", config.monitoring_port.unwrap_or(9090));
-    let app_state = AppState {
-        db_pool: pool.clone(),
-        registry: registry.clone(),
-        embedder: embedder.clone(),
-    };
-    
-    let monitoring_handle: JoinHandle<()> = tokio::spawn({
-        let mut shutdown_rx = shutdown_receiver.subscribe();
-        async move {
-            tokio::select! {
-                result = run_monitoring_server(&monitoring_addr, app_state) => {
-                    if let Err(e) = result {
-                        error!("Monitoring server error: {}", e);
+        // Service-wide state (circuit snapshots, the monitoring server's health check) lives with
+        // the first tenant
+        let client = tenants[0].client.clone();
+        let pool = tenants[0].pool.clone();
+
+        // Initialize components
+        let embedder = match self.embedder {
+            Some(embedder) => embedder,
+            None => Arc::new(Embedder::new(config.clone())?),
+        };
+        if embedder.provider_name() == "ollama" {
+            prepare_ollama_model(&embedder, &config).await;
+        }
+        let rate_limiter = Arc::new(RateLimiterManager::new());
+        let (circuit_breaker, circuit_snapshots) = if config.cb_persist {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (CircuitBreakerManager::new().with_persistence(tx), Some(rx))
+        } else {
+            (CircuitBreakerManager::new(), None)
+        };
+        let circuit_breaker = Arc::new(circuit_breaker);
+        let quality = Arc::new(ProviderQualityTracker::new());
+        let validator = Arc::new(
+            EmbeddingValidator::new(config.validation_config())
+                .with_metrics(embedder.model_name())
+                .with_quality(quality.clone(), embedder.provider_name()),
+        );
+        let cache = self.cache.unwrap_or_else(|| {
+            Arc::new(
+                EmbeddingCache::new(10_000, 3600) // 10k entries, 1 hour TTL
+                    .with_negative_ttl(config.negative_cache_ttl_secs),
+            )
+        });
+        // Only float vectors can be served from the cache, and quantized-only storage drops them
+        if config.cache_warm_size > 0 && config.quantized_only {
+            info!("Not warming the embedding cache, only quantized vectors are stored");
+        } else if config.cache_warm_size > 0 {
+            for tenant in &tenants {
+                let warming = warm_cache(&cache, &tenant.client, config.cache_warm_size, embedder.model_name());
+                in_tenant(tenant.name.clone(), warming).await;
+            }
+        }
+
+        // Setup shutdown handling
+        let (shutdown_controller, shutdown_receiver) = ShutdownController::new();
+        if self.handle_signals {
+            listen_for_signals(shutdown_controller.clone());
+        }
+        let mut graceful_shutdown = GracefulShutdown::new(shutdown_controller.clone())
+            .with_drain_deadline(Duration::from_secs(config.shutdown_drain_secs));
+
+        // Configure the circuit breaker and initial rate limits for the provider. process_batch looks
+        // both up by provider name, so every model served by one provider shares them; OpenAI and
+        // Together quotas are adjusted from their rate-limit headers
+        let provider_key = embedder.provider_name();
+        configure_provider_limits(&config, provider_key, &rate_limiter, &circuit_breaker).await?;
+        if let Some(snapshots) = circuit_snapshots {
+            // Restore before any worker starts so a deliberately opened circuit stays open
+            let max_age = Duration::from_secs(config.cb_persist_max_age_secs);
+            match client.load_circuit_snapshots().await {
+                Ok(saved) => {
+                    for snapshot in saved {
+                        circuit_breaker.restore(snapshot, max_age);
                     }
                 }
-                _ = shutdown_rx.recv() => {
-                    info!("Monitoring server shutting down");
+                Err(e) => warn!("Failed to load persisted circuit breaker state: {}", e),
+            }
+
+            let persister = tokio::spawn({
+                let client = client.clone();
+                let tenant = tenants[0].name.clone();
+                let flush_rx = graceful_shutdown.subscribe(ShutdownPhase::Flush);
+
+                in_tenant(tenant, async move {
+                    persist_circuit_snapshots(client, snapshots, flush_rx).await;
+                })
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Flush, "circuit_persister".to_string(), persister);
+        }
+        if let Some(tpm) = config.tokens_per_minute {
+            rate_limiter.configure_provider_tokens(provider_key, tpm).await?;
+        }
+        if let Some(max_in_flight) = config.provider_max_in_flight {
+            rate_limiter.configure_provider_concurrency(provider_key, max_in_flight).await?;
+        }
+
+        // The A/B model gets its own embedder. On another provider it also gets that provider's
+        // breaker and request limit; on the same one it shares them. Its validations stay out of
+        // the provider quality statistics, which describe what is stored
+        let shadow = match config.ab_config() {
+            Some(ab_config) => {
+                let ab_embedder = Arc::new(Embedder::new(Arc::new(ab_config.clone()))?);
+                let ab_validator = EmbeddingValidator::new(ab_config.validation_config()).with_metrics(ab_embedder.model_name());
+                if ab_embedder.provider_name() != provider_key {
+                    configure_provider_limits(&ab_config, ab_embedder.provider_name(), &rate_limiter, &circuit_breaker)
+                        .await?;
+                }
+                info!(model = %ab_config.embedding_model, sample_rate = config.ab_sample_rate, "A/B model enabled");
+                Some(Arc::new(AbShadow::new(ab_embedder, Arc::new(ab_validator), config.ab_sample_rate)))
+            }
+            None => None,
+        };
+
+        // Likewise for the multilingual model, whose vectors go to each tenant's routed sink
+        let router = match config.multilingual_config() {
+            Some(multilingual_config) => {
+                let multilingual = Arc::new(Embedder::new(Arc::new(multilingual_config.clone()))?);
+                let multilingual_validator =
+                    EmbeddingValidator::new(multilingual_config.validation_config()).with_metrics(multilingual.model_name());
+                if multilingual.provider_name() != provider_key {
+                    configure_provider_limits(
+                        &multilingual_config,
+                        multilingual.provider_name(),
+                        &rate_limiter,
+                        &circuit_breaker,
+                    )
+                    .await?;
                 }
+                info!(model = %multilingual_config.embedding_model, "Routing non-English repos to the multilingual model");
+                Some(Arc::new(LanguageRouter::new(multilingual, Arc::new(multilingual_validator))))
             }
+            None => None,
+        };
+
+        // Get initial statistics
+        let mut pending_repos = 0;
+        for tenant in &tenants {
+            let pending = in_tenant(tenant.name.clone(), async {
+                let total_repos = tenant.client.get_total_repos_count().await?;
+                let embedded_repos = tenant.client.get_embedded_repos_count().await?;
+                let pending_repos = tenant.source.pending_count().await?;
+
+                info!(
+                    tenant = %tenant.name,
+                    total_repos = total_repos,
+                    embedded_repos = embedded_repos,
+                    pending_repos = pending_repos,
+                    "Database statistics"
+                );
+
+                crate::metrics::set_pending_repos(pending_repos as i64);
+                Ok::<_, anyhow::Error>(pending_repos)
+            })
+            .await?;
+            pending_repos += pending;
+        }
+
+        let intake = Arc::new(IntakeControl::new(shutdown_controller.clone()));
+
+        // One processing channel per tenant, with larger buffers for parallel workers
+        let queues: Vec<_> = tenants
+            .iter()
+            .map(|_| mpsc::channel::<Repo>(config.batch_size * config.parallel_workers * 2))
+            .collect();
+        let pipeline = queues[1..].iter().fold(
+            PipelineState::new(
+                &queues[0].0,
+                config.parallel_workers,
+                config.batch_size,
+                config.batch_delay_ms,
+            ),
+            |pipeline, (tx, _)| pipeline.with_queue(tx),
+        );
+        let pipeline = Arc::new(pipeline);
+        pipeline.set_pending_repos(pending_repos);
+
+        let provider_prober = Arc::new(ProviderProber::new(
+            embedder.clone(),
+            circuit_breaker.clone(),
+            Duration::from_secs(config.provider_probe_interval_secs),
+        ));
+
+        // Batch settings, rate limits and the log filter can be reloaded without a restart
+        let reloader = Arc::new(ConfigReloader::new(
+            Tunables::from_config(&config),
+            rate_limiter.clone(),
+            provider_key.to_string(),
+            pipeline.clone(),
+        ));
+
+        let state = AppState {
+            config: config.clone(),
+            db_pool: pool,
+            registry,
+            embedder,
+            circuit_breaker,
+            admin_token: config.admin_token.clone().map(Arc::from),
+            monitoring_token: config.monitoring_token.clone().map(Arc::from),
+            intake,
+            pipeline,
+            cache,
+            provider_prober,
+            reloader,
+            quality,
+        };
+
+        Ok(ServiceHandle {
+            state,
+            tenants,
+            rate_limiter,
+            validator,
+            shadow,
+            router,
+            queues: Some(queues),
+            shutdown_controller,
+            shutdown_receiver,
+            graceful_shutdown,
+        })
+    }
+}
+
+/// Snapshot returned by [`ServiceHandle::status`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub started: bool,
+    pub intake: IntakeMode,
+    pub pipeline: PipelineStatus,
+}
+
+/// One tenant's database connections and where its repos are read from and written to
+struct TenantComponents {
+    name: Arc<str>,
+    /// Share of the workers' batches, see [`Dispatcher`]
+    weight: u32,
+    pool: Pool,
+    client: Arc<SurrealClient>,
+    source: Arc<dyn RepoSource>,
+    sink: Arc<dyn EmbeddingSink>,
+    shadow: Option<Arc<dyn EmbeddingSink>>,
+    routed: Option<Arc<dyn EmbeddingSink>>,
+    /// Present when failed writes are queued for retry
+    write_retry: Option<Arc<RetryingSink>>,
+}
+
+/// A built service: start it, watch it and shut it down programmatically
+pub struct ServiceHandle {
+    /// The components shared with the monitoring server
+    state: AppState,
+    tenants: Vec<TenantComponents>,
+    rate_limiter: Arc<RateLimiterManager>,
+    validator: Arc<EmbeddingValidator>,
+    shadow: Option<Arc<AbShadow>>,
+    router: Option<Arc<LanguageRouter>>,
+    /// One per tenant, in the order of `tenants`; taken by `start`
+    queues: Option<Vec<(mpsc::Sender<Repo>, mpsc::Receiver<Repo>)>>,
+    shutdown_controller: ShutdownController,
+    shutdown_receiver: ShutdownReceiver,
+    graceful_shutdown: GracefulShutdown,
+}
+
+impl ServiceHandle {
+    /// Spawn the workers, the intake tasks and the monitoring server
+    pub fn start(&mut self) -> anyhow::Result<()> {
+        let Some(queues) = self.queues.take() else {
+            anyhow::bail!("Service already started");
+        };
+        let config = self.state.config.clone();
+        let graceful_shutdown = &mut self.graceful_shutdown;
+        let AppState {
+            embedder,
+            circuit_breaker,
+            intake,
+            pipeline,
+            cache,
+            provider_prober,
+            reloader,
+            registry,
+            ..
+        } = self.state.clone();
+
+        // Start monitoring server
+        let monitoring_handle: JoinHandle<()> = tokio::spawn({
+            let monitoring_addr = config.monitoring_address();
+            let app_state = self.state.clone();
+            let mut shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+            async move {
+                tokio::select! {
+                    result = run_monitoring_server(&monitoring_addr, app_state) => {
+                        if let Err(e) = result {
+                            error!("Monitoring server error: {}", e);
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Monitoring server shutting down");
+                    }
+                }
+            }
+        });
+        graceful_shutdown.register_task(ShutdownPhase::Background, "monitoring_server".to_string(), monitoring_handle);
+
+        // Workers share the tenant queues and take batches from them in turn
+        let mut dispatcher = Dispatcher::new();
+        let mut senders = Vec::with_capacity(queues.len());
+        for (tenant, (tx, rx)) in self.tenants.iter().zip(queues) {
+            let target = TenantTarget {
+                tenant: tenant.name.clone(),
+                sink: tenant.sink.clone(),
+                shadow: tenant.shadow.clone(),
+                routed: tenant.routed.clone(),
+            };
+            dispatcher = dispatcher.with_queue(target, tenant.weight, tenant.pool.db_health(), rx);
+            senders.push(tx);
         }
-    });
-    graceful_shutdown.register_task("monitoring_server".to_string(), monitoring_handle);
+        let dispatcher = Arc::new(dispatcher);
+        // Task names carry the tenant only when there is more than one
+        let single_tenant = self.tenants.len() == 1;
+        let task_name = |task: &str, tenant: &str| {
+            if single_tenant {
+                task.to_string()
+            } else {
+                format!("{}[{}]", task, tenant)
+            }
+        };
 
-    // Create shared receiver wrapped in Arc<Mutex> for multiple workers
-    let rx = Arc::new(tokio::sync::Mutex::new(rx));
+        // Workers share one retry budget
+        let retry_config = RetryConfig::from_config(&config);
 
-    // Start multiple batch processor workers
-    for worker_id in 0..config.parallel_workers {
-        let batch_processor = tokio::spawn({
-            let rx = rx.clone();
-            let client = client.clone();
+        // Batch processor workers, spawned and drained by a supervisor as PARALLEL_WORKERS changes
+        let spawn_worker = {
+            let dispatcher = dispatcher.clone();
             let embedder = embedder.clone();
-            let config = config.clone();
-            let rate_limiter = rate_limiter.clone();
+            let reloader = reloader.clone();
+            let rate_limiter = self.rate_limiter.clone();
             let circuit_breaker = circuit_breaker.clone();
-            let validator = validator.clone();
+            let validator = self.validator.clone();
+            let shadow = self.shadow.clone();
+            let router = self.router.clone();
             let cache = cache.clone();
-            let shutdown_rx = shutdown_receiver.subscribe();
-            
+            let intake = intake.clone();
+            let pipeline = pipeline.clone();
+            let cancel = graceful_shutdown.cancellation_token();
+
+            move |worker_id: usize, shutdown_rx: tokio::sync::broadcast::Receiver<()>| {
+                let dispatcher = dispatcher.clone();
+                let embedder = embedder.clone();
+                let tunables = reloader.subscribe();
+                let rate_limiter = rate_limiter.clone();
+                let circuit_breaker = circuit_breaker.clone();
+                let validator = validator.clone();
+                let shadow = shadow.clone();
+                let router = router.clone();
+                let cache = cache.clone();
+                let retry_config = retry_config.clone();
+                let intake = intake.clone();
+                let pipeline = pipeline.clone();
+                let cancel = cancel.clone();
+
+                tokio::spawn(async move {
+                    info!("Starting batch processor worker {}", worker_id);
+                    process_batch_loop_worker(
+                        worker_id,
+                        dispatcher,
+                        embedder,
+                        tunables,
+                        rate_limiter,
+                        circuit_breaker,
+                        validator,
+                        shadow,
+                        router,
+                        cache,
+                        retry_config,
+                        intake,
+                        pipeline,
+                        cancel,
+                        shutdown_rx,
+                    ).await;
+                })
+            }
+        };
+        let worker_pool = tokio::spawn({
+            let tunables = reloader.subscribe();
+            let pipeline = pipeline.clone();
+            let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Workers);
             async move {
-                info!("Starting batch processor worker {}", worker_id);
-                process_batch_loop_worker(
-                    worker_id,
-                    rx,
-                    client,
-                    embedder,
-                    config,
-                    rate_limiter,
-                    circuit_breaker,
-                    validator,
-                    cache,
-                    shutdown_rx,
-                ).await;
+                supervise_workers(spawn_worker, tunables, pipeline, shutdown_rx).await;
             }
         });
-        graceful_shutdown.register_task(
-            format!("batch_processor_{}", worker_id),
-            batch_processor,
-        );
-    }
+        graceful_shutdown.register_task(ShutdownPhase::Workers, "worker_pool".to_string(), worker_pool);
+
+        // The backlog lane only feeds the queue while the gate is open; without windows or a
+        // latency limit it stays open
+        let backlog_policy = config.backlog_policy()?;
+        let (backlog_gate, backlog_open) = watch::channel(true);
+        if backlog_policy.is_restricted() {
+            let gate = tokio::spawn({
+                let embedder = embedder.clone();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+                async move {
+                    run_backlog_gate(backlog_policy, embedder, backlog_gate, shutdown_rx).await;
+                }
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Background, "backlog_gate".to_string(), gate);
+        }
+
+        for (tenant, tx) in self.tenants.iter().zip(senders) {
+            // Start initial batch processor
+            let initial_processor = tokio::spawn({
+                let source = tenant.source.clone();
+                let tx = tx.clone();
+                let intake = intake.clone();
+                let backlog_open = backlog_open.clone();
+                let fetch_batch_size = config.fetch_batch_size;
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Producers);
+
+                in_tenant(tenant.name.clone(), async move {
+                    if let Err(e) =
+                        process_initial_batch(&source, &tx, &intake, backlog_open, fetch_batch_size, shutdown_rx).await
+                    {
+                        error!("Error processing initial batch: {}", e);
+                    }
+                })
+            });
+            graceful_shutdown.register_task(
+                ShutdownPhase::Producers,
+                task_name("initial_processor", &tenant.name),
+                initial_processor,
+            );
+
+            // Start live query processor
+            let live_query_processor = tokio::spawn({
+                let source = tenant.source.clone();
+                let intake = intake.clone();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Producers);
+
+                in_tenant(tenant.name.clone(), async move {
+                    if let Err(e) = process_live_query(source, tx, intake, shutdown_rx).await {
+                        error!("Error in live query processor: {}", e);
+                    }
+                })
+            });
+            graceful_shutdown.register_task(
+                ShutdownPhase::Producers,
+                task_name("live_query_processor", &tenant.name),
+                live_query_processor,
+            );
+
+            // Start removal processor for deleted and private repos
+            let removal_processor = tokio::spawn({
+                let source = tenant.source.clone();
+                // Every sink the tenant writes vectors to
+                let sinks: Vec<_> = std::iter::once(tenant.sink.clone())
+                    .chain(tenant.routed.clone())
+                    .chain(tenant.shadow.clone())
+                    .collect();
+                let cache = cache.clone();
+                let model = embedder.model_name().to_string();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Producers);
+
+                in_tenant(tenant.name.clone(), async move {
+                    if let Err(e) = process_removals(source, sinks, cache, model, shutdown_rx).await {
+                        error!("Error in removal processor: {}", e);
+                    }
+                })
+            });
+            graceful_shutdown.register_task(
+                ShutdownPhase::Producers,
+                task_name("removal_processor", &tenant.name),
+                removal_processor,
+            );
+        }
+
+        // Start statistics reporter
+        let stats_reporter = tokio::spawn({
+            let sources = self
+                .tenants
+                .iter()
+                .map(|tenant| (tenant.name.clone(), tenant.source.clone()))
+                .collect();
+            let pipeline = pipeline.clone();
+            let embedder = embedder.clone();
+            let estimator = ScalingEstimator::new(Duration::from_secs(config.autoscale_drain_target_secs));
+            let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
 
-    // Start initial batch processor
-    let initial_processor = tokio::spawn({
-        let client = client.clone();
-        let tx = tx.clone();
-        let shutdown_rx = shutdown_receiver.subscribe();
-        
-        async move {
-            if let Err(e) = process_initial_batch(&client, &tx, shutdown_rx).await {
-                error!("Error processing initial batch: {}", e);
+            async move {
+                report_stats_loop(sources, pipeline, embedder, estimator, shutdown_rx).await;
             }
+        });
+        graceful_shutdown.register_task(ShutdownPhase::Background, "stats_reporter".to_string(), stats_reporter);
+
+        // Start background provider prober; /health serves its latest result
+        if config.provider_probe_interval_secs > 0 {
+            let prober = tokio::spawn({
+                let provider_prober = provider_prober.clone();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+                async move {
+                    provider_prober.run(shutdown_rx).await;
+                }
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Background, "provider_prober".to_string(), prober);
+        }
+
+        // Write embeddings again whose database write failed; the last attempt is made once
+        // the workers have stopped
+        for tenant in &self.tenants {
+            let Some(write_retry) = tenant.write_retry.clone() else {
+                continue;
+            };
+            let replayer = tokio::spawn({
+                let flush_rx = graceful_shutdown.subscribe(ShutdownPhase::Flush);
+
+                in_tenant(tenant.name.clone(), async move {
+                    write_retry.run(flush_rx).await;
+                })
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Flush, task_name("write_retry", &tenant.name), replayer);
+        }
+
+        // Start pool metrics monitors
+        for tenant in &self.tenants {
+            let pool_monitor = tokio::spawn({
+                let pool = tenant.pool.clone();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+                in_tenant(tenant.name.clone(), async move {
+                    monitor_pool_metrics(pool, shutdown_rx).await;
+                })
+            });
+            graceful_shutdown.register_task(
+                ShutdownPhase::Background,
+                task_name("pool_monitor", &tenant.name),
+                pool_monitor,
+            );
         }
-    });
-    graceful_shutdown.register_task("initial_processor".to_string(), initial_processor);
-
-    // Start live query processor
-    let live_query_processor = tokio::spawn({
-        let client = client.clone();
-        let shutdown_rx = shutdown_receiver.subscribe();
-        
-        async move {
-            if let Err(e) = process_live_query(client, tx, shutdown_rx).await {
-                error!("Error in live query processor: {}", e);
+
+        // Reload tunable settings on SIGHUP
+        let sighup_handle = tokio::spawn({
+            let reloader = reloader.clone();
+            let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+            async move {
+                reload_on_sighup(reloader, shutdown_rx).await;
             }
+        });
+        graceful_shutdown.register_task(ShutdownPhase::Background, "config_reloader".to_string(), sighup_handle);
+
+        // Add or remove a worker on SIGUSR1 / SIGUSR2
+        let resize_handle = tokio::spawn({
+            let reloader = reloader.clone();
+            let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+            async move {
+                resize_workers_on_signals(reloader, shutdown_rx).await;
+            }
+        });
+        graceful_shutdown.register_task(ShutdownPhase::Background, "worker_resizer".to_string(), resize_handle);
+
+        // Start alert notifier
+        if let Some(url) = &config.alert_webhook_url {
+            let notifier = Notifier::new(url.clone(), config.webhook_format()?)?;
+            let notifier_handle = tokio::spawn({
+                let thresholds = config.alert_thresholds();
+                let circuit_breaker = circuit_breaker.clone();
+                let embedder = embedder.clone();
+                let pipeline = pipeline.clone();
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+                async move {
+                    run_notifier(notifier, thresholds, circuit_breaker, embedder, pipeline, shutdown_rx).await;
+                }
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Background, "notifier".to_string(), notifier_handle);
         }
-    });
-    graceful_shutdown.register_task("live_query_processor".to_string(), live_query_processor);
-
-    // Start statistics reporter
-    let stats_reporter = tokio::spawn({
-        let client = client.clone();
-        let shutdown_rx = shutdown_receiver.subscribe();
-        
-        async move {
-            report_stats_loop(client, shutdown_rx).await;
-        }
-    });
-    graceful_shutdown.register_task("stats_reporter".to_string(), stats_reporter);
-
-
-    // Start pool metrics monitor
-    let pool_monitor = tokio::spawn({
-        let pool = pool.clone();
-        let shutdown_rx = shutdown_receiver.subscribe();
-        
-        async move {
-            monitor_pool_metrics(pool, shutdown_rx).await;
-        }
-    });
-    graceful_shutdown.register_task("pool_monitor".to_string(), pool_monitor);
-
-    // Start cache cleanup task
-    let cache_cleanup = tokio::spawn({
-        let cache = cache.clone();
-        let shutdown_rx = shutdown_receiver.subscribe();
-        
-        async move {
-            cache_cleanup_task(cache, shutdown_rx).await;
-        }
-    });
-    graceful_shutdown.register_task("cache_cleanup".to_string(), cache_cleanup);
-
-    // Wait for shutdown signal
-    shutdown_receiver.wait_for_shutdown().await;
-    
-    // Perform graceful shutdown
-    graceful_shutdown.shutdown(Duration::from_secs(30)).await;
-    
-    info!(session_id = %session_id, "embed_star service shut down successfully");
-    Ok(())
+
+        // Start scheduled maintenance jobs (coverage report, cache cleanup, audit pruning, ...)
+        let jobs = config.schedule()?;
+        if !jobs.is_empty() {
+            for tenant in &self.tenants {
+                let scheduler = tokio::spawn({
+                    let jobs = jobs.clone();
+                    let context = JobContext {
+                        config: config.clone(),
+                        source: tenant.source.clone(),
+                        client: tenant.client.clone(),
+                        cache: cache.clone(),
+                    };
+                    let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+                    in_tenant(tenant.name.clone(), async move {
+                        run_scheduler(jobs, context, shutdown_rx).await;
+                    })
+                });
+                graceful_shutdown.register_task(
+                    ShutdownPhase::Background,
+                    task_name("scheduler", &tenant.name),
+                    scheduler,
+                );
+            }
+        }
+
+        // Start runtime metrics monitor
+        let runtime_monitor = tokio::spawn({
+            let pipeline = pipeline.clone();
+            let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+
+            async move {
+                monitor_runtime_metrics(pipeline, shutdown_rx).await;
+            }
+        });
+        graceful_shutdown.register_task(ShutdownPhase::Background, "runtime_monitor".to_string(), runtime_monitor);
+
+        // Push metrics for environments that don't scrape /metrics
+        if let Some(settings) = config.metrics_push()? {
+            let pusher = tokio::spawn({
+                let shutdown_rx = graceful_shutdown.subscribe(ShutdownPhase::Background);
+                async move {
+                    match MetricsPusher::new(registry, settings).await {
+                        Ok(pusher) => pusher.run(shutdown_rx).await,
+                        Err(e) => error!("Failed to start metrics pusher: {}", e),
+                    }
+                }
+            });
+            graceful_shutdown.register_task(ShutdownPhase::Background, "metrics_pusher".to_string(), pusher);
+        }
+
+        Ok(())
+    }
+
+    pub fn status(&self) -> ServiceStatus {
+        ServiceStatus {
+            started: self.queues.is_none(),
+            intake: self.state.intake.mode(),
+            pipeline: self.state.pipeline.status(),
+        }
+    }
+
+    /// The monitoring endpoints over this service's state, to mount in another server; `start`
+    /// serves them on `MONITORING_PORT` as well
+    pub fn monitoring_router(&self) -> axum::Router {
+        create_monitoring_router(self.state.clone())
+    }
+
+    /// Triggers the same shutdown as SIGTERM; `run` returns once the tasks have stopped
+    pub fn shutdown_controller(&self) -> ShutdownController {
+        self.shutdown_controller.clone()
+    }
+
+    /// Stop all tasks, letting workers finish their current batch until `SHUTDOWN_DRAIN_SECS`;
+    /// after that in-flight provider requests are aborted and unfinished repos stay pending
+    pub async fn shutdown(self) {
+        // Producers, then workers, then pending writes and the background tasks
+        self.graceful_shutdown.shutdown(SHUTDOWN_TIMEOUT).await;
+
+        // Nothing writes any more, so the connections can go
+        for tenant in &self.tenants {
+            tenant.pool.close();
+        }
+        info!("Database pools closed");
+        info!(session_id = %telemetry::session_id(), "embed_star service shut down successfully");
+    }
+
+    /// Start if needed, then run until a signal, a drain or `shutdown_controller` stops the service
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        if self.queues.is_some() {
+            self.start()?;
+        }
+
+        // Wait for shutdown signal
+        self.shutdown_receiver.wait_for_shutdown().await;
+
+        // Perform graceful shutdown
+        self.shutdown().await;
+        Ok(())
+    }
 }
 
 async fn process_initial_batch(
-    client: &Arc<SurrealClient>,
+    source: &Arc<dyn RepoSource>,
     tx: &mpsc::Sender<Repo>,
+    intake: &IntakeControl,
+    mut backlog_open: watch::Receiver<bool>,
+    fetch_batch_size: usize,
     mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("Starting initial batch processing");
 
     loop {
+        if !intake.accepting() {
+            info!("Draining, stopping initial batch processing");
+            break;
+        }
+
+        // Outside the backlog windows, or while the provider is slow, wait for the gate
+        if !*backlog_open.borrow_and_update() {
+            tokio::select! {
+                _ = shutdown_rx.recv() => {
+                    info!("Initial batch processor received shutdown signal");
+                    break;
+                }
+                changed = backlog_open.changed() => {
+                    if changed.is_err() {
+                        // The gate is gone (shutting down); stop rather than ignore the policy
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
         tokio::select! {
             _ = shutdown_rx.recv() => {
                 info!("Initial batch processor received shutdown signal");
                 break;
             }
-            result = client.get_repos_needing_embeddings(100) => {
+            result = source.fetch_pending(fetch_batch_size) => {
                 match result {
                     Ok(repos) => {
                         if repos.is_empty() {
@@ -309,13 +968,14 @@ async fn process_initial_batch(
 }
 
 async fn process_live_query(
-    client: Arc<SurrealClient>,
+    source: Arc<dyn RepoSource>,
     tx: mpsc::Sender<Repo>,
+    intake: Arc<IntakeControl>,
     mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
 ) -> Result<()> {
     info!("Starting live query processor");
 
-    let mut rx = client.setup_live_query().await?;
+    let mut rx = source.watch().await?;
 
     loop {
         tokio::select! {
@@ -325,6 +985,9 @@ async fn process_live_query(
             }
             repo_opt = rx.recv() => {
                 match repo_opt {
+                    Some(repo) if !intake.accepting() => {
+                        debug!(repo = %repo.full_name, "Draining, not queueing live query update");
+                    }
                     Some(repo) => {
                         info!(repo = %repo.full_name, "Live query: repo needs embedding");
                         if tx.send(repo).await.is_err() {
@@ -344,58 +1007,352 @@ async fn process_live_query(
     Ok(())
 }
 
+/// Forget deleted and private repos: drop their cached embeddings and negative cache entries,
+/// and remove them from every sink: the primary one and those of the multilingual and A/B models
+async fn process_removals(
+    source: Arc<dyn RepoSource>,
+    sinks: Vec<Arc<dyn EmbeddingSink>>,
+    cache: Arc<EmbeddingCache>,
+    model: String,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) -> Result<()> {
+    let Some(mut rx) = source.watch_removals().await? else {
+        debug!("Repo source doesn't report removals");
+        return Ok(());
+    };
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {
+                info!("Removal processor received shutdown signal");
+                break;
+            }
+            change = rx.recv() => {
+                let Some(LiveQueryNotification { action, result: repo }) = change else {
+                    warn!("Removal channel closed");
+                    break;
+                };
+                let reason = match action {
+                    LiveAction::Delete => "deleted",
+                    _ if repo.is_private => "private",
+                    _ => continue,
+                };
+
+                cache.remove(&EmbeddingCache::cache_key(&repo.prepare_text_for_embedding(), &model));
+                for sink in &sinks {
+                    if let Err(e) = sink.remove_embeddings(vec![repo.id.clone()]).await {
+                        warn!(repo = %repo.full_name, "Failed to remove embedding from sink: {}", e);
+                    }
+                }
+                crate::metrics::record_repo_removed(reason);
+                info!(repo = %repo.full_name, reason, "Dropped embeddings of removed repo");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep `parallel_workers` workers running: spawn more when it grows, and when it shrinks tell
+/// the newest ones to stop after their current batch. On shutdown every worker is stopped and
+/// awaited, including those still draining.
+async fn supervise_workers<F>(
+    spawn_worker: F,
+    mut tunables: watch::Receiver<Tunables>,
+    pipeline: Arc<PipelineState>,
+    mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+) where
+    F: Fn(usize, tokio::sync::broadcast::Receiver<()>) -> JoinHandle<()>,
+{
+    let mut workers: Vec<(tokio::sync::broadcast::Sender<()>, JoinHandle<()>)> = Vec::new();
+    let mut draining: Vec<(usize, JoinHandle<()>)> = Vec::new();
+    let mut target = tunables.borrow_and_update().parallel_workers;
+
+    loop {
+        while workers.len() < target {
+            let (stop, stop_rx) = tokio::sync::broadcast::channel(1);
+            workers.push((stop, spawn_worker(workers.len(), stop_rx)));
+        }
+        while workers.len() > target {
+            let (stop, handle) = workers.pop().expect("more workers than the target");
+            let _ = stop.send(());
+            draining.push((workers.len(), handle));
+        }
+        pipeline.set_workers(workers.len());
+
+        tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            changed = tunables.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let next = tunables.borrow_and_update().parallel_workers;
+                if next != target {
+                    info!(from = target, to = next, "Resizing batch processor workers");
+                    target = next;
+                }
+            }
+        }
+        draining.retain(|(_, handle)| !handle.is_finished());
+    }
+
+    for (stop, _) in &workers {
+        let _ = stop.send(());
+    }
+    let all = workers
+        .into_iter()
+        .enumerate()
+        .map(|(worker_id, (_, handle))| (worker_id, handle))
+        .chain(draining);
+    for (worker_id, handle) in all {
+        if let Err(e) = handle.await {
+            error!("Batch processor worker {} panicked: {:?}", worker_id, e);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn process_batch_loop_worker(
     worker_id: usize,
-    rx: Arc<tokio::sync::Mutex<mpsc::Receiver<Repo>>>,
-    client: Arc<SurrealClient>,
+    dispatcher: Arc<Dispatcher>,
     embedder: Arc<Embedder>,
-    config: Arc<Config>,
+    mut tunables: watch::Receiver<Tunables>,
     rate_limiter: Arc<RateLimiterManager>,
     circuit_breaker: Arc<CircuitBreakerManager>,
     validator: Arc<EmbeddingValidator>,
+    shadow: Option<Arc<AbShadow>>,
+    router: Option<Arc<LanguageRouter>>,
     cache: Arc<EmbeddingCache>,
+    retry_config: RetryConfig,
+    intake: Arc<IntakeControl>,
+    pipeline: Arc<PipelineState>,
+    cancel: CancellationToken,
     mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
 ) {
-    let mut batch = Vec::with_capacity(config.batch_size);
-    let mut interval = interval(Duration::from_millis(config.batch_delay_ms));
-    let retry_config = RetryConfig::default();
+    let (mut batch_size, mut batch_delay_ms) = {
+        let tunables = tunables.borrow_and_update();
+        (tunables.batch_size, tunables.batch_delay_ms)
+    };
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut interval = interval(Duration::from_millis(batch_delay_ms));
+    let mut paused = false;
 
     loop {
         tokio::select! {
+            // Never start a new batch once shutdown has been signalled
+            biased;
+
+            // Batches are taken and finished within one tick, so there is nothing left to flush
             _ = shutdown_rx.recv() => {
                 info!("Worker {} received shutdown signal", worker_id);
-                if !batch.is_empty() {
-                    info!("Worker {} processing final batch of {} repos", worker_id, batch.len());
-                    process_batch(&batch, &client, &embedder, &rate_limiter, &circuit_breaker, &validator, &cache, &retry_config).await;
-                }
                 break;
             }
             _ = interval.tick() => {
-                // Try to fill the batch
-                let mut rx_guard = rx.lock().await;
-                while batch.len() < config.batch_size {
-                    match rx_guard.try_recv() {
-                        Ok(repo) => batch.push(repo),
-                        Err(_) => break,
+                // Pick up reloaded batch settings between batches
+                if tunables.has_changed().unwrap_or(false) {
+                    let reloaded = tunables.borrow_and_update();
+                    batch_size = reloaded.batch_size;
+                    if reloaded.batch_delay_ms != batch_delay_ms {
+                        batch_delay_ms = reloaded.batch_delay_ms;
+                        interval = tokio::time::interval(Duration::from_millis(batch_delay_ms));
                     }
                 }
-                drop(rx_guard);
 
-                if !batch.is_empty() {
-                    debug!("Worker {} processing batch of {} repos", worker_id, batch.len());
-                    process_batch(&batch, &client, &embedder, &rate_limiter, &circuit_breaker, &validator, &cache, &retry_config).await;
-                    batch.clear();
+                // While paused by an operator, the database is down, the provider's circuit is open or
+                // the budget is spent, leave repos queued instead of failing them. process_batch keys
+                // the breaker by provider name.
+                let pause_reason = if intake.mode() == IntakeMode::Paused {
+                    Some("paused by operator".to_string())
+                } else if dispatcher.all_unavailable() {
+                    Some("database unavailable".to_string())
+                } else {
+                    match circuit_breaker.open_remaining(embedder.provider_name()) {
+                        Some(remaining) => Some(format!("provider circuit open for another {}s", remaining.as_secs())),
+                        None => embedder.budget_exceeded().map(|period| format!("{} budget exceeded", period)),
+                    }
+                };
+                if let Some(reason) = pause_reason {
+                    if !paused {
+                        warn!("Worker {} pausing intake, {}", worker_id, reason);
+                        paused = true;
+                        crate::metrics::set_intake_paused(true);
+                    }
+                    continue;
+                }
+                if paused {
+                    info!("Worker {} resuming intake", worker_id);
+                    paused = false;
+                    crate::metrics::set_intake_paused(false);
                 }
+
+                // Try to fill the batch from the next tenant with queued repos
+                let Some((target, _in_flight)) = dispatcher.next_batch(&mut batch, batch_size, &intake).await else {
+                    intake.queue_empty();
+                    continue;
+                };
+
+                debug!("Worker {} processing batch of {} repos for {}", worker_id, batch.len(), target.tenant);
+                crate::metrics::set_queue_depth(pipeline.queue_depth());
+                pipeline.set_worker_batch(worker_id, batch.len());
+                crate::metrics::record_batch_size(worker_id, batch.len());
+                // Repos with non-English descriptions go to the multilingual model
+                let routed = match &router {
+                    Some(router) => {
+                        let (primary, routed) = router.split(&batch);
+                        batch = primary;
+                        routed
+                    }
+                    None => Vec::new(),
+                };
+                let stored = in_tenant(target.tenant, async {
+                    let mut lanes = vec![(&batch, &target.sink, &embedder, &validator)];
+                    if let (Some(router), Some(routed_sink)) = (&router, &target.routed) {
+                        lanes.push((&routed, routed_sink, router.embedder(), router.validator()));
+                    }
+                    let mut stored = 0;
+                    for (repos, sink, embedder, validator) in lanes {
+                        if repos.is_empty() {
+                            continue;
+                        }
+                        let mut run = process_batch(repos, sink, embedder, &rate_limiter, &circuit_breaker, validator, &cache, &retry_config, &cancel).await;
+                        run.worker = Some(worker_id);
+                        run.batch_size = Some(batch_size);
+                        run.batch_delay_ms = Some(batch_delay_ms);
+                        if let Err(e) = target.sink.write_batch_run(&run).await {
+                            warn!(batch_id = %run.batch_id, "Failed to write batch run: {}", e);
+                        }
+                        stored += run.stored;
+                    }
+
+                    // Embed the sampled repos again with the A/B model, into its own slot
+                    if let (Some(shadow), Some(shadow_sink)) = (&shadow, &target.shadow) {
+                        let sampled = shadow.sample(&batch);
+                        if !sampled.is_empty() && !cancel.is_cancelled() {
+                            process_batch(&sampled, shadow_sink, shadow.embedder(), &rate_limiter, &circuit_breaker, shadow.validator(), &cache, &retry_config, &cancel).await;
+                        }
+                    }
+                    stored
+                })
+                .await;
+                pipeline.record_embeddings_stored(stored);
+                pipeline.set_worker_batch(worker_id, 0);
+                batch.clear();
             }
         }
     }
 }
 
-async fn report_stats_loop(
+/// Apply a provider's configured request limit, which rate-limit hints never raise, and its
+/// circuit breaker settings
+async fn configure_provider_limits(
+    config: &Config,
+    provider: &str,
+    rate_limiter: &RateLimiterManager,
+    circuit_breaker: &CircuitBreakerManager,
+) -> Result<()> {
+    if let Some(rpm) = config.requests_per_minute() {
+        rate_limiter.configure_provider(provider, rpm).await?;
+    }
+    circuit_breaker.configure_service(provider, config.circuit_breaker_config());
+    Ok(())
+}
+
+/// Write circuit breaker snapshots as they change. Runs in the flush phase of shutdown, after
+/// the workers have stopped, and writes whatever they queued before exiting.
+async fn persist_circuit_snapshots(
     client: Arc<SurrealClient>,
+    mut snapshots: mpsc::UnboundedReceiver<CircuitSnapshot>,
+    mut flush_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    let mut flushing = false;
+    loop {
+        let snapshot = if flushing {
+            match snapshots.try_recv() {
+                Ok(snapshot) => snapshot,
+                Err(_) => break,
+            }
+        } else {
+            tokio::select! {
+                snapshot = snapshots.recv() => match snapshot {
+                    Some(snapshot) => snapshot,
+                    None => break,
+                },
+                _ = flush_rx.recv() => {
+                    flushing = true;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = client.save_circuit_snapshot(&snapshot).await {
+            warn!("Failed to persist circuit breaker state for {}: {}", snapshot.service, e);
+        }
+    }
+}
+
+/// Load the most recently generated embeddings into the cache, oldest first so the newest are
+/// the last to be evicted, keyed by the text each was generated from. A failure is logged and
+/// the cache just starts cold.
+async fn warm_cache(cache: &EmbeddingCache, client: &SurrealClient, limit: usize, model: &str) {
+    match client.get_recent_embeddings(limit).await {
+        Ok(rows) => {
+            let loaded = rows.len();
+            for row in rows.into_iter().rev() {
+                let key = EmbeddingCache::cache_key(&row.repo.prepare_text_for_embedding(), model);
+                cache.put(key, row.embedding, model.to_string());
+            }
+            info!(loaded, "Warmed the embedding cache");
+        }
+        Err(e) => warn!("Failed to warm the embedding cache: {}", e),
+    }
+}
+
+/// Make sure the Ollama model is installed (pulling it if allowed) and optionally warm it up.
+/// Problems are logged rather than fatal; the provider probe keeps reporting them on `/health`.
+async fn prepare_ollama_model(embedder: &Embedder, config: &Config) {
+    let model = &config.embedding_model;
+    match embedder.model_available().await {
+        Ok(Some(false)) if config.ollama_auto_pull => {
+            info!("Ollama model {} is not installed, pulling it", model);
+            match embedder.pull_model().await {
+                Ok(()) => info!("Pulled Ollama model {}", model),
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            }
+        }
+        Ok(Some(false)) => {
+            warn!(
+                "Ollama model {} is not installed; run `ollama pull {}` or set OLLAMA_AUTO_PULL=true",
+                model, model
+            );
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Could not check Ollama models: {}", e),
+    }
+
+    if config.ollama_preload {
+        let start = std::time::Instant::now();
+        match embedder.preload().await {
+            Ok(()) => info!("Warmed up Ollama model {} in {:?}", model, start.elapsed()),
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+/// Stats ticks (one a minute) between coverage summaries
+const COVERAGE_REPORT_TICKS: u32 = 10;
+
+async fn report_stats_loop(
+    sources: Vec<(Arc<str>, Arc<dyn RepoSource>)>,
+    pipeline: Arc<PipelineState>,
+    embedder: Arc<Embedder>,
+    mut estimator: ScalingEstimator,
     mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
 ) {
     let mut interval = interval(Duration::from_secs(60));
+    let mut ticks: u32 = 0;
 
     loop {
         tokio::select! {
@@ -404,19 +1361,313 @@ async fn report_stats_loop(
                 break;
             }
             _ = interval.tick() => {
-                match client.get_pending_repos_count().await {
-                    Ok(count) => {
-                        crate::metrics::set_pending_repos(count as i64);
-                        info!(
-                            pending_repos = count,
-                            "Updated statistics"
-                        );
-                    }
-                    Err(e) => {
-                        error!("Failed to get pending repos count: {}", e);
+                // The coverage summary scans whole tables, so it's only logged every few minutes
+                let coverage = ticks.is_multiple_of(COVERAGE_REPORT_TICKS);
+                ticks = ticks.wrapping_add(1);
+                let mut pending_repos = 0;
+                for (tenant, source) in &sources {
+                    if let Some(count) = in_tenant(tenant.clone(), report_tenant_stats(source, coverage)).await {
+                        pending_repos += count;
                     }
                 }
+                pipeline.set_pending_repos(pending_repos);
+
+                // Autoscaling hint from the backlog, the arrival rate and the provider latency
+                let status = pipeline.status();
+                let inputs = estimator.observe(
+                    pending_repos,
+                    pipeline.embeddings_stored(),
+                    embedder.recent_latency(),
+                    status.batch_size,
+                    Duration::from_millis(status.batch_delay_ms),
+                );
+                if let Some(inputs) = inputs {
+                    let desired = estimator.desired_workers(&inputs);
+                    debug!(desired, arrival_rate = inputs.arrival_rate, "Updated autoscaling hint");
+                    pipeline.set_desired_workers(desired);
+                }
+            }
+        }
+    }
+}
+
+/// Export one tenant's pending count and oldest pending age, and with `coverage` log its
+/// coverage summary when the source has one; returns the pending count if it was read
+async fn report_tenant_stats(source: &Arc<dyn RepoSource>, coverage: bool) -> Option<usize> {
+    let summary = if coverage {
+        source.coverage().await.unwrap_or_else(|e| {
+            error!("Failed to get coverage stats: {}", e);
+            None
+        })
+    } else {
+        None
+    };
+    let count = match summary {
+        Some(stats) => {
+            stats.log();
+            Ok(stats.pending)
+        }
+        None => source.pending_count().await,
+    };
+    let pending = match count {
+        Ok(count) => {
+            crate::metrics::set_pending_repos(count as i64);
+            debug!(pending_repos = count, "Updated statistics");
+            Some(count)
+        }
+        Err(e) => {
+            error!("Failed to get pending repos count: {}", e);
+            None
+        }
+    };
+
+    match source.oldest_pending().await {
+        Ok(oldest) => {
+            crate::metrics::set_oldest_pending_age(
+                oldest.map(|updated_at| chrono::Utc::now() - updated_at),
+            );
+        }
+        Err(e) => {
+            error!("Failed to get oldest pending repo: {}", e);
+        }
+    }
+
+    pending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit_breaker::CircuitState, models::LiveAction, rate_limiter::RateLimitHint,
+        surreal_client::{BatchUpdateResult, EmbeddingUpdate},
+    };
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn test_provider_options_reach_limiter_and_breaker() {
+        let config = Config::parse_from([
+            "embed_star",
+            "--embedding-provider",
+            "together",
+            "--together-api-key",
+            "key",
+            "--provider-rpm",
+            "120",
+            "--cb-failure-threshold",
+            "2",
+            "--cb-timeout-secs",
+            "45",
+        ]);
+        let rate_limiter = RateLimiterManager::new();
+        let circuit_breaker = CircuitBreakerManager::new();
+        configure_provider_limits(&config, "together", &rate_limiter, &circuit_breaker)
+            .await
+            .unwrap();
+
+        assert_eq!(rate_limiter.requests_per_minute("together").await, Some(120));
+        let hint = RateLimitHint {
+            requests_per_minute: Some(6000),
+            ..Default::default()
+        };
+        rate_limiter.apply_hint("together", &hint).await.unwrap();
+        assert_eq!(rate_limiter.requests_per_minute("together").await, Some(120));
+
+        circuit_breaker.record_failure("together");
+        assert_eq!(circuit_breaker.get_state("together"), Some(CircuitState::Closed));
+        circuit_breaker.record_failure("together");
+        assert_eq!(circuit_breaker.get_state("together"), Some(CircuitState::Open));
+        let remaining = circuit_breaker.open_remaining("together").unwrap();
+        assert!(remaining > Duration::from_secs(40) && remaining <= Duration::from_secs(45));
+    }
+
+    fn test_repo(id: &str) -> Repo {
+        let now = chrono::Utc::now();
+        Repo {
+            id: surrealdb::RecordId::from(("repo", id)),
+            github_id: 1,
+            name: id.to_string(),
+            full_name: format!("test/{}", id),
+            description: None,
+            url: format!("https://github.com/test/{}", id),
+            stars: 0,
+            language: None,
+            readme: None,
+            owner: crate::models::RepoOwner {
+                login: "test".to_string(),
+                avatar_url: String::new(),
+            },
+            is_private: false,
+            created_at: now,
+            updated_at: now,
+            embedding: None,
+            embedding_generated_at: None,
+            extra: Default::default(),
+        }
+    }
+
+    struct StaticProvider;
+
+    #[async_trait::async_trait]
+    impl crate::embedder::EmbeddingProvider for StaticProvider {
+        async fn generate_embedding(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok((1..=128).map(|i| i as f32 / 128.0).collect())
+        }
+
+        fn model_name(&self) -> &str {
+            "static"
+        }
+    }
+
+    /// Hands out its repos once
+    struct VecSource(parking_lot::Mutex<Vec<Repo>>);
+
+    #[async_trait::async_trait]
+    impl RepoSource for VecSource {
+        async fn fetch_pending(&self, limit: usize) -> Result<Vec<Repo>> {
+            let mut repos = self.0.lock();
+            let take = limit.min(repos.len());
+            Ok(repos.drain(..take).collect())
+        }
+
+        async fn watch(&self) -> Result<mpsc::Receiver<Repo>> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn pending_count(&self) -> Result<usize> {
+            Ok(self.0.lock().len())
+        }
+    }
+
+    #[derive(Default)]
+    struct VecSink(parking_lot::Mutex<Vec<EmbeddingUpdate>>);
+
+    #[async_trait::async_trait]
+    impl EmbeddingSink for VecSink {
+        async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+            let total = updates.len();
+            self.0.lock().extend(updates);
+            Ok(BatchUpdateResult {
+                total,
+                successful: total,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_service_with_injected_components() {
+        let repos = vec![test_repo("injected-1"), test_repo("injected-2")];
+        let source = Arc::new(VecSource(parking_lot::Mutex::new(repos.clone())));
+        let sink = Arc::new(VecSink::default());
+        let mut service = ServiceBuilder::new(Config::for_tests())
+            .with_embedder(Arc::new(Embedder::builder("static", Box::new(StaticProvider)).build()))
+            .with_source(source)
+            .with_sink(sink.clone())
+            .with_signal_handling(false)
+            .build()
+            .await
+            .unwrap();
+        assert!(!service.status().started);
+
+        service.start().unwrap();
+        assert!(service.status().started);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while sink.0.lock().len() < 2 {
+                sleep(Duration::from_millis(10)).await;
             }
+        })
+        .await
+        .expect("The injected sink should receive both embeddings");
+        service.shutdown().await;
+
+        let stored = sink.0.lock();
+        for repo in &repos {
+            assert!(stored.iter().any(|update| update.repo_id == repo.id), "{} not stored", repo.id);
         }
     }
-}
\ No newline at end of file
+
+    /// Reports the removals sent on its channel and nothing else
+    struct RemovalSource {
+        removals: parking_lot::Mutex<Option<mpsc::Receiver<LiveQueryNotification>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl RepoSource for RemovalSource {
+        async fn fetch_pending(&self, _limit: usize) -> Result<Vec<Repo>> {
+            Ok(Vec::new())
+        }
+
+        async fn watch(&self) -> Result<mpsc::Receiver<Repo>> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn pending_count(&self) -> Result<usize> {
+            Ok(0)
+        }
+
+        async fn watch_removals(&self) -> Result<Option<mpsc::Receiver<LiveQueryNotification>>> {
+            Ok(self.removals.lock().take())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_removals_reach_every_sink() {
+        Metrics::register(&Registry::new()).unwrap();
+        let pool = create_pool(Arc::new(Config::for_tests())).await.unwrap();
+        let table_sink = |model: &str| {
+            Arc::new(SurrealClient::new(pool.clone()).with_storage_mode(StorageMode::Table, model))
+        };
+        let (routed, shadow) = (table_sink("removal-multilingual"), table_sink("removal-ab"));
+
+        let repo = test_repo("removed");
+        for sink in [&routed, &shadow] {
+            let update = EmbeddingUpdate {
+                repo_id: repo.id.clone(),
+                embedding: vec![0.6, 0.8].into(),
+                normalized: true,
+                repaired: false,
+                updated_at: repo.updated_at,
+                model: None,
+                language: None,
+            };
+            sink.store_embeddings(vec![update]).await.unwrap();
+        }
+
+        let rows = |pool: Pool| async move {
+            let conn = pool.get().await.unwrap();
+            let mut response = conn
+                .query("SELECT count() FROM repo_embedding WHERE repo = repo:removed GROUP ALL")
+                .await
+                .unwrap();
+            response.take::<Option<usize>>((0, "count")).unwrap().unwrap_or(0)
+        };
+        assert_eq!(rows(pool.clone()).await, 2);
+
+        let (tx, rx) = mpsc::channel(1);
+        let source = Arc::new(RemovalSource {
+            removals: parking_lot::Mutex::new(Some(rx)),
+        });
+        tx.send(LiveQueryNotification {
+            action: LiveAction::Delete,
+            result: repo,
+        })
+        .await
+        .unwrap();
+        drop(tx);
+
+        let primary = Arc::new(SurrealClient::new(pool.clone())) as Arc<dyn EmbeddingSink>;
+        let (_shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+        process_removals(
+            source,
+            vec![primary, routed, shadow],
+            Arc::new(EmbeddingCache::new(10, 60)),
+            "primary".to_string(),
+            shutdown_rx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(rows(pool).await, 0);
+    }
+}
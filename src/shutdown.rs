@@ -1,8 +1,12 @@
 use tokio::sync::broadcast;
 use tokio::task::JoinHandle;
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 use std::time::Duration;
 
+/// Total time tasks get to stop once shutdown starts
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct ShutdownController {
     tx: broadcast::Sender<()>,
@@ -27,7 +31,7 @@ pub struct ShutdownReceiver {
 }
 
 impl ShutdownReceiver {
-    pub async fn wait_for_shutdown(mut self) {
+    pub async fn wait_for_shutdown(&mut self) {
         let _ = self.rx.recv().await;
     }
     
@@ -36,9 +40,37 @@ impl ShutdownReceiver {
     }
 }
 
+/// Order in which [`GracefulShutdown`] stops tasks. Each phase is signalled only after every
+/// task of the previous one has finished, so nothing a later phase depends on goes away early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownPhase {
+    /// Tasks that queue repos, stopped first so no new work arrives
+    Producers,
+    /// Batch workers, which finish (or, past the drain deadline, cancel) their current batch
+    Workers,
+    /// Writers of state the workers produced, such as circuit breaker snapshots
+    Flush,
+    /// Monitoring, metrics and maintenance, stopped last
+    Background,
+}
+
+impl ShutdownPhase {
+    pub const ALL: [ShutdownPhase; 4] = [
+        ShutdownPhase::Producers,
+        ShutdownPhase::Workers,
+        ShutdownPhase::Flush,
+        ShutdownPhase::Background,
+    ];
+}
+
 pub struct GracefulShutdown {
-    tasks: Vec<(String, JoinHandle<()>)>,
+    tasks: Vec<(ShutdownPhase, String, JoinHandle<()>)>,
     controller: ShutdownController,
+    /// One signal per phase, in shutdown order
+    phases: Vec<(ShutdownPhase, broadcast::Sender<()>)>,
+    /// Cancelled once tasks have had `drain_deadline` to finish on their own
+    cancel: CancellationToken,
+    drain_deadline: Duration,
 }
 
 impl GracefulShutdown {
@@ -46,30 +78,76 @@ impl GracefulShutdown {
         Self {
             tasks: Vec::new(),
             controller,
+            phases: ShutdownPhase::ALL
+                .into_iter()
+                .map(|phase| (phase, broadcast::channel(1).0))
+                .collect(),
+            cancel: CancellationToken::new(),
+            drain_deadline: SHUTDOWN_TIMEOUT,
         }
     }
-    
-    pub fn register_task(&mut self, name: String, handle: JoinHandle<()>) {
-        self.tasks.push((name, handle));
+
+    /// How long tasks may keep working after the shutdown signal before in-flight work is cancelled
+    pub fn with_drain_deadline(mut self, deadline: Duration) -> Self {
+        self.drain_deadline = deadline;
+        self
     }
-    
+
+    /// Token that fires at the drain deadline; workers abort provider requests when it does
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Receiver that fires when `phase` is told to stop
+    pub fn subscribe(&self, phase: ShutdownPhase) -> broadcast::Receiver<()> {
+        self.phases
+            .iter()
+            .find(|(p, _)| *p == phase)
+            .map(|(_, signal)| signal.subscribe())
+            .expect("every phase has a signal")
+    }
+
+    pub fn register_task(&mut self, phase: ShutdownPhase, name: String, handle: JoinHandle<()>) {
+        self.tasks.push((phase, name, handle));
+    }
+
     pub async fn shutdown(self, timeout: Duration) {
         info!("Initiating graceful shutdown...");
-        
-        // Signal all tasks to shutdown
+
+        // Wake anything waiting on the service-wide signal
         self.controller.shutdown();
-        
-        // Wait for all tasks with timeout
-        let shutdown_future = async {
-            for (name, handle) in self.tasks {
-                match handle.await {
-                    Ok(_) => info!("Task '{}' shut down successfully", name),
-                    Err(e) => error!("Task '{}' panicked during shutdown: {:?}", name, e),
+
+        // Stop the phases in order, waiting for each one's tasks before signalling the next
+        let Self { tasks, phases, cancel, drain_deadline, .. } = self;
+        let shutdown_future = async move {
+            let mut remaining = tasks;
+            for (phase, signal) in phases {
+                let _ = signal.send(());
+                let (current, rest): (Vec<_>, Vec<_>) =
+                    remaining.into_iter().partition(|(p, _, _)| *p == phase);
+                remaining = rest;
+
+                debug!(phase = ?phase, tasks = current.len(), "Stopping shutdown phase");
+                for (_, name, handle) in current {
+                    match handle.await {
+                        Ok(_) => info!("Task '{}' shut down successfully", name),
+                        Err(e) => error!("Task '{}' panicked during shutdown: {:?}", name, e),
+                    }
                 }
             }
         };
-        
-        match tokio::time::timeout(timeout, shutdown_future).await {
+        tokio::pin!(shutdown_future);
+
+        // Let tasks finish their current work, then cancel whatever is still in flight
+        let drain_deadline = drain_deadline.min(timeout);
+        if tokio::time::timeout(drain_deadline, &mut shutdown_future).await.is_ok() {
+            info!("All tasks shut down successfully");
+            return;
+        }
+        warn!("Drain deadline of {:?} reached, cancelling in-flight work", drain_deadline);
+        cancel.cancel();
+
+        match tokio::time::timeout(timeout.saturating_sub(drain_deadline), shutdown_future).await {
             Ok(_) => info!("All tasks shut down successfully"),
             Err(_) => warn!("Shutdown timeout exceeded, some tasks may not have completed cleanly"),
         }
@@ -78,7 +156,12 @@ impl GracefulShutdown {
 
 pub async fn setup_signal_handlers() -> ShutdownReceiver {
     let (controller, receiver) = ShutdownController::new();
-    
+    listen_for_signals(controller);
+    receiver
+}
+
+/// Trigger `controller` on SIGINT/SIGTERM, so signals and programmatic shutdowns share one channel
+pub fn listen_for_signals(controller: ShutdownController) {
     tokio::spawn(async move {
         let ctrl_c = tokio::signal::ctrl_c();
         
@@ -104,6 +187,50 @@ pub async fn setup_signal_handlers() -> ShutdownReceiver {
         
         controller.shutdown();
     });
-    
-    receiver
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_deadline_cancels_in_flight_work() {
+        let (controller, _receiver) = ShutdownController::new();
+        let mut shutdown = GracefulShutdown::new(controller).with_drain_deadline(Duration::from_millis(20));
+
+        // Ignores the shutdown signal and only stops when cancelled, like a slow provider call
+        let cancel = shutdown.cancellation_token();
+        shutdown.register_task(ShutdownPhase::Workers, "slow".to_string(), tokio::spawn(async move {
+            cancel.cancelled().await;
+        }));
+
+        let token = shutdown.cancellation_token();
+        let started = std::time::Instant::now();
+        shutdown.shutdown(Duration::from_secs(5)).await;
+
+        assert!(token.is_cancelled());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_phases_stop_in_order() {
+        let (controller, _receiver) = ShutdownController::new();
+        let mut shutdown = GracefulShutdown::new(controller);
+        let order = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        // Registered in reverse so only the phases can explain the order
+        for phase in ShutdownPhase::ALL.into_iter().rev() {
+            let mut signal = shutdown.subscribe(phase);
+            let order = order.clone();
+            let handle = tokio::spawn(async move {
+                let _ = signal.recv().await;
+                // Give a later phase the chance to run early if it were signalled too
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                order.lock().push(phase);
+            });
+            shutdown.register_task(phase, format!("{:?}", phase), handle);
+        }
+
+        shutdown.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(*order.lock(), ShutdownPhase::ALL.to_vec());
+    }
+}
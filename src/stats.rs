@@ -0,0 +1,193 @@
+use crate::{
+    config::Config, metrics::Metrics, pool::create_pool, surreal_client::SurrealClient, tenant::current_tenant,
+};
+use prometheus::Registry;
+use serde::Serialize;
+use std::{fmt, sync::Arc};
+use tracing::info;
+
+/// Languages named in the logged summary; the rest are only counted
+const LOGGED_LANGUAGES: usize = 10;
+
+/// Embedding coverage for one language; repos without a language are grouped as "unknown"
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageCoverage {
+    pub language: String,
+    pub total: usize,
+    pub pending: usize,
+}
+
+/// Percentiles of how long pending repos have waited since their last update, in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct BacklogAge {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Result of `stats`, also logged periodically by the service
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CoverageStats {
+    pub total: usize,
+    pub embedded: usize,
+    pub pending: usize,
+    /// Largest languages first
+    pub by_language: Vec<LanguageCoverage>,
+    /// Stored embeddings per model; inline storage only holds the configured model
+    pub by_model: Vec<(String, usize)>,
+    pub backlog_age: Option<BacklogAge>,
+    pub avg_dimensions: Option<f64>,
+}
+
+impl CoverageStats {
+    /// Share of repos with a current embedding, 0-100
+    pub fn coverage_percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        self.total.saturating_sub(self.pending) as f64 * 100.0 / self.total as f64
+    }
+
+    /// Log the summary as one structured line for the current tenant, with the models and the
+    /// largest languages as `name embedded/total` lists
+    pub fn log(&self) {
+        let by_model = self
+            .by_model
+            .iter()
+            .map(|(model, count)| format!("{} {}", model, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut by_language = self
+            .by_language
+            .iter()
+            .take(LOGGED_LANGUAGES)
+            .map(|language| {
+                format!(
+                    "{} {}/{}",
+                    language.language,
+                    language.total.saturating_sub(language.pending),
+                    language.total
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.by_language.len() > LOGGED_LANGUAGES {
+            by_language.push_str(&format!(", {} more", self.by_language.len() - LOGGED_LANGUAGES));
+        }
+        info!(
+            tenant = %current_tenant(),
+            total_repos = self.total,
+            embedded_repos = self.embedded,
+            pending_repos = self.pending,
+            coverage_percent = self.coverage_percent(),
+            backlog_p50_secs = self.backlog_age.as_ref().map(|age| age.p50),
+            backlog_p90_secs = self.backlog_age.as_ref().map(|age| age.p90),
+            backlog_p99_secs = self.backlog_age.as_ref().map(|age| age.p99),
+            backlog_max_secs = self.backlog_age.as_ref().map(|age| age.max),
+            avg_dimensions = self.avg_dimensions,
+            by_model = %by_model,
+            by_language = %by_language,
+            "Coverage report"
+        );
+    }
+}
+
+impl fmt::Display for CoverageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Embedding coverage:")?;
+        writeln!(f, "  Total repos: {}", self.total)?;
+        writeln!(f, "  Embedded: {}", self.embedded)?;
+        writeln!(f, "  Pending: {} ({:.1}% covered)", self.pending, self.coverage_percent())?;
+        match &self.backlog_age {
+            Some(age) => writeln!(
+                f,
+                "  Backlog age: p50 {}, p90 {}, p99 {}, max {}",
+                format_age(age.p50),
+                format_age(age.p90),
+                format_age(age.p99),
+                format_age(age.max)
+            )?,
+            None => writeln!(f, "  Backlog age: no pending repos")?,
+        }
+        match self.avg_dimensions {
+            Some(dimensions) => writeln!(f, "  Average dimensions: {:.1}", dimensions)?,
+            None => writeln!(f, "  Average dimensions: no stored embeddings")?,
+        }
+        writeln!(f, "  By model:")?;
+        for (model, count) in &self.by_model {
+            writeln!(f, "    {}: {}", model, count)?;
+        }
+        writeln!(f, "  By language:")?;
+        for language in &self.by_language {
+            writeln!(
+                f,
+                "    {}: {}/{} embedded",
+                language.language,
+                language.total.saturating_sub(language.pending),
+                language.total
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Seconds as the largest whole unit, e.g. "3d", "5h", "42s"
+fn format_age(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    match seconds {
+        s if s >= 86_400 => format!("{}d", s / 86_400),
+        s if s >= 3_600 => format!("{}h", s / 3_600),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Connect and print embedding coverage for the configured model
+pub async fn run_stats(config: Config) -> anyhow::Result<CoverageStats> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool)
+        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone());
+
+    Ok(client.get_coverage_stats().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_report() {
+        let stats = CoverageStats {
+            total: 200,
+            embedded: 150,
+            pending: 50,
+            by_language: vec![LanguageCoverage {
+                language: "Rust".to_string(),
+                total: 120,
+                pending: 20,
+            }],
+            by_model: vec![("nomic-embed-text".to_string(), 150)],
+            backlog_age: Some(BacklogAge {
+                p50: 90.0,
+                p90: 7_200.0,
+                p99: 200_000.0,
+                max: 200_000.0,
+            }),
+            avg_dimensions: Some(768.0),
+        };
+
+        assert_eq!(stats.coverage_percent(), 75.0);
+        let report = stats.to_string();
+        assert!(report.contains("Pending: 50 (75.0% covered)"));
+        assert!(report.contains("Backlog age: p50 1m, p90 2h, p99 2d, max 2d"));
+        assert!(report.contains("Rust: 100/120 embedded"));
+    }
+}
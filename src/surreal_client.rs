@@ -1,64 +1,380 @@
-use crate::{ models::Repo, pool::{ Pool, PoolExt }, error::{ EmbedError, Result } };
-use serde_json;
-use surrealdb::RecordId;
-use tracing::{ debug, error, info, warn };
-use std::time::Instant;
-#[cfg(test)]
+use crate::{
+    audit::{AuditRecord, BatchRun},
+    batch_jobs::BatchJob,
+    circuit_breaker::CircuitSnapshot,
+    models::{ LiveAction, LiveQueryNotification, Repo },
+    pool::{ Pool, PoolExt },
+    error::{ EmbedError, Result },
+    precision::StoragePrecision,
+    quantization::{ quantize, QuantizationMode },
+    stats::{ BacklogAge, CoverageStats, LanguageCoverage },
+    tenant::{ current_tenant, in_tenant },
+};
+use chrono::{ DateTime, SecondsFormat, Utc };
+use futures::StreamExt;
+use surrealdb::{ Action, Notification, RecordId };
+use tracing::{ debug, error, info, instrument, warn };
+use std::{ str::FromStr, sync::Arc, time::{ Duration, Instant } };
 use deadpool::managed::Object;
 
+/// Idle polls stretch the poll interval up to this many times its configured value
+const MAX_POLL_BACKOFF: u32 = 8;
+
+/// Where embedding vectors are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageMode {
+    /// Vectors are stored on the `repo` record itself
+    #[default]
+    Inline,
+    /// Vectors are stored in `repo_embedding`, one row per repo and model
+    Table,
+}
+
+impl FromStr for StorageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inline" => Ok(StorageMode::Inline),
+            "table" => Ok(StorageMode::Table),
+            other => Err(format!("Unknown embedding storage mode '{}' (expected inline or table)", other)),
+        }
+    }
+}
+
+/// Where reading the repo change feed resumes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChangeCursor {
+    /// First run: changes from this time on
+    Since(DateTime<Utc>),
+    /// The versionstamp after the last change set read
+    Versionstamp(u64),
+}
+
+impl ChangeCursor {
+    /// `SINCE` clause value of `SHOW CHANGES`
+    fn since(&self) -> String {
+        match self {
+            ChangeCursor::Since(at) => format!("d\"{}\"", at.to_rfc3339_opts(SecondsFormat::Micros, true)),
+            ChangeCursor::Versionstamp(versionstamp) => versionstamp.to_string(),
+        }
+    }
+}
+
+/// One transaction's entries in the output of `SHOW CHANGES`
+#[derive(Debug, serde::Deserialize)]
+struct ChangeSet {
+    versionstamp: u64,
+    changes: Vec<RecordChange>,
+}
+
+/// A change entry. Deletions are reported by the removal watcher and table definition
+/// entries carry no record, so only updates (which include creations) are read.
+#[derive(Debug, serde::Deserialize)]
+struct RecordChange {
+    #[serde(default)]
+    update: Option<ChangedRecord>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChangedRecord {
+    id: RecordId,
+}
+
+/// Repos updated in `change_sets`, each once, in feed order
+fn updated_repo_ids(change_sets: &[ChangeSet]) -> Vec<RecordId> {
+    let mut ids: Vec<RecordId> = Vec::new();
+    for change in change_sets.iter().flat_map(|set| &set.changes) {
+        if let Some(record) = &change.update {
+            if !ids.contains(&record.id) {
+                ids.push(record.id.clone());
+            }
+        }
+    }
+    ids
+}
+
+/// Cursor after `change_sets`; `None` when there were none
+fn next_versionstamp(change_sets: &[ChangeSet]) -> Option<u64> {
+    change_sets.iter().map(|set| set.versionstamp).max().map(|last| last + 1)
+}
+
 #[derive(Clone)]
 pub struct SurrealClient {
     pool: Pool,
+    quantization: QuantizationMode,
+    quantized_only: bool,
+    precision: StoragePrecision,
+    storage_mode: StorageMode,
+    model: String,
+    write_chunk_size: usize,
+    audit: bool,
+    poll_interval: Duration,
+    fetch_batch_size: usize,
+    history_limit: usize,
+    history_vectors: bool,
+    change_feed_days: u32,
+    language_metadata: bool,
+    routed_model: Option<String>,
 }
 
 impl SurrealClient {
     pub fn new(pool: Pool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            quantization: QuantizationMode::None,
+            quantized_only: false,
+            precision: StoragePrecision::F32,
+            storage_mode: StorageMode::Inline,
+            model: String::new(),
+            write_chunk_size: 50,
+            audit: false,
+            poll_interval: Duration::from_secs(5),
+            fetch_batch_size: 50,
+            history_limit: 0,
+            history_vectors: true,
+            change_feed_days: 0,
+            language_metadata: false,
+            routed_model: None,
+        }
+    }
+
+    /// How often `setup_live_query` polls and how many pending repos each poll fetches. Polls
+    /// that find nothing new or fail back off up to `MAX_POLL_BACKOFF` times the interval.
+    pub fn with_polling(mut self, interval: Duration, fetch_batch_size: usize) -> Self {
+        self.poll_interval = interval.max(Duration::from_millis(1));
+        self.fetch_batch_size = fetch_batch_size.max(1);
+        self
+    }
+
+    /// The wait after `delay` when a poll found nothing new: doubled, within the configured
+    /// interval and `MAX_POLL_BACKOFF` times it
+    fn idle_poll_delay(&self, delay: Duration) -> Duration {
+        (delay * 2).clamp(self.poll_interval, self.poll_interval * MAX_POLL_BACKOFF)
+    }
+
+    /// Have `setup_live_query` follow the repo table's change feed, kept `retention_days`,
+    /// instead of polling (0 keeps polling)
+    pub fn with_change_feed(mut self, retention_days: u32) -> Self {
+        self.change_feed_days = retention_days;
+        self
+    }
+
+    /// Write a row per processed repo to `embedding_audit`
+    pub fn with_audit(mut self, enabled: bool) -> Self {
+        self.audit = enabled;
+        self
+    }
+
+    pub fn audit_enabled(&self) -> bool {
+        self.audit
+    }
+
+    /// Maximum number of updates written in a single transaction
+    pub fn with_write_chunk_size(mut self, size: usize) -> Self {
+        self.write_chunk_size = size.max(1);
+        self
+    }
+
+    /// Before overwriting an embedding, copy it to `repo_embedding_history`, keeping the `limit`
+    /// newest entries per repo (0 keeps no history). Without `keep_vectors` only the model and
+    /// timestamps are kept.
+    pub fn with_history(mut self, limit: usize, keep_vectors: bool) -> Self {
+        self.history_limit = limit;
+        self.history_vectors = keep_vectors;
+        self
+    }
+
+    /// Record the detected language of each repo's description as `description_language`,
+    /// next to the vector
+    pub fn with_language_metadata(mut self, enabled: bool) -> Self {
+        self.language_metadata = enabled;
+        self
+    }
+
+    /// Choose where vectors are stored; `model` keys rows in the `repo_embedding` table
+    pub fn with_storage_mode(mut self, mode: StorageMode, model: impl Into<String>) -> Self {
+        self.storage_mode = mode;
+        self.model = model.into();
+        self
+    }
+
+    /// Repos embedded by `model` into its `repo_embedding` rows count as embedded, so repos the
+    /// language router sends to the multilingual model aren't picked up again
+    pub fn with_routed_model(mut self, model: Option<String>) -> Self {
+        self.routed_model = model;
+        self
+    }
+
+    /// Condition selecting repos whose embedding is missing or stale
+    fn pending_condition(&self) -> String {
+        let pending = match self.storage_mode {
+            StorageMode::Inline =>
+                "(embedding IS NONE AND embedding_quantized IS NONE) OR (updated_at > embedding_generated_at)",
+            StorageMode::Table =>
+                "type::thing('repo_embedding', [id, $model]).generated_at IS NONE \
+                 OR updated_at > type::thing('repo_embedding', [id, $model]).generated_at",
+        };
+        match self.routed_model {
+            Some(_) =>
+                format!(
+                    "({}) AND (type::thing('repo_embedding', [id, $routed_model]).generated_at IS NONE \
+                     OR updated_at > type::thing('repo_embedding', [id, $routed_model]).generated_at)",
+                    pending
+                ),
+            None => pending.to_string(),
+        }
+    }
+
+    /// Statements writing one embedding; parameters are suffixed so several can share a query.
+    /// The write is the last of [`Self::statements_per_update`] statements, after archiving the
+    /// previous vector when history is kept.
+    fn update_statement(&self, suffix: &str) -> String {
+        let language = if self.language_metadata {
+            format!("description_language = $language{0}, ", suffix)
+        } else {
+            String::new()
+        };
+        let update = match self.storage_mode {
+            StorageMode::Inline =>
+                format!(
+                    "UPDATE $repo{0} SET embedding = $embedding{0}, embedding_normalized = $normalized{0}, \
+                     embedding_repaired = $repaired{0}, embedding_quantization = $quantization{0}, \
+                     embedding_quantized = $quantized{0}, embedding_scale = $scale{0}, \
+                     embedding_precision = $precision{0}, {1}\
+                     embedding_model = $model{0}, embedding_generated_at = time::now() RETURN VALUE id;",
+                    suffix, language
+                ),
+            StorageMode::Table =>
+                format!(
+                    "UPSERT type::thing('repo_embedding', [$repo{0}, $model{0}]) SET repo = $repo{0}, model = $model{0}, \
+                     embedding = $embedding{0}, normalized = $normalized{0}, repaired = $repaired{0}, \
+                     quantization = $quantization{0}, quantized = $quantized{0}, {1}\
+                     scale = $scale{0}, precision = $precision{0}, generated_at = time::now() RETURN VALUE repo;",
+                    suffix, language
+                ),
+        };
+        if self.history_limit == 0 {
+            return update;
+        }
+
+        let vector = if self.history_vectors { "embedding" } else { "NONE AS embedding" };
+        let archive = match self.storage_mode {
+            StorageMode::Inline =>
+                format!(
+                    "INSERT INTO repo_embedding_history (SELECT id AS repo, embedding_model AS model, {1}, \
+                     embedding_normalized AS normalized, embedding_generated_at AS generated_at, \
+                     time::now() AS archived_at FROM $repo{0} WHERE embedding_generated_at IS NOT NONE) RETURN NONE;",
+                    suffix, vector
+                ),
+            StorageMode::Table =>
+                format!(
+                    "INSERT INTO repo_embedding_history (SELECT repo, model, {1}, normalized, generated_at, \
+                     time::now() AS archived_at FROM type::thing('repo_embedding', [$repo{0}, $model{0}])) RETURN NONE;",
+                    suffix, vector
+                ),
+        };
+        format!(
+            "{1}\nDELETE (SELECT id, archived_at FROM repo_embedding_history WHERE repo = $repo{0} \
+             ORDER BY archived_at DESC START $history_limit).id RETURN NONE;\n{2}",
+            suffix, archive, update
+        )
+    }
+
+    /// Number of statements [`Self::update_statement`] produces
+    fn statements_per_update(&self) -> usize {
+        if self.history_limit == 0 { 1 } else { 3 }
+    }
+
+    /// Store a quantized copy of each embedding, optionally dropping the float vector
+    pub fn with_quantization(mut self, mode: QuantizationMode, quantized_only: bool) -> Self {
+        self.quantization = mode;
+        self.quantized_only = quantized_only && mode != QuantizationMode::None;
+        self
+    }
+
+    /// Round the float vector to `precision` before writing it
+    pub fn with_precision(mut self, precision: StoragePrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Model an update is recorded under
+    fn model_for<'a>(&'a self, update: &'a EmbeddingUpdate) -> &'a str {
+        match (self.storage_mode, update.model.as_deref()) {
+            (StorageMode::Inline, Some(model)) => model,
+            _ => &self.model,
+        }
+    }
+
+    /// Split an embedding into the values written to the float and quantized fields
+    fn storage_fields(&self, embedding: &[f32]) -> StoredEmbedding {
+        let quantized = quantize(self.quantization, embedding);
+        StoredEmbedding {
+            quantization: quantized.as_ref().map(|q| q.mode().as_str().to_string()),
+            values: quantized.as_ref().map(|q| q.stored_values()),
+            scale: quantized.as_ref().and_then(|q| q.scale()),
+            embedding: if self.quantized_only { None } else { Some(self.precision.round(embedding)) },
+            precision: if self.quantized_only { None } else { Some(self.precision.as_str()) },
+        }
     }
 
     pub async fn update_repo_embedding(
         &self,
         repo_id: &RecordId,
-        embedding: Vec<f32>
+        embedding: &[f32],
+        normalized: bool,
+        repaired: bool
+    ) -> Result<()> {
+        self.write_embedding(repo_id, embedding, normalized, repaired, &self.model, None).await
+    }
+
+    /// Write one embedding generated by `model`
+    async fn write_embedding(
+        &self,
+        repo_id: &RecordId,
+        embedding: &[f32],
+        normalized: bool,
+        repaired: bool,
+        model: &str,
+        language: Option<&str>,
     ) -> Result<()> {
         // Get a connection from the pool
         let conn = self.pool
             .get().await
             .map_err(|e|
-                EmbedError::Database(
+                EmbedError::from(
                     surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
                 )
             )?;
 
-        let query =
-            r#"
-            UPDATE $repo_id SET
-                embedding = $embedding,
-                embedding_generated_at = time::now()
-        "#;
+        let dimensions = embedding.len();
+        let stored = self.storage_fields(embedding);
 
         let mut response = conn
-            .query(query)
-            .bind(("repo_id", repo_id.clone()))
-            .bind(("embedding", embedding)).await?;
-        let result: Option<Repo> = response.take(0)?;
+            .query(self.update_statement(""))
+            .bind(("repo", repo_id.clone()))
+            .bind(("model", model.to_string()))
+            .bind(("language", language.map(str::to_string)))
+            .bind(("embedding", stored.embedding))
+            .bind(("normalized", normalized))
+            .bind(("repaired", repaired))
+            .bind(("quantization", stored.quantization))
+            .bind(("quantized", stored.values))
+            .bind(("scale", stored.scale))
+            .bind(("precision", stored.precision))
+            .bind(("history_limit", self.history_limit)).await?;
+        let result: Option<RecordId> = response.take(self.statements_per_update() - 1)?;
 
         match result {
-            Some(repo) => {
-                debug!(
-                    "Updated embedding for repo {}: {} dimensions",
-                    repo.full_name,
-                    repo.embedding
-                        .as_ref()
-                        .map(|e| e.len())
-                        .unwrap_or(0)
-                );
+            Some(id) => {
+                debug!("Updated embedding for repo {}: {} dimensions", id, dimensions);
                 Ok(())
             }
             None => {
                 warn!("Failed to update embedding for repo {:?}", repo_id);
                 Err(
-                    EmbedError::Database(
+                    EmbedError::from(
                         surrealdb::Error::Api(
                             surrealdb::error::Api::InternalError(
                                 format!("Record not found and could not be updated: {}", repo_id)
@@ -70,31 +386,50 @@ impl SurrealClient {
         }
     }
 
+    #[instrument(name = "db.fetch_repos", skip(self))]
     pub async fn get_repos_needing_embeddings(&self, limit: usize) -> Result<Vec<Repo>> {
         // Get a connection from the pool
         let conn = self.pool
             .get().await
             .map_err(|e|
-                EmbedError::Database(
+                EmbedError::from(
                     surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
                 )
             )?;
 
-        let query =
-            r#"
-            SELECT * FROM repo
-            WHERE embedding IS NONE
-                OR (updated_at > embedding_generated_at)
-            LIMIT $limit
-        "#;
+        let query = format!("SELECT * FROM repo WHERE {} LIMIT $limit", self.pending_condition());
+
+        let mut response = conn
+            .query(query)
+            .bind(("limit", limit))
+            .bind(("model", self.model.clone()))
+            .bind(("routed_model", self.routed_model.clone())).await?;
+        let repos: Vec<Repo> = response.take(0)?;
+
+        Ok(repos)
+    }
 
-        let mut response = conn.query(query).bind(("limit", limit)).await?;
+    /// Fetch a page of all repos, ordered by record id and without their vectors
+    pub async fn get_repos(&self, start: usize, limit: usize) -> Result<Vec<Repo>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let mut response = conn
+            .query("SELECT * OMIT embedding, embedding_quantized FROM repo START $start LIMIT $limit")
+            .bind(("start", start))
+            .bind(("limit", limit)).await?;
         let repos: Vec<Repo> = response.take(0)?;
 
         Ok(repos)
     }
 
     pub async fn setup_live_query(&self) -> Result<tokio::sync::mpsc::Receiver<Repo>> {
+        if self.change_feed_days > 0 {
+            return self.follow_change_feed().await;
+        }
+
         // For now, we'll use a polling approach instead of live queries
         // as the API for live queries has changed significantly
         let (tx, rx) = tokio::sync::mpsc::channel(100);
@@ -102,15 +437,22 @@ impl SurrealClient {
         info!("Starting polling for repos needing embeddings");
 
         let client = self.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+        // Keep labelling the poller's metrics with the tenant that started it
+        tokio::spawn(in_tenant(current_tenant(), async move {
+            let mut delay = client.poll_interval;
+            // Keyed by the id string: `RecordId` has interior mutability, so it makes a poor hash key
             let mut processed_ids = std::collections::HashSet::new();
             let mut clear_counter = 0;
             const MAX_PROCESSED_IDS: usize = 10000;
-            const CLEAR_INTERVAL: u32 = 100; // Clear every 100 iterations (500 seconds)
+            const CLEAR_INTERVAL: u32 = 100; // Clear every 100 polls
 
             loop {
-                interval.tick().await;
+                tokio::time::sleep(delay).await;
+                // The consumer stopped (e.g. during shutdown), so stop polling before the pool closes
+                if tx.is_closed() {
+                    debug!("Repo poll receiver dropped, stopping polling");
+                    return;
+                }
                 clear_counter += 1;
 
                 // Periodically clear the processed IDs to prevent unbounded growth
@@ -120,24 +462,174 @@ impl SurrealClient {
                     clear_counter = 0;
                 }
 
-                match client.get_repos_needing_embeddings(50).await {
+                match client.get_repos_needing_embeddings(client.fetch_batch_size).await {
                     Ok(repos) => {
+                        let mut found = false;
                         for repo in repos {
-                            if !processed_ids.contains(&repo.id) {
-                                processed_ids.insert(repo.id.clone());
+                            if processed_ids.insert(repo.id.to_string()) {
+                                found = true;
                                 if tx.send(repo).await.is_err() {
                                     error!("Failed to send repo through channel");
                                     return;
                                 }
                             }
                         }
+
+                        // Back off while idle, return to the base interval as soon as work appears
+                        delay = if found { client.poll_interval } else { client.idle_poll_delay(delay) };
                     }
                     Err(e) => {
                         error!("Error fetching repos needing embeddings: {}", e);
+                        delay = client.idle_poll_delay(delay);
                     }
                 }
             }
-        });
+        }));
+
+        Ok(rx)
+    }
+
+    /// Queue updated repos that need an embedding from the repo table's change feed, resuming
+    /// from the cursor persisted for this model. The first run starts from now; the startup
+    /// backlog query covers everything before, as it does for repos queued but not processed
+    /// when the service stopped.
+    async fn follow_change_feed(&self) -> Result<tokio::sync::mpsc::Receiver<Repo>> {
+        self.enable_change_feed().await?;
+        let mut cursor = match self.get_change_feed_cursor().await? {
+            Some(versionstamp) => ChangeCursor::Versionstamp(versionstamp),
+            None => ChangeCursor::Since(Utc::now()),
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+
+        info!(cursor = ?cursor, "Following the repo change feed");
+
+        let client = self.clone();
+        tokio::spawn(in_tenant(current_tenant(), async move {
+            let mut delay = client.poll_interval;
+            loop {
+                tokio::time::sleep(delay).await;
+                if tx.is_closed() {
+                    debug!("Repo change receiver dropped, stopping change feed");
+                    return;
+                }
+
+                // Read again straight away while changes keep coming, back off while idle
+                delay = match client.forward_repo_changes(&mut cursor, &tx).await {
+                    Ok(true) => Duration::ZERO,
+                    Ok(false) => client.idle_poll_delay(delay),
+                    Err(e) => {
+                        error!("Error reading the repo change feed: {}", e);
+                        client.idle_poll_delay(delay)
+                    }
+                };
+            }
+        }));
+
+        Ok(rx)
+    }
+
+    /// Record changes to the repo table for `change_feed_days`. Changes made before this ran
+    /// aren't in the feed.
+    async fn enable_change_feed(&self) -> Result<()> {
+        let conn = self.get_connection().await?;
+        conn.query(format!("ALTER TABLE repo CHANGEFEED {}d", self.change_feed_days))
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    /// Send the repos among the next `fetch_batch_size` change sets after `cursor` that still
+    /// need an embedding (our own writes show up as updates too), then persist the advanced
+    /// cursor. Returns whether there were any changes.
+    async fn forward_repo_changes(&self, cursor: &mut ChangeCursor, tx: &tokio::sync::mpsc::Sender<Repo>) -> Result<bool> {
+        let conn = self.get_connection().await?;
+        let mut response = conn
+            .query(format!("SHOW CHANGES FOR TABLE repo SINCE {} LIMIT {}", cursor.since(), self.fetch_batch_size))
+            .await?;
+        let change_sets: Vec<ChangeSet> = response.take(0)?;
+        let Some(next) = next_versionstamp(&change_sets) else {
+            return Ok(false);
+        };
+
+        let ids = updated_repo_ids(&change_sets);
+        if !ids.is_empty() {
+            let query = format!("SELECT * FROM $ids WHERE {}", self.pending_condition());
+            let mut response = conn
+                .query(query)
+                .bind(("ids", ids))
+                .bind(("model", self.model.clone()))
+                .bind(("routed_model", self.routed_model.clone())).await?;
+            let repos: Vec<Repo> = response.take(0)?;
+            for repo in repos {
+                if tx.send(repo).await.is_err() {
+                    return Ok(true);
+                }
+            }
+        }
+
+        conn.query("UPSERT type::thing('change_feed_cursor', $model) SET versionstamp = $versionstamp RETURN NONE")
+            .bind(("model", self.model.clone()))
+            .bind(("versionstamp", next)).await?
+            .check()?;
+        *cursor = ChangeCursor::Versionstamp(next);
+        Ok(true)
+    }
+
+    /// Versionstamp the change feed resumes from for this model
+    pub async fn get_change_feed_cursor(&self) -> Result<Option<u64>> {
+        let conn = self.get_connection().await?;
+        let mut response = conn
+            .query("RETURN type::thing('change_feed_cursor', $model).versionstamp")
+            .bind(("model", self.model.clone())).await?;
+        let versionstamp: Option<u64> = response.take(0)?;
+        Ok(versionstamp)
+    }
+
+    /// Follow the repo table with a live query and report deletions and repos made private.
+    /// The live query holds a pooled connection until the receiver is dropped.
+    pub async fn watch_repo_removals(&self) -> Result<tokio::sync::mpsc::Receiver<LiveQueryNotification>> {
+        let conn = self.get_connection().await?;
+        let mut stream = conn.select::<Vec<Repo>>("repo").live().await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+
+        info!("Watching for deleted and private repos");
+
+        tokio::spawn(in_tenant(current_tenant(), async move {
+            let _conn = conn;
+            loop {
+                let notification = tokio::select! {
+                    _ = tx.closed() => {
+                        debug!("Removal receiver dropped, stopping live query");
+                        return;
+                    }
+                    notification = stream.next() => notification,
+                };
+                let notification: Notification<Repo> = match notification {
+                    Some(Ok(notification)) => notification,
+                    Some(Err(e)) => {
+                        warn!("Skipping unreadable repo change: {}", e);
+                        continue;
+                    }
+                    None => {
+                        warn!("Repo live query ended");
+                        return;
+                    }
+                };
+
+                let action = match notification.action {
+                    Action::Delete => LiveAction::Delete,
+                    Action::Update if notification.data.is_private => LiveAction::Update,
+                    _ => continue,
+                };
+                let change = LiveQueryNotification {
+                    action,
+                    result: notification.data,
+                };
+                if tx.send(change).await.is_err() {
+                    return;
+                }
+            }
+        }));
 
         Ok(rx)
     }
@@ -147,7 +639,7 @@ impl SurrealClient {
         let conn = self.pool
             .get().await
             .map_err(|e|
-                EmbedError::Database(
+                EmbedError::from(
                     surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
                 )
             )?;
@@ -172,14 +664,18 @@ impl SurrealClient {
         let conn = self.pool
             .get().await
             .map_err(|e|
-                EmbedError::Database(
+                EmbedError::from(
                     surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
                 )
             )?;
 
-        let mut response = conn.query(
-            "SELECT count() FROM repo WHERE embedding IS NOT NONE GROUP ALL"
-        ).await?;
+        let query = match self.storage_mode {
+            StorageMode::Inline =>
+                "SELECT count() FROM repo WHERE embedding IS NOT NONE OR embedding_quantized IS NOT NONE GROUP ALL",
+            StorageMode::Table =>
+                "SELECT count() FROM repo_embedding WHERE model = $model GROUP ALL",
+        };
+        let mut response = conn.query(query).bind(("model", self.model.clone())).await?;
         // SurrealDB 2.3 returns count as { "count": value }
         let result: Option<serde_json::Value> = response.take(0)?;
         match result {
@@ -199,19 +695,16 @@ impl SurrealClient {
         let conn = self.pool
             .get().await
             .map_err(|e|
-                EmbedError::Database(
+                EmbedError::from(
                     surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
                 )
             )?;
 
-        let query =
-            r#"
-            SELECT count() FROM repo
-            WHERE embedding IS NONE
-                OR (updated_at > embedding_generated_at)
-            GROUP ALL
-        "#;
-        let mut response = conn.query(query).await?;
+        let query = format!("SELECT count() FROM repo WHERE {} GROUP ALL", self.pending_condition());
+        let mut response = conn
+            .query(query)
+            .bind(("model", self.model.clone()))
+            .bind(("routed_model", self.routed_model.clone())).await?;
         // SurrealDB 2.3 returns count as { "count": value }
         let result: Option<serde_json::Value> = response.take(0)?;
         match result {
@@ -226,7 +719,137 @@ impl SurrealClient {
         }
     }
 
-    /// Batch update multiple repository embeddings in a single transaction
+    /// `updated_at` of the least recently updated repo still waiting for an embedding
+    pub async fn get_oldest_pending_updated_at(&self) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.pool
+            .get().await
+            .map_err(|e|
+                EmbedError::from(
+                    surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+                )
+            )?;
+
+        let query = format!(
+            "SELECT VALUE updated_at FROM repo WHERE {} ORDER BY updated_at ASC LIMIT 1",
+            self.pending_condition()
+        );
+        let mut response = conn
+            .query(query)
+            .bind(("model", self.model.clone()))
+            .bind(("routed_model", self.routed_model.clone())).await?;
+        let oldest: Vec<DateTime<Utc>> = response.take(0)?;
+        Ok(oldest.into_iter().next())
+    }
+
+    /// Totals, per-language and per-model breakdowns, backlog age percentiles and average
+    /// dimensions. Scans the whole table, so call it sparingly.
+    pub async fn get_coverage_stats(&self) -> Result<CoverageStats> {
+        // Before taking a connection, so this works with a pool of one
+        let embedded = self.get_embedded_repos_count().await?;
+
+        let conn = self.pool
+            .get().await
+            .map_err(|e|
+                EmbedError::from(
+                    surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+                )
+            )?;
+
+        let pending = self.pending_condition();
+        let (stored, vector) = match self.storage_mode {
+            StorageMode::Inline => (
+                "repo WHERE embedding IS NOT NONE OR embedding_quantized IS NOT NONE",
+                "embedding ?? embedding_quantized",
+            ),
+            StorageMode::Table => (
+                "repo_embedding WHERE model = $model AND (embedding IS NOT NONE OR quantized IS NOT NONE)",
+                "embedding ?? quantized",
+            ),
+        };
+        let query = format!(
+            "SELECT language, count() AS total FROM repo GROUP BY language;
+             SELECT language, count() AS pending FROM repo WHERE {pending} GROUP BY language;
+             LET $ages = (SELECT VALUE time::unix(time::now()) - time::unix(updated_at) FROM repo WHERE {pending});
+             RETURN IF array::len($ages) > 0 THEN {{
+                 p50: math::percentile($ages, 50),
+                 p90: math::percentile($ages, 90),
+                 p99: math::percentile($ages, 99),
+                 max: math::max($ages)
+             }} ELSE NONE END;
+             RETURN math::mean((SELECT VALUE array::len({vector}) FROM {stored}));",
+        );
+        let mut response = conn
+            .query(query)
+            .bind(("model", self.model.clone()))
+            .bind(("routed_model", self.routed_model.clone())).await?;
+
+        let totals: Vec<serde_json::Value> = response.take(0)?;
+        let pending_rows: Vec<serde_json::Value> = response.take(1)?;
+        let age: Option<serde_json::Value> = response.take(3)?;
+        let avg_dimensions: Option<serde_json::Value> = response.take(4)?;
+
+        let language_of = |row: &serde_json::Value| {
+            row.get("language")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string()
+        };
+        let count_of = |row: &serde_json::Value, field: &str| {
+            row.get(field).and_then(|v| v.as_u64()).unwrap_or(0) as usize
+        };
+        let mut by_language: Vec<LanguageCoverage> = totals
+            .iter()
+            .map(|row| LanguageCoverage {
+                language: language_of(row),
+                total: count_of(row, "total"),
+                pending: 0,
+            })
+            .collect();
+        for row in &pending_rows {
+            let language = language_of(row);
+            if let Some(entry) = by_language.iter_mut().find(|entry| entry.language == language) {
+                entry.pending = count_of(row, "pending");
+            }
+        }
+        by_language.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.language.cmp(&b.language)));
+
+        let by_model = match self.storage_mode {
+            StorageMode::Inline => vec![(self.model.clone(), embedded)],
+            StorageMode::Table => {
+                let mut response = conn
+                    .query("SELECT model, count() AS count FROM repo_embedding GROUP BY model")
+                    .await?;
+                let rows: Vec<serde_json::Value> = response.take(0)?;
+                rows.iter()
+                    .map(|row| {
+                        let model = row.get("model").and_then(|v| v.as_str()).unwrap_or("unknown");
+                        (model.to_string(), count_of(row, "count"))
+                    })
+                    .collect()
+            }
+        };
+
+        let number = |value: &serde_json::Value, field: &str| {
+            value.get(field).and_then(|v| v.as_f64()).unwrap_or(0.0)
+        };
+        Ok(CoverageStats {
+            total: by_language.iter().map(|entry| entry.total).sum(),
+            embedded,
+            pending: by_language.iter().map(|entry| entry.pending).sum(),
+            by_language,
+            by_model,
+            backlog_age: age.filter(|age| age.is_object()).map(|age| BacklogAge {
+                p50: number(&age, "p50"),
+                p90: number(&age, "p90"),
+                p99: number(&age, "p99"),
+                max: number(&age, "max"),
+            }),
+            avg_dimensions: avg_dimensions.and_then(|v| v.as_f64()).filter(|v| v.is_finite()),
+        })
+    }
+
+    /// Batch update repository embeddings, one transaction per chunk of `write_chunk_size` updates
+    #[instrument(name = "db.write_embeddings", skip_all, fields(updates = updates.len()))]
     pub async fn batch_update_embeddings(
         &self,
         updates: Vec<EmbeddingUpdate>
@@ -236,33 +859,40 @@ impl SurrealClient {
         }
 
         let start = Instant::now();
-        let total = updates.len();
-
-        // Try to use proper batch update with transaction
-        match self.batch_update_with_transaction(updates.clone()).await {
-            Ok(successful) => {
-                Ok(BatchUpdateResult {
-                    total,
-                    successful,
-                    failed: total - successful,
-                    duration: start.elapsed(),
-                })
-            }
-            Err(e) => {
-                warn!("Batch update failed, falling back to individual updates: {}", e);
-                // Fallback to individual updates if batch fails
-                self.fallback_individual_updates(updates).await
+        let mut result = BatchUpdateResult {
+            total: updates.len(),
+            ..Default::default()
+        };
+
+        for chunk in updates.chunks(self.write_chunk_size) {
+            match self.batch_update_with_transaction(chunk).await {
+                Ok(outcomes) => {
+                    for (update, outcome) in chunk.iter().zip(outcomes) {
+                        self.record_outcome(update, outcome, &mut result);
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Batch update of {} records failed, falling back to individual updates: {}",
+                        chunk.len(),
+                        e
+                    );
+                    self.fallback_individual_updates(chunk, &mut result).await;
+                }
             }
         }
+
+        result.duration = start.elapsed();
+        Ok(result)
     }
 
-    /// Perform batch updates using a transaction
+    /// Perform one chunk of updates in a transaction and report the outcome of each statement
     async fn batch_update_with_transaction(
         &self,
-        updates: Vec<EmbeddingUpdate>
-    ) -> Result<usize> {
+        updates: &[EmbeddingUpdate]
+    ) -> Result<Vec<std::result::Result<(), String>>> {
         let conn = self.pool.get().await
-            .map_err(|e| EmbedError::Database(
+            .map_err(|e| EmbedError::from(
                 surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
             ))?;
 
@@ -270,60 +900,537 @@ impl SurrealClient {
         let mut query = String::from("BEGIN TRANSACTION;\n");
         
         for (idx, _) in updates.iter().enumerate() {
-            query.push_str(&format!(
-                "UPDATE $repo_{} SET embedding = $embedding_{}, embedding_generated_at = time::now();\n",
-                idx, idx
-            ));
+            query.push_str(&self.update_statement(&format!("_{}", idx)));
+            query.push('\n');
         }
         
         query.push_str("COMMIT TRANSACTION;");
 
         // Create query and bind parameters
-        let mut bound_query = conn.query(query);
+        let mut bound_query = conn
+            .query(query)
+            .bind(("history_limit", self.history_limit));
         for (idx, update) in updates.iter().enumerate() {
+            let stored = self.storage_fields(&update.embedding);
             bound_query = bound_query
                 .bind((format!("repo_{}", idx), update.repo_id.clone()))
-                .bind((format!("embedding_{}", idx), update.embedding.clone()));
+                .bind((format!("model_{}", idx), self.model_for(update).to_string()))
+                .bind((format!("language_{}", idx), update.language.clone()))
+                .bind((format!("embedding_{}", idx), stored.embedding))
+                .bind((format!("normalized_{}", idx), update.normalized))
+                .bind((format!("repaired_{}", idx), update.repaired))
+                .bind((format!("quantization_{}", idx), stored.quantization))
+                .bind((format!("quantized_{}", idx), stored.values))
+                .bind((format!("scale_{}", idx), stored.scale))
+                .bind((format!("precision_{}", idx), stored.precision));
         }
 
         // Execute the transaction
-        let _response = bound_query.await?;
-        
-        // Count successful updates
-        let successful = updates.len(); // If transaction succeeds, all updates succeeded
-        
-        Ok(successful)
+        let mut response = bound_query.await?;
+
+        // A failed statement cancels the whole transaction, so let the caller retry record by record
+        let mut errors = response.take_errors();
+        if let Some(idx) = errors.keys().min().copied() {
+            return Err(EmbedError::from(errors.remove(&idx).expect("key exists")));
+        }
+
+        // BEGIN/COMMIT produce no results, so the last statement of group `idx` is the update
+        // for `updates[idx]`
+        let per_update = self.statements_per_update();
+        let mut outcomes = Vec::with_capacity(updates.len());
+        for (idx, update) in updates.iter().enumerate() {
+            let outcome = match response.take::<Option<RecordId>>(idx * per_update + per_update - 1) {
+                Ok(Some(_)) => Ok(()),
+                Ok(None) => Err(format!("Record not found and could not be updated: {}", update.repo_id)),
+                Err(e) => Err(e.to_string()),
+            };
+            outcomes.push(outcome);
+        }
+
+        Ok(outcomes)
     }
 
-    /// Fallback to individual updates if batch update fails
+    /// Fallback to individual updates if a chunk's transaction fails
     async fn fallback_individual_updates(
         &self,
-        updates: Vec<EmbeddingUpdate>
-    ) -> Result<BatchUpdateResult> {
-        let start = Instant::now();
-        let mut successful = 0;
-        let mut failed = 0;
-
+        updates: &[EmbeddingUpdate],
+        result: &mut BatchUpdateResult
+    ) {
         for update in updates {
-            match
-                self.update_repo_embedding(&update.repo_id, update.embedding).await
-            {
-                Ok(_) => {
-                    successful += 1;
-                }
-                Err(e) => {
-                    error!("Failed to update embedding for {:?}: {}", update.repo_id, e);
-                    failed += 1;
-                }
+            let outcome = self
+                .write_embedding(
+                    &update.repo_id,
+                    &update.embedding,
+                    update.normalized,
+                    update.repaired,
+                    self.model_for(update),
+                    update.language.as_deref(),
+                ).await
+                .map_err(|e| e.to_string());
+            self.record_outcome(update, outcome, result);
+        }
+    }
+
+    fn record_outcome(
+        &self,
+        update: &EmbeddingUpdate,
+        outcome: std::result::Result<(), String>,
+        result: &mut BatchUpdateResult
+    ) {
+        if outcome.is_ok() {
+            crate::metrics::record_embedding_freshness_lag(self.model_for(update), Utc::now() - update.updated_at);
+        }
+        result.record(&update.repo_id, outcome);
+    }
+
+    /// Append audit records; a no-op unless auditing is enabled
+    pub async fn write_audit_records(&self, records: Vec<AuditRecord>) -> Result<()> {
+        if !self.audit || records.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query("INSERT INTO embedding_audit $records RETURN NONE")
+            .bind(("records", records))
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    /// Append a batch summary to `batch_run`; a no-op unless auditing is enabled
+    pub async fn write_batch_run(&self, run: &BatchRun) -> Result<()> {
+        if !self.audit {
+            return Ok(());
+        }
+
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query("CREATE batch_run CONTENT $run RETURN NONE")
+            .bind(("run", run.clone()))
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    /// Delete audit records and batch summaries older than `retention`, returning how many
+    /// audit records were removed
+    pub async fn prune_audit_records(&self, retention: std::time::Duration) -> Result<usize> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let retention = chrono::Duration::from_std(retention)
+            .map_err(|e| EmbedError::Internal(anyhow::anyhow!("Invalid audit retention: {}", e)))?;
+        let cutoff = Utc::now() - retention;
+        let mut response = conn
+            .query("DELETE embedding_audit WHERE created_at < $cutoff RETURN BEFORE; DELETE batch_run WHERE created_at < $cutoff RETURN NONE")
+            .bind(("cutoff", cutoff))
+            .await?;
+        let deleted: Vec<serde_json::Value> = response.take(0)?;
+        Ok(deleted.len())
+    }
+
+    /// Fetch a page of stored float embeddings, ordered by record id
+    pub async fn get_stored_embeddings(&self, start: usize, limit: usize) -> Result<Vec<StoredEmbeddingRow>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let query = match self.storage_mode {
+            StorageMode::Inline =>
+                "SELECT id, embedding FROM repo WHERE embedding IS NOT NONE START $start LIMIT $limit",
+            StorageMode::Table =>
+                "SELECT repo AS id, embedding FROM repo_embedding \
+                 WHERE model = $model AND embedding IS NOT NONE START $start LIMIT $limit",
+        };
+
+        let mut response = conn
+            .query(query)
+            .bind(("start", start))
+            .bind(("limit", limit))
+            .bind(("model", self.model.clone())).await?;
+        let rows: Vec<StoredEmbeddingRow> = response.take(0)?;
+
+        Ok(rows)
+    }
+
+    /// The `limit` most recently generated float embeddings that are still current, newest first
+    pub async fn get_recent_embeddings(&self, limit: usize) -> Result<Vec<RecentEmbeddingRow>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let query = match self.storage_mode {
+            StorageMode::Inline =>
+                "SELECT id.* AS repo, embedding, embedding_generated_at FROM repo \
+                 WHERE embedding IS NOT NONE AND embedding_generated_at >= updated_at \
+                 ORDER BY embedding_generated_at DESC LIMIT $limit",
+            StorageMode::Table =>
+                "SELECT repo.* AS repo, embedding, generated_at FROM repo_embedding \
+                 WHERE model = $model AND embedding IS NOT NONE AND generated_at >= repo.updated_at \
+                 ORDER BY generated_at DESC LIMIT $limit",
+        };
+
+        let mut response = conn
+            .query(query)
+            .bind(("limit", limit))
+            .bind(("model", self.model.clone())).await?;
+        let rows: Vec<RecentEmbeddingRow> = response.take(0)?;
+
+        Ok(rows)
+    }
+
+    /// Every stored float embedding, read `page_size` rows at a time
+    pub async fn get_all_stored_embeddings(&self, page_size: usize) -> Result<Vec<StoredEmbeddingRow>> {
+        let page_size = page_size.max(1);
+        let mut rows = Vec::new();
+        loop {
+            let page = self.get_stored_embeddings(rows.len(), page_size).await?;
+            let done = page.len() < page_size;
+            rows.extend(page);
+            if done {
+                return Ok(rows);
             }
         }
+    }
 
-        Ok(BatchUpdateResult {
-            total: successful + failed,
-            successful,
-            failed,
-            duration: start.elapsed(),
-        })
+    /// The most recently archived vector of each repo for the client's model, as
+    /// `repo_embedding_history` record ids
+    pub async fn get_latest_history_ids(&self) -> Result<Vec<RecordId>> {
+        let conn = self.get_connection().await?;
+        let mut response = conn
+            .query(
+                "SELECT id, repo, archived_at FROM repo_embedding_history \
+                 WHERE model = $model AND embedding IS NOT NONE",
+            )
+            .bind(("model", self.model.clone())).await?;
+        let rows: Vec<HistoryHead> = response.take(0)?;
+
+        let mut latest: std::collections::HashMap<String, HistoryHead> = std::collections::HashMap::new();
+        for row in rows {
+            let repo = row.repo.to_string();
+            if latest.get(&repo).is_none_or(|current| current.archived_at < row.archived_at) {
+                latest.insert(repo, row);
+            }
+        }
+        Ok(latest.into_values().map(|row| row.id).collect())
+    }
+
+    /// Archived vectors by `repo_embedding_history` record id
+    pub async fn get_history_embeddings(&self, ids: &[RecordId]) -> Result<Vec<HistoryEmbeddingRow>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.get_connection().await?;
+        let mut response = conn
+            .query("SELECT repo, embedding, normalized FROM $ids WHERE embedding IS NOT NONE")
+            .bind(("ids", ids.to_vec())).await?;
+        Ok(response.take(0)?)
+    }
+
+    /// Delete the `repo_embedding` rows this client's model holds for `repo_ids`. Inline
+    /// embeddings live on the repo rows and go with them, so that mode has nothing to delete.
+    pub async fn delete_table_embeddings(&self, repo_ids: &[RecordId]) -> Result<()> {
+        if repo_ids.is_empty() || self.storage_mode == StorageMode::Inline {
+            return Ok(());
+        }
+        let conn = self.get_connection().await?;
+        conn.query("DELETE repo_embedding WHERE repo IN $ids AND model = $model RETURN NONE")
+            .bind(("ids", repo_ids.to_vec()))
+            .bind(("model", self.model.clone())).await?
+            .check()?;
+        Ok(())
+    }
+
+    /// Clear stored embeddings so the repos are picked up again by the pipeline
+    pub async fn mark_for_reembedding(&self, repo_ids: &[RecordId]) -> Result<usize> {
+        if repo_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let query = match self.storage_mode {
+            StorageMode::Inline =>
+                "UPDATE $ids SET embedding = NONE, embedding_quantized = NONE RETURN NONE",
+            StorageMode::Table =>
+                "DELETE repo_embedding WHERE repo IN $ids AND model = $model RETURN NONE",
+        };
+
+        conn.query(query)
+            .bind(("ids", repo_ids.to_vec()))
+            .bind(("model", self.model.clone())).await?
+            .check()?;
+
+        info!("Marked {} repos for re-embedding", repo_ids.len());
+        Ok(repo_ids.len())
+    }
+
+    /// Users with a star (an `edge` relation from user to repo) on a repo whose embedding was
+    /// written after `since`, plus users with stars but no aggregate yet. With no `since`, every
+    /// user with stars.
+    pub async fn get_users_to_refresh(&self, edge: &str, since: Option<DateTime<Utc>>) -> Result<Vec<RecordId>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let changed = match self.storage_mode {
+            StorageMode::Inline => "out.embedding_generated_at > <datetime>$since",
+            StorageMode::Table =>
+                "type::thing('repo_embedding', [out, $model]).generated_at > <datetime>$since",
+        };
+        let query = match since {
+            Some(_) => format!(
+                "RETURN array::distinct(array::concat(
+                     (SELECT VALUE in FROM type::table($edge) WHERE {changed}),
+                     (SELECT VALUE in FROM type::table($edge) WHERE in.embedding_updated_at IS NONE)
+                 ));"
+            ),
+            None => "RETURN array::distinct((SELECT VALUE in FROM type::table($edge)));".to_string(),
+        };
+
+        let mut response = conn
+            .query(query)
+            .bind(("edge", edge.to_string()))
+            .bind(("since", since))
+            .bind(("model", self.model.clone())).await?;
+        let users: Option<Vec<RecordId>> = response.take(0)?;
+
+        Ok(users.unwrap_or_default())
+    }
+
+    /// Float embeddings of the repos `user` starred, with their star counts
+    pub async fn get_starred_embeddings(&self, edge: &str, user: &RecordId) -> Result<Vec<StarredEmbeddingRow>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let query = match self.storage_mode {
+            StorageMode::Inline =>
+                "SELECT out.embedding AS embedding, (out.stars ?? 0) AS stars FROM type::table($edge) \
+                 WHERE in = $user AND out.embedding IS NOT NONE",
+            StorageMode::Table =>
+                "SELECT type::thing('repo_embedding', [out, $model]).embedding AS embedding, (out.stars ?? 0) AS stars \
+                 FROM type::table($edge) \
+                 WHERE in = $user AND type::thing('repo_embedding', [out, $model]).embedding IS NOT NONE",
+        };
+
+        let mut response = conn
+            .query(query)
+            .bind(("edge", edge.to_string()))
+            .bind(("user", user.clone()))
+            .bind(("model", self.model.clone())).await?;
+        let rows: Vec<StarredEmbeddingRow> = response.take(0)?;
+
+        Ok(rows)
+    }
+
+    /// Write a user's aggregate embedding, or clear it when none of their stars are embedded
+    pub async fn update_user_embedding(
+        &self,
+        user: &RecordId,
+        embedding: Option<Vec<f32>>,
+        repo_count: usize,
+    ) -> Result<()> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query(
+            "UPDATE $user SET embedding = $embedding, embedding_model = $model, \
+             embedding_repo_count = $count, embedding_updated_at = time::now() RETURN NONE",
+        )
+        .bind(("user", user.clone()))
+        .bind(("embedding", embedding))
+        .bind(("model", self.model.clone()))
+        .bind(("count", repo_count)).await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// When the last user embedding refresh for this model started
+    pub async fn get_user_embedding_watermark(&self) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let mut response = conn
+            .query("RETURN type::thing('user_embedding_state', $model).since")
+            .bind(("model", self.model.clone())).await?;
+        let since: Option<DateTime<Utc>> = response.take(0)?;
+
+        Ok(since)
+    }
+
+    pub async fn set_user_embedding_watermark(&self, since: DateTime<Utc>) -> Result<()> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query("UPSERT type::thing('user_embedding_state', $model) SET since = <datetime>$since RETURN NONE")
+            .bind(("model", self.model.clone()))
+            .bind(("since", since)).await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Replace this model's rows in `repo_duplicate` with `pairs`
+    pub async fn replace_duplicates(&self, pairs: &[DuplicatePair]) -> Result<()> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query("DELETE repo_duplicate WHERE model = $model RETURN NONE")
+            .bind(("model", self.model.clone())).await?
+            .check()?;
+
+        for chunk in pairs.chunks(self.write_chunk_size) {
+            let rows: Vec<DuplicateRow> = chunk
+                .iter()
+                .map(|pair| DuplicateRow {
+                    repo_a: pair.repo_a.clone(),
+                    repo_b: pair.repo_b.clone(),
+                    model: self.model.clone(),
+                    similarity: pair.similarity,
+                })
+                .collect();
+            conn.query("INSERT INTO repo_duplicate $rows RETURN NONE")
+                .bind(("rows", rows)).await?
+                .check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Non-empty repo descriptions keyed by repo id, for labelling clusters
+    pub async fn get_repo_descriptions(&self) -> Result<std::collections::HashMap<String, String>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let mut response = conn
+            .query("SELECT id, description FROM repo WHERE description IS NOT NONE AND description != ''")
+            .await?;
+        let rows: Vec<RepoDescriptionRow> = response.take(0)?;
+
+        Ok(rows.into_iter().map(|row| (row.id.to_string(), row.description)).collect())
+    }
+
+    /// Write each repo's cluster and replace this model's rows in `repo_cluster`
+    pub async fn replace_clusters(
+        &self,
+        assignments: &[(RecordId, usize)],
+        clusters: &[ClusterSummary],
+    ) -> Result<()> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        for chunk in assignments.chunks(self.write_chunk_size) {
+            let rows: Vec<ClusterAssignmentRow> = chunk
+                .iter()
+                .map(|(repo, cluster)| ClusterAssignmentRow {
+                    repo: repo.clone(),
+                    cluster: *cluster,
+                })
+                .collect();
+            conn.query(
+                "FOR $row IN $rows { UPDATE $row.repo SET cluster = $row.cluster, cluster_model = $model RETURN NONE; };",
+            )
+            .bind(("rows", rows))
+            .bind(("model", self.model.clone())).await?
+            .check()?;
+        }
+
+        conn.query(
+            "DELETE repo_cluster WHERE model = $model RETURN NONE;
+             FOR $cluster IN $clusters {
+                 CREATE repo_cluster CONTENT {
+                     model: $model,
+                     cluster: $cluster.cluster,
+                     size: $cluster.size,
+                     terms: $cluster.terms
+                 } RETURN NONE;
+             };",
+        )
+        .bind(("clusters", clusters.to_vec()))
+        .bind(("model", self.model.clone())).await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// Store a circuit breaker snapshot, one record per service
+    pub async fn save_circuit_snapshot(&self, snapshot: &CircuitSnapshot) -> Result<()> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        conn.query("UPSERT type::thing('circuit_breaker', $service) CONTENT $snapshot RETURN NONE")
+            .bind(("service", snapshot.service.clone()))
+            .bind(("snapshot", snapshot.clone())).await?
+            .check()?;
+
+        debug!("Saved circuit breaker state for {}: {}", snapshot.service, snapshot.state);
+        Ok(())
+    }
+
+    /// Load all persisted circuit breaker snapshots
+    pub async fn load_circuit_snapshots(&self) -> Result<Vec<CircuitSnapshot>> {
+        let conn = self.pool.get().await
+            .map_err(|e| EmbedError::from(
+                surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
+            ))?;
+
+        let mut response = conn.query("SELECT * OMIT id FROM circuit_breaker").await?;
+        let snapshots: Vec<CircuitSnapshot> = response.take(0)?;
+
+        Ok(snapshots)
+    }
+
+    /// Insert or update the persisted state of an OpenAI batch
+    pub async fn save_batch_job(&self, job: &BatchJob) -> Result<()> {
+        let conn = self.get_connection().await?;
+        conn.query("UPSERT type::thing('batch_job', $batch_id) CONTENT $job RETURN NONE")
+            .bind(("batch_id", job.batch_id.clone()))
+            .bind(("job", job.clone())).await?
+            .check()?;
+
+        debug!("Saved batch job {}: {}", job.batch_id, job.status);
+        Ok(())
+    }
+
+    /// Batches of the client's model that haven't been ingested or given up on, oldest first
+    pub async fn load_open_batch_jobs(&self) -> Result<Vec<BatchJob>> {
+        let conn = self.get_connection().await?;
+        let mut response = conn
+            .query("SELECT * OMIT id FROM batch_job WHERE model = $model AND done = false ORDER BY created_at")
+            .bind(("model", self.model.clone())).await?;
+        Ok(response.take(0)?)
     }
 
     /// Get current pool statistics
@@ -331,9 +1438,9 @@ impl SurrealClient {
         self.pool.stats()
     }
     
-    #[cfg(test)]
+    /// A pooled connection for queries without a dedicated method
     pub async fn get_connection(&self) -> Result<Object<crate::pool::SurrealDBManager>> {
-        self.pool.get().await.map_err(|e| EmbedError::Database(
+        self.pool.get().await.map_err(|e| EmbedError::from(
             surrealdb::Error::Api(surrealdb::error::Api::InternalError(e.to_string()))
         ))
     }
@@ -343,7 +1450,101 @@ impl SurrealClient {
 #[derive(Debug, Clone)]
 pub struct EmbeddingUpdate {
     pub repo_id: RecordId,
+    /// Shared with the embedding cache, so queuing an update never copies the vector
+    pub embedding: Arc<[f32]>,
+    /// Whether the vector was L2-normalized before storage
+    pub normalized: bool,
+    /// Whether validation repair rescaled the vector to get it accepted
+    pub repaired: bool,
+    /// The repo's `updated_at` when it was read, used to measure embedding freshness
+    pub updated_at: DateTime<Utc>,
+    /// Model that generated the vector, recorded as `embedding_model` with inline storage;
+    /// `None` for the sink's own model. Table rows are always keyed by the sink's model.
+    pub model: Option<String>,
+    /// ISO 639-3 code of the description's language, when detected
+    pub language: Option<String>,
+}
+
+/// A stored embedding as read back for auditing
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct StoredEmbeddingRow {
+    pub id: RecordId,
+    pub embedding: Vec<f32>,
+}
+
+/// A current embedding with the repo whose text it was generated from
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RecentEmbeddingRow {
+    pub repo: Repo,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct HistoryHead {
+    id: RecordId,
+    repo: RecordId,
+    archived_at: DateTime<Utc>,
+}
+
+/// A vector archived in `repo_embedding_history`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct HistoryEmbeddingRow {
+    pub repo: RecordId,
+    pub embedding: Vec<f32>,
+    pub normalized: Option<bool>,
+}
+
+/// One cluster found by the `cluster` command, labelled by its most distinctive terms
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ClusterSummary {
+    pub cluster: usize,
+    pub size: usize,
+    pub terms: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ClusterAssignmentRow {
+    repo: RecordId,
+    cluster: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct RepoDescriptionRow {
+    id: RecordId,
+    description: String,
+}
+
+/// Two repos whose embeddings are nearly identical
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    pub repo_a: RecordId,
+    pub repo_b: RecordId,
+    pub similarity: f32,
+}
+
+#[derive(serde::Serialize)]
+struct DuplicateRow {
+    repo_a: RecordId,
+    repo_b: RecordId,
+    model: String,
+    similarity: f32,
+}
+
+/// A starred repo's embedding as read for a user aggregate
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct StarredEmbeddingRow {
     pub embedding: Vec<f32>,
+    pub stars: u32,
+}
+
+/// Embedding values as written to the repo record
+struct StoredEmbedding {
+    embedding: Option<Vec<f32>>,
+    quantization: Option<String>,
+    values: Option<Vec<i64>>,
+    scale: Option<f32>,
+    /// Precision of `embedding`; `None` when only the quantized vector is stored
+    precision: Option<&'static str>,
 }
 
 /// Result of a batch update operation
@@ -353,6 +1554,21 @@ pub struct BatchUpdateResult {
     pub successful: usize,
     pub failed: usize,
     pub duration: std::time::Duration,
+    /// Records that could not be written, with the reason
+    pub errors: Vec<(RecordId, String)>,
+}
+
+impl BatchUpdateResult {
+    fn record(&mut self, repo_id: &RecordId, outcome: std::result::Result<(), String>) {
+        match outcome {
+            Ok(()) => self.successful += 1,
+            Err(e) => {
+                error!("Failed to update embedding for {}: {}", repo_id, e);
+                self.failed += 1;
+                self.errors.push((repo_id.clone(), e));
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -363,30 +1579,29 @@ mod tests {
     use std::sync::Arc;
 
     async fn setup_test_client() -> (SurrealClient, Pool) {
+        crate::metrics::Metrics::register(&prometheus::Registry::new()).expect("Failed to register metrics");
         let config = Arc::new(Config {
-            db_url: "memory://test".to_string(),
-            db_user: "root".to_string(),
-            db_pass: "root".to_string(),
             db_namespace: "test_ns".to_string(),
             db_database: "test_db".to_string(),
             embedding_provider: "ollama".to_string(),
-            ollama_url: "http://localhost:11434".to_string(),
-            openai_api_key: None,
-            together_api_key: None,
             embedding_model: "test-model".to_string(),
-            batch_size: 10,
             pool_size: 2,
             retry_attempts: 3,
             retry_delay_ms: 100,
             batch_delay_ms: 100,
             monitoring_port: Some(9090),
-            parallel_workers: 1,
-            token_limit: 8000,
             pool_max_size: 5,
             pool_timeout_secs: 30,
             pool_wait_timeout_secs: 10,
             pool_create_timeout_secs: 30,
             pool_recycle_timeout_secs: 30,
+            price_per_million_tokens: None,
+            provider_probe_interval_secs: 300,
+            monitoring_bind_addr: "0.0.0.0".to_string(),
+            mock_dimensions: 768,
+            poll_interval_secs: 5,
+            shutdown_drain_secs: 20,
+            ..Config::for_tests()
         });
 
         let pool = crate::pool::create_pool(config).await.expect("Failed to create pool");
@@ -410,6 +1625,7 @@ mod tests {
             url: format!("https://github.com/owner/test-{}", id),
             stars: 42,
             language: Some("Rust".to_string()),
+            readme: None,
             owner: crate::models::RepoOwner {
                 login: "owner".to_string(),
                 avatar_url: "https://github.com/owner.png".to_string(),
@@ -419,6 +1635,7 @@ mod tests {
             updated_at: now,
             embedding: if needs_embedding { None } else { Some(vec![0.1, 0.2, 0.3]) },
             embedding_generated_at: if needs_embedding { None } else { Some(now) },
+            extra: Default::default(),
         }
     }
 
@@ -431,13 +1648,13 @@ mod tests {
         let conn = client.get_connection().await.expect("Failed to get connection");
         let _: Option<Repo> = conn
             .create(("repo", "test1"))
-            .content(&repo)
+            .content(repo.clone())
             .await
             .expect("Failed to create repo");
         
         // Update embedding
         let embedding = vec![0.1, 0.2, 0.3, 0.4, 0.5];
-        let result = client.update_repo_embedding(&repo.id, embedding.clone()).await;
+        let result = client.update_repo_embedding(&repo.id, &embedding, false, false).await;
         
         assert!(result.is_ok(), "Failed to update embedding: {:?}", result.err());
         
@@ -453,6 +1670,41 @@ mod tests {
         assert!(updated_repo.embedding_generated_at.is_some());
     }
 
+    #[tokio::test]
+    async fn test_embedding_history_keeps_previous_vectors() {
+        let (client, _pool) = setup_test_client().await;
+        let client = client
+            .with_storage_mode(StorageMode::Inline, "model-a")
+            .with_history(2, true);
+
+        let repo = create_test_repo("history1", true);
+        let conn = client.get_connection().await.expect("Failed to get connection");
+        let _: Option<Repo> = conn.create(("repo", "history1")).content(repo.clone()).await.expect("Failed to create repo");
+
+        for value in [0.1, 0.2, 0.3, 0.4] {
+            client
+                .update_repo_embedding(&repo.id, &[value, 0.5, 0.5], false, false).await
+                .expect("Failed to update embedding");
+        }
+
+        // Four writes archive three vectors, of which the newest two are kept
+        let mut response = conn
+            .query("SELECT count() FROM repo_embedding_history WHERE repo = $repo GROUP ALL")
+            .bind(("repo", repo.id.clone())).await
+            .expect("Failed to count history");
+        let count: Option<usize> = response.take((0, "count")).expect("Failed to read count");
+        assert_eq!(count, Some(2));
+
+        let latest = client.get_latest_history_ids().await.expect("Failed to read history");
+        assert_eq!(latest.len(), 1);
+        let rows = client.get_history_embeddings(&latest).await.expect("Failed to read history");
+        assert_eq!(rows[0].repo, repo.id);
+        assert_eq!(rows[0].embedding, vec![0.3, 0.5, 0.5]);
+
+        let other_model = client.clone().with_storage_mode(StorageMode::Inline, "model-b");
+        assert!(other_model.get_latest_history_ids().await.expect("Failed to read history").is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_repos_needing_embeddings() {
         let (client, pool) = setup_test_client().await;
@@ -463,9 +1715,9 @@ mod tests {
         let repo2 = create_test_repo("needs2", true);
         let repo3 = create_test_repo("has_embedding", false);
         
-        let _: Option<Repo> = conn.create(("repo", "needs1")).content(&repo1).await.expect("Failed to create repo");
-        let _: Option<Repo> = conn.create(("repo", "needs2")).content(&repo2).await.expect("Failed to create repo");
-        let _: Option<Repo> = conn.create(("repo", "has_embedding")).content(&repo3).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "needs1")).content(repo1.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "needs2")).content(repo2.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "has_embedding")).content(repo3.clone()).await.expect("Failed to create repo");
         
         // Get repos needing embeddings
         let repos = client.get_repos_needing_embeddings(10).await.expect("Failed to get repos");
@@ -485,18 +1737,28 @@ mod tests {
         let repo1 = create_test_repo("batch1", true);
         let repo2 = create_test_repo("batch2", true);
         
-        let _: Option<Repo> = conn.create(("repo", "batch1")).content(&repo1).await.expect("Failed to create repo");
-        let _: Option<Repo> = conn.create(("repo", "batch2")).content(&repo2).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "batch1")).content(repo1.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "batch2")).content(repo2.clone()).await.expect("Failed to create repo");
         
         // Prepare batch updates
         let updates = vec![
             EmbeddingUpdate {
                 repo_id: repo1.id.clone(),
-                embedding: vec![0.1, 0.2, 0.3],
+                embedding: vec![0.1, 0.2, 0.3].into(),
+                normalized: false,
+                repaired: false,
+                updated_at: repo1.updated_at,
+                model: None,
+                language: None,
             },
             EmbeddingUpdate {
                 repo_id: repo2.id.clone(),
-                embedding: vec![0.4, 0.5, 0.6],
+                embedding: vec![0.4, 0.5, 0.6].into(),
+                normalized: false,
+                repaired: false,
+                updated_at: repo2.updated_at,
+                model: None,
+                language: None,
             },
         ];
         
@@ -515,6 +1777,136 @@ mod tests {
         assert!(updated2.unwrap().embedding.is_some());
     }
 
+    #[tokio::test]
+    async fn test_freshness_metrics() {
+        let (client, pool) = setup_test_client().await;
+        let conn = pool.get().await.expect("Failed to get connection");
+
+        // Older than anything else in the shared table, so it is the oldest pending repo
+        let updated_at = DateTime::parse_from_rfc3339("1990-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let repo = Repo { updated_at, ..create_test_repo("stale", true) };
+        let _: Option<Repo> = conn.create(("repo", "stale")).content(repo.clone()).await.expect("Failed to create repo");
+        assert_eq!(client.get_oldest_pending_updated_at().await.unwrap(), Some(updated_at));
+
+        let lag = crate::metrics::Metrics::get()
+            .embedding_freshness_lag
+            .with_label_values(&[&client.model, &crate::tenant::current_tenant()]);
+        let (count, sum) = (lag.get_sample_count(), lag.get_sample_sum());
+        let update = EmbeddingUpdate {
+            repo_id: repo.id.clone(),
+            embedding: vec![0.1, 0.2, 0.3].into(),
+            normalized: false,
+            repaired: false,
+            updated_at,
+            model: None,
+            language: None,
+        };
+        let result = client.batch_update_embeddings(vec![update]).await.expect("Batch update failed");
+        assert_eq!(result.successful, 1);
+
+        // The lag runs from the repo's update to the write; other tests may write concurrently
+        assert!(lag.get_sample_count() > count);
+        assert!(lag.get_sample_sum() - sum > (Utc::now() - updated_at).num_seconds() as f64 - 60.0);
+        assert_ne!(client.get_oldest_pending_updated_at().await.unwrap(), Some(updated_at));
+    }
+
+    #[tokio::test]
+    async fn test_polling_settings() {
+        let (client, pool) = setup_test_client().await;
+        let client = client.with_polling(Duration::from_secs(5), 0);
+
+        // Idle polls double the wait up to 8x the interval; work resets it in the poller
+        let mut delay = client.poll_interval;
+        let delays: Vec<u64> = (0..5)
+            .map(|_| {
+                delay = client.idle_poll_delay(delay);
+                delay.as_secs()
+            })
+            .collect();
+        assert_eq!(delays, vec![10, 20, 40, 40, 40]);
+
+        // A fetch size of 0 would never fetch anything, so it is raised to 1
+        let conn = pool.get().await.expect("Failed to get connection");
+        for id in ["poll1", "poll2"] {
+            let repo = create_test_repo(id, true);
+            let _: Option<Repo> = conn.create(("repo", id)).content(repo).await.expect("Failed to create repo");
+        }
+        let repos = client.get_repos_needing_embeddings(client.fetch_batch_size).await.unwrap();
+        assert_eq!(repos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_batch_run() {
+        let (client, pool) = setup_test_client().await;
+        let mut audit = crate::audit::BatchAudit::new(uuid::Uuid::new_v4(), "openai", "text-embedding-3-small");
+        let repo = RecordId::from(("repo", "batch_run"));
+        audit.record(&repo, crate::audit::AuditOutcome::Stored, None, std::time::Duration::ZERO, true);
+        let mut run = audit.summary(1, std::time::Duration::from_millis(40));
+        run.worker = Some(3);
+        run.batch_size = Some(10);
+
+        let runs = |batch_id: String| {
+            let pool = pool.clone();
+            async move {
+                let conn = pool.get().await.unwrap();
+                let mut response = conn
+                    .query("SELECT * OMIT id FROM batch_run WHERE batch_id = $id")
+                    .bind(("id", batch_id))
+                    .await
+                    .unwrap();
+                response.take::<Vec<crate::audit::BatchRun>>(0).unwrap()
+            }
+        };
+
+        // Nothing is written unless auditing is on
+        client.write_batch_run(&run).await.unwrap();
+        assert!(runs(run.batch_id.clone()).await.is_empty());
+
+        client.with_audit(true).write_batch_run(&run).await.unwrap();
+        let rows = runs(run.batch_id.clone()).await;
+        assert_eq!(rows.len(), 1);
+        let stored = &rows[0];
+        assert_eq!((stored.worker, stored.size, stored.stored, stored.cache_hits), (Some(3), 1, 1, 1));
+        assert_eq!((stored.duration_ms, stored.batch_size), (40, Some(10)));
+    }
+
+    #[tokio::test]
+    async fn test_batch_update_reports_per_record_errors() {
+        let (client, pool) = setup_test_client().await;
+        let client = client.with_write_chunk_size(2);
+        let conn = pool.get().await.expect("Failed to get connection");
+
+        let repo1 = create_test_repo("chunk1", true);
+        let repo2 = create_test_repo("chunk2", true);
+        let _: Option<Repo> = conn.create(("repo", "chunk1")).content(repo1.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "chunk2")).content(repo2.clone()).await.expect("Failed to create repo");
+
+        let missing = RecordId::from(("repo", "missing"));
+        let updates = vec![repo1.id.clone(), missing.clone(), repo2.id.clone()]
+            .into_iter()
+            .map(|repo_id| EmbeddingUpdate {
+                repo_id,
+                embedding: vec![0.1, 0.2, 0.3].into(),
+                normalized: false,
+                repaired: false,
+                updated_at: Utc::now(),
+                model: None,
+                language: None,
+            })
+            .collect();
+
+        let result = client.batch_update_embeddings(updates).await.expect("Batch update failed");
+
+        assert_eq!(result.total, 3);
+        assert_eq!(result.successful, 2);
+        assert_eq!(result.failed, 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].0, missing);
+
+        let updated2: Option<Repo> = conn.select(&repo2.id).await.expect("Failed to select repo");
+        assert!(updated2.unwrap().embedding.is_some());
+    }
+
     #[tokio::test]
     async fn test_get_counts() {
         let (client, pool) = setup_test_client().await;
@@ -525,9 +1917,9 @@ mod tests {
         let repo2 = create_test_repo("count2", false);
         let repo3 = create_test_repo("count3", true);
         
-        let _: Option<Repo> = conn.create(("repo", "count1")).content(&repo1).await.expect("Failed to create repo");
-        let _: Option<Repo> = conn.create(("repo", "count2")).content(&repo2).await.expect("Failed to create repo");
-        let _: Option<Repo> = conn.create(("repo", "count3")).content(&repo3).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "count1")).content(repo1.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "count2")).content(repo2.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "count3")).content(repo3.clone()).await.expect("Failed to create repo");
         
         // Test counts
         let total = client.get_total_repos_count().await.expect("Failed to get total count");
@@ -551,6 +1943,104 @@ mod tests {
         assert_eq!(result.failed, 0);
     }
 
+    #[tokio::test]
+    async fn test_table_storage_mode() {
+        let (client, pool) = setup_test_client().await;
+        let client = client.with_storage_mode(StorageMode::Table, "test-model");
+        let conn = pool.get().await.expect("Failed to get connection");
+
+        let repo = create_test_repo("table1", true);
+        let _: Option<Repo> = conn.create(("repo", "table1")).content(repo.clone()).await.expect("Failed to create repo");
+
+        assert_eq!(client.get_pending_repos_count().await.expect("Failed to get pending count"), 1);
+
+        client
+            .update_repo_embedding(&repo.id, &[0.1, 0.2, 0.3], false, false).await
+            .expect("Failed to store embedding");
+
+        assert_eq!(client.get_pending_repos_count().await.expect("Failed to get pending count"), 0);
+        assert_eq!(client.get_embedded_repos_count().await.expect("Failed to get embedded count"), 1);
+
+        // The repo record itself stays lean
+        let stored: Option<Repo> = conn.select(&repo.id).await.expect("Failed to select repo");
+        assert!(stored.unwrap().embedding.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stored_embeddings_and_marking() {
+        let (client, pool) = setup_test_client().await;
+        let conn = pool.get().await.expect("Failed to get connection");
+
+        let repo1 = create_test_repo("stored1", false);
+        let repo2 = create_test_repo("stored2", true);
+        let _: Option<Repo> = conn.create(("repo", "stored1")).content(repo1.clone()).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "stored2")).content(repo2.clone()).await.expect("Failed to create repo");
+
+        let rows = client.get_stored_embeddings(0, 10).await.expect("Failed to read embeddings");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].id, repo1.id);
+
+        let marked = client.mark_for_reembedding(std::slice::from_ref(&repo1.id)).await.expect("Failed to mark repo");
+        assert_eq!(marked, 1);
+        assert!(client.get_stored_embeddings(0, 10).await.expect("Failed to read embeddings").is_empty());
+        assert_eq!(client.get_pending_repos_count().await.expect("Failed to get pending count"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_change_feed_forwards_pending_repos() {
+        let (client, pool) = setup_test_client().await;
+        // The cursor is stored per model
+        let client = client.with_storage_mode(StorageMode::Inline, "test-model").with_change_feed(1);
+        client.enable_change_feed().await.expect("Failed to enable change feed");
+
+        let conn = pool.get().await.expect("Failed to get connection");
+        for (id, needs_embedding) in [("fresh", true), ("done", false)] {
+            let _: Option<Repo> = conn
+                .create(("repo", id))
+                .content(create_test_repo(id, needs_embedding))
+                .await
+                .expect("Failed to create repo");
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let mut cursor = ChangeCursor::Versionstamp(0);
+        assert!(client.forward_repo_changes(&mut cursor, &tx).await.expect("Failed to read changes"));
+        let repo = rx.try_recv().expect("Pending repo was not forwarded");
+        assert_eq!(repo.id, RecordId::from(("repo", "fresh")));
+        assert!(rx.try_recv().is_err());
+
+        // The cursor is persisted, so nothing is read twice
+        let ChangeCursor::Versionstamp(versionstamp) = cursor else {
+            panic!("Cursor did not advance");
+        };
+        assert_eq!(client.get_change_feed_cursor().await.expect("Failed to read cursor"), Some(versionstamp));
+        assert!(!client.forward_repo_changes(&mut cursor, &tx).await.expect("Failed to read changes"));
+    }
+
+    #[tokio::test]
+    async fn test_recent_embeddings_skip_stale_vectors() {
+        let (client, pool) = setup_test_client().await;
+        let conn = pool.get().await.expect("Failed to get connection");
+
+        let mut stale = create_test_repo("stale", false);
+        stale.updated_at = Utc::now() + chrono::Duration::minutes(1);
+        let _: Option<Repo> = conn.create(("repo", "current")).content(create_test_repo("current", false)).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "pending")).content(create_test_repo("pending", true)).await.expect("Failed to create repo");
+        let _: Option<Repo> = conn.create(("repo", "stale")).content(stale).await.expect("Failed to create repo");
+
+        let rows = client.get_recent_embeddings(10).await.expect("Failed to read embeddings");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].repo.full_name, "owner/test-current");
+        assert_eq!(rows[0].embedding, vec![0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn test_storage_mode_parsing() {
+        assert_eq!("inline".parse::<StorageMode>().unwrap(), StorageMode::Inline);
+        assert_eq!("TABLE".parse::<StorageMode>().unwrap(), StorageMode::Table);
+        assert!("sidecar".parse::<StorageMode>().is_err());
+    }
+
     #[tokio::test]
     async fn test_pool_stats() {
         let (client, _pool) = setup_test_client().await;
@@ -0,0 +1,165 @@
+use opentelemetry::{trace::TraceContextExt, KeyValue};
+use opentelemetry_sdk::{runtime, trace, Resource};
+use opentelemetry_otlp::WithExportConfig;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+use uuid::Uuid;
+
+/// Log line format written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Single-line human readable output
+    #[default]
+    Compact,
+    /// Multi-line human readable output, for local development
+    Pretty,
+    /// One JSON object per line with event and span fields flattened, for log pipelines
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "compact" | "" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "Unknown log format '{}' (expected compact, pretty or json)",
+                other
+            )),
+        }
+    }
+}
+
+static SESSION_ID: OnceLock<Uuid> = OnceLock::new();
+
+/// Whether spans are exported, so trace ids are worth attaching to metrics
+static OTEL_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Handle to swap the stdout log filter at runtime
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Id of this process' run, attached to logs and exported spans
+pub fn session_id() -> Uuid {
+    *SESSION_ID.get_or_init(Uuid::new_v4)
+}
+
+/// Install the global subscriber. Spans are also exported over OTLP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set (service name from `OTEL_SERVICE_NAME`, default `embed_star`).
+/// `log_filter` overrides `RUST_LOG` for stdout logs and can be changed later with [`set_log_filter`].
+pub fn init_tracing(format: LogFormat, log_filter: Option<&str>) -> anyhow::Result<()> {
+    let otel_layer = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) if !endpoint.trim().is_empty() => {
+            let service_name =
+                std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "embed_star".to_string());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", service_name),
+                    KeyValue::new("service.instance.id", session_id().to_string()),
+                ])))
+                .install_batch(runtime::Tokio)?;
+            OTEL_ENABLED.store(true, Ordering::Relaxed);
+            Some(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(env_filter()))
+        }
+        _ => None,
+    };
+
+    let fmt_layer = match format {
+        LogFormat::Compact => tracing_subscriber::fmt::layer()
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_thread_names(true)
+            .compact()
+            .boxed(),
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().pretty().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_thread_ids(true)
+            .boxed(),
+    };
+
+    // tokio-console needs the runtime's own trace events, so filters are per layer rather than global
+    #[cfg(feature = "console")]
+    let console_layer = Some(console_subscriber::spawn());
+    #[cfg(not(feature = "console"))]
+    let console_layer: Option<tracing_subscriber::layer::Identity> = None;
+
+    let (stdout_filter, handle) = reload::Layer::new(log_filter_or_default(log_filter)?);
+    let _ = LOG_FILTER.set(handle);
+
+    // Initialize structured logging with correlation IDs
+    tracing_subscriber::registry()
+        .with(fmt_layer.with_filter(stdout_filter))
+        .with(otel_layer)
+        .with(console_layer)
+        .init();
+
+    Ok(())
+}
+
+/// Trace id of the current span when spans are exported over OTLP, for metric exemplars
+pub fn current_trace_id() -> Option<String> {
+    if !OTEL_ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "warn,embed_star=info,tower_http=debug".into())
+}
+
+fn log_filter_or_default(log_filter: Option<&str>) -> anyhow::Result<EnvFilter> {
+    match log_filter {
+        Some(directives) => EnvFilter::try_new(directives)
+            .map_err(|e| anyhow::anyhow!("Invalid log filter '{}': {}", directives, e)),
+        None => Ok(env_filter()),
+    }
+}
+
+/// Replace the stdout log filter; `None` goes back to `RUST_LOG` or the default
+pub fn set_log_filter(log_filter: Option<&str>) -> anyhow::Result<()> {
+    let filter = log_filter_or_default(log_filter)?;
+    if let Some(handle) = LOG_FILTER.get() {
+        handle.reload(filter)?;
+    }
+    Ok(())
+}
+
+/// Flush spans still buffered by the OTLP exporter
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_format_parsing() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("Pretty".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert_eq!("".parse::<LogFormat>().unwrap(), LogFormat::Compact);
+        assert!("logfmt".parse::<LogFormat>().is_err());
+    }
+}
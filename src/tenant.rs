@@ -0,0 +1,340 @@
+use crate::{
+    intake::{BatchGuard, IntakeControl},
+    models::Repo,
+    pool::DbHealth,
+    repo_store::EmbeddingSink,
+};
+use std::{future::Future, str::FromStr, sync::Arc};
+use tokio::sync::{mpsc, Mutex};
+
+/// Tenant name used when `TENANTS` is unset, and for work done outside any tenant
+pub const DEFAULT_TENANT: &str = "default";
+
+/// One namespace/database pair processed by the service, with its own pool and queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tenant {
+    /// Label on metrics and task names; defaults to `namespace/database`
+    pub name: String,
+    pub namespace: String,
+    pub database: String,
+    /// Share of batches relative to the other tenants while they all have work (default 1)
+    pub weight: u32,
+}
+
+impl FromStr for Tenant {
+    type Err = String;
+
+    /// `namespace/database` or `name=namespace/database`, optionally followed by `:weight`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, target) = match s.split_once('=') {
+            Some((name, target)) => (Some(name.trim()), target.trim()),
+            None => (None, s.trim()),
+        };
+        let (target, weight) = match target.rsplit_once(':') {
+            Some((target, weight)) => {
+                let weight = weight
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|weight| *weight > 0)
+                    .ok_or_else(|| format!("Invalid tenant '{}': weight must be a positive integer", s.trim()))?;
+                (target.trim(), weight)
+            }
+            None => (target, 1),
+        };
+        let (namespace, database) = target
+            .split_once('/')
+            .map(|(ns, db)| (ns.trim(), db.trim()))
+            .filter(|(ns, db)| !ns.is_empty() && !db.is_empty())
+            .ok_or_else(|| format!("Invalid tenant '{}' (expected [name=]namespace/database[:weight])", s.trim()))?;
+        let name = match name {
+            Some("") => return Err(format!("Invalid tenant '{}': empty name", s.trim())),
+            Some(name) => name.to_string(),
+            None => format!("{}/{}", namespace, database),
+        };
+
+        Ok(Tenant {
+            name,
+            namespace: namespace.to_string(),
+            database: database.to_string(),
+            weight,
+        })
+    }
+}
+
+/// Parse `TENANTS`: comma-separated `[name=]namespace/database[:weight]` entries with unique names
+pub fn parse_tenants(spec: &str) -> Result<Vec<Tenant>, String> {
+    let tenants = spec
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(Tenant::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, tenant) in tenants.iter().enumerate() {
+        if tenants[..i].iter().any(|other| other.name == tenant.name) {
+            return Err(format!("Duplicate tenant '{}'", tenant.name));
+        }
+    }
+    Ok(tenants)
+}
+
+tokio::task_local! {
+    static CURRENT_TENANT: Arc<str>;
+}
+
+/// Tenant the current task works for, used as the `tenant` label on metrics
+pub fn current_tenant() -> Arc<str> {
+    CURRENT_TENANT
+        .try_with(|tenant| tenant.clone())
+        .unwrap_or_else(|_| Arc::from(DEFAULT_TENANT))
+}
+
+/// Run `future` on behalf of `tenant`. Tasks spawned inside don't inherit it; wrap them too.
+pub async fn in_tenant<F: Future>(tenant: Arc<str>, future: F) -> F::Output {
+    CURRENT_TENANT.scope(tenant, future).await
+}
+
+/// Where a batch taken by [`Dispatcher::next_batch`] came from and must be written back to
+#[derive(Clone)]
+pub struct TenantTarget {
+    pub tenant: Arc<str>,
+    pub sink: Arc<dyn EmbeddingSink>,
+    /// Where A/B model embeddings of sampled repos go, when `AB_MODEL` is set
+    pub shadow: Option<Arc<dyn EmbeddingSink>>,
+    /// Where the multilingual model's embeddings go, when `MULTILINGUAL_MODEL` is set
+    pub routed: Option<Arc<dyn EmbeddingSink>>,
+}
+
+struct TenantQueue {
+    target: TenantTarget,
+    weight: u32,
+    health: Arc<DbHealth>,
+    rx: Mutex<mpsc::Receiver<Repo>>,
+}
+
+/// Hands out batches from the per-tenant queues by smooth weighted round-robin: among the
+/// tenants with queued repos, each gets batches in proportion to its weight, interleaved rather
+/// than in runs, so one tenant's large backfill can't monopolize the workers and the provider
+/// quota. Tenants with nothing queued neither bank nor lose their turn, and tenants whose
+/// database is unavailable are skipped until it is back.
+#[derive(Default)]
+pub struct Dispatcher {
+    queues: Vec<TenantQueue>,
+    /// Smooth weighted round-robin credit of each queue
+    credits: parking_lot::Mutex<Vec<i64>>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_queue(
+        mut self,
+        target: TenantTarget,
+        weight: u32,
+        health: Arc<DbHealth>,
+        rx: mpsc::Receiver<Repo>,
+    ) -> Self {
+        self.queues.push(TenantQueue {
+            target,
+            weight: weight.max(1),
+            health,
+            rx: Mutex::new(rx),
+        });
+        self.credits.get_mut().push(0);
+        self
+    }
+
+    /// Whether every tenant's database is unreachable, so workers should pause
+    pub fn all_unavailable(&self) -> bool {
+        !self.queues.is_empty() && self.queues.iter().all(|queue| queue.health.is_unavailable())
+    }
+
+    /// Order in which to try the tenants with queued repos for the next batch: the one with the
+    /// most credit first, after every candidate earned its weight
+    async fn turn_order(&self) -> Vec<usize> {
+        let mut candidates = Vec::with_capacity(self.queues.len());
+        for (index, queue) in self.queues.iter().enumerate() {
+            if !queue.health.is_unavailable() && !queue.rx.lock().await.is_empty() {
+                candidates.push(index);
+            }
+        }
+
+        let mut credits = self.credits.lock();
+        for &index in &candidates {
+            credits[index] += self.queues[index].weight as i64;
+        }
+        // Stable, so ties go to the tenant listed first
+        candidates.sort_by_key(|&index| std::cmp::Reverse(credits[index]));
+        candidates
+    }
+
+    /// Charge the tenant that got the batch the weight of every tenant that competed for it
+    fn charge(&self, index: usize, candidates: &[usize]) {
+        let total: i64 = candidates.iter().map(|&i| self.queues[i].weight as i64).sum();
+        self.credits.lock()[index] -= total;
+    }
+
+    /// Fill `batch` with up to `max` repos from the tenant whose turn it is. The batch is
+    /// counted as in flight before its queue is released, so a drain can't finish under it.
+    /// Returns `None`, and leaves `batch` empty, when every reachable queue is empty.
+    pub async fn next_batch<'a>(
+        &self,
+        batch: &mut Vec<Repo>,
+        max: usize,
+        intake: &'a IntakeControl,
+    ) -> Option<(TenantTarget, BatchGuard<'a>)> {
+        let candidates = self.turn_order().await;
+        // Another worker may empty a queue in the meantime; fall through to the next tenant
+        for &index in &candidates {
+            let queue = &self.queues[index];
+            let mut rx = queue.rx.lock().await;
+            while batch.len() < max {
+                match rx.try_recv() {
+                    Ok(repo) => batch.push(repo),
+                    Err(_) => break,
+                }
+            }
+            if !batch.is_empty() {
+                let in_flight = intake.begin_batch();
+                drop(rx);
+                self.charge(index, &candidates);
+                return Some((queue.target.clone(), in_flight));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::RepoOwner,
+        shutdown::ShutdownController,
+        surreal_client::{BatchUpdateResult, EmbeddingUpdate},
+    };
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use surrealdb::RecordId;
+
+    struct NullSink;
+
+    #[async_trait]
+    impl EmbeddingSink for NullSink {
+        async fn store_embeddings(&self, _updates: Vec<EmbeddingUpdate>) -> crate::error::Result<BatchUpdateResult> {
+            Ok(BatchUpdateResult::default())
+        }
+    }
+
+    fn create_test_repo(id: &str) -> Repo {
+        let now = Utc::now();
+        Repo {
+            id: RecordId::from(("repo", id)),
+            github_id: 1,
+            name: id.to_string(),
+            full_name: format!("owner/{}", id),
+            description: None,
+            url: format!("https://github.com/owner/{}", id),
+            stars: 1,
+            language: None,
+            readme: None,
+            owner: RepoOwner {
+                login: "owner".to_string(),
+                avatar_url: "https://github.com/owner.png".to_string(),
+            },
+            is_private: false,
+            created_at: now,
+            updated_at: now,
+            embedding: None,
+            embedding_generated_at: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_alternates_tenants() {
+        assert_eq!(
+            parse_tenants("gitstars/stars, acme=acme/repos").unwrap(),
+            vec![
+                Tenant {
+                    name: "gitstars/stars".to_string(),
+                    namespace: "gitstars".to_string(),
+                    database: "stars".to_string(),
+                    weight: 1,
+                },
+                Tenant {
+                    name: "acme".to_string(),
+                    namespace: "acme".to_string(),
+                    database: "repos".to_string(),
+                    weight: 1,
+                },
+            ]
+        );
+        assert!(parse_tenants("a=x/y,a=z/w").is_err());
+        assert!(parse_tenants("no-database").is_err());
+        assert_eq!(parse_tenants("acme=acme/repos:3").unwrap()[0].weight, 3);
+        assert!(parse_tenants("acme/repos:0").is_err());
+
+        let mut dispatcher = Dispatcher::new();
+        let mut senders = Vec::new();
+        for name in ["big", "small"] {
+            let (tx, rx) = mpsc::channel(16);
+            let target = TenantTarget {
+                tenant: Arc::from(name),
+                sink: Arc::new(NullSink),
+                shadow: None,
+                routed: None,
+            };
+            dispatcher = dispatcher.with_queue(target, 1, Arc::new(DbHealth::default()), rx);
+            senders.push(tx);
+        }
+        for i in 0..10 {
+            senders[0].send(create_test_repo(&format!("big-{}", i))).await.unwrap();
+        }
+        senders[1].send(create_test_repo("small-0")).await.unwrap();
+
+        // The small tenant gets the second batch even though the big one still has work
+        let (controller, _receiver) = ShutdownController::new();
+        let intake = IntakeControl::new(controller);
+        let mut tenants = Vec::new();
+        for _ in 0..3 {
+            let mut batch = Vec::new();
+            let (target, _in_flight) = dispatcher.next_batch(&mut batch, 4, &intake).await.unwrap();
+            tenants.push(target.tenant.to_string());
+        }
+        assert_eq!(tenants, vec!["big", "small", "big"]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_follows_weights() {
+        let mut dispatcher = Dispatcher::new();
+        let mut senders = Vec::new();
+        for (name, weight) in [("backfill", 3), ("live", 1)] {
+            let (tx, rx) = mpsc::channel(64);
+            let target = TenantTarget {
+                tenant: Arc::from(name),
+                sink: Arc::new(NullSink),
+                shadow: None,
+                routed: None,
+            };
+            dispatcher = dispatcher.with_queue(target, weight, Arc::new(DbHealth::default()), rx);
+            for i in 0..20 {
+                tx.send(create_test_repo(&format!("{}-{}", name, i))).await.unwrap();
+            }
+            senders.push(tx);
+        }
+
+        // Three batches for every one, interleaved
+        let (controller, _receiver) = ShutdownController::new();
+        let intake = IntakeControl::new(controller);
+        let mut tenants = Vec::new();
+        for _ in 0..8 {
+            let mut batch = Vec::new();
+            let (target, _in_flight) = dispatcher.next_batch(&mut batch, 2, &intake).await.unwrap();
+            tenants.push(target.tenant.to_string());
+        }
+        let expected = ["backfill", "backfill", "live", "backfill"].repeat(2);
+        assert_eq!(tenants, expected);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    config::Config, metrics::Metrics, pool::create_pool, rate_limiter::estimate_tokens, surreal_client::SurrealClient,
+};
+use prometheus::Registry;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BinaryHeap, fmt, sync::Arc};
+
+/// A repo whose prepared text is longer than the token limit
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct OversizedRepo {
+    pub chars: usize,
+    pub full_name: String,
+}
+
+/// Result of `text-sizes`: how long the texts sent to the provider are, measured the way
+/// the embedder measures them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TextSizeReport {
+    pub model: String,
+    /// TOKEN_LIMIT, in characters
+    pub token_limit: usize,
+    pub total: usize,
+    /// Repos whose text is truncated, or split into chunks when CHUNKING is on
+    pub over_limit: usize,
+    /// Estimated token count percentiles: p50, p90, p99 and max
+    pub tokens: Option<[u32; 4]>,
+    /// Longest texts over the limit, longest first
+    pub top: Vec<OversizedRepo>,
+}
+
+impl fmt::Display for TextSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Prepared text sizes for {}:", self.model)?;
+        writeln!(f, "  Repos: {}", self.total)?;
+        match self.tokens {
+            Some([p50, p90, p99, max]) => {
+                writeln!(f, "  Estimated tokens: p50 {}, p90 {}, p99 {}, max {}", p50, p90, p99, max)?
+            }
+            None => writeln!(f, "  Estimated tokens: no repos")?,
+        }
+        let percent = if self.total == 0 { 0.0 } else { self.over_limit as f64 * 100.0 / self.total as f64 };
+        writeln!(
+            f,
+            "  Over the {} character limit: {} ({:.1}%)",
+            self.token_limit, self.over_limit, percent
+        )?;
+        if !self.top.is_empty() {
+            writeln!(f, "  Longest:")?;
+            for repo in &self.top {
+                writeln!(f, "    {}: {} characters", repo.full_name, repo.chars)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`TextSizeReport`] one prepared text at a time, keeping only the `top` longest
+/// texts over the limit
+struct TextSizeCollector {
+    report: TextSizeReport,
+    token_counts: Vec<u32>,
+    longest: BinaryHeap<Reverse<OversizedRepo>>,
+    top: usize,
+}
+
+impl TextSizeCollector {
+    fn new(model: &str, token_limit: usize, top: usize) -> Self {
+        Self {
+            report: TextSizeReport {
+                model: model.to_string(),
+                token_limit,
+                ..Default::default()
+            },
+            token_counts: Vec::new(),
+            longest: BinaryHeap::new(),
+            top,
+        }
+    }
+
+    fn add(&mut self, full_name: &str, text: &str) {
+        self.report.total += 1;
+        self.token_counts.push(estimate_tokens(text));
+        // Same comparison as `truncate_to_limit`
+        if text.len() <= self.report.token_limit {
+            return;
+        }
+        self.report.over_limit += 1;
+        self.longest.push(Reverse(OversizedRepo {
+            chars: text.len(),
+            full_name: full_name.to_string(),
+        }));
+        if self.longest.len() > self.top {
+            self.longest.pop();
+        }
+    }
+
+    fn finish(mut self) -> TextSizeReport {
+        self.token_counts.sort_unstable();
+        if let Some(&max) = self.token_counts.last() {
+            let at = |q: f64| self.token_counts[((self.token_counts.len() - 1) as f64 * q).round() as usize];
+            self.report.tokens = Some([at(0.5), at(0.9), at(0.99), max]);
+        }
+        self.report.top = self.longest.into_sorted_vec().into_iter().map(|Reverse(repo)| repo).collect();
+        self.report
+    }
+}
+
+/// Prepare every repo's text as the embedder would and report the size distribution and the
+/// `top` longest texts over TOKEN_LIMIT
+pub async fn run_text_sizes(config: Config, top: usize, page_size: usize) -> anyhow::Result<TextSizeReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool);
+    let sanitizer = config.text_sanitizer();
+
+    let page_size = page_size.max(1);
+    let mut collector = TextSizeCollector::new(&config.embedding_model, config.token_limit, top);
+    let mut start = 0;
+    loop {
+        let repos = client.get_repos(start, page_size).await?;
+        for repo in &repos {
+            let text = repo.prepare_text_for_embedding();
+            match &sanitizer {
+                Some(sanitizer) => collector.add(&repo.full_name, &sanitizer.sanitize(&text)),
+                None => collector.add(&repo.full_name, &text),
+            }
+        }
+        if repos.len() < page_size {
+            break;
+        }
+        start += repos.len();
+    }
+
+    Ok(collector.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_size_report() {
+        let mut collector = TextSizeCollector::new("nomic-embed-text", 100, 2);
+        collector.add("owner/short", &"a".repeat(40));
+        collector.add("owner/long", &"a".repeat(400));
+        collector.add("owner/longest", &"a".repeat(800));
+        collector.add("owner/longer", &"a".repeat(600));
+        collector.add("owner/exact", &"a".repeat(100));
+        let report = collector.finish();
+
+        assert_eq!(report.total, 5);
+        assert_eq!(report.over_limit, 3);
+        assert_eq!(report.tokens, Some([100, 200, 200, 200]));
+        let top: Vec<_> = report.top.iter().map(|repo| repo.full_name.as_str()).collect();
+        assert_eq!(top, ["owner/longest", "owner/longer"]);
+
+        let printed = report.to_string();
+        assert!(printed.contains("Over the 100 character limit: 3 (60.0%)"));
+        assert!(printed.contains("owner/longest: 800 characters"));
+    }
+}
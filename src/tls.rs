@@ -0,0 +1,216 @@
+use crate::config::Config;
+use anyhow::Context;
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::warn;
+
+/// TLS settings for outgoing connections (SurrealDB and the embedding provider clients)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsSettings {
+    /// PEM bundle trusted in addition to the built-in roots
+    pub ca_cert: Option<PathBuf>,
+    /// PEM certificate chain and PKCS#8 key presented for mutual TLS
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// Skip certificate verification entirely; only for testing against self-signed endpoints
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ca_cert: config.tls_ca_cert.clone(),
+            client_cert: config.tls_client_cert.clone(),
+            client_key: config.tls_client_key.clone(),
+            accept_invalid_certs: config.tls_accept_invalid_certs,
+        }
+    }
+
+    /// Whether anything differs from the platform defaults
+    pub fn is_custom(&self) -> bool {
+        self.ca_cert.is_some() || self.client_cert.is_some() || self.accept_invalid_certs
+    }
+
+    /// Apply the settings to a reqwest client
+    pub fn configure_reqwest(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        if let Some(path) = &self.ca_cert {
+            for pem in split_pem_certificates(&read(path)?) {
+                let cert = reqwest::Certificate::from_pem(pem.as_bytes())
+                    .with_context(|| format!("Invalid certificate in {}", path.display()))?;
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            let identity = reqwest::Identity::from_pkcs8_pem(&read(cert)?, &read(key)?)
+                .context("Invalid TLS client certificate or key (the key must be PKCS#8)")?;
+            // A PKCS#8 identity only works with the native-tls backend
+            builder = builder.use_native_tls().identity(identity);
+        }
+        if self.accept_invalid_certs {
+            warn!("TLS certificate verification is disabled for provider requests");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// rustls configuration for the SurrealDB connection; `None` keeps the driver's defaults
+    pub fn rustls_config(&self) -> anyhow::Result<Option<ClientConfig>> {
+        if !self.is_custom() {
+            return Ok(None);
+        }
+
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let builder = if self.accept_invalid_certs {
+            warn!("TLS certificate verification is disabled for the database connection");
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+        } else {
+            let mut roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            if let Some(path) = &self.ca_cert {
+                for cert in read_certificates(path)? {
+                    roots
+                        .add(cert)
+                        .with_context(|| format!("Invalid certificate in {}", path.display()))?;
+                }
+            }
+            builder.with_root_certificates(roots)
+        };
+
+        let config = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(read_certificates(cert)?, read_private_key(key)?)
+                .context("Invalid TLS client certificate or key")?,
+            _ => builder.with_no_client_auth(),
+        };
+        Ok(Some(config))
+    }
+}
+
+/// rustls configuration for serving the monitoring endpoints over HTTPS. The crypto provider is
+/// chosen here rather than relying on a process-wide default being installed.
+pub fn server_config(cert: &Path, key: &Path) -> anyhow::Result<ServerConfig> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(read_certificates(cert)?, read_private_key(key)?)
+        .context("Invalid monitoring TLS certificate or key")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn read_certificates(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let pem = read(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn read_private_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let pem = read(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
+}
+
+/// Split a PEM bundle into one PEM block per certificate, since reqwest reads only the first
+fn split_pem_certificates(pem: &[u8]) -> Vec<String> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    String::from_utf8_lossy(pem)
+        .split_inclusive(END)
+        .filter_map(|block| block.find(BEGIN).map(|start| block[start..].to_string()))
+        .collect()
+}
+
+/// Accepts any server certificate; signatures are still checked so the handshake is well-formed
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pem_bundle() {
+        let bundle = b"# corporate root\n-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\nBBB\n-----END CERTIFICATE-----\n";
+        let certs = split_pem_certificates(bundle);
+
+        assert_eq!(certs.len(), 2);
+        assert_eq!(certs[0], "-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----");
+        assert!(certs[1].starts_with("-----BEGIN CERTIFICATE-----\nBBB"));
+    }
+
+    #[test]
+    fn test_server_config() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let (cert, key) = (fixtures.join("localhost.crt"), fixtures.join("localhost.key"));
+
+        let config = server_config(&cert, &key).unwrap();
+        assert!(config.alpn_protocols.contains(&b"http/1.1".to_vec()));
+
+        // The certificate where the key belongs, and a missing file
+        assert!(server_config(&cert, &cert).is_err());
+        assert!(server_config(&fixtures.join("missing.crt"), &key).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    config::Config,
+    metrics::{self, Metrics},
+    pool::create_pool,
+    surreal_client::{StarredEmbeddingRow, SurrealClient},
+};
+use chrono::Utc;
+use prometheus::Registry;
+use std::{fmt, str::FromStr, sync::Arc};
+use tracing::{debug, info};
+
+/// How much each starred repo counts towards a user's embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UserWeighting {
+    /// Every starred repo counts the same
+    #[default]
+    Uniform,
+    /// Repos count `1 / ln(e + stars)`, so niche stars say more about a user than hugely
+    /// popular ones everybody stars
+    InversePopularity,
+}
+
+impl UserWeighting {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserWeighting::Uniform => "uniform",
+            UserWeighting::InversePopularity => "inverse_popularity",
+        }
+    }
+
+    fn weight(&self, stars: u32) -> f32 {
+        match self {
+            UserWeighting::Uniform => 1.0,
+            UserWeighting::InversePopularity => 1.0 / (std::f32::consts::E + stars as f32).ln(),
+        }
+    }
+}
+
+impl FromStr for UserWeighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "uniform" | "" => Ok(UserWeighting::Uniform),
+            "inverse_popularity" => Ok(UserWeighting::InversePopularity),
+            other => Err(format!(
+                "Unknown user embedding weighting '{}' (expected uniform or inverse_popularity)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UserWeighting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Weighted mean of the starred repos' embeddings. Repos whose dimensions differ from the
+/// first one (e.g. left over from another model) are skipped; `None` when nothing is left.
+pub fn aggregate(rows: &[StarredEmbeddingRow], weighting: UserWeighting) -> Option<(Vec<f32>, usize)> {
+    let dimensions = rows.iter().map(|row| row.embedding.len()).find(|&len| len > 0)?;
+    let mut sum = vec![0.0f32; dimensions];
+    let mut total_weight = 0.0f32;
+    let mut count = 0;
+
+    for row in rows.iter().filter(|row| row.embedding.len() == dimensions) {
+        let weight = weighting.weight(row.stars);
+        for (sum, value) in sum.iter_mut().zip(&row.embedding) {
+            *sum += value * weight;
+        }
+        total_weight += weight;
+        count += 1;
+    }
+
+    for value in sum.iter_mut() {
+        *value /= total_weight;
+    }
+    Some((sum, count))
+}
+
+/// Summary of a user embedding refresh
+#[derive(Debug, Default)]
+pub struct UserEmbeddingReport {
+    pub users: usize,
+    pub updated: usize,
+    /// Users none of whose starred repos are embedded; their aggregate was cleared
+    pub cleared: usize,
+}
+
+impl fmt::Display for UserEmbeddingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "User embedding refresh:")?;
+        writeln!(f, "  Users refreshed: {}", self.users)?;
+        writeln!(f, "  Embeddings updated: {}", self.updated)?;
+        writeln!(f, "  Embeddings cleared: {}", self.cleared)?;
+        Ok(())
+    }
+}
+
+/// Recompute the aggregate embedding of every user with a star on a repo embedded since the
+/// last refresh, and of users that don't have one yet. `full` recomputes every user, which
+/// also picks up stars added or removed since their last refresh.
+pub async fn refresh_user_embeddings(
+    client: &SurrealClient,
+    config: &Config,
+    full: bool,
+) -> anyhow::Result<UserEmbeddingReport> {
+    let weighting = config.user_embedding_weighting()?;
+    let edge = &config.user_star_edge;
+    // Taken before reading so embeddings written during the refresh are seen by the next one
+    let started = Utc::now();
+    let since = if full {
+        None
+    } else {
+        client.get_user_embedding_watermark().await?
+    };
+
+    let users = client.get_users_to_refresh(edge, since).await?;
+    let mut report = UserEmbeddingReport {
+        users: users.len(),
+        ..Default::default()
+    };
+    for user in &users {
+        let rows = client.get_starred_embeddings(edge, user).await?;
+        match aggregate(&rows, weighting) {
+            Some((embedding, count)) => {
+                client.update_user_embedding(user, Some(embedding), count).await?;
+                report.updated += 1;
+            }
+            None => {
+                client.update_user_embedding(user, None, 0).await?;
+                report.cleared += 1;
+            }
+        }
+        debug!(user = %user, repos = rows.len(), "Refreshed user embedding");
+    }
+    metrics::record_user_embeddings_refreshed(report.updated, report.cleared);
+
+    client.set_user_embedding_watermark(started).await?;
+    if report.users > 0 {
+        info!(
+            users = report.users,
+            updated = report.updated,
+            cleared = report.cleared,
+            "Refreshed user embeddings"
+        );
+    }
+    Ok(report)
+}
+
+/// Run the `user-embeddings` command
+pub async fn run_user_embeddings(config: Config, full: bool) -> anyhow::Result<UserEmbeddingReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool)
+        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone());
+
+    refresh_user_embeddings(&client, &config, full).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(embedding: Vec<f32>, stars: u32) -> StarredEmbeddingRow {
+        StarredEmbeddingRow { embedding, stars }
+    }
+
+    #[test]
+    fn test_aggregate_weighting() {
+        let rows = vec![row(vec![1.0, 0.0], 0), row(vec![0.0, 1.0], 100_000), row(vec![1.0], 5)];
+
+        let (uniform, count) = aggregate(&rows, UserWeighting::Uniform).unwrap();
+        // The one-dimensional leftover is skipped
+        assert_eq!(count, 2);
+        assert_eq!(uniform, vec![0.5, 0.5]);
+
+        let (weighted, _) = aggregate(&rows, UserWeighting::InversePopularity).unwrap();
+        assert!(weighted[0] > weighted[1]);
+        assert!((weighted[0] + weighted[1] - 1.0).abs() < 1e-6);
+
+        assert!(aggregate(&[], UserWeighting::Uniform).is_none());
+        assert_eq!("inverse_popularity".parse::<UserWeighting>().unwrap(), UserWeighting::InversePopularity);
+        assert!("recency".parse::<UserWeighting>().is_err());
+    }
+}
@@ -1,6 +1,45 @@
-use crate::error::{EmbedError, Result};
+use crate::{
+    error::{EmbedError, Result},
+    metrics,
+    precision::StoragePrecision,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 use tracing::{debug, warn};
 
+/// A model name, its native output dimensions and, for models known to return unit-length
+/// vectors, magnitude bounds
+type ModelProfile = (&'static str, usize, Option<(f32, f32)>);
+
+/// Validation profiles of well-known models
+const MODEL_PROFILES: &[ModelProfile] = &[
+    ("nomic-embed-text", 768, None),
+    ("mxbai-embed-large", 1024, None),
+    ("all-minilm", 384, None),
+    ("text-embedding-3-small", 1536, None),
+    ("text-embedding-3-large", 3072, None),
+    ("text-embedding-ada-002", 1536, None),
+    ("togethercomputer/m2-bert-80M-8k-retrieval", 768, None),
+    ("BAAI/bge-base-en-v1.5", 768, None),
+    ("BAAI/bge-large-en-v1.5", 1024, None),
+    ("intfloat/multilingual-e5-large-instruct", 1024, Some((0.5, 2.0))),
+];
+
+fn model_profile(model: &str) -> Option<&'static ModelProfile> {
+    // Ollama tags (`nomic-embed-text:latest`) match the base name
+    let base = model.split(':').next().unwrap_or(model);
+    MODEL_PROFILES.iter().find(|(name, _, _)| *name == model || *name == base)
+}
+
+/// Dimensions a model produces, if known
+pub fn known_dimensions(model: &str) -> Option<usize> {
+    model_profile(model).map(|(_, dimensions, _)| *dimensions)
+}
+
 /// Configuration for embedding validation
 #[derive(Debug, Clone)]
 pub struct ValidationConfig {
@@ -8,6 +47,8 @@ pub struct ValidationConfig {
     pub min_dimension: usize,
     /// Maximum acceptable embedding dimension
     pub max_dimension: usize,
+    /// Exact dimension the model must produce, when known; checked instead of the min/max bounds
+    pub expected_dimension: Option<usize>,
     /// Maximum allowed zero values (as percentage)
     pub max_zero_ratio: f32,
     /// Minimum magnitude (L2 norm) for embeddings
@@ -18,6 +59,14 @@ pub struct ValidationConfig {
     pub check_finite: bool,
     /// Maximum allowed duplicate values (as percentage)
     pub max_duplicate_ratio: f32,
+    /// Minimum variance of the values; nearly constant vectors are suspicious. 0.0 disables the check
+    pub min_variance: f32,
+    /// Rescale vectors that only fail because of their magnitude instead of rejecting them
+    pub repair: bool,
+    /// L2-normalize embeddings before they are stored
+    pub normalize: bool,
+    /// Precision the float vector is stored in; values outside its range are rejected
+    pub precision: StoragePrecision,
 }
 
 impl Default for ValidationConfig {
@@ -25,38 +74,152 @@ impl Default for ValidationConfig {
         Self {
             min_dimension: 100,
             max_dimension: 4096,
+            expected_dimension: None,
             max_zero_ratio: 0.9, // Allow up to 90% zeros
             min_magnitude: 0.01,
             max_magnitude: 100.0,
             check_finite: true,
             max_duplicate_ratio: 0.5, // Allow up to 50% duplicate values
+            min_variance: 0.0,
+            repair: false,
+            normalize: false,
+            precision: StoragePrecision::F32,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// The profile for `model`: the defaults plus its known dimension, a variance check and,
+    /// for models returning unit-length vectors, tighter magnitude bounds
+    pub fn for_model(model: &str) -> Self {
+        let mut config = Self {
+            min_variance: 1e-6,
+            ..Default::default()
+        };
+        if let Some((_, dimensions, magnitude)) = model_profile(model) {
+            config.expected_dimension = Some(*dimensions);
+            if let Some((min, max)) = magnitude {
+                config.min_magnitude = *min;
+                config.max_magnitude = *max;
+            }
         }
+        config
     }
 }
 
-/// Validates embeddings based on various quality criteria
+/// Result of [`EmbeddingValidator::validate_or_repair`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationOutcome {
+    Valid,
+    /// The vector failed validation and was rescaled to pass
+    Repaired,
+}
+
+/// Validates embeddings based on various quality criteria. The pipeline validates each vector
+/// once, after generation; validators labelled with a model record every result in the
+/// validation metrics.
 pub struct EmbeddingValidator {
     config: ValidationConfig,
+    /// Label of the validation metrics; audits and imports leave it unset and record nothing
+    model: Option<String>,
+    /// Where validation results feed the provider's rolling quality statistics
+    quality: Option<(Arc<ProviderQualityTracker>, String)>,
 }
 
 impl EmbeddingValidator {
     pub fn new(config: ValidationConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            model: None,
+            quality: None,
+        }
+    }
+
+    /// Record every validation result under `model`
+    pub fn with_metrics(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Add every validation result to `provider`'s quality statistics in `tracker`
+    pub fn with_quality(mut self, tracker: Arc<ProviderQualityTracker>, provider: impl Into<String>) -> Self {
+        self.quality = Some((tracker, provider.into()));
+        self
     }
 
     /// Validate an embedding vector
     pub fn validate(&self, embedding: &[f32], source: &str) -> Result<()> {
+        let result = self.check(embedding, source);
+        if let Some(model) = &self.model {
+            metrics::record_embedding_validation(model, result.is_ok());
+        }
+        if let Some((tracker, provider)) = &self.quality {
+            tracker.record(provider, result.is_ok(), embedding);
+        }
+        result
+    }
+
+    /// Validate an embedding, rescaling it in place when repair is enabled and its magnitude is
+    /// the only problem: to unit length when the bounds allow it, otherwise to the nearest bound.
+    /// Validation metrics record the original result; repairs are counted separately.
+    pub fn validate_or_repair(&self, embedding: &mut [f32], source: &str) -> Result<ValidationOutcome> {
+        let error = match self.validate(embedding, source) {
+            Ok(()) => return Ok(ValidationOutcome::Valid),
+            Err(e) => e,
+        };
+        if !self.config.repair {
+            return Err(error);
+        }
+
+        let repaired = self
+            .rescaled(embedding)
+            .filter(|rescaled| self.check(rescaled, source).is_ok());
+        if let Some(model) = &self.model {
+            metrics::record_validation_repair(model, repaired.is_some());
+        }
+        match repaired {
+            Some(rescaled) => {
+                warn!("Repaired embedding for {} by rescaling: {}", source, error);
+                embedding.copy_from_slice(&rescaled);
+                Ok(ValidationOutcome::Repaired)
+            }
+            None => Err(error),
+        }
+    }
+
+    /// `embedding` scaled to the magnitude closest to 1.0 within the bounds; `None` when it
+    /// can't be rescaled
+    fn rescaled(&self, embedding: &[f32]) -> Option<Vec<f32>> {
+        let magnitude = embedding.iter().map(|&x| x * x).sum::<f32>().sqrt();
+        if !magnitude.is_finite() || magnitude == 0.0 {
+            return None;
+        }
+        let target = 1.0f32.max(self.config.min_magnitude).min(self.config.max_magnitude);
+        Some(embedding.iter().map(|&x| x * target / magnitude).collect())
+    }
+
+    fn check(&self, embedding: &[f32], source: &str) -> Result<()> {
+        if embedding.is_empty() {
+            return Err(EmbedError::ValidationError(format!("Embedding is empty for {}", source)));
+        }
+
         // Check dimension
-        if embedding.len() < self.config.min_dimension {
+        // A known dimension replaces the min/max bounds, so small models aren't held to them
+        if let Some(expected) = self.config.expected_dimension {
+            if embedding.len() != expected {
+                return Err(EmbedError::InvalidDimension {
+                    expected,
+                    actual: embedding.len(),
+                });
+            }
+        } else if embedding.len() < self.config.min_dimension {
             return Err(EmbedError::ValidationError(format!(
                 "Embedding dimension {} is below minimum {} for {}",
                 embedding.len(),
                 self.config.min_dimension,
                 source
             )));
-        }
-
-        if embedding.len() > self.config.max_dimension {
+        } else if embedding.len() > self.config.max_dimension {
             return Err(EmbedError::ValidationError(format!(
                 "Embedding dimension {} exceeds maximum {} for {}",
                 embedding.len(),
@@ -119,6 +282,13 @@ impl EmbeddingValidator {
             )));
         }
 
+        if self.config.min_variance > 0.0 && stats.variance < self.config.min_variance {
+            return Err(EmbedError::ValidationError(format!(
+                "Embedding variance {:.2e} is below minimum {:.2e} for {}, values are nearly identical",
+                stats.variance, self.config.min_variance, source
+            )));
+        }
+
         // Check duplicate ratio
         if stats.duplicate_ratio > self.config.max_duplicate_ratio {
             warn!(
@@ -145,19 +315,22 @@ impl EmbeddingValidator {
     fn calculate_stats(&self, embedding: &[f32]) -> EmbeddingStats {
         let mut zero_count = 0;
         let mut magnitude_squared = 0.0;
-        let mut value_counts = std::collections::HashMap::new();
+        let mut sum = 0.0;
+        let mut value_counts = HashMap::new();
 
         for &value in embedding {
             if value == 0.0 {
                 zero_count += 1;
             }
             magnitude_squared += value * value;
+            sum += value;
             *value_counts.entry(value.to_bits()).or_insert(0) += 1;
         }
 
         let total = embedding.len() as f32;
         let zero_ratio = zero_count as f32 / total;
         let magnitude = magnitude_squared.sqrt();
+        let variance = magnitude_squared / total - (sum / total).powi(2);
 
         // Calculate duplicate ratio
         let max_count = value_counts.values().max().copied().unwrap_or(0);
@@ -171,7 +344,54 @@ impl EmbeddingValidator {
             zero_ratio,
             magnitude,
             duplicate_ratio,
+            variance,
+        }
+    }
+
+    /// Validate a batch of embeddings and collect statistics on the valid ones
+    pub fn validate_batch(&self, embeddings: &[(String, Vec<f32>)]) -> BatchValidationResult {
+        let mut results = BatchValidationResult::default();
+
+        for (context, embedding) in embeddings {
+            match self.validate(embedding, context) {
+                Ok(_) => {
+                    results.valid += 1;
+                    let magnitude: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                    results.magnitudes.push(magnitude);
+                    if results.dimension.is_none() {
+                        results.dimension = Some(embedding.len());
+                    }
+                }
+                Err(e) => {
+                    results.invalid += 1;
+                    results.errors.push((context.clone(), e.to_string()));
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Whether embeddings should be normalized before storage
+    pub fn normalizes(&self) -> bool {
+        self.config.normalize
+    }
+
+    /// Apply the storage policy to a validated embedding, returning whether it is normalized
+    pub fn prepare_for_storage(&self, embedding: &mut [f32]) -> Result<bool> {
+        if self.config.normalize {
+            self.normalize(embedding)?;
+        }
+
+        // Half precision overflows to infinity, so such vectors can't be stored faithfully
+        let max = self.config.precision.max_value();
+        if let Some(value) = embedding.iter().find(|x| x.abs() > max) {
+            return Err(EmbedError::InvalidEmbedding(format!(
+                "Value {} is out of range for {} storage",
+                value, self.config.precision
+            )));
         }
+        Ok(self.config.normalize)
     }
 
     /// Normalize an embedding to unit length
@@ -221,15 +441,53 @@ struct EmbeddingStats {
     zero_ratio: f32,
     magnitude: f32,
     duplicate_ratio: f32,
+    variance: f32,
 }
 
-/// Quality metrics for embedding providers
+#[derive(Debug, Default)]
+pub struct BatchValidationResult {
+    pub valid: usize,
+    pub invalid: usize,
+    pub errors: Vec<(String, String)>,
+    pub magnitudes: Vec<f32>,
+    pub dimension: Option<usize>,
+}
+
+impl BatchValidationResult {
+    pub fn success_rate(&self) -> f32 {
+        if self.valid + self.invalid == 0 {
+            0.0
+        } else {
+            self.valid as f32 / (self.valid + self.invalid) as f32
+        }
+    }
+
+    pub fn average_magnitude(&self) -> Option<f32> {
+        if self.magnitudes.is_empty() {
+            None
+        } else {
+            Some(self.magnitudes.iter().sum::<f32>() / self.magnitudes.len() as f32)
+        }
+    }
+}
+
+/// Validations kept in each provider's rolling quality window
+const QUALITY_WINDOW: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+struct QualitySample {
+    passed: bool,
+    magnitude: f32,
+    zero_ratio: f32,
+    dimension: usize,
+}
+
+/// Quality of one provider's embeddings over its last [`QUALITY_WINDOW`] validations
 pub struct ProviderQualityMetrics {
     pub provider: String,
     pub total_validations: u64,
     pub failed_validations: u64,
-    pub average_magnitude: f32,
-    pub average_zero_ratio: f32,
+    window: VecDeque<QualitySample>,
 }
 
 impl ProviderQualityMetrics {
@@ -238,29 +496,128 @@ impl ProviderQualityMetrics {
             provider,
             total_validations: 0,
             failed_validations: 0,
-            average_magnitude: 0.0,
-            average_zero_ratio: 0.0,
+            window: VecDeque::with_capacity(QUALITY_WINDOW),
         }
     }
 
-    pub fn update(&mut self, passed: bool, magnitude: f32, zero_ratio: f32) {
+    pub fn update(&mut self, passed: bool, magnitude: f32, zero_ratio: f32, dimension: usize) {
         self.total_validations += 1;
         if !passed {
             self.failed_validations += 1;
         }
-        
-        // Update running averages
-        let n = self.total_validations as f32;
-        self.average_magnitude = (self.average_magnitude * (n - 1.0) + magnitude) / n;
-        self.average_zero_ratio = (self.average_zero_ratio * (n - 1.0) + zero_ratio) / n;
+
+        if self.window.len() == QUALITY_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(QualitySample {
+            passed,
+            magnitude,
+            zero_ratio,
+            dimension,
+        });
     }
 
+    /// Share of failed validations in the window
     pub fn failure_rate(&self) -> f32 {
-        if self.total_validations == 0 {
+        if self.window.is_empty() {
             0.0
         } else {
-            self.failed_validations as f32 / self.total_validations as f32
+            self.window.iter().filter(|s| !s.passed).count() as f32 / self.window.len() as f32
+        }
+    }
+
+    /// Mean magnitude in the window, ignoring vectors with NaN or infinite values
+    pub fn average_magnitude(&self) -> Option<f32> {
+        let magnitudes: Vec<f32> = self.window.iter().map(|s| s.magnitude).filter(|m| m.is_finite()).collect();
+        (!magnitudes.is_empty()).then(|| magnitudes.iter().sum::<f32>() / magnitudes.len() as f32)
+    }
+
+    pub fn average_zero_ratio(&self) -> Option<f32> {
+        (!self.window.is_empty())
+            .then(|| self.window.iter().map(|s| s.zero_ratio).sum::<f32>() / self.window.len() as f32)
+    }
+
+    /// Most common dimension in the window and the share of vectors that have it
+    pub fn dimension_consistency(&self) -> Option<(usize, f32)> {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for sample in &self.window {
+            *counts.entry(sample.dimension).or_insert(0) += 1;
         }
+        counts
+            .into_iter()
+            .max_by_key(|&(dimension, count)| (count, dimension))
+            .map(|(dimension, count)| (dimension, count as f32 / self.window.len() as f32))
+    }
+
+    pub fn snapshot(&self) -> ProviderQuality {
+        let consistency = self.dimension_consistency();
+        ProviderQuality {
+            provider: self.provider.clone(),
+            total_validations: self.total_validations,
+            failed_validations: self.failed_validations,
+            window: self.window.len(),
+            failure_rate: self.failure_rate(),
+            average_magnitude: self.average_magnitude(),
+            average_zero_ratio: self.average_zero_ratio(),
+            dimension: consistency.map(|(dimension, _)| dimension),
+            dimension_consistency: consistency.map(|(_, share)| share),
+        }
+    }
+}
+
+/// Serializable view of [`ProviderQualityMetrics`]; rates and averages cover the window only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderQuality {
+    pub provider: String,
+    pub total_validations: u64,
+    pub failed_validations: u64,
+    /// Validations the rolling statistics cover
+    pub window: usize,
+    pub failure_rate: f32,
+    pub average_magnitude: Option<f32>,
+    pub average_zero_ratio: Option<f32>,
+    /// Most common dimension
+    pub dimension: Option<usize>,
+    /// Share of vectors with the most common dimension
+    pub dimension_consistency: Option<f32>,
+}
+
+/// Rolling quality statistics of every provider the pipeline validates embeddings from,
+/// served by `/status` and exported as gauges
+#[derive(Default)]
+pub struct ProviderQualityTracker {
+    providers: Mutex<HashMap<String, ProviderQualityMetrics>>,
+}
+
+impl ProviderQualityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one validation result for `provider`
+    pub fn record(&self, provider: &str, passed: bool, embedding: &[f32]) {
+        let magnitude = embedding.iter().map(|&x| x * x).sum::<f32>().sqrt();
+        let zeros = embedding.iter().filter(|&&x| x == 0.0).count();
+        let zero_ratio = if embedding.is_empty() { 0.0 } else { zeros as f32 / embedding.len() as f32 };
+
+        let mut providers = self.providers.lock();
+        let quality = providers
+            .entry(provider.to_string())
+            .or_insert_with(|| ProviderQualityMetrics::new(provider.to_string()));
+        quality.update(passed, magnitude, zero_ratio, embedding.len());
+        metrics::set_provider_quality(
+            provider,
+            quality.failure_rate(),
+            quality.average_magnitude(),
+            quality.dimension_consistency().map(|(_, share)| share),
+        );
+    }
+
+    /// Every provider's statistics, by provider name
+    pub fn snapshot(&self) -> Vec<ProviderQuality> {
+        let mut snapshot: Vec<ProviderQuality> = self.providers.lock().values().map(|q| q.snapshot()).collect();
+        snapshot.sort_by(|a, b| a.provider.cmp(&b.provider));
+        snapshot
     }
 }
 
@@ -305,8 +662,8 @@ mod tests {
 
         let mut embedding = vec![0.1; 200];
         // Set 60% to zero (exceeds 50% threshold)
-        for i in 0..120 {
-            embedding[i] = 0.0;
+        for value in embedding.iter_mut().take(120) {
+            *value = 0.0;
         }
         
         assert!(validator.validate(&embedding, "test").is_err());
@@ -343,6 +700,29 @@ mod tests {
         assert!((embedding[1] - 0.8).abs() < 0.0001);
     }
 
+    #[test]
+    fn test_prepare_for_storage() {
+        let validator = EmbeddingValidator::new(ValidationConfig::default());
+        let mut embedding = vec![3.0, 4.0];
+        assert!(!validator.prepare_for_storage(&mut embedding).unwrap());
+        assert_eq!(embedding, vec![3.0, 4.0]);
+
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            normalize: true,
+            ..Default::default()
+        });
+        assert!(validator.prepare_for_storage(&mut embedding).unwrap());
+        assert!((embedding[0] - 0.6).abs() < 0.0001);
+        assert!((embedding[1] - 0.8).abs() < 0.0001);
+
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            precision: StoragePrecision::F16,
+            ..Default::default()
+        });
+        assert!(validator.prepare_for_storage(&mut [70000.0, 1.0]).is_err());
+        assert!(!validator.prepare_for_storage(&mut [60000.0, 1.0]).unwrap());
+    }
+
     #[test]
     fn test_cosine_similarity() {
         let validator = EmbeddingValidator::new(ValidationConfig::default());
@@ -440,4 +820,119 @@ mod tests {
         // Should report the first error (NaN at index 10)
         assert!(err.to_string().contains("NaN value at index 10"));
     }
+
+    #[test]
+    fn test_empty_embedding() {
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            min_dimension: 0,
+            ..Default::default()
+        });
+        let result = validator.validate(&[], "test");
+        assert!(result.unwrap_err().to_string().contains("empty"));
+    }
+
+    #[test]
+    fn test_model_profiles() {
+        let e5 = ValidationConfig::for_model("intfloat/multilingual-e5-large-instruct");
+        assert_eq!(e5.expected_dimension, Some(1024));
+        assert_eq!((e5.min_magnitude, e5.max_magnitude), (0.5, 2.0));
+
+        let validator = EmbeddingValidator::new(ValidationConfig::for_model("nomic-embed-text"));
+        let wrong_dim: Vec<f32> = (0..512).map(|i| (i % 7) as f32 * 0.01).collect();
+        assert!(matches!(
+            validator.validate(&wrong_dim, "test"),
+            Err(EmbedError::InvalidDimension { expected: 768, actual: 512 })
+        ));
+        // Profiles reject constant vectors; the defaults don't check variance
+        let constant = vec![0.1; 768];
+        assert!(validator.validate(&constant, "test").unwrap_err().to_string().contains("variance"));
+        assert!(EmbeddingValidator::new(ValidationConfig::default()).validate(&constant, "test").is_ok());
+    }
+
+    #[test]
+    fn test_repair_magnitude_failures() {
+        let config = ValidationConfig {
+            min_magnitude: 0.5,
+            max_magnitude: 2.0,
+            ..Default::default()
+        };
+        let large: Vec<f32> = (0..200).map(|i| 1.0 + (i % 5) as f32).collect();
+
+        // Without repair the vector is rejected and left untouched
+        let mut embedding = large.clone();
+        assert!(EmbeddingValidator::new(config.clone()).validate_or_repair(&mut embedding, "test").is_err());
+        assert_eq!(embedding, large);
+
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            repair: true,
+            ..config
+        });
+        assert_eq!(validator.validate_or_repair(&mut embedding, "test").unwrap(), ValidationOutcome::Repaired);
+        let magnitude = embedding.iter().map(|&x| x * x).sum::<f32>().sqrt();
+        assert!((magnitude - 1.0).abs() < 1e-4);
+        assert_eq!(validator.validate_or_repair(&mut embedding, "test").unwrap(), ValidationOutcome::Valid);
+
+        // Rescaling can't fix anything but the magnitude
+        let mut wrong = large.clone();
+        wrong[3] = f32::NAN;
+        assert!(validator.validate_or_repair(&mut wrong, "test").is_err());
+        let mut zeros = vec![0.0; 200];
+        assert!(validator.validate_or_repair(&mut zeros, "test").is_err());
+    }
+
+    #[test]
+    fn test_batch_validation() {
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            min_dimension: 1,
+            expected_dimension: Some(3),
+            ..Default::default()
+        });
+
+        let batch = vec![
+            ("repo1".to_string(), vec![0.1, 0.2, 0.3]),
+            ("repo2".to_string(), vec![0.4, 0.5, 0.6]),
+            ("repo3".to_string(), vec![0.0, 0.0]), // Wrong dimension
+            ("repo4".to_string(), vec![f32::NAN, 0.1, 0.2]), // Contains NaN
+        ];
+
+        let result = validator.validate_batch(&batch);
+        assert_eq!(result.valid, 2);
+        assert_eq!(result.invalid, 2);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.success_rate(), 0.5);
+        assert!(result.average_magnitude().is_some());
+    }
+    #[test]
+    fn test_provider_quality_tracking() {
+        let tracker = Arc::new(ProviderQualityTracker::new());
+        let validator = EmbeddingValidator::new(ValidationConfig {
+            min_dimension: 1,
+            ..Default::default()
+        })
+        .with_quality(tracker.clone(), "openai");
+
+        assert!(validator.validate(&[3.0, 4.0], "a").is_ok());
+        assert!(validator.validate(&[0.6, 0.8], "b").is_ok());
+        assert!(validator.validate(&[0.0, 0.0, 0.0], "c").is_err());
+        tracker.record("ollama", true, &[1.0, 0.0]);
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].provider, "ollama");
+        let openai = &snapshot[1];
+        assert_eq!((openai.total_validations, openai.failed_validations), (3, 1));
+        assert!((openai.failure_rate - 1.0 / 3.0).abs() < 1e-6);
+        assert!((openai.average_magnitude.unwrap() - 2.0).abs() < 1e-6);
+        assert_eq!(openai.dimension, Some(2));
+        assert!((openai.dimension_consistency.unwrap() - 2.0 / 3.0).abs() < 1e-6);
+
+        // Only the most recent validations count towards the rates
+        let mut quality = ProviderQualityMetrics::new("openai".to_string());
+        quality.update(false, 1.0, 0.0, 2);
+        for _ in 0..QUALITY_WINDOW {
+            quality.update(true, 1.0, 0.0, 2);
+        }
+        assert_eq!(quality.failed_validations, 1);
+        assert_eq!(quality.failure_rate(), 0.0);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    pool::create_pool,
+    surreal_client::SurrealClient,
+    validation::EmbeddingValidator,
+};
+use prometheus::Registry;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+use surrealdb::RecordId;
+use tracing::{info, warn};
+
+/// Maximum number of individual failures kept for the report
+const MAX_SAMPLE_ERRORS: usize = 20;
+
+/// Summary of an embedding audit
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub scanned: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    pub marked: usize,
+    pub dimensions: BTreeMap<usize, usize>,
+    pub sample_errors: Vec<(String, String)>,
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Embedding verification report:")?;
+        writeln!(f, "  Scanned: {}", self.scanned)?;
+        writeln!(f, "  Valid: {}", self.valid)?;
+        writeln!(f, "  Invalid: {}", self.invalid)?;
+        writeln!(f, "  Marked for re-embedding: {}", self.marked)?;
+        writeln!(f, "  Dimensions:")?;
+        for (dimension, count) in &self.dimensions {
+            writeln!(f, "    {}: {}", dimension, count)?;
+        }
+        if !self.sample_errors.is_empty() {
+            writeln!(f, "  Sample errors:")?;
+            for (id, error) in &self.sample_errors {
+                writeln!(f, "    {}: {}", id, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validator used for audits, matching the checks applied before storage
+pub fn audit_validator(config: &Config) -> EmbeddingValidator {
+    EmbeddingValidator::new(config.validation_config())
+}
+
+/// Scan all stored embeddings and validate them, optionally marking invalid rows for re-embedding
+pub async fn run_verify(config: Config, mark_invalid: bool, page_size: usize) -> anyhow::Result<VerifyReport> {
+    let config = Arc::new(config);
+    config.validate()?;
+
+    // Pool code records metrics, so they must exist even for one-off commands
+    let registry = Registry::new();
+    Metrics::register(&registry)?;
+
+    let pool = create_pool(config.clone()).await?;
+    let client = SurrealClient::new(pool)
+        .with_quantization(config.quantization_mode()?, config.quantized_only)
+        .with_storage_mode(config.storage_mode()?, config.embedding_model.clone())
+        .with_write_chunk_size(config.db_write_chunk_size);
+
+    verify_embeddings(&client, &audit_validator(&config), mark_invalid, page_size).await
+}
+
+/// Validate every stored embedding through an existing client; also run by the `verify` scheduled job
+pub async fn verify_embeddings(
+    client: &SurrealClient,
+    validator: &EmbeddingValidator,
+    mark_invalid: bool,
+    page_size: usize,
+) -> anyhow::Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let mut start = 0;
+
+    loop {
+        let rows = client.get_stored_embeddings(start, page_size.max(1)).await?;
+        if rows.is_empty() {
+            break;
+        }
+        start += rows.len();
+
+        let mut invalid_ids: Vec<RecordId> = Vec::new();
+        for row in rows {
+            report.scanned += 1;
+            *report.dimensions.entry(row.embedding.len()).or_insert(0) += 1;
+
+            match validator.validate(&row.embedding, &row.id.to_string()) {
+                Ok(_) => report.valid += 1,
+                Err(e) => {
+                    report.invalid += 1;
+                    if report.sample_errors.len() < MAX_SAMPLE_ERRORS {
+                        report.sample_errors.push((row.id.to_string(), e.to_string()));
+                    }
+                    invalid_ids.push(row.id);
+                }
+            }
+        }
+
+        if mark_invalid && !invalid_ids.is_empty() {
+            report.marked += client.mark_for_reembedding(&invalid_ids).await?;
+            // Cleared rows drop out of the scan, so the next page starts earlier
+            start -= invalid_ids.len();
+        }
+
+        info!(scanned = report.scanned, invalid = report.invalid, "Verification progress");
+    }
+
+    if report.invalid > 0 {
+        warn!(invalid = report.invalid, "Found invalid stored embeddings");
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,294 @@
+use crate::{
+    audit::{AuditRecord, BatchRun},
+    error::Result,
+    repo_store::EmbeddingSink,
+    surreal_client::{BatchUpdateResult, EmbeddingUpdate},
+    write_retry::SpilledUpdate,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use surrealdb::RecordId;
+use tracing::{info, warn};
+
+/// Log size past which it is rewritten with only the uncommitted embeddings
+const COMPACT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// One line of the log
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WalEntry {
+    /// An embedding came back from the provider and passed validation
+    Generated(SpilledUpdate),
+    /// These repos' embeddings were written (or rejected individually by the database)
+    Committed(Vec<String>),
+}
+
+struct WalState {
+    file: File,
+    /// Bytes appended since the log was last emptied or compacted
+    written: u64,
+    /// Generated but not yet committed, by repo id
+    outstanding: HashMap<String, EmbeddingUpdate>,
+}
+
+impl WalState {
+    fn append(&mut self, entry: &WalEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Wraps a sink with a local write-ahead log: every generated embedding is appended as it
+/// arrives, the log is synced once before its batch is written, and the batch is marked
+/// committed once the write returns. After a crash or a database outage the uncommitted
+/// embeddings are written by [`WalSink::recover`] instead of being paid for again.
+pub struct WalSink {
+    inner: Arc<dyn EmbeddingSink>,
+    path: PathBuf,
+    state: Mutex<WalState>,
+}
+
+impl WalSink {
+    /// Open the log at `path`, reading the embeddings a previous run left uncommitted
+    pub fn open(inner: Arc<dyn EmbeddingSink>, path: PathBuf) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let outstanding = read_log(&path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            inner,
+            path,
+            state: Mutex::new(WalState {
+                file,
+                written,
+                outstanding,
+            }),
+        })
+    }
+
+    /// Embeddings generated but not yet committed
+    pub fn pending(&self) -> usize {
+        self.state.lock().outstanding.len()
+    }
+
+    /// Write what a previous run left uncommitted. Returns how many were stored; on error the
+    /// embeddings stay in the log for the next attempt.
+    pub async fn recover(&self) -> Result<usize> {
+        let updates: Vec<EmbeddingUpdate> = self.state.lock().outstanding.values().cloned().collect();
+        if updates.is_empty() {
+            return Ok(0);
+        }
+        info!(path = %self.path.display(), updates = updates.len(), "Writing embeddings left uncommitted in the write-ahead log");
+
+        let ids: Vec<RecordId> = updates.iter().map(|update| update.repo_id.clone()).collect();
+        let result = self.inner.store_embeddings(updates).await?;
+        self.commit(&ids);
+        Ok(result.successful)
+    }
+
+    /// Flush what was appended since the last sync to disk, on the blocking pool
+    async fn sync(&self) -> anyhow::Result<()> {
+        let file = self.state.lock().file.try_clone()?;
+        tokio::task::spawn_blocking(move || file.sync_data()).await??;
+        Ok(())
+    }
+
+    /// Mark `ids` written, emptying the log when nothing is outstanding and compacting it
+    /// when it has grown large
+    fn commit(&self, ids: &[RecordId]) {
+        let mut state = self.state.lock();
+        let ids: Vec<String> = ids
+            .iter()
+            .map(|id| id.to_string())
+            .filter(|id| state.outstanding.remove(id).is_some())
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let result = if state.outstanding.is_empty() {
+            truncate(&mut state)
+        } else if state.written > COMPACT_BYTES {
+            self.compact(&mut state)
+        } else {
+            state.append(&WalEntry::Committed(ids))
+        };
+        if let Err(e) = result {
+            warn!(path = %self.path.display(), "Failed to update write-ahead log: {}", e);
+        }
+    }
+
+    /// Rewrite the log with only the outstanding embeddings
+    fn compact(&self, state: &mut WalState) -> anyhow::Result<()> {
+        let temp = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        for update in state.outstanding.values() {
+            serde_json::to_writer(&mut writer, &WalEntry::Generated(SpilledUpdate::from(update)))?;
+            writer.write_all(b"\n")?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+
+        state.file = OpenOptions::new().append(true).open(&self.path)?;
+        state.written = state.file.metadata()?.len();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmbeddingSink for WalSink {
+    async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+        let ids: Vec<RecordId> = updates.iter().map(|update| update.repo_id.clone()).collect();
+        // One sync covers every embedding the batch appended
+        self.sync()
+            .await
+            .with_context(|| format!("Failed to sync {}", self.path.display()))?;
+        let result = self.inner.store_embeddings(updates).await?;
+        // Records rejected individually won't succeed on a retry either
+        self.commit(&ids);
+        Ok(result)
+    }
+
+    async fn record_generated(&self, update: &EmbeddingUpdate) -> Result<()> {
+        let mut state = self.state.lock();
+        state.outstanding.insert(update.repo_id.to_string(), update.clone());
+        state
+            .append(&WalEntry::Generated(SpilledUpdate::from(update)))
+            .with_context(|| format!("Failed to append to {}", self.path.display()))?;
+        Ok(())
+    }
+
+    async fn write_audit_records(&self, records: Vec<AuditRecord>) -> Result<()> {
+        self.inner.write_audit_records(records).await
+    }
+
+    async fn write_batch_run(&self, run: &BatchRun) -> Result<()> {
+        self.inner.write_batch_run(run).await
+    }
+
+    async fn remove_embeddings(&self, ids: Vec<RecordId>) -> Result<()> {
+        self.commit(&ids);
+        self.inner.remove_embeddings(ids).await
+    }
+}
+
+/// Empty the log; appends always go to the end, so truncating in place is enough
+fn truncate(state: &mut WalState) -> anyhow::Result<()> {
+    state.file.set_len(0)?;
+    state.written = 0;
+    Ok(())
+}
+
+/// Replay the log into the embeddings still uncommitted
+fn read_log(path: &Path) -> anyhow::Result<HashMap<String, EmbeddingUpdate>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+    };
+    let mut outstanding = HashMap::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The last line may have been cut short by a crash
+        match serde_json::from_str::<WalEntry>(&line) {
+            Ok(WalEntry::Generated(spilled)) => match spilled.into_update() {
+                Ok(update) => {
+                    outstanding.insert(update.repo_id.to_string(), update);
+                }
+                Err(e) => warn!(path = %path.display(), line = number + 1, "Skipping log entry: {}", e),
+            },
+            Ok(WalEntry::Committed(ids)) => {
+                for id in ids {
+                    outstanding.remove(&id);
+                }
+            }
+            Err(e) => warn!(path = %path.display(), line = number + 1, "Skipping unreadable log entry: {}", e),
+        }
+    }
+    Ok(outstanding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EmbedError;
+    use chrono::Utc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct FlakySink {
+        down: AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmbeddingSink for FlakySink {
+        async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(EmbedError::Internal(anyhow::anyhow!("database unavailable")));
+            }
+            Ok(BatchUpdateResult {
+                total: updates.len(),
+                successful: updates.len(),
+                ..Default::default()
+            })
+        }
+    }
+
+    fn update(key: &str) -> EmbeddingUpdate {
+        EmbeddingUpdate {
+            repo_id: RecordId::from(("repo", key)),
+            embedding: vec![0.6, 0.8].into(),
+            normalized: true,
+            repaired: false,
+            updated_at: Utc::now(),
+            model: None,
+            language: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_embeddings_are_recovered() {
+        let path = std::env::temp_dir().join(format!("embed_star_wal_{}.jsonl", uuid::Uuid::new_v4()));
+        let inner = Arc::new(FlakySink::default());
+
+        let wal = WalSink::open(inner.clone(), path.clone()).unwrap();
+        wal.record_generated(&update("written")).await.unwrap();
+        wal.record_generated(&update("lost")).await.unwrap();
+        wal.store_embeddings(vec![update("written")]).await.unwrap();
+        inner.down.store(true, Ordering::Relaxed);
+        assert!(wal.store_embeddings(vec![update("lost")]).await.is_err());
+        assert_eq!(wal.pending(), 1);
+
+        // A restart finds only the embedding whose write failed
+        let wal = WalSink::open(inner.clone(), path.clone()).unwrap();
+        assert_eq!(wal.pending(), 1);
+        assert!(wal.recover().await.is_err());
+        inner.down.store(false, Ordering::Relaxed);
+        assert_eq!(wal.recover().await.unwrap(), 1);
+        assert_eq!(wal.pending(), 0);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    audit::{AuditRecord, BatchRun},
+    error::Result,
+    import::parse_repo_id,
+    metrics,
+    repo_store::EmbeddingSink,
+    surreal_client::{BatchUpdateResult, EmbeddingUpdate},
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use surrealdb::RecordId;
+use tracing::{info, warn};
+
+/// How often queued updates are written again
+const REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Updates written per replay attempt
+const REPLAY_CHUNK: usize = 500;
+
+/// An update as kept in the spill file and the write-ahead log
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SpilledUpdate {
+    repo: String,
+    embedding: Vec<f32>,
+    normalized: bool,
+    repaired: bool,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+impl From<&EmbeddingUpdate> for SpilledUpdate {
+    fn from(update: &EmbeddingUpdate) -> Self {
+        Self {
+            repo: update.repo_id.to_string(),
+            embedding: update.embedding.to_vec(),
+            normalized: update.normalized,
+            repaired: update.repaired,
+            updated_at: update.updated_at,
+            model: update.model.clone(),
+            language: update.language.clone(),
+        }
+    }
+}
+
+impl SpilledUpdate {
+    pub(crate) fn into_update(self) -> std::result::Result<EmbeddingUpdate, String> {
+        Ok(EmbeddingUpdate {
+            repo_id: parse_repo_id(&self.repo)?,
+            embedding: self.embedding.into(),
+            normalized: self.normalized,
+            repaired: self.repaired,
+            updated_at: self.updated_at,
+            model: self.model,
+            language: self.language,
+        })
+    }
+}
+
+/// Wraps a sink so embeddings from writes that fail outright (database down, transaction
+/// aborted) are queued and written again later instead of lost. Records the write rejected
+/// individually, such as deleted repos, are not retried. With a spill file the queue survives
+/// restarts.
+pub struct RetryingSink {
+    inner: Arc<dyn EmbeddingSink>,
+    queue: Mutex<VecDeque<EmbeddingUpdate>>,
+    max_queued: usize,
+    spill: Option<PathBuf>,
+    /// Bumped for every queue change, so a slow spill write never replaces a newer one
+    version: AtomicU64,
+    /// The version the spill file holds; held while writing it
+    spilled: Arc<Mutex<u64>>,
+}
+
+impl RetryingSink {
+    /// Queue at most `max_queued` updates; the oldest are dropped beyond that
+    pub fn new(inner: Arc<dyn EmbeddingSink>, max_queued: usize) -> Self {
+        Self {
+            inner,
+            queue: Mutex::new(VecDeque::new()),
+            max_queued: max_queued.max(1),
+            spill: None,
+            version: AtomicU64::new(0),
+            spilled: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Mirror the queue to `path`, loading what a previous run left there
+    pub fn with_spill_file(mut self, path: PathBuf) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let loaded = read_spill_file(&path)?;
+        if !loaded.is_empty() {
+            info!(path = %path.display(), updates = loaded.len(), "Loaded unwritten embeddings from spill file");
+        }
+        self.queue.get_mut().extend(loaded);
+        self.spill = Some(path);
+        metrics::set_write_retry_queued(self.queued());
+        Ok(self)
+    }
+
+    /// Updates waiting to be written again
+    pub fn queued(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    async fn enqueue(&self, updates: Vec<EmbeddingUpdate>) {
+        {
+            let mut queue = self.queue.lock();
+            queue.extend(updates);
+            let overflow = queue.len().saturating_sub(self.max_queued);
+            if overflow > 0 {
+                queue.drain(..overflow);
+                metrics::record_write_retry_dropped(overflow);
+                warn!(dropped = overflow, "Write retry queue full, dropped the oldest unwritten embeddings");
+            }
+        }
+        self.persist().await;
+    }
+
+    /// Put the updates a replay took back in front of anything queued since
+    async fn requeue(&self, updates: Vec<EmbeddingUpdate>) {
+        {
+            let mut queue = self.queue.lock();
+            for update in updates.into_iter().rev() {
+                queue.push_front(update);
+            }
+            queue.truncate(self.max_queued);
+        }
+        self.persist().await;
+    }
+
+    /// Mirror the queue to the spill file. The file is written on the blocking pool, from a
+    /// snapshot taken under the lock; the updates share their vectors with the queue.
+    async fn persist(&self) {
+        let (version, snapshot) = {
+            let queue = self.queue.lock();
+            metrics::set_write_retry_queued(queue.len());
+            if self.spill.is_none() {
+                return;
+            }
+            let version = self.version.fetch_add(1, Ordering::Relaxed) + 1;
+            (version, queue.iter().cloned().collect::<Vec<_>>())
+        };
+        let Some(path) = self.spill.clone() else {
+            return;
+        };
+
+        let spilled = self.spilled.clone();
+        let write = tokio::task::spawn_blocking(move || {
+            let mut spilled = spilled.lock();
+            if *spilled > version {
+                return Ok(());
+            }
+            write_spill_file(&path, &snapshot)?;
+            *spilled = version;
+            Ok::<_, anyhow::Error>(())
+        });
+        match write.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Failed to write spill file: {}", e),
+            Err(e) => warn!("Spill file write panicked: {}", e),
+        }
+    }
+
+    /// Write up to [`REPLAY_CHUNK`] queued updates. Returns how many were stored; on error they
+    /// stay queued.
+    pub async fn replay(&self) -> Result<usize> {
+        let updates: Vec<EmbeddingUpdate> = {
+            let mut queue = self.queue.lock();
+            let take = queue.len().min(REPLAY_CHUNK);
+            queue.drain(..take).collect()
+        };
+        if updates.is_empty() {
+            return Ok(0);
+        }
+
+        match self.inner.store_embeddings(updates.clone()).await {
+            Ok(result) => {
+                for (repo, error) in &result.errors {
+                    warn!(repo = %repo, "Dropping queued embedding the database rejected: {}", error);
+                }
+                self.persist().await;
+                metrics::record_write_retry_replayed(result.successful);
+                Ok(result.successful)
+            }
+            Err(e) => {
+                self.requeue(updates).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Replay queued updates until shutdown, then make one last attempt
+    pub async fn run(&self, mut shutdown_rx: tokio::sync::broadcast::Receiver<()>) {
+        let mut interval = tokio::time::interval(REPLAY_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = interval.tick() => self.replay_all().await,
+            }
+        }
+        self.replay_all().await;
+
+        let left = self.queued();
+        if left > 0 {
+            match &self.spill {
+                Some(path) => info!(updates = left, path = %path.display(), "Unwritten embeddings kept for the next run"),
+                None => warn!(updates = left, "Unwritten embeddings lost at shutdown; set WRITE_RETRY_SPILL_DIR to keep them"),
+            }
+        }
+    }
+
+    /// Replay chunk after chunk while the writes succeed
+    async fn replay_all(&self) {
+        while self.queued() > 0 {
+            match self.replay().await {
+                Ok(stored) => info!(stored, queued = self.queued(), "Wrote queued embeddings"),
+                Err(e) => {
+                    warn!(queued = self.queued(), "Database still refusing queued embeddings: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingSink for RetryingSink {
+    async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+        match self.inner.store_embeddings(updates.clone()).await {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                warn!(updates = updates.len(), "Embedding write failed, queueing for retry");
+                self.enqueue(updates).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn record_generated(&self, update: &EmbeddingUpdate) -> Result<()> {
+        self.inner.record_generated(update).await
+    }
+
+    async fn write_audit_records(&self, records: Vec<AuditRecord>) -> Result<()> {
+        self.inner.write_audit_records(records).await
+    }
+
+    async fn write_batch_run(&self, run: &BatchRun) -> Result<()> {
+        self.inner.write_batch_run(run).await
+    }
+
+    async fn remove_embeddings(&self, ids: Vec<RecordId>) -> Result<()> {
+        self.queue.lock().retain(|update| !ids.contains(&update.repo_id));
+        self.persist().await;
+        self.inner.remove_embeddings(ids).await
+    }
+}
+
+fn read_spill_file(path: &Path) -> anyhow::Result<Vec<EmbeddingUpdate>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+    };
+    let mut updates = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A line cut short by a crash mid-write is skipped rather than failing startup
+        let update = serde_json::from_str::<SpilledUpdate>(&line)
+            .map_err(|e| e.to_string())
+            .and_then(SpilledUpdate::into_update);
+        match update {
+            Ok(update) => updates.push(update),
+            Err(e) => warn!(path = %path.display(), line = number + 1, "Skipping unreadable spilled update: {}", e),
+        }
+    }
+    Ok(updates)
+}
+
+/// Replace the spill file with `queue`, through a temporary file so a crash never leaves it
+/// half written
+fn write_spill_file(path: &Path, queue: &[EmbeddingUpdate]) -> anyhow::Result<()> {
+    if queue.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    let temp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    for update in queue {
+        serde_json::to_writer(&mut writer, &SpilledUpdate::from(update))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EmbedError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Fails every write while `down` is set
+    #[derive(Default)]
+    struct FlakySink {
+        down: AtomicBool,
+        stored: Mutex<Vec<RecordId>>,
+    }
+
+    #[async_trait]
+    impl EmbeddingSink for FlakySink {
+        async fn store_embeddings(&self, updates: Vec<EmbeddingUpdate>) -> Result<BatchUpdateResult> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(EmbedError::Internal(anyhow::anyhow!("database unavailable")));
+            }
+            self.stored.lock().extend(updates.iter().map(|update| update.repo_id.clone()));
+            Ok(BatchUpdateResult {
+                total: updates.len(),
+                successful: updates.len(),
+                ..Default::default()
+            })
+        }
+    }
+
+    fn update(key: &str) -> EmbeddingUpdate {
+        EmbeddingUpdate {
+            repo_id: RecordId::from(("repo", key)),
+            embedding: vec![0.6, 0.8].into(),
+            normalized: true,
+            repaired: false,
+            updated_at: Utc::now(),
+            model: None,
+            language: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_are_spilled_and_replayed() {
+        let path = std::env::temp_dir().join(format!("embed_star_spill_{}.jsonl", uuid::Uuid::new_v4()));
+        let inner = Arc::new(FlakySink::default());
+        inner.down.store(true, Ordering::Relaxed);
+
+        let sink = RetryingSink::new(inner.clone(), 10).with_spill_file(path.clone()).unwrap();
+        assert!(sink.store_embeddings(vec![update("a"), update("b")]).await.is_err());
+        assert_eq!(sink.queued(), 2);
+        assert!(sink.replay().await.is_err());
+        assert_eq!(sink.queued(), 2);
+
+        // A restart picks the queue up from the spill file
+        let sink = RetryingSink::new(inner.clone(), 10).with_spill_file(path.clone()).unwrap();
+        assert_eq!(sink.queued(), 2);
+
+        // A removed repo leaves the spill file too, so a restart doesn't write it back
+        assert!(sink.store_embeddings(vec![update("c")]).await.is_err());
+        sink.remove_embeddings(vec![RecordId::from(("repo", "c"))]).await.unwrap();
+        let sink = RetryingSink::new(inner.clone(), 10).with_spill_file(path.clone()).unwrap();
+        assert_eq!(sink.queued(), 2);
+
+        inner.down.store(false, Ordering::Relaxed);
+        assert_eq!(sink.replay().await.unwrap(), 2);
+        assert_eq!(sink.queued(), 0);
+        assert_eq!(*inner.stored.lock(), vec![RecordId::from(("repo", "a")), RecordId::from(("repo", "b"))]);
+        assert!(!path.exists());
+    }
+}
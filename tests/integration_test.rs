@@ -1,9 +1,11 @@
-/// Integration tests for embed_star service
-///
-/// These tests verify the complete embedding pipeline works correctly
-/// Note: Requires SurrealDB running locally on port 8000
+//! Integration tests for embed_star service
+//!
+//! These tests verify the complete embedding pipeline works correctly
+//! Note: The database tests run on an embedded mem:// datastore; the ignored ones need
+//! SurrealDB running locally on port 8000
 
 use anyhow::Result;
+use embed_star::pool::is_embedded_url;
 use surrealdb::{ engine::any::{ Any, connect }, opt::auth::Root, sql::Datetime, RecordId, Surreal };
 use std::time::Duration;
 use tokio::time::{ sleep, timeout };
@@ -25,7 +27,7 @@ struct TestRepo {
 /// Test database connection and basic operations
 #[tokio::test]
 async fn test_database_connection() -> Result<()> {
-    let db = create_test_db().await?;
+    let db = create_test_db("mem://").await?;
 
     // Test basic query
     let mut response = db.query("RETURN 'hello'").await?;
@@ -40,7 +42,7 @@ async fn test_database_connection() -> Result<()> {
 /// Test repository creation and retrieval
 #[tokio::test]
 async fn test_repo_operations() -> Result<()> {
-    let db = create_test_db().await?;
+    let db = create_test_db("mem://").await?;
     setup_schema(&db).await?;
 
     // Create a test repository
@@ -89,28 +91,26 @@ async fn test_embedding_generation() -> Result<()> {
     // Create config for Ollama
     let config = Config {
         db_url: "ws://localhost:8000".to_string(),
-        db_user: "root".to_string(),
-        db_pass: "root".to_string(),
-        db_namespace: "test".to_string(),
         db_database: "embed_star_test".to_string(),
         embedding_provider: "ollama".to_string(),
-        ollama_url: "http://localhost:11434".to_string(),
-        openai_api_key: None,
-        together_api_key: None,
-        batch_size: 10,
         batch_delay_ms: 100,
         pool_size: 10,
         retry_attempts: 3,
         retry_delay_ms: 1000,
         monitoring_port: Some(9090),
-        parallel_workers: 1,
-        token_limit: 8000,
         pool_max_size: 10,
         pool_timeout_secs: 30,
         pool_wait_timeout_secs: 10,
         pool_create_timeout_secs: 30,
         pool_recycle_timeout_secs: 30,
         embedding_model: "nomic-embed-text".to_string(),
+        price_per_million_tokens: None,
+        provider_probe_interval_secs: 300,
+        monitoring_bind_addr: "0.0.0.0".to_string(),
+        mock_dimensions: 768,
+        poll_interval_secs: 5,
+        shutdown_drain_secs: 20,
+        ..Config::for_tests()
     };
 
     let embedder = Embedder::new(Arc::new(config))?;
@@ -147,7 +147,7 @@ async fn test_full_pipeline() -> Result<()> {
     std::env::set_var("BATCH_SIZE", "2");
     std::env::set_var("PARALLEL_WORKERS", "1");
 
-    let db = create_test_db().await?;
+    let db = create_test_db("ws://localhost:8000").await?;
     setup_schema(&db).await?;
 
     // Create test repositories
@@ -224,14 +224,16 @@ async fn test_full_pipeline() -> Result<()> {
     Ok(())
 }
 
-/// Helper function to create test database connection
-async fn create_test_db() -> Result<Surreal<Any>> {
-    let db: Surreal<Any> = connect("ws://localhost:8000").await?;
+/// Helper function to create test database connection; embedded datastores skip authentication
+async fn create_test_db(url: &str) -> Result<Surreal<Any>> {
+    let db: Surreal<Any> = connect(url).await?;
 
-    db.signin(Root {
-        username: "root",
-        password: "root",
-    }).await?;
+    if !is_embedded_url(url) {
+        db.signin(Root {
+            username: "root",
+            password: "root",
+        }).await?;
+    }
 
     db.use_ns("test").use_db("embed_star_test").await?;
 
@@ -18,6 +18,7 @@ fn test_repo_needs_embedding() {
         url: "https://github.com/test/repo".to_string(),
         stars: 100,
         language: Some("Rust".to_string()),
+        readme: None,
         owner: RepoOwner {
             login: "test".to_string(),
             avatar_url: "https://github.com/test.png".to_string(),
@@ -27,6 +28,7 @@ fn test_repo_needs_embedding() {
         updated_at: now,
         embedding: None,
         embedding_generated_at: None,
+        extra: Default::default(),
     };
 
     assert!(repo.needs_embedding());
@@ -61,6 +63,7 @@ fn test_prepare_text_for_embedding() {
         url: "https://github.com/rust-lang/rust".to_string(),
         stars: 90000,
         language: Some("Rust".to_string()),
+        readme: None,
         owner: RepoOwner {
             login: "rust-lang".to_string(),
             avatar_url: "https://github.com/rust-lang.png".to_string(),
@@ -70,6 +73,7 @@ fn test_prepare_text_for_embedding() {
         updated_at: Utc::now(),
         embedding: None,
         embedding_generated_at: None,
+        extra: Default::default(),
     };
 
     let text = repo.prepare_text_for_embedding();
@@ -93,28 +97,26 @@ fn test_error_retryable() {
 fn test_config_validation() {
     let mut config = Config {
         db_url: "ws://localhost:8000".to_string(),
-        db_user: "root".to_string(),
-        db_pass: "root".to_string(),
-        db_namespace: "test".to_string(),
-        db_database: "test".to_string(),
         embedding_provider: "openai".to_string(),
-        ollama_url: "http://localhost:11434".to_string(),
-        openai_api_key: None,
-        together_api_key: None,
         embedding_model: "text-embedding-3-small".to_string(),
-        batch_size: 10,
         pool_size: 10,
         retry_attempts: 3,
         retry_delay_ms: 1000,
         batch_delay_ms: 100,
         monitoring_port: Some(9090),
         parallel_workers: 3,
-        token_limit: 8000,
         pool_max_size: 10,
         pool_timeout_secs: 30,
         pool_wait_timeout_secs: 10,
         pool_create_timeout_secs: 30,
         pool_recycle_timeout_secs: 30,
+        price_per_million_tokens: None,
+        provider_probe_interval_secs: 300,
+        monitoring_bind_addr: "0.0.0.0".to_string(),
+        mock_dimensions: 768,
+        poll_interval_secs: 5,
+        shutdown_drain_secs: 20,
+        ..Config::for_tests()
     };
 
     // Should fail - OpenAI provider without API key
@@ -131,7 +133,29 @@ fn test_config_validation() {
     config.together_api_key = Some("test-key".to_string());
     assert!(config.validate().is_ok());
 
+    // Test polling validation
+    config.poll_interval_secs = 0;
+    assert!(config.validate().is_err());
+    config.poll_interval_secs = 5;
+    config.fetch_batch_size = 0;
+    assert!(config.validate().is_err());
+    config.fetch_batch_size = 100;
+
     // Test batch size validation
     config.batch_size = 0;
     assert!(config.validate().is_err());
+    config.batch_size = 10;
+
+    // Validation thresholds override the model's profile
+    assert_eq!(config.validation_config().expected_dimension, Some(1536));
+    config.validation_min_magnitude = Some(0.5);
+    config.validation_max_magnitude = Some(2.0);
+    let validation = config.validation_config();
+    assert_eq!((validation.min_magnitude, validation.max_magnitude), (0.5, 2.0));
+    assert!(config.validate().is_ok());
+    config.validation_max_magnitude = Some(0.1);
+    assert!(config.validate().is_err());
+    config.validation_max_magnitude = Some(2.0);
+    config.validation_max_zero_ratio = Some(1.5);
+    assert!(config.validate().is_err());
 }
@@ -19,6 +19,7 @@ async fn test_together_api_connection() {
     let embedder = TogetherAIEmbedder::new(
         &api_key,
         "togethercomputer/m2-bert-80M-8k-retrieval".to_string(),
+        reqwest::Client::new(),
     )
     .expect("Failed to create Together AI embedder");
 
@@ -55,6 +56,7 @@ async fn test_together_multilingual_e5() {
     let embedder = TogetherAIEmbedder::new(
         &api_key,
         "intfloat/multilingual-e5-large-instruct".to_string(),
+        reqwest::Client::new(),
     )
     .expect("Failed to create Together AI embedder");
 
@@ -111,6 +113,7 @@ async fn test_together_similarity() {
     let embedder = TogetherAIEmbedder::new(
         &api_key,
         "intfloat/multilingual-e5-large-instruct".to_string(),
+        reqwest::Client::new(),
     )
     .expect("Failed to create Together AI embedder");
 
@@ -142,6 +145,34 @@ async fn test_together_similarity() {
     );
 }
 
+#[tokio::test]
+#[ignore] // Run with: cargo test test_together_batch_input -- --ignored
+async fn test_together_batch_input() {
+    // Several inputs in one request come back in order, with the tokens billed for all of them
+    let api_key = match std::env::var("TOGETHER_API_KEY") {
+        Ok(key) => key,
+        Err(_) => {
+            println!("Skipping test: TOGETHER_API_KEY not set");
+            return;
+        }
+    };
+
+    let embedder = TogetherAIEmbedder::new(
+        &api_key,
+        "intfloat/multilingual-e5-large-instruct".to_string(),
+        reqwest::Client::new(),
+    )
+    .expect("Failed to create Together AI embedder");
+
+    let texts = ["The Rust programming language", "El lenguaje de programación Rust"];
+    let (embeddings, usage) = embedder.generate_embeddings_with_usage(&texts).await.unwrap();
+    assert_eq!(embeddings.len(), 2);
+    assert!(usage.is_some_and(|tokens| tokens > 0), "Usage should be reported");
+
+    let single = embedder.generate_embedding(texts[1]).await.unwrap();
+    assert!(cosine_similarity(&embeddings[1], &single) > 0.99);
+}
+
 #[tokio::test]
 #[ignore] // Run with: cargo test test_together_error_handling -- --ignored
 async fn test_together_error_handling() {
@@ -149,6 +180,7 @@ async fn test_together_error_handling() {
     let embedder = TogetherAIEmbedder::new(
         "invalid-api-key",
         "intfloat/multilingual-e5-large-instruct".to_string(),
+        reqwest::Client::new(),
     )
     .expect("Failed to create Together AI embedder");
 
@@ -177,28 +209,26 @@ async fn test_embedder_integration() {
 
     let config = Config {
         db_url: "ws://localhost:8000".to_string(),
-        db_user: "root".to_string(),
-        db_pass: "root".to_string(),
-        db_namespace: "test".to_string(),
-        db_database: "test".to_string(),
         embedding_provider: "together".to_string(),
-        ollama_url: "http://localhost:11434".to_string(),
-        openai_api_key: None,
         together_api_key: Some(api_key),
         embedding_model: "intfloat/multilingual-e5-large-instruct".to_string(),
-        batch_size: 10,
         batch_delay_ms: 100,
         pool_size: 10,
         retry_attempts: 3,
         retry_delay_ms: 1000,
         monitoring_port: None,
-        parallel_workers: 1,
-        token_limit: 8000,
         pool_max_size: 10,
         pool_timeout_secs: 30,
         pool_wait_timeout_secs: 10,
         pool_create_timeout_secs: 30,
         pool_recycle_timeout_secs: 30,
+        price_per_million_tokens: None,
+        provider_probe_interval_secs: 300,
+        monitoring_bind_addr: "0.0.0.0".to_string(),
+        mock_dimensions: 768,
+        poll_interval_secs: 5,
+        shutdown_drain_secs: 20,
+        ..Config::for_tests()
     };
 
     let embedder = Embedder::new(Arc::new(config)).expect("Failed to create embedder");
@@ -232,6 +262,7 @@ async fn test_together_rate_limiting() {
     let embedder = TogetherAIEmbedder::new(
         &api_key,
         "intfloat/multilingual-e5-large-instruct".to_string(),
+        reqwest::Client::new(),
     )
     .expect("Failed to create Together AI embedder");
 